@@ -0,0 +1,34 @@
+//! Build script recording build details for `trs version --build`
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+/// First line of the output of a command, if it ran successfully
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    stdout.lines().next().map(|line| line.trim().to_string())
+}
+
+fn main() {
+    let commit = command_output("git", &["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=TRS_GIT_COMMIT={}", commit);
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = command_output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=TRS_RUSTC_VERSION={}", rustc_version);
+
+    println!("cargo:rustc-env=TRS_TARGET={}", env::var("TARGET").unwrap_or_default());
+
+    // Only rerun when the checked out commit changes, not on every build
+    println!("cargo:rerun-if-changed=build.rs");
+    for path in [".git/HEAD", ".git/refs/heads"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Async versions of the core trash operations
+//!
+//! Filesystem checks use `tokio::fs` and the blocking archive and
+//! compression work runs on Tokio's blocking thread pool, so progress
+//! sinks are taken by value. The metadata name given to
+//! `use_metadata_name` when an operation is called applies to it there
+//! too. Enabled with the `async` feature.
+
+use std::io;
+use std::path::Path;
+use tokio::task::{spawn_blocking, JoinError};
+
+use crate::error::{Result, TrsError};
+use crate::metadata;
+use crate::ProgressSink;
+use crate::trash::{self, expand_tilde, EmptyOptions, EmptySummary, MoveOptions, MovedItem, RestoreOptions, RestoredItem, ShowOptions};
+
+/// Convert a failed blocking task into an error
+fn join_error(e: JoinError) -> TrsError {
+    io::Error::other(e).into()
+}
+
+/// Run `f` on the blocking thread pool with metadata files named `metadata_name`
+async fn blocking<T: Send + 'static>(metadata_name: String, f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    spawn_blocking(move || {
+        let _metadata_name = metadata::use_metadata_name(&metadata_name)?;
+        f()
+    })
+    .await
+    .map_err(join_error)?
+}
+
+/// Move a file or directory to trash
+pub async fn move_to_trash(
+    file: &str,
+    trash_dir: &Path,
+    options: &MoveOptions,
+    progress: impl ProgressSink + 'static,
+) -> Result<MovedItem> {
+    let metadata_name = metadata::metadata_name();
+    tokio::fs::create_dir_all(trash_dir).await?;
+    // Fail early without occupying a blocking thread if the source is missing
+    let path = expand_tilde(file);
+    tokio::fs::symlink_metadata(&path).await.map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => TrsError::SourceNotFound(path),
+        _ => e.into(),
+    })?;
+
+    let file = file.to_string();
+    let trash_dir = trash_dir.to_path_buf();
+    let options = options.clone();
+    blocking(metadata_name, move || trash::move_to_trash(&file, &trash_dir, &options, &progress)).await
+}
+
+/// Restore a file from trash
+pub async fn restore_from_trash(
+    file: &str,
+    trash_dir: &Path,
+    options: &RestoreOptions,
+    progress: impl ProgressSink + 'static,
+) -> Result<RestoredItem> {
+    let metadata_name = metadata::metadata_name();
+    tokio::fs::symlink_metadata(trash_dir.join(file)).await
+        .map_err(|_| TrsError::NotInTrash(file.to_string()))?;
+
+    let file = file.to_string();
+    let trash_dir = trash_dir.to_path_buf();
+    let options = options.clone();
+    blocking(metadata_name, move || trash::restore_from_trash(&file, &trash_dir, &options, &progress)).await
+}
+
+/// Empty trash folder permanently
+pub async fn empty_trash(trash_dir: &Path, options: &EmptyOptions, progress: impl ProgressSink + 'static) -> Result<EmptySummary> {
+    let metadata_name = metadata::metadata_name();
+    if !tokio::fs::try_exists(trash_dir).await? {
+        return Ok(EmptySummary::default());
+    }
+
+    let trash_dir = trash_dir.to_path_buf();
+    let options = options.clone();
+    blocking(metadata_name, move || trash::empty_trash(&trash_dir, &options, &progress)).await
+}
+
+/// Display contents of trash folder
+pub async fn show_trash_contents(trash_dir: &Path, options: &ShowOptions) -> Result<()> {
+    let metadata_name = metadata::metadata_name();
+    let trash_dir = trash_dir.to_path_buf();
+    let options = options.clone();
+    blocking(metadata_name, move || trash::show_trash_contents(&trash_dir, &options)).await
+}
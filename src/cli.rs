@@ -1,56 +1,2057 @@
 //! CLI handling
 
-use std::io;
-use clap::{Command, Arg, ArgAction};
-use crate::trash::{move_to_trash, empty_trash, show_trash_contents, interactive_restore};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use clap::{Command, Arg, ArgAction, ArgGroup, ArgMatches, ValueHint, value_parser};
+use clap::builder::PossibleValuesParser;
+use clap_complete::{generate, Shell};
+use clap_mangen::Man;
+use serde::Serialize;
+use crate::error::{Context, Result, TrsError};
+use crate::tui::{self, Theme};
+use crate::{IndicatifProgress, NoOpProgress};
+use crate::compression::{ArchiveCompression, BundleCompression};
+use crate::config::{parse_duration, Config, ValueSource, KEYS};
+use crate::crypto::{self, Passphrase};
+use crate::device;
+use crate::interrupt;
+use crate::logging;
+use crate::style::{ColorChoice, Palette};
+use crate::macos;
+use crate::owner::{self, SudoUser};
+use crate::output::{print_json, print_json_error, print_json_line, ItemResult, OutputFormat};
+use crate::xdg;
+use crate::format::{display_path, display_size, escape_field, format_count, format_size, format_timestamp, parse_datetime, parse_size};
+use crate::metadata::{self, now_secs};
+use crate::schedule;
+use crate::trash::{capacity_warning, deepest_existing_ancestor, default_trash_dir, trash_dir_in, hand_over_trash, find_trash_dir_for_path, entries_matching, purge_entry, pin_trash_item, empty_candidates, entry_size, expand_tilde, duplicate_groups, item_details, item_info, trash_stats, prune_older_than, prune_to_size, move_to_trash, empty_trash, lookup_entry, show_item_details, show_merged_contents, show_sourced_entries, show_trash_contents, shown_entries, shown_items, stream_items, undo_last, interactive_restore, export_trash, import_trash, plan_import, list_trash_items, locate_entry, restore_from_trash, restore_member, restore_preview, import_system_item, import_path, import_trashinfo, system_trash_items, EmptyOptions, EmptySummary, KeepDuplicate, ExportOptions, LargeDirLimits, ImportAction, ImportOptions, MergePreference, MoveOptions, RestoreOptions, RestoredItem, ShowOptions, ShredOptions, SourcedEntry, MissingParentEntry, Timestamps, TrashBackend, Undone, DEFAULT_TREE_DEPTH};
 
-/// Run the application
-pub fn run() -> io::Result<()> {
+/// Run the application, returning the process exit code
+///
+/// Moving several files exits with 0 when all succeed, 1 when all fail and
+/// 2 when only some of them fail. With `--json`, an error that stops the
+/// command is printed to stderr as a JSON object and its exit code returned.
+pub fn run() -> Result<i32> {
     let matches = create_cli().get_matches();
+    // `show --json-lines` is JSON too, so nothing meant for people gets between its lines
+    let json_lines = matches.subcommand_matches("show").is_some_and(|show| show.get_flag("json_lines"));
+    let format = if matches.get_flag("json") || json_lines {
+        OutputFormat::Json
+    } else if matches.get_flag("porcelain") {
+        OutputFormat::Porcelain
+    } else {
+        OutputFormat::Human
+    };
+
+    let mut capacity_check = None;
+    let result = run_command(&matches, format, &mut capacity_check);
+    // Measured once the command is done, unless nothing was added
+    if let (Ok(code), Some(check)) = (&result, capacity_check)
+        && *code != 1
+    {
+        check.warn();
+    }
+    match result {
+        Err(e) if format == OutputFormat::Json => {
+            print_json_error(&e);
+            Ok(e.exit_code())
+        }
+        result => result,
+    }
+}
+
+/// Run the command selected by `matches`, printing its results in `format`
+///
+/// Commands that add items to the trash set `capacity_check` to warn
+/// afterwards if it has grown too large.
+fn run_command(matches: &ArgMatches, format: OutputFormat, capacity_check: &mut Option<CapacityCheck>) -> Result<i32> {
+    logging::init(matches.get_one::<String>("log_file").map(|path| expand_tilde(path)).as_deref())?;
+
+    let config = Config::load()?;
+    // Under sudo, root's trash is used unless --user-trash asks for the one of the user who ran it
+    let sudo_user = owner::sudo_user();
+    let user_trash = match (matches.get_flag("user_trash"), sudo_user) {
+        (false, _) => None,
+        (true, Some(user)) => Some(user),
+        (true, None) => return Err(TrsError::invalid_input("--user-trash only applies when running as root under sudo")),
+    };
+    let root_trash = trash_dir(&config);
+    let global_trash = match user_trash {
+        Some(user) => trash_dir_in(&user.data_local_dir()),
+        None => root_trash.clone(),
+    };
+    let native = matches.get_flag("native_trash") || config.boolean("native_trash").unwrap_or(false);
+    let desktop_sync = !native && (matches.get_flag("desktop_sync") || config.boolean("desktop_sync").unwrap_or(false));
+    // trash-cli keeps its items in the FreeDesktop.org trash
+    let compat = matches.get_one::<String>("compat").is_some();
+    let xdg_mode = desktop_sync || !native && (matches.get_flag("xdg") || compat || config.boolean("xdg_compat").unwrap_or(false));
+    let trash_dir = if native {
+        macos::native_trash_dir()?
+    } else if xdg_mode {
+        xdg::home_trash_files()?
+    } else {
+        global_trash.clone()
+    };
+    // With desktop sync, items trashed before it was turned on stay in the trs trash directory
+    let store = desktop_sync.then_some(global_trash.as_path());
+    // In XDG mode items go to the trash on their own filesystem unless --home-trash is given
+    let per_mount = |matches: &ArgMatches| (xdg_mode && !matches.get_flag("home_trash")).then_some(global_trash.as_path());
+
+    if user_trash.is_some() && (xdg_mode || native) {
+        return Err(TrsError::invalid_input("--user-trash only applies to the trs trash directory, not the FreeDesktop.org or native trash"));
+    }
+    // Items trashed for the user are recorded as theirs, and the files written are given back to them
+    let _hand_over = user_trash.map(|user| {
+        owner::act_for(user);
+        HandOver { trash_dir: trash_dir.clone(), user }
+    });
+
+    // Lazily expire old items before commands that touch the trash
+    let touches_trash = !matches!(matches.subcommand_name(), Some("config" | "completions" | "complete-entries" | "man" | "prune" | "version"));
+    // Checked only where it is used, so a bad name can still be fixed with `trs config`
+    let metadata_name = config.string("metadata_name").filter(|_| matches.subcommand_name() != Some("config"));
+    let _metadata_name = metadata_name.as_deref().map(metadata::use_metadata_name).transpose()?;
+    // Commands that add to the trash warn once they are done if it has grown too large
+    let adds_items = matches.get_many::<String>("default_file").is_some() || match matches.subcommand() {
+        Some(("move", sub_m)) => sub_m.get_one::<String>("schedule").is_none(),
+        Some(("import" | "import-system-trash", sub_m)) => !sub_m.get_flag("dry_run"),
+        Some(("schedule", sub_m)) => sub_m.subcommand_name() == Some("run"),
+        _ => false,
+    };
+    *capacity_check = (adds_items && format.is_human() && !matches.get_flag("no_warn")).then(|| CapacityCheck {
+        trash_dir: trash_dir.clone(),
+        metadata_name: metadata_name.clone(),
+        threshold: config.size("warn_size").unwrap_or(1_000_000_000),
+        color: color_choice(matches).enabled() && io::stderr().is_terminal(),
+    });
+    if touches_trash
+        && format.is_human()
+        && let Some(user) = sudo_user
+        && !xdg_mode
+        && !native
+    {
+        sudo_note(user, user_trash.is_some(), &trash_dir, color_choice(matches).enabled() && io::stderr().is_terminal());
+    }
+    if touches_trash
+        && !matches.get_flag("no_auto_prune")
+        && let Some(days) = config.integer("retention_days")
+    {
+        let expired = prune_older_than(&trash_dir, retention(days))?;
+        if !expired.is_empty() && format.is_human() {
+            println!("Expired {} item(s) older than {} day(s) from Trash", expired.len(), days);
+        }
+    }
+
+    if let Some(default_files) = matches.get_many::<String>("default_file") {
+        // Process multiple files for the default command
+        let options = move_options(matches, &config)?;
+        let mut input = interactive_input(matches, format)?;
+        let (files, unmatched) = expand_globs(default_files, matches.get_flag("glob"))?;
+        let input = input.as_mut().map(|input| input as &mut dyn BufRead);
+        return move_files(&files, &unmatched, &trash_dir, per_mount(matches), &options, input, format);
+    } else {
+        match matches.subcommand() {
+            Some(("move", sub_m)) => {
+                // Process multiple files for the move command
+                let mut options = move_options(sub_m, &config)?;
+                options.name = sub_m.get_one::<String>("name").cloned();
+                if let Some(due) = sub_m.get_one::<String>("schedule") {
+                    let files: Vec<&String> = sub_m.get_many::<String>("file").unwrap_or_default().collect();
+                    return schedule_files(&files, due, &trash_dir, format);
+                }
+                if let Some(files) = sub_m.get_many::<String>("file") {
+                    if options.name.is_some() && files.len() > 1 {
+                        return Err(TrsError::invalid_input("--name can only be used when moving a single file"));
+                    }
+                    let mut input = interactive_input(sub_m, format)?;
+                    let (files, unmatched) = expand_globs(files, sub_m.get_flag("glob"))?;
+                    let input = input.as_mut().map(|input| input as &mut dyn BufRead);
+                    return move_files(&files, &unmatched, &trash_dir, per_mount(sub_m), &options, input, format);
+                }
+            }
+            Some(("interactive", _)) => {
+                let theme = Theme::from_config(&config)?;
+                tui::run(&trash_dir, &restore_options(&config), theme)?;
+            }
+            Some(("restore", sub_m)) => {
+                return run_restore(sub_m, &config, &trash_dir, store, format);
+            }
+            Some(("empty", sub_m)) => {
+                run_empty(sub_m, &config, &trash_dir, format)?;
+            }
+            Some(("purge", sub_m)) => {
+                return run_purge(sub_m, &config, &trash_dir, format);
+            }
+            Some(("undo", _)) => {
+                run_undo(&trash_dir, format)?;
+            }
+            Some(("pin", sub_m)) => {
+                return run_pin(sub_m, &trash_dir, true, format);
+            }
+            Some(("unpin", sub_m)) => {
+                return run_pin(sub_m, &trash_dir, false, format);
+            }
+            Some(("show", sub_m)) => {
+                let all_users = if sub_m.get_flag("all_users") {
+                    match sudo_user {
+                        Some(user) if !xdg_mode && !native => Some([
+                            (root_trash.clone(), "root".to_string()),
+                            (trash_dir_in(&user.data_local_dir()), user.name.clone()),
+                        ]),
+                        Some(_) => return Err(TrsError::invalid_input("--all-users only lists trs trash directories, not the FreeDesktop.org or native trash")),
+                        None => return Err(TrsError::invalid_input("--all-users only applies when running as root under sudo")),
+                    }
+                } else {
+                    None
+                };
+                run_show(sub_m, &trash_dir, store, all_users.as_ref(), format, color_choice(matches))?;
+            }
+            Some(("info", sub_m)) => {
+                run_info(sub_m, &trash_dir, format)?;
+            }
+            Some(("stats", sub_m)) => {
+                run_stats(sub_m, &trash_dir, format)?;
+            }
+            Some(("export", sub_m)) => {
+                run_export(sub_m, &trash_dir, format)?;
+            }
+            Some(("import", sub_m)) => {
+                run_import(sub_m, &config, &trash_dir, format)?;
+            }
+            Some(("import-system-trash", sub_m)) => {
+                return run_import_system_trash(sub_m, &config, &global_trash, format);
+            }
+            Some(("schedule", sub_m)) => {
+                return run_schedule(sub_m, &config, &trash_dir, format);
+            }
+            Some(("prune", sub_m)) => {
+                run_prune(sub_m, &config, &trash_dir, format)?;
+            }
+            Some(("config", sub_m)) => {
+                run_config(sub_m, config, format)?;
+            }
+            Some(("version", sub_m)) => {
+                print_version(sub_m.get_flag("build"), format)?;
+            }
+            Some(("completions", sub_m)) => {
+                let shell = *sub_m.get_one::<Shell>("shell").unwrap();
+                if sub_m.get_flag("install") {
+                    install_completions(shell)?;
+                } else {
+                    io::stdout().write_all(&completion_script(shell)).with_context(|| "writing completions".to_string())?;
+                }
+            }
+            Some(("complete-entries", _)) => {
+                // Run by the completion scripts, which have nothing to offer if the trash cannot be read
+                for entry in list_trash_items(&trash_dir).unwrap_or_default() {
+                    println!("{}", entry.name);
+                }
+            }
+            Some(("man", sub_m)) => {
+                run_man(sub_m, format)?;
+            }
+            _ => {
+                // Show the help page for invalid commands
+                create_cli().print_help().expect("Failed to print help");
+                println!();
+            }
+        }
+    }
+
+    Ok(0)
+}
+
+/// Expand arguments that name no existing path but contain glob characters
+///
+/// Patterns are only expanded when `enabled`, relative to the current
+/// directory. Returns the paths to move and the patterns that matched
+/// nothing.
+fn expand_globs<'a>(args: impl Iterator<Item = &'a String>, enabled: bool) -> Result<(Vec<String>, Vec<String>)> {
+    let mut files = Vec::new();
+    let mut unmatched = Vec::new();
+    for arg in args {
+        let is_pattern = arg.contains(['*', '?', '[']);
+        if !enabled || !is_pattern || Path::new(arg).symlink_metadata().is_ok() {
+            files.push(arg.clone());
+            continue;
+        }
+
+        let paths = glob::glob(arg)
+            .map_err(|e| TrsError::invalid_input(format!("Invalid pattern '{}': {}", arg, e)))?;
+        let mut matched = false;
+        for path in paths {
+            let path = path.map_err(io::Error::from)?.to_string_lossy().to_string();
+            matched = true;
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+        if !matched {
+            unmatched.push(arg.clone());
+        }
+    }
+    Ok((files, unmatched))
+}
+
+/// Move every file to trash, reporting failures without stopping
+///
+/// When `per_mount` holds the global trash, each file goes to the trash
+/// chosen by `find_trash_dir_for_path` instead of `trash_dir`. With `input`,
+/// each file is confirmed first as with `rm -i`, reading answers from it.
+/// Directories refused as too large are asked about on the terminal, and
+/// fail when there is none.
+/// Patterns in `unmatched` count as failures. Returns the exit code.
+fn move_files(
+    files: &[String],
+    unmatched: &[String],
+    trash_dir: &Path,
+    per_mount: Option<&Path>,
+    options: &MoveOptions,
+    mut input: Option<&mut dyn BufRead>,
+    format: OutputFormat,
+) -> Result<i32> {
+    // Ctrl-C stops after the current item, or part way into archiving a directory
+    let _guard = interrupt::Guard::new();
+    let mut results = Vec::new();
+    for file in files {
+        if interrupt::requested() {
+            break;
+        }
+        if let Some(answers) = input.as_deref_mut() {
+            match ask_to_trash(file, answers)? {
+                Answer::Yes => {}
+                Answer::No => continue,
+                Answer::All => input = None,
+                Answer::Quit => break,
+            }
+        }
+        let trash_dir = match per_mount {
+            Some(global_trash) => find_trash_dir_for_path(&expand_tilde(file), global_trash),
+            None => trash_dir.to_path_buf(),
+        };
+        let progress = || format.progress(format!("Moving {} to Trash", file));
+        let mut result = move_to_trash(file, &trash_dir, options, &progress());
+        // Large directories are refused until confirmed, which needs someone to ask
+        if let Err(TrsError::LargeDirectory { files, bytes, .. }) = &result
+            && format.is_human()
+            && io::stdin().is_terminal()
+        {
+            if !confirm(&format!("really trash {} ({} files, {})?", file, format_count(*files), format_size(*bytes)))? {
+                continue;
+            }
+            let confirmed = MoveOptions { confirm_above: None, ..options.clone() };
+            result = move_to_trash(file, &trash_dir, &confirmed, &progress());
+        }
+        let result = match result {
+            Err(TrsError::Interrupted) => break,
+            result => ItemResult::new(file, result),
+        };
+        if format == OutputFormat::Porcelain {
+            print_status(file, result.error.as_deref());
+        }
+        results.push(result);
+    }
+    let failed = results.iter().filter(|result| !result.is_ok()).count() + unmatched.len();
+
+    match format {
+        OutputFormat::Human => {
+            for pattern in unmatched {
+                eprintln!("No files match '{}'", pattern);
+            }
+            for result in &results {
+                if let Some(e) = &result.error {
+                    eprintln!("Failed to move {}: {}", result.source, e);
+                }
+            }
+        }
+        OutputFormat::Porcelain => {
+            for pattern in unmatched {
+                print_status(pattern, Some("No files match"));
+            }
+        }
+        OutputFormat::Json => {
+            results.extend(unmatched.iter().map(|pattern| ItemResult::failed(pattern, "No files match")));
+            print_json(&results)?;
+        }
+    }
+
+    if interrupt::requested() {
+        return Err(TrsError::Interrupted);
+    }
+    Ok(exit_code(failed, files.len() + unmatched.len()))
+}
+
+/// An answer to the `--interactive` question about one file
+enum Answer {
+    Yes,
+    No,
+    /// Yes to this file and all remaining ones
+    All,
+    /// No to this file and all remaining ones
+    Quit,
+}
+
+/// Ask whether to trash `file`, reading answers from `input` until one is understood
+///
+/// An empty answer is no, and the end of the input stops like `q`.
+fn ask_to_trash(file: &str, input: &mut dyn BufRead) -> Result<Answer> {
+    loop {
+        print!("trash {}? [y/N/a/q] ", file);
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            println!();
+            return Ok(Answer::Quit);
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => return Ok(Answer::Yes),
+            "" | "n" | "no" => return Ok(Answer::No),
+            "a" | "all" => return Ok(Answer::All),
+            "q" | "quit" => return Ok(Answer::Quit),
+            _ => {}
+        }
+    }
+}
+
+/// Standard input to read `--interactive` answers from, if the flag was given
+///
+/// Answers are only read from a terminal, so a script cannot hang waiting
+/// for them, and the questions would get in the way of `--porcelain` and
+/// `--json` output.
+fn interactive_input(matches: &ArgMatches, format: OutputFormat) -> Result<Option<io::StdinLock<'static>>> {
+    if !matches.get_flag("interactive") {
+        return Ok(None);
+    }
+    if !format.is_human() {
+        return Err(TrsError::invalid_input("--interactive cannot be used with --porcelain or --json"));
+    }
+    if !io::stdin().is_terminal() {
+        return Err(TrsError::invalid_input("--interactive needs a terminal to ask on"));
+    }
+    Ok(Some(io::stdin().lock()))
+}
+
+/// Create the `--interactive` argument shared by the move commands
+fn interactive_arg() -> Arg {
+    Arg::new("interactive")
+        .short('i')
+        .long("interactive")
+        .action(ArgAction::SetTrue)
+        .help("Ask before trashing each file: y for yes, n for no, a for all remaining, q to stop")
+}
+
+/// Exit code of a command that processed `total` items, `failed` of which failed
+fn exit_code(failed: usize, total: usize) -> i32 {
+    if failed == 0 {
+        0
+    } else if failed == total {
+        1
+    } else {
+        2
+    }
+}
+
+/// Print the `--porcelain` result of one item: `ok` and its name, or `err`, its name and the reason
+fn print_status(name: &str, error: Option<&str>) {
+    match error {
+        None => println!("ok\t{}", escape_field(name)),
+        Some(reason) => println!("err\t{}\t{}", escape_field(name), escape_field(reason)),
+    }
+}
+
+/// Print the version, and with `build` the details recorded by the build script
+fn print_version(build: bool, format: OutputFormat) -> Result<()> {
+    let features: Vec<&str> = [("async", cfg!(feature = "async"))]
+        .into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect();
+    if format == OutputFormat::Json {
+        let mut version = serde_json::json!({ "version": env!("CARGO_PKG_VERSION") });
+        if build {
+            version["commit"] = env!("TRS_GIT_COMMIT").into();
+            version["rustc"] = env!("TRS_RUSTC_VERSION").into();
+            version["target"] = env!("TRS_TARGET").into();
+            version["features"] = features.into();
+        }
+        return print_json(&version);
+    }
+
+    println!("trs {}", env!("CARGO_PKG_VERSION"));
+    if build {
+        println!("commit: {}", env!("TRS_GIT_COMMIT"));
+        println!("rustc: {}", env!("TRS_RUSTC_VERSION"));
+        println!("target: {}", env!("TRS_TARGET"));
+        println!("features: {}", if features.is_empty() { "none".to_string() } else { features.join(", ") });
+    }
+    Ok(())
+}
+
+/// The `--color` choice
+fn color_choice(matches: &ArgMatches) -> ColorChoice {
+    matches.get_one::<String>("color")
+        .and_then(|name| ColorChoice::from_name(name))
+        .unwrap_or_default()
+}
+
+/// Gives the files written in the trash of the user who ran `sudo` back to them when dropped
+struct HandOver {
+    trash_dir: PathBuf,
+    user: &'static SudoUser,
+}
+
+impl Drop for HandOver {
+    fn drop(&mut self) {
+        if let Err(e) = hand_over_trash(&self.trash_dir, self.user) {
+            eprintln!("Failed to give the trash back to {}: {}", self.user.name, e);
+        }
+    }
+}
+
+/// Warns on stderr if the trash holds more than `threshold` bytes
+///
+/// Run once at the end of the command, so a batch of moves measures the
+/// trash once rather than after each item.
+struct CapacityCheck {
+    trash_dir: PathBuf,
+    /// The `metadata_name` config key, as the command ran with it
+    metadata_name: Option<String>,
+    threshold: u64,
+    color: bool,
+}
+
+impl CapacityCheck {
+    fn warn(self) {
+        let _metadata_name = self.metadata_name.as_deref().and_then(|name| metadata::use_metadata_name(name).ok());
+        match capacity_warning(&self.trash_dir, self.threshold) {
+            Ok(Some(warning)) => eprintln!("{} {}", Palette::new(self.color).notice("Warning:"), warning),
+            Ok(None) => {}
+            Err(e) => tracing::debug!(error = %e, "could not measure the trash"),
+        }
+    }
+}
+
+/// Say on stderr which trash is used when running as root under sudo
+///
+/// Items trashed with `sudo` are easily looked for later in the trash of
+/// the user who ran it, where they are not.
+fn sudo_note(user: &SudoUser, user_trash: bool, trash_dir: &Path, color: bool) {
+    let palette = Palette::new(color);
+    if user_trash {
+        eprintln!(
+            "{} running under sudo, using the trash of {} in {}",
+            palette.notice("Note:"),
+            user.name,
+            display_path(&trash_dir.to_string_lossy()),
+        );
+    } else {
+        eprintln!(
+            "{} running under sudo, using root's trash in {}; pass --user-trash to use the trash of {}",
+            palette.notice("Note:"),
+            display_path(&trash_dir.to_string_lossy()),
+            user.name,
+        );
+    }
+}
+
+/// Resolve the trash directory from the config or the default location
+fn trash_dir(config: &Config) -> PathBuf {
+    match config.string("trash_dir") {
+        Some(dir) => expand_tilde(&dir),
+        None => default_trash_dir(),
+    }
+}
+
+/// Build move options from parsed arguments and the config
+///
+/// With `--encrypt` the passphrase is read here, before anything is moved.
+fn move_options(matches: &ArgMatches, config: &Config) -> Result<MoveOptions> {
+    let mut options = config_move_options(config);
+    if let Some(jobs) = matches.get_one::<usize>("jobs") {
+        options.jobs = *jobs;
+    }
+    if let Some(threshold) = matches.get_one::<u64>("compress_threshold") {
+        options.compress_threshold = *threshold;
+    }
+    if let Some(policy) = matches.get_one::<String>("timestamps").and_then(|name| Timestamps::from_name(name)) {
+        options.timestamps = policy;
+    }
+    if let Some(compression) = matches.get_one::<String>("compression").and_then(|name| ArchiveCompression::from_name(name)) {
+        options.compression = compression;
+        options.compress = true;
+    }
+    if let Some(threads) = matches.get_one::<u32>("zstd_threads") {
+        options.zstd_threads = *threads;
+    }
+    if matches.get_flag("auto_compress") {
+        options.auto_compress = true;
+        options.compress = true;
+    }
+    if matches.get_flag("shred") {
+        let passes = matches.get_one::<usize>("shred_passes")
+            .copied()
+            .or_else(|| config.integer("shred_passes").map(|passes| passes.max(0) as usize))
+            .unwrap_or(1);
+        options.shred = Some(ShredOptions { passes, zero: true });
+    }
+    if matches.get_flag("encrypt") {
+        options.encrypt = Some(read_passphrase(true)?);
+    }
+    if !matches.get_flag("yes") && !matches.get_flag("force") {
+        options.confirm_above = Some(large_dir_limits(config));
+    }
+    options.check_space = !matches.get_flag("force");
+    options.force = matches.get_flag("force");
+    options.device_trash = matches.get_flag("device_trash");
+    options.prealloc = !matches.get_flag("no_prealloc");
+    options.strict = matches.get_flag("strict");
+    Ok(options)
+}
+
+/// Sizes from the config above which a directory is only trashed once confirmed, 0 meaning no limit
+fn large_dir_limits(config: &Config) -> LargeDirLimits {
+    let limit = |value: u64| if value == 0 { u64::MAX } else { value };
+    LargeDirLimits {
+        files: limit(config.integer("confirm_files").unwrap_or(10_000).max(0) as u64),
+        bytes: limit(config.size("confirm_size").unwrap_or(5_000_000_000)),
+    }
+}
+
+/// Create the `rm` flags the move commands accept so `trs rm` can stand in for `rm`
+///
+/// Directories are always trashed whole and every item moved is already
+/// reported, so `-r` and `-v` change nothing; `-f` is `--force`.
+fn rm_compat_args() -> [Arg; 2] {
+    [
+        Arg::new("recursive")
+            .short('r')
+            .short_alias('R')
+            .long("recursive")
+            .action(ArgAction::SetTrue)
+            .help("Accepted for compatibility with rm; directories are always trashed with their contents"),
+        Arg::new("verbose")
+            .short('v')
+            .long("verbose")
+            .action(ArgAction::SetTrue)
+            .help("Accepted for compatibility with rm; every item trashed is already reported"),
+    ]
+}
+
+/// Create the `--yes` and `--force` arguments shared by the move commands
+fn move_confirm_args() -> [Arg; 2] {
+    [
+        Arg::new("yes")
+            .long("yes")
+            .short('y')
+            .action(ArgAction::SetTrue)
+            .help("Trash directories above confirm_files or confirm_size without asking"),
+        Arg::new("force")
+            .long("force")
+            .short('f')
+            .action(ArgAction::SetTrue)
+            .help("Trash without asking about large directories or checking for free space first, making read-only directories writable"),
+    ]
+}
+
+/// Environment variable holding the passphrase for `--encrypt` and for restoring encrypted items
+const PASSPHRASE_VAR: &str = "TRS_PASSPHRASE";
+
+/// Read the passphrase for encrypted archives from `TRS_PASSPHRASE`, or ask for it on the terminal
+///
+/// A new passphrase is asked for twice, so a typo does not lock the item away.
+fn read_passphrase(new: bool) -> Result<Passphrase> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_VAR)
+        && !passphrase.is_empty()
+    {
+        return Ok(Passphrase::new(passphrase));
+    }
+    let no_terminal = |e: io::Error| {
+        TrsError::invalid_input(format!("Cannot ask for the passphrase ({}); set {} instead", e, PASSPHRASE_VAR))
+    };
+    let passphrase = rpassword::prompt_password("Passphrase: ").map_err(no_terminal)?;
+    if passphrase.is_empty() {
+        return Err(TrsError::invalid_input("The passphrase cannot be empty"));
+    }
+    if new && rpassword::prompt_password("Repeat passphrase: ").map_err(no_terminal)? != passphrase {
+        return Err(TrsError::invalid_input("The passphrases do not match"));
+    }
+    Ok(Passphrase::new(passphrase))
+}
+
+/// Ask for the passphrase once, when the first encrypted item `name` is about to be restored
+fn passphrase_for(name: &str, options: &mut RestoreOptions) -> Result<()> {
+    if crypto::is_encrypted(name) && options.passphrase.is_none() {
+        options.passphrase = Some(read_passphrase(false)?);
+    }
+    Ok(())
+}
+
+/// Create the `--encrypt` argument shared by the move commands
+fn encrypt_arg() -> Arg {
+    Arg::new("encrypt")
+        .long("encrypt")
+        .action(ArgAction::SetTrue)
+        .help("Encrypt archives with AES-256-GCM, using a passphrase from TRS_PASSPHRASE or asked for")
+}
+
+/// Build move options from the config alone
+fn config_move_options(config: &Config) -> MoveOptions {
+    let mut options = MoveOptions::default();
+    if let Some(jobs) = config.integer("jobs") {
+        options.jobs = jobs.max(1) as usize;
+    }
+    if let Some(size) = config.integer("buffer_size") {
+        options.buffer_size = size.max(1) as usize;
+    }
+    options.max_size = config.size("max_size");
+    if let Some(min_age) = config.duration("min_age") {
+        options.min_age = min_age;
+    }
+    if let Some(threshold) = config.size("compress_threshold") {
+        options.compress_threshold = threshold;
+    }
+    if let Some(policy) = config.string("timestamps").and_then(|name| Timestamps::from_name(&name)) {
+        options.timestamps = policy;
+    }
+    if let Some(compression) = config.string("compression").and_then(|name| ArchiveCompression::from_name(&name)) {
+        options.compression = compression;
+    }
+    if let Some(threads) = config.integer("zstd_threads") {
+        options.zstd_threads = threads.clamp(0, u32::MAX as i64) as u32;
+    }
+    options.auto_compress = config.boolean("auto_compress").unwrap_or(false);
+    options.dedupe = config.boolean("dedupe").unwrap_or(false);
+    // Files are only archived on the trash's filesystem when a compression is chosen
+    options.compress = matches!(config.get("compression"), Ok((_, ValueSource::File))) || options.auto_compress;
+    options
+}
+
+/// Build shred options when `--shred` was passed
+fn shred_options(matches: &ArgMatches, config: &Config) -> Option<ShredOptions> {
+    if !matches.get_flag("shred") {
+        return None;
+    }
+    let passes = matches.get_one::<usize>("passes")
+        .copied()
+        .or_else(|| config.integer("shred_passes").map(|passes| passes.max(0) as usize))
+        .unwrap_or(1);
+    Some(ShredOptions {
+        passes,
+        zero: matches.get_flag("zero"),
+    })
+}
+
+/// Create the arguments controlling `--shred`
+fn shred_args() -> [Arg; 3] {
+    [
+        Arg::new("shred")
+            .long("shred")
+            .action(ArgAction::SetTrue)
+            .help("Overwrite data before deleting it (best effort on SSDs and copy-on-write filesystems)"),
+        Arg::new("passes")
+            .long("passes")
+            .value_name("N")
+            .value_parser(value_parser!(usize))
+            .requires("shred")
+            .help("Number of random-data overwrite passes (defaults to shred_passes from the config)"),
+        Arg::new("zero")
+            .long("zero")
+            .action(ArgAction::SetTrue)
+            .requires("shred")
+            .help("Finish with a pass of zeros"),
+    ]
+}
+
+/// Create the arguments controlling `--shred` on the move commands
+fn move_shred_args() -> [Arg; 2] {
+    [
+        Arg::new("shred")
+            .long("shred")
+            .action(ArgAction::SetTrue)
+            .help("Overwrite files with random data and then zeros once archived, before removing them (best effort on SSDs)"),
+        Arg::new("shred_passes")
+            .long("shred-passes")
+            .value_name("N")
+            .value_parser(value_parser!(usize))
+            .requires("shred")
+            .help("Number of random-data overwrite passes (defaults to shred_passes from the config)"),
+    ]
+}
+
+/// Handle the `empty` subcommand
+fn run_empty(matches: &ArgMatches, config: &Config, trash_dir: &Path, format: OutputFormat) -> Result<()> {
+    let options = EmptyOptions {
+        older_than: matches.get_one::<String>("older_than").map(|raw| parse_duration(raw)).transpose()?,
+        patterns: matches.get_many::<String>("pattern").map(|patterns| patterns.cloned().collect()).unwrap_or_default(),
+        shred: shred_options(matches, config),
+        include_pinned: matches.get_flag("include_pinned"),
+        duplicates: if matches.get_flag("duplicates") {
+            let keep = matches.get_one::<String>("keep").map_or("newest", String::as_str);
+            Some(KeepDuplicate::from_name(keep).ok_or_else(|| TrsError::invalid_input(format!("Unknown --keep '{}'", keep)))?)
+        } else {
+            None
+        },
+        // Without --duplicates, --keep is a number of items
+        keep_newest: match matches.get_one::<String>("keep") {
+            Some(raw) if !matches.get_flag("duplicates") => Some(raw.parse().map_err(|_| {
+                TrsError::invalid_input(format!("--keep takes a number of items, or newest or oldest with --duplicates, not '{}'", raw))
+            })?),
+            _ => None,
+        },
+    };
+    let exact = matches.get_flag("bytes");
+
+    if matches.get_flag("dry_run") {
+        let entries = empty_candidates(trash_dir, &options)?;
+        if format == OutputFormat::Json {
+            let mut summary = EmptySummary::default();
+            for entry in entries {
+                summary.size += entry_size(&trash_dir.join(&entry.name))?;
+                summary.deleted.push(entry.name);
+            }
+            return print_json(&summary);
+        }
+        if entries.is_empty() {
+            println!("Nothing would be deleted.");
+            return Ok(());
+        }
+
+        let mut total = 0;
+        println!("{:<30} {:>12}", "Name", "Size");
+        for entry in &entries {
+            let size = entry_size(&trash_dir.join(&entry.name))?;
+            total += size;
+            println!("{:<30} {:>12}", entry.name, display_size(size, exact));
+        }
+        println!("Would delete {} item(s), freeing {}", entries.len(), display_size(total, exact));
+        return Ok(());
+    }
 
-    let trash_dir = dirs::data_local_dir()
-        .expect("Could not find local share directory")
-        .join("trash");
+    // Show what the patterns, --duplicates or --keep matched before deleting anything
+    if (!options.patterns.is_empty() || options.duplicates.is_some() || options.keep_newest.is_some()) && !matches.get_flag("yes") {
+        if format == OutputFormat::Json {
+            return Err(TrsError::invalid_input("--json needs --yes to delete items matching patterns, duplicates or --keep"));
+        }
+        let entries = empty_candidates(trash_dir, &options)?;
+        let total = entries.iter().map(|entry| entry.size).sum();
+        println!("Matched {} item(s), {}:", entries.len(), display_size(total, exact));
+        for entry in &entries {
+            println!(
+                "  {} ({}, {})",
+                entry.name,
+                entry.original_path.as_deref().map(display_path).unwrap_or("Unknown".into()),
+                display_size(entry.size, exact)
+            );
+        }
+        if !confirm("Permanently delete these items?")? {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    let message = if options.shred.is_some() { "Shredding Trash..." } else { "Emptying Trash..." };
+    let _guard = interrupt::Guard::new();
+    let summary = empty_trash(trash_dir, &options, &format.progress(message))?;
+    if format == OutputFormat::Json {
+        print_json(&summary)?;
+    }
+    Ok(())
+}
+
+/// Handle the `purge` subcommand, returning the exit code
+///
+/// With `--json` every name is attempted and reported, like `move`;
+/// otherwise the first failure stops the command.
+fn run_purge(matches: &ArgMatches, config: &Config, trash_dir: &Path, format: OutputFormat) -> Result<i32> {
+    let shred = shred_options(matches, config);
+    let names = matches.get_many::<String>("name").unwrap();
+    if format == OutputFormat::Json {
+        let results: Vec<_> = names
+            .map(|name| ItemResult::new(name, purge_entry(trash_dir, name, shred.as_ref(), &NoOpProgress)))
+            .collect();
+        print_json(&results)?;
+        let failed = results.iter().filter(|result| !result.is_ok()).count();
+        return Ok(exit_code(failed, results.len()));
+    }
+
+    for name in names {
+        if shred.is_some() {
+            purge_entry(trash_dir, name, shred.as_ref(), &format.progress(format!("Shredding {}", name)))?;
+        } else {
+            purge_entry(trash_dir, name, None, &NoOpProgress)?;
+        }
+    }
+    Ok(0)
+}
+
+/// Handle the `undo` subcommand
+fn run_undo(trash_dir: &Path, format: OutputFormat) -> Result<()> {
+    let undone = undo_last(trash_dir, &format.progress("Undoing"))?;
+    match format {
+        OutputFormat::Json => print_json(&undone)?,
+        OutputFormat::Porcelain => match &undone {
+            Undone::Move(restored) => print_status(&restored.path, None),
+            Undone::Restore(moved) => print_status(&moved.original_path, None),
+        },
+        OutputFormat::Human => {}
+    }
+    Ok(())
+}
+
+/// Handle the `pin` and `unpin` subcommands, returning the exit code
+///
+/// With `--json` every name is attempted and reported, like `purge`;
+/// otherwise the first failure stops the command.
+fn run_pin(matches: &ArgMatches, trash_dir: &Path, pinned: bool, format: OutputFormat) -> Result<i32> {
+    let names = matches.get_many::<String>("name").unwrap();
+    if format == OutputFormat::Json {
+        let results: Vec<_> = names
+            .map(|name| {
+                let result = pin_trash_item(trash_dir, name, pinned).map(|trash_name| serde_json::json!({ "trash_name": trash_name }));
+                ItemResult::new(name, result)
+            })
+            .collect();
+        print_json(&results)?;
+        let failed = results.iter().filter(|result| !result.is_ok()).count();
+        return Ok(exit_code(failed, results.len()));
+    }
+
+    for name in names {
+        let trash_name = pin_trash_item(trash_dir, name, pinned)?;
+        match format {
+            OutputFormat::Porcelain => print_status(&trash_name, None),
+            _ => println!("{} {}", if pinned { "Pinned" } else { "Unpinned" }, trash_name),
+        }
+    }
+    Ok(0)
+}
+
+/// Handle the `show` subcommand
+///
+/// With `all_users`, the trash directories listed with the name of the
+/// user owning each are shown as one listing instead.
+fn run_show(
+    matches: &ArgMatches,
+    trash_dir: &Path,
+    store: Option<&Path>,
+    all_users: Option<&[(PathBuf, String); 2]>,
+    format: OutputFormat,
+    color: ColorChoice,
+) -> Result<()> {
+    let name = matches.get_one::<String>("name");
+    let json_lines = matches.get_flag("json_lines");
+    let options = ShowOptions {
+        age: matches.get_flag("age"),
+        size: matches.get_flag("size"),
+        bytes: matches.get_flag("bytes"),
+        newer_than: matches.get_one::<String>("newer_than").map(|raw| parse_duration(raw)).transpose()?,
+        older_than: matches.get_one::<String>("older_than").map(|raw| parse_duration(raw)).transpose()?,
+        pinned: matches.get_flag("pinned"),
+        files_only: matches.get_flag("files_only"),
+        dirs_only: matches.get_flag("dirs_only"),
+        duplicates: matches.get_flag("duplicates"),
+        missing_parent: matches.get_flag("missing_parent"),
+        owner: matches.get_flag("owner"),
+        // The contents of a single item are drawn unless asked not to
+        tree: if name.is_some() { !matches.get_flag("no_tree") } else { matches.get_flag("tree") },
+        depth: matches.get_one::<usize>("depth").copied().unwrap_or(DEFAULT_TREE_DEPTH),
+        no_header: matches.get_flag("no_header"),
+        porcelain: format == OutputFormat::Porcelain,
+        color: format.is_human() && color.enabled(),
+    };
+    if let Some(name) = name {
+        // With desktop sync, look in the trs trash directory for items not in the desktop trash
+        let trash_dir = match (store, lookup_entry(trash_dir, name)) {
+            (Some(store), Err(TrsError::NotInTrash(_))) => store,
+            _ => trash_dir,
+        };
+        if format == OutputFormat::Json {
+            return print_json(&item_details(trash_dir, name)?);
+        }
+        return show_item_details(trash_dir, name, &options);
+    }
+
+    // Under sudo, root's trash and the one of the user who ran it, as one listing
+    if let Some(trashes) = all_users {
+        let mut entries = Vec::new();
+        for (dir, user) in trashes {
+            for mut entry in shown_items(dir, &options)? {
+                entry.user.get_or_insert_with(|| user.clone());
+                entries.push(SourcedEntry { entry, backend: TrashBackend::Trs, trash_dir: dir.clone() });
+            }
+        }
+        if json_lines {
+            for sourced in &entries {
+                print_entry_line(sourced, sourced.entry.original_path.as_deref(), options.missing_parent)?;
+            }
+            return Ok(());
+        }
+        if format == OutputFormat::Json {
+            return print_json(&entries);
+        }
+        return show_sourced_entries(&entries, false, &ShowOptions { owner: true, ..options });
+    }
+
+    // Items kept on other filesystems with --device-trash
+    let device_trashes = device::known_trash_dirs();
+
+    // With desktop sync, list both stores as one, with the backend of each item
+    if let Some(store) = store
+        && !options.duplicates
+    {
+        let sources: Vec<_> = [(trash_dir.to_path_buf(), TrashBackend::Desktop), (store.to_path_buf(), TrashBackend::Trs)]
+            .into_iter()
+            .chain(device_trashes.into_iter().map(|dir| (dir, TrashBackend::Trs)))
+            .collect();
+        if json_lines {
+            for sourced in shown_entries(&sources, &options)? {
+                print_entry_line(&sourced, sourced.entry.original_path.as_deref(), options.missing_parent)?;
+            }
+            return Ok(());
+        }
+        if format == OutputFormat::Json {
+            let entries = shown_entries(&sources, &options)?;
+            if options.missing_parent {
+                return print_json(&entries.into_iter()
+                    .map(|sourced| MissingParentEntry {
+                        deepest_existing_ancestor: sourced.entry.original_path.as_deref().and_then(deepest_existing_ancestor),
+                        entry: sourced,
+                    })
+                    .collect::<Vec<_>>());
+            }
+            return print_json(&entries);
+        }
+        return show_merged_contents(&sources, &options);
+    }
+
+    if options.duplicates && format == OutputFormat::Json {
+        let mut groups = duplicate_groups(trash_dir)?;
+        for dir in &device_trashes {
+            groups.extend(duplicate_groups(dir)?);
+        }
+        return print_json(&groups);
+    }
+
+    // Entries are written as they are read, without collecting them
+    if json_lines {
+        for dir in std::iter::once(trash_dir).chain(device_trashes.iter().map(PathBuf::as_path)) {
+            stream_items(dir, &options, |entry| print_entry_line(&entry, entry.original_path.as_deref(), options.missing_parent))?;
+        }
+        return Ok(());
+    }
+
+    if format == OutputFormat::Json {
+        let mut entries = shown_items(trash_dir, &options)?;
+        for dir in &device_trashes {
+            entries.extend(shown_items(dir, &options)?);
+        }
+        if options.missing_parent {
+            return print_json(&entries.into_iter()
+                .map(|entry| MissingParentEntry {
+                    deepest_existing_ancestor: entry.original_path.as_deref().and_then(deepest_existing_ancestor),
+                    entry,
+                })
+                .collect::<Vec<_>>());
+        }
+        return print_json(&entries);
+    }
+
+    show_trash_contents(trash_dir, &options)?;
+    for dir in &device_trashes {
+        if list_trash_items(dir)?.is_empty() {
+            continue;
+        }
+        if !options.no_header && format.is_human() {
+            println!();
+            println!("In {}:", dir.display());
+        }
+        show_trash_contents(dir, &options)?;
+    }
+    Ok(())
+}
+
+/// Print one entry for `show --json-lines`, with its deepest existing ancestor when `missing_parent` is set
+fn print_entry_line(entry: &impl Serialize, original_path: Option<&str>, missing_parent: bool) -> Result<()> {
+    if missing_parent {
+        return print_json_line(&MissingParentEntry {
+            entry,
+            deepest_existing_ancestor: original_path.and_then(deepest_existing_ancestor),
+        });
+    }
+    print_json_line(entry)
+}
+
+/// Ask a yes/no question on stdin, defaulting to no
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Handle the `info` subcommand
+fn run_info(matches: &ArgMatches, trash_dir: &Path, format: OutputFormat) -> Result<()> {
+    let name = matches.get_one::<String>("name").unwrap();
+    let info = item_info(trash_dir, name)?;
+
+    if format == OutputFormat::Json {
+        return print_json(&info);
+    }
+
+    println!("{:<18} {}", "Name:", info.name);
+    println!("{:<18} {}", "Original Location:", info.original_path.as_deref().map(display_path).unwrap_or("Unknown".into()));
+    println!("{:<18} {}", "Type:", if info.is_dir { "Directory" } else { "File" });
+    println!("{:<18} {}", "Deleted:", info.deleted_at.map(format_timestamp).unwrap_or_else(|| "Unknown".to_string()));
+    println!("{:<18} {}", "Size:", display_size(info.size, matches.get_flag("bytes")));
+    println!("{:<18} {}", "Compression:", info.compression);
+    if !info.contents.is_empty() {
+        println!("Contents:");
+        for path in &info.contents {
+            println!("  {}", path);
+        }
+    }
+    if !info.skipped.is_empty() {
+        println!("Skipped (could not be read, left in place):");
+        for path in &info.skipped {
+            println!("  {}", display_path(path));
+        }
+    }
+    Ok(())
+}
+
+/// Handle the `stats` subcommand
+fn run_stats(matches: &ArgMatches, trash_dir: &Path, format: OutputFormat) -> Result<()> {
+    let stats = trash_stats(trash_dir)?;
+
+    if format == OutputFormat::Json {
+        return print_json(&stats);
+    }
+
+    let exact = matches.get_flag("bytes");
+    let date = |secs: Option<u64>| secs.map(format_timestamp).unwrap_or_else(|| "-".to_string());
+    let original_size = if stats.unknown_original_count == stats.item_count && stats.item_count > 0 {
+        "unknown".to_string()
+    } else if stats.unknown_original_count > 0 {
+        format!("{} ({} item(s) unknown)", display_size(stats.original_size, exact), stats.unknown_original_count)
+    } else {
+        display_size(stats.original_size, exact)
+    };
+
+    println!("{:<17} {} ({} file(s), {} directory(ies))", "Items:", stats.item_count, stats.file_count, stats.dir_count);
+    println!("{:<17} {}", "Compressed size:", display_size(stats.compressed_size, exact));
+    println!("{:<17} {}", "Original size:", original_size);
+    println!("{:<17} {}", "Oldest deletion:", date(stats.oldest));
+    println!("{:<17} {}", "Newest deletion:", date(stats.newest));
+    if !stats.largest.is_empty() {
+        println!("Largest items:");
+        for entry in &stats.largest {
+            println!("  {:<30} {:>12}", entry.name, display_size(entry.size, exact));
+        }
+    }
+    Ok(())
+}
+
+/// Handle the `export` subcommand
+fn run_export(matches: &ArgMatches, trash_dir: &Path, format: OutputFormat) -> Result<()> {
+    let output = expand_tilde(matches.get_one::<String>("output").unwrap());
+    let compress = matches.get_one::<String>("compress").unwrap();
+    let options = ExportOptions {
+        compression: BundleCompression::from_name(compress)
+            .ok_or_else(|| TrsError::invalid_input(format!("Unknown compression '{}'", compress)))?,
+        overwrite: matches.get_flag("overwrite"),
+    };
+    let count = export_trash(trash_dir, &output, &options, &format.progress("Exporting Trash"))?;
+    if format == OutputFormat::Json {
+        print_json(&serde_json::json!({ "output": output, "items": count }))?;
+    }
+    Ok(())
+}
+
+/// Handle the `import` subcommand
+fn run_import(matches: &ArgMatches, config: &Config, trash_dir: &Path, format: OutputFormat) -> Result<()> {
+    let bundle = expand_tilde(matches.get_one::<String>("bundle").unwrap());
+    let original_path = matches.get_one::<String>("original_path").map(|path| expand_tilde(path));
+    let trashinfo = bundle.extension().is_some_and(|extension| extension == "trashinfo");
+    if trashinfo && (matches.get_flag("dry_run") || matches.get_flag("replace")) {
+        return Err(TrsError::invalid_input("--dry-run and --replace only apply to bundles"));
+    }
+    if matches.get_flag("item") || original_path.is_some() || trashinfo {
+        return import_item(&bundle, original_path.as_deref(), trashinfo, config, trash_dir, format);
+    }
+    if !bundle.is_file() {
+        return Err(TrsError::SourceNotFound(bundle));
+    }
+    let options = ImportOptions {
+        replace: matches.get_flag("replace"),
+    };
+
+    if matches.get_flag("dry_run") {
+        let plan = plan_import(trash_dir, &bundle, &options)?;
+        if format == OutputFormat::Json {
+            return print_json(&plan);
+        }
+        if plan.is_empty() {
+            println!("Nothing would be imported.");
+            return Ok(());
+        }
+
+        let exact = matches.get_flag("bytes");
+        let mut count = 0;
+        let mut total = 0;
+        println!("{:<30} {:>12} Action", "Name", "Size");
+        for item in &plan {
+            let action = match item.action {
+                ImportAction::Add if item.local_name != item.name => format!("add as {}", item.local_name),
+                ImportAction::Add => "add".to_string(),
+                ImportAction::Replace => "replace".to_string(),
+                ImportAction::Skip => "skip, already in Trash".to_string(),
+            };
+            if item.action != ImportAction::Skip {
+                count += 1;
+                total += item.size;
+            }
+            println!("{:<30} {:>12} {}", item.name, display_size(item.size, exact), action);
+        }
+        println!("Would import {} item(s), {}", count, display_size(total, exact));
+        return Ok(());
+    }
+
+    let imported = import_trash(trash_dir, &bundle, &options, &format.progress("Importing Trash"))?;
+    if format == OutputFormat::Json {
+        print_json(&imported)?;
+    }
+    Ok(())
+}
+
+/// Import a single file or directory, or the XDG trash item described by a `.trashinfo` file
+fn import_item(path: &Path, original_path: Option<&Path>, trashinfo: bool, config: &Config, trash_dir: &Path, format: OutputFormat) -> Result<()> {
+    let options = config_move_options(config);
+    let progress = format.progress(format!("Importing {}", path.display()));
+    let moved = if trashinfo {
+        import_trashinfo(trash_dir, path, &options, &progress)?
+    } else {
+        let original_path = original_path.map(|path| path.to_string_lossy());
+        import_path(trash_dir, path, original_path.as_deref(), &options, &progress)?
+    };
+    match format {
+        OutputFormat::Json => print_json(&moved)?,
+        OutputFormat::Porcelain => print_status(&moved.trash_name, None),
+        OutputFormat::Human => println!("Recorded as trashed from {}", display_path(&moved.original_path)),
+    }
+    Ok(())
+}
+
+/// Handle the `import-system-trash` subcommand
+///
+/// Items always go to the trs trash directory, whichever trash the other
+/// options select. Every item is attempted, and the exit code follows
+/// `move_files`.
+fn run_import_system_trash(matches: &ArgMatches, config: &Config, trash_dir: &Path, format: OutputFormat) -> Result<i32> {
+    let root = xdg::home_trash();
+    if !root.join("info").is_dir() {
+        return Err(TrsError::invalid_input(format!("There is no system trash at {}", root.display())));
+    }
+    let items = system_trash_items(&root)?;
+    let exact = matches.get_flag("bytes");
+
+    if matches.get_flag("dry_run") {
+        match format {
+            OutputFormat::Json => print_json(&items)?,
+            OutputFormat::Porcelain => {
+                for item in &items {
+                    println!("{}\t{}", escape_field(&item.name), escape_field(&item.original_path));
+                }
+            }
+            OutputFormat::Human if items.is_empty() => println!("Nothing would be imported."),
+            OutputFormat::Human => {
+                println!("{:<30} {:>12} {:<19} Original Location", "Name", "Size", "Deleted");
+                for item in &items {
+                    let deleted = item.deleted_at.map(format_timestamp);
+                    println!(
+                        "{:<30} {:>12} {:<19} {}",
+                        item.name,
+                        display_size(item.size, exact),
+                        deleted.as_deref().unwrap_or("-"),
+                        display_path(&item.original_path)
+                    );
+                }
+                let total = items.iter().map(|item| item.size).sum();
+                println!("Would import {} item(s), {}", items.len(), display_size(total, exact));
+            }
+        }
+        return Ok(0);
+    }
+
+    let options = config_move_options(config);
+    let mut results = Vec::new();
+    for item in &items {
+        let progress = format.progress(format!("Importing {}", item.name));
+        let result = ItemResult::new(&item.name, import_system_item(trash_dir, &root, item, &options, &progress));
+        if format == OutputFormat::Porcelain {
+            print_status(&item.name, result.error.as_deref());
+        }
+        results.push(result);
+    }
+
+    let failed = results.iter().filter(|result| !result.is_ok()).count();
+    match format {
+        OutputFormat::Human => {
+            println!("Imported {} of {} item(s) from {}", results.len() - failed, results.len(), root.display());
+            for result in &results {
+                if let Some(e) = &result.error {
+                    eprintln!("Failed to import {}: {}", result.source, e);
+                }
+            }
+        }
+        OutputFormat::Porcelain => {}
+        OutputFormat::Json => print_json(&results)?,
+    }
+    Ok(exit_code(failed, results.len()))
+}
+
+/// Schedule `files` to be moved to the trash at `due`, without touching them now
+fn schedule_files(files: &[&String], due: &str, trash_dir: &Path, format: OutputFormat) -> Result<i32> {
+    let due_at = parse_datetime(due)?;
+    if due_at <= now_secs() {
+        return Err(TrsError::invalid_input(format!("{} is in the past", format_timestamp(due_at))));
+    }
+    let mut results = Vec::new();
+    for file in files {
+        let result = ItemResult::new(file.as_str(), schedule::schedule(trash_dir, &expand_tilde(file), due_at));
+        match format {
+            OutputFormat::Human => match (&result.result, &result.error) {
+                (Some(item), _) => println!("Scheduled {} for {} (id {})", file, format_timestamp(item.due_at), item.id),
+                (_, Some(e)) => eprintln!("Failed to schedule {}: {}", file, e),
+                _ => {}
+            },
+            OutputFormat::Porcelain => print_status(file, result.error.as_deref()),
+            OutputFormat::Json => {}
+        }
+        results.push(result);
+    }
+    if format == OutputFormat::Json {
+        print_json(&results)?;
+    }
+    let failed = results.iter().filter(|result| !result.is_ok()).count();
+    Ok(exit_code(failed, results.len()))
+}
+
+/// Handle the `schedule` subcommand
+fn run_schedule(matches: &ArgMatches, config: &Config, trash_dir: &Path, format: OutputFormat) -> Result<i32> {
+    match matches.subcommand() {
+        Some(("list", _)) => {
+            let items = schedule::read_schedule(trash_dir)?;
+            match format {
+                OutputFormat::Json => print_json(&items)?,
+                OutputFormat::Porcelain => {
+                    for item in &items {
+                        println!("{}\t{}\t{}", item.id, item.due_at, escape_field(&item.path));
+                    }
+                }
+                OutputFormat::Human if items.is_empty() => println!("Nothing is scheduled."),
+                OutputFormat::Human => {
+                    println!("{:<5} {:<19} Path", "ID", "Due");
+                    for item in &items {
+                        println!("{:<5} {:<19} {}", item.id, format_timestamp(item.due_at), display_path(&item.path));
+                    }
+                }
+            }
+            Ok(0)
+        }
+        Some(("run", _)) => {
+            let due = schedule::due(trash_dir, now_secs())?;
+            let options = config_move_options(config);
+            let mut results = Vec::new();
+            let mut done = Vec::new();
+            for item in &due {
+                let progress = format.progress(format!("Moving {} to Trash", item.path));
+                let result = move_to_trash(&item.path, trash_dir, &options, &progress);
+                // An item that is already gone has nothing left to trash
+                if matches!(result, Ok(_) | Err(TrsError::SourceNotFound(_))) {
+                    done.push(item.id);
+                }
+                let result = ItemResult::new(&item.path, result);
+                if format == OutputFormat::Porcelain {
+                    print_status(&item.path, result.error.as_deref());
+                }
+                results.push(result);
+            }
+            schedule::remove(trash_dir, &done)?;
+
+            let failed = results.iter().filter(|result| !result.is_ok()).count();
+            match format {
+                OutputFormat::Human if results.is_empty() => println!("Nothing is due."),
+                OutputFormat::Human => {
+                    for result in &results {
+                        if let Some(e) = &result.error {
+                            eprintln!("Failed to move {}: {}", result.source, e);
+                        }
+                    }
+                }
+                OutputFormat::Porcelain => {}
+                OutputFormat::Json => print_json(&results)?,
+            }
+            Ok(exit_code(failed, results.len()))
+        }
+        Some(("cancel", sub_m)) => {
+            let item = schedule::cancel(trash_dir, *sub_m.get_one::<u64>("id").unwrap())?;
+            match format {
+                OutputFormat::Json => print_json(&item)?,
+                OutputFormat::Porcelain => print_status(&item.path, None),
+                OutputFormat::Human => println!("Cancelled deletion of {}", display_path(&item.path)),
+            }
+            Ok(0)
+        }
+        _ => unreachable!("subcommand is required"),
+    }
+}
+
+/// Retention period for a number of days
+fn retention(days: i64) -> Duration {
+    Duration::from_secs(days.max(0) as u64 * 24 * 60 * 60)
+}
+
+/// Handle the `prune` subcommand
+fn run_prune(matches: &ArgMatches, config: &Config, trash_dir: &Path, format: OutputFormat) -> Result<()> {
+    let retention_days = matches.get_one::<i64>("retention_days")
+        .copied()
+        .or_else(|| config.integer("retention_days"));
+    let max_size = match matches.get_one::<String>("max_size") {
+        Some(raw) => Some(parse_size(raw)?),
+        None => config.size("max_size"),
+    };
+    if retention_days.is_none() && max_size.is_none() {
+        return Err(TrsError::invalid_input(
+            "Nothing to prune; pass --retention-days or --max-size, or set them in the config",
+        ));
+    }
+
+    let mut pruned = Vec::new();
+    if let Some(days) = retention_days {
+        pruned.extend(prune_older_than(trash_dir, retention(days))?);
+    }
+    if let Some(max_size) = max_size {
+        let min_age = match matches.get_one::<String>("min_age") {
+            Some(raw) => parse_duration(raw)?,
+            None => config.duration("min_age").unwrap_or_default(),
+        };
+        pruned.extend(prune_to_size(trash_dir, max_size, min_age, None)?);
+    }
+
+    if format == OutputFormat::Json {
+        return print_json(&serde_json::json!({ "deleted": pruned }));
+    }
+    for name in &pruned {
+        println!("Deleted {} from Trash", name);
+    }
+    println!("Pruned {} item(s)", pruned.len());
+    Ok(())
+}
+
+/// An item, or a member of one, put back by `restore`, as printed by `--json`
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Restored {
+    Item(RestoredItem),
+    Member {
+        trash_name: String,
+        member: String,
+        /// Location the member was extracted to
+        path: PathBuf,
+    },
+}
+
+/// Handle the `restore` subcommand, returning the exit code
+fn run_restore(matches: &ArgMatches, config: &Config, trash_dir: &Path, store: Option<&Path>, format: OutputFormat) -> Result<i32> {
+    let mut options = restore_options(config);
+    options.create_parents = matches.get_flag("restore_parent");
+    if let Some(batch) = matches.get_one::<String>("batch") {
+        return restore_batch(batch, trash_dir, store, &options, format);
+    }
+    if let Some(pattern) = matches.get_one::<String>("pattern") {
+        return restore_pattern(pattern, matches.get_flag("yes"), trash_dir, store, &options, format);
+    }
+    options.destination = matches.get_one::<String>("to").map(|path| expand_tilde(path));
+    if matches.get_flag("merge") {
+        let prefer = matches.get_one::<String>("prefer").unwrap();
+        options.merge = Some(MergePreference::from_name(prefer).ok_or_else(|| TrsError::invalid_input(format!("Unknown --prefer '{}'", prefer)))?);
+    }
+
+    let Some(entry) = matches.get_one::<String>("entry") else {
+        if !format.is_human() {
+            return Err(TrsError::invalid_input("--porcelain and --json need an entry or --batch to restore"));
+        }
+        interactive_restore(trash_dir, &options, &IndicatifProgress::new("Restoring from Trash"))?;
+        return Ok(0);
+    };
+
+    if matches.get_flag("preview") && !preview_restore(entry, matches.get_flag("yes"), trash_dir, store, &options, format)? {
+        return Ok(0);
+    }
+
+    let progress = format.progress("Restoring from Trash");
+    let result = locate(trash_dir, store, entry).and_then(|(trash_dir, name)| {
+        passphrase_for(&name, &mut options)?;
+        match matches.get_one::<String>("member") {
+            Some(member) => restore_member(&name, member, &trash_dir, &options, &progress).map(|path| {
+                Restored::Member { trash_name: name, member: member.clone(), path }
+            }),
+            None => {
+                let mut restored = restore_from_trash(&name, &trash_dir, &options, &progress);
+                // A missing parent directory is refused until someone chooses what to do
+                if let Err(TrsError::ParentMissing { path, missing }) = &restored
+                    && format.is_human()
+                    && io::stdin().is_terminal()
+                    && let Some(chosen) = choose_destination(path, missing, &options, &mut io::stdin().lock())?
+                {
+                    restored = restore_from_trash(&name, &trash_dir, &chosen, &progress);
+                }
+                restored.map(Restored::Item)
+            }
+        }
+    });
+    let result = match format {
+        OutputFormat::Human => {
+            if let Restored::Item(restored) = result? {
+                print_conflicts(&restored);
+            }
+            return Ok(0);
+        }
+        OutputFormat::Porcelain => {
+            let result = ItemResult::new(entry, result);
+            print_status(entry, result.error.as_deref());
+            result
+        }
+        OutputFormat::Json => {
+            let result = ItemResult::new(entry, result);
+            print_json(&[&result])?;
+            result
+        }
+    };
+    Ok(if result.is_ok() { 0 } else { 1 })
+}
+
+/// Ask where to restore an item to `path`, whose directory `missing` no longer exists
+///
+/// The choices are recreating the missing directories, restoring into the
+/// home directory under the same name, or typing another path. Returns the
+/// options to restore with, or `None` if the restore is cancelled, which an
+/// empty answer or the end of the input does.
+fn choose_destination(path: &Path, missing: &Path, options: &RestoreOptions, input: &mut dyn BufRead) -> Result<Option<RestoreOptions>> {
+    let home = dirs::home_dir().and_then(|home| Some(home.join(path.file_name()?)));
+    println!("{} no longer exists. Restore:", display_path(&missing.to_string_lossy()));
+    println!("  [r] to {}, recreating the missing directories", display_path(&path.to_string_lossy()));
+    if let Some(home) = &home {
+        println!("  [h] to {} in the home directory", display_path(&home.to_string_lossy()));
+    }
+    println!("  [o] to another path");
+    loop {
+        print!("Where to? [r/h/o/N] ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            println!();
+            return Ok(None);
+        }
+        let destination = match answer.trim().to_ascii_lowercase().as_str() {
+            "r" => return Ok(Some(RestoreOptions { create_parents: true, ..options.clone() })),
+            "h" if home.is_some() => home.clone(),
+            "o" => {
+                print!("Restore to: ");
+                io::stdout().flush()?;
+                let mut other = String::new();
+                input.read_line(&mut other)?;
+                match other.trim() {
+                    "" => continue,
+                    other => Some(expand_tilde(other)),
+                }
+            }
+            "" | "n" | "no" => return Ok(None),
+            _ => continue,
+        };
+        return Ok(Some(RestoreOptions { destination, ..options.clone() }));
+    }
+}
+
+/// Print which copy was kept of each file a directory restored with `--merge` had in common with the one there
+fn print_conflicts(restored: &RestoredItem) {
+    if restored.conflicts.is_empty() {
+        return;
+    }
+    let replaced = restored.conflicts.iter().filter(|conflict| conflict.replaced).count();
+    println!(
+        "{} file(s) already existed: {} replaced with the trashed copy, {} kept",
+        restored.conflicts.len(),
+        replaced,
+        restored.conflicts.len() - replaced
+    );
+    for conflict in &restored.conflicts {
+        let decision = if conflict.replaced { "replaced" } else { "kept" };
+        println!("  {:<9} {}", decision, display_path(&conflict.path));
+    }
+}
 
-    if let Some(default_files) = matches.get_many::<String>("default_file") {
-        // Process multiple files for the default command
-        for file in default_files {
-            move_to_trash(file, &trash_dir)?;
+/// Print what restoring `entry` would write, returning whether to go on and restore it
+///
+/// People are asked unless `yes` is set. With `--porcelain` and `--json`
+/// the preview is only printed: one path per line, or a JSON object.
+fn preview_restore(entry: &str, yes: bool, trash_dir: &Path, store: Option<&Path>, options: &RestoreOptions, format: OutputFormat) -> Result<bool> {
+    if yes && !format.is_human() {
+        return Err(TrsError::invalid_input("--porcelain and --json only print the preview; restore without --preview"));
+    }
+    let (trash_dir, name) = locate(trash_dir, store, entry)?;
+    let preview = restore_preview(&name, &trash_dir, options)?;
+    match format {
+        OutputFormat::Json => print_json(&preview)?,
+        OutputFormat::Porcelain => {
+            for path in &preview.contents {
+                println!("{}", escape_field(path));
+            }
         }
-    } else {
-        match matches.subcommand() {
-            Some(("move", sub_m)) => {
-                // Process multiple files for the move command
-                if let Some(files) = sub_m.get_many::<String>("file") {
-                    for file in files {
-                        move_to_trash(file, &trash_dir)?;
+        OutputFormat::Human => {
+            println!("{:<12} {}", "Restore to:", display_path(&preview.destination));
+            for dir in &preview.missing_parents {
+                let action = if options.create_parents { "will be created" } else { "is missing; pass --restore-parent to create it" };
+                println!("{:<12} {} {}", "Directory:", display_path(dir), action);
+            }
+            if !preview.is_dir {
+                let status = if preview.occupied { "something already exists there and would be overwritten" } else { "free" };
+                println!("{:<12} {}", "Status:", status);
+            } else {
+                let existing: HashSet<&String> = preview.overwritten.iter().chain(&preview.merged).collect();
+                let new: Vec<&String> = preview.contents.iter().filter(|path| !existing.contains(path)).collect();
+                let sections = [("Overwritten", preview.overwritten.iter().collect()), ("Merged", preview.merged.iter().collect()), ("New", new)];
+                for (title, paths) in sections {
+                    if paths.is_empty() {
+                        continue;
                     }
+                    println!("{} ({}):", title, paths.len());
+                    for path in paths {
+                        println!("  {}", display_path(path));
+                    }
+                }
+            }
+            println!("{:<12} {} in {} path(s)", "Total:", format_size(preview.total_size), preview.contents.len());
+        }
+    }
+    if !format.is_human() {
+        return Ok(false);
+    }
+    Ok(yes || confirm(&format!("Restore {}?", name))?)
+}
+
+/// Find an item with `locate_entry` or, with desktop sync, in the trs trash directory `store`
+fn locate(trash_dir: &Path, store: Option<&Path>, name: &str) -> Result<(PathBuf, String)> {
+    match (locate_entry(trash_dir, name), store) {
+        (Err(TrsError::NotInTrash(_)), Some(store)) => locate_entry(store, name),
+        (result, _) => result,
+    }
+}
+
+/// Restore the items named one per line in `source`, or stdin for `-`
+///
+/// Names are as shown by `trs show`; blank lines and lines starting with `#`
+/// are skipped. Every item is attempted, and the exit code follows
+/// `move_files`.
+fn restore_batch(source: &str, trash_dir: &Path, store: Option<&Path>, options: &RestoreOptions, format: OutputFormat) -> Result<i32> {
+    let mut options = options.clone();
+    let contents = if source == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(expand_tilde(source)).with_context(|| format!("reading batch file {}", source))?
+    };
+
+    let mut results = Vec::new();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // Directories are listed with a trailing slash
+        let name = line.trim_end_matches('/');
+        let result = locate(trash_dir, store, name).and_then(|(trash_dir, entry)| {
+            passphrase_for(&entry, &mut options)?;
+            restore_from_trash(&entry, &trash_dir, &options, &format.progress(format!("Restoring {}", name)))
+        });
+        let result = ItemResult::new(name, result);
+        if format == OutputFormat::Porcelain {
+            print_status(name, result.error.as_deref());
+        }
+        results.push(result);
+    }
+    report_restores(&results, format)
+}
+
+/// Restore every item whose name matches the glob `pattern`, in order of name
+///
+/// The device trashes and the trs trash directory of desktop sync are
+/// searched too. Matches are listed and confirmed first unless `yes` is set.
+/// Every item is attempted, and the exit code follows `move_files`.
+fn restore_pattern(pattern: &str, yes: bool, trash_dir: &Path, store: Option<&Path>, options: &RestoreOptions, format: OutputFormat) -> Result<i32> {
+    let mut matched = Vec::new();
+    let dirs = std::iter::once(trash_dir).chain(store).map(Path::to_path_buf).chain(device::known_trash_dirs());
+    for dir in dirs {
+        matched.extend(entries_matching(&dir, pattern)?.into_iter().map(|entry| (dir.clone(), entry)));
+    }
+    if matched.is_empty() {
+        return Err(TrsError::NotInTrash(pattern.to_string()));
+    }
+
+    if !yes {
+        if !format.is_human() {
+            return Err(TrsError::invalid_input("--porcelain and --json need --yes to restore items matching a pattern"));
+        }
+        println!("Matched {} item(s):", matched.len());
+        for (_, entry) in &matched {
+            println!("  {} ({})", entry.name, entry.original_path.as_deref().map(display_path).unwrap_or("Unknown".into()));
+        }
+        if !confirm("Restore these items?")? {
+            println!("Aborted.");
+            return Ok(0);
+        }
+    }
+
+    let mut options = options.clone();
+    if let Some((_, entry)) = matched.iter().find(|(_, entry)| crypto::is_encrypted(&entry.name)) {
+        passphrase_for(&entry.name, &mut options)?;
+    }
+    let mut results = Vec::new();
+    for (dir, entry) in &matched {
+        let progress = format.progress(format!("Restoring {}", entry.name));
+        let result = ItemResult::new(&entry.name, restore_from_trash(&entry.name, dir, &options, &progress));
+        if format == OutputFormat::Porcelain {
+            print_status(&entry.name, result.error.as_deref());
+        }
+        results.push(result);
+    }
+    report_restores(&results, format)
+}
+
+/// Print the summary of restoring several items, returning the exit code
+fn report_restores(results: &[ItemResult<RestoredItem>], format: OutputFormat) -> Result<i32> {
+    let failed = results.iter().filter(|result| !result.is_ok()).count();
+    match format {
+        OutputFormat::Human => {
+            println!("Restored {} of {} item(s)", results.len() - failed, results.len());
+            for result in results {
+                if let Some(e) = &result.error {
+                    eprintln!("Failed to restore {}: {}", result.source, e);
                 }
             }
-            Some(("restore", _)) => {
-                interactive_restore(&trash_dir)?;
+        }
+        OutputFormat::Porcelain => {}
+        OutputFormat::Json => print_json(&results)?,
+    }
+    Ok(exit_code(failed, results.len()))
+}
+
+/// Build restore options from the config
+///
+/// The passphrase for encrypted items is taken from `TRS_PASSPHRASE` if
+/// set; otherwise it is asked for when one is restored.
+fn restore_options(config: &Config) -> RestoreOptions {
+    let mut options = RestoreOptions::default();
+    if let Some(size) = config.integer("buffer_size") {
+        options.buffer_size = size.max(1) as usize;
+    }
+    options.passphrase = env::var(PASSPHRASE_VAR).ok().filter(|passphrase| !passphrase.is_empty()).map(Passphrase::new);
+    options
+}
+
+/// Handle the `config` subcommand
+fn run_config(matches: &ArgMatches, mut config: Config, format: OutputFormat) -> Result<()> {
+    match matches.subcommand() {
+        Some(("get", sub_m)) => {
+            let key = sub_m.get_one::<String>("key").unwrap();
+            let (value, source) = config.get(key)?;
+            if format == OutputFormat::Json {
+                return print_json(&serde_json::json!({ "key": key, "value": value, "source": source.name() }));
             }
-            Some(("empty", _)) => {
-                empty_trash(&trash_dir)?;
+            println!("{}", value.unwrap_or_default());
+        }
+        Some(("set", sub_m)) => {
+            let key = sub_m.get_one::<String>("key").unwrap();
+            let value = sub_m.get_one::<String>("value").unwrap();
+            config.set(key, value)?;
+            config.save()?;
+        }
+        Some(("unset", sub_m)) => {
+            let key = sub_m.get_one::<String>("key").unwrap();
+            if config.unset(key)? {
+                config.save()?;
             }
-            Some(("show", _)) => {
-                show_trash_contents(&trash_dir)?;
+        }
+        Some(("list", _)) if format == OutputFormat::Json => {
+            let keys = KEYS.iter()
+                .map(|key| {
+                    let (value, source) = config.get(key.name)?;
+                    Ok(serde_json::json!({ "key": key.name, "value": value, "source": source.name() }))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            print_json(&keys)?;
+        }
+        Some(("list", _)) => {
+            println!("{:<20} {:<40} Source", "Key", "Value");
+            for key in KEYS {
+                let (value, source) = config.get(key.name)?;
+                let value = value.unwrap_or_else(|| "(not set)".to_string());
+                println!("{:<20} {:<40} {}", key.name, value, source.name());
             }
-            _ => {
-                // Show the help page for invalid commands
-                create_cli().print_help().expect("Failed to print help");
-                println!();
+        }
+        _ => unreachable!("subcommand is required"),
+    }
+    Ok(())
+}
+
+/// Path where completions for `shell` are installed
+fn completion_path(shell: Shell) -> Result<PathBuf> {
+    let home = dirs::home_dir().expect("Could not find home directory");
+    match shell {
+        Shell::Bash => Ok(home.join(".local/share/bash-completion/completions/trs")),
+        Shell::Zsh => Ok(home.join(".zsh/completions/_trs")),
+        Shell::Fish => Ok(home.join(".config/fish/completions/trs.fish")),
+        Shell::Elvish => Ok(home.join(".config/elvish/lib/trs.elv")),
+        _ => Err(TrsError::invalid_input(
+            format!("--install is not supported for {}; redirect the output to your profile instead", shell),
+        )),
+    }
+}
+
+/// Completion script for `shell`, completing trash entry names where they are expected
+///
+/// Arguments naming trash entries are marked with `ValueHint::Other`, and
+/// the script gets a hook that lists the entries with the hidden
+/// `complete-entries` subcommand as the user types. PowerShell and Elvish
+/// only complete commands and flags.
+fn completion_script(shell: Shell) -> Vec<u8> {
+    let mut cli = create_cli();
+    let mut script = Vec::new();
+    generate(shell, &mut cli, "trs", &mut script);
+    let script = String::from_utf8(script).expect("completion scripts are UTF-8");
+
+    // Subcommands with an entry name as their positional argument
+    let commands: Vec<&str> = cli
+        .get_subcommands()
+        .filter(|command| command.get_positionals().any(|arg| arg.get_value_hint() == ValueHint::Other))
+        .map(|command| command.get_name())
+        .collect();
+    let script = match shell {
+        Shell::Bash => format!(
+            r#"{script}
+_trs_with_entries() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}" command="" i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        if [[ "${{COMP_WORDS[i]}}" != -* ]]; then
+            command="${{COMP_WORDS[i]}}"
+            break
+        fi
+    done
+    if [[ " {commands} " == *" $command "* && "$cur" != -* && "$prev" != -* ]]; then
+        local IFS=$'\n'
+        compopt -o filenames
+        COMPREPLY=($(compgen -W "$(trs complete-entries 2>/dev/null)" -- "$cur"))
+        return 0
+    fi
+    _trs "$@"
+}}
+complete -F _trs_with_entries -o bashdefault -o default trs
+"#,
+            commands = commands.join(" ")
+        ),
+        // `_arguments` specs of arguments hinted `Other` end with an empty action
+        Shell::Zsh => script
+            .replacen(
+                "#compdef trs\n",
+                "#compdef trs\n\n_trs_entries() {\n    local -a entries\n    entries=(${(f)\"$(trs complete-entries 2>/dev/null)\"})\n    compadd -a entries\n}\n",
+                1,
+            )
+            .lines()
+            .map(|line| match line.strip_suffix(":' \\") {
+                Some(spec) if line.starts_with('\'') => format!("{}:_trs_entries' \\\n", spec),
+                _ => format!("{}\n", line),
+            })
+            .collect(),
+        Shell::Fish => commands.iter().fold(script, |script, command| {
+            script
+                + &format!(
+                    "complete -c trs -n \"__fish_trs_using_subcommand {}\" -f -a \"(trs complete-entries 2>/dev/null)\"\n",
+                    command
+                )
+        }),
+        _ => script,
+    };
+    script.into_bytes()
+}
+
+/// Write the completion script for `shell` to its standard location
+fn install_completions(shell: Shell) -> Result<()> {
+    let path = completion_path(shell)?;
+    let writing = || format!("writing completions to {}", path.display());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(writing)?;
+    }
+
+    fs::write(&path, completion_script(shell)).with_context(writing)?;
+
+    println!("Installed {} completions to {}", shell, path.display());
+    Ok(())
+}
+
+/// Handle the `man` subcommand
+///
+/// Pages are rendered from `create_cli`, so they always describe the current
+/// arguments. Without `--out-dir` one page is printed to stdout; with it,
+/// a page for `trs` and one for every subcommand are written to the directory.
+fn run_man(matches: &ArgMatches, format: OutputFormat) -> Result<()> {
+    let mut cli = create_cli().name("trs").disable_help_subcommand(true);
+    cli.build();
+
+    let Some(out_dir) = matches.get_one::<String>("out_dir") else {
+        let page = match matches.get_one::<String>("command") {
+            Some(name) => cli.find_subcommand(name)
+                .cloned()
+                .ok_or_else(|| TrsError::invalid_input(format!("Unknown command '{}'", name)))?,
+            None => cli,
+        };
+        man_page(page).render(&mut io::stdout())?;
+        return Ok(());
+    };
+
+    let out_dir = expand_tilde(out_dir);
+    let writing = || format!("writing manual pages to {}", out_dir.display());
+    fs::create_dir_all(&out_dir).with_context(writing)?;
+    let mut written = Vec::new();
+    write_man_pages(cli, &out_dir, &mut written).with_context(writing)?;
+    match format {
+        OutputFormat::Human => println!("Wrote {} manual page(s) to {}", written.len(), out_dir.display()),
+        OutputFormat::Porcelain => {
+            for path in &written {
+                println!("{}", escape_field(&path.to_string_lossy()));
             }
         }
+        OutputFormat::Json => print_json(&written)?,
     }
+    Ok(())
+}
+
+/// Manual page of `cmd`, naming the version of trs as its source
+fn man_page(cmd: Command) -> Man {
+    Man::new(cmd).source(format!("trs {}", env!("CARGO_PKG_VERSION")))
+}
 
+/// Write the page of `cmd` and of each of its visible subcommands to `out_dir`
+fn write_man_pages(cmd: Command, out_dir: &Path, written: &mut Vec<PathBuf>) -> io::Result<()> {
+    for subcommand in cmd.get_subcommands().filter(|subcommand| !subcommand.is_hide_set()).cloned() {
+        write_man_pages(subcommand, out_dir, written)?;
+    }
+    written.push(man_page(cmd).generate_to(out_dir)?);
     Ok(())
 }
 
+/// Flag enabling internal glob expansion of paths to move
+fn glob_arg() -> Arg {
+    Arg::new("glob")
+        .long("glob")
+        .action(ArgAction::SetTrue)
+        .help("Expand quoted glob patterns such as '*.log' that do not name an existing path")
+}
+
+/// Flag keeping items on other filesystems in a trash on their own filesystem
+fn device_trash_arg() -> Arg {
+    Arg::new("device_trash")
+        .long("device-trash")
+        .action(ArgAction::SetTrue)
+        .help("Trash items on other filesystems to a .trs-trash directory at their mount point, avoiding a copy")
+}
+
+/// Flag disabling the per-mount trashes of XDG mode
+fn home_trash_arg() -> Arg {
+    Arg::new("home_trash")
+        .long("home-trash")
+        .action(ArgAction::SetTrue)
+        .conflicts_with("device_trash")
+        .help("With --xdg, always use the home trash instead of a .Trash-$uid directory on the item's filesystem")
+}
+
+/// Create the `--jobs` argument shared by the move commands
+fn jobs_arg() -> Arg {
+    Arg::new("jobs")
+        .long("jobs")
+        .short('j')
+        .value_name("N")
+        .value_parser(value_parser!(usize))
+        .help("Number of threads used to compress archives (defaults to the number of cores, 1 disables parallelism)")
+}
+
+/// Create the `--compress-threshold` argument shared by the move commands
+fn compress_threshold_arg() -> Arg {
+    Arg::new("compress_threshold")
+        .long("compress-threshold")
+        .value_name("SIZE")
+        .value_parser(parse_size)
+        .help("Store files smaller than this (e.g. 4KiB) as they are instead of archived (defaults to compress_threshold from the config)")
+}
+
+/// Create the `--timestamps` argument shared by the move commands
+fn timestamps_arg() -> Arg {
+    Arg::new("timestamps")
+        .long("timestamps")
+        .value_name("POLICY")
+        .value_parser(PossibleValuesParser::new(Timestamps::NAMES))
+        .help("Whether restored files keep their modification times or get the time of the restore (defaults to timestamps from the config, or keep)")
+}
+
+/// Create the `--compression` argument shared by the move commands
+fn compression_arg() -> Arg {
+    Arg::new("compression")
+        .long("compression")
+        .value_name("ALGORITHM")
+        .value_parser(PossibleValuesParser::new(ArchiveCompression::NAMES))
+        .help("Compress archives with gzip (.tar.gz), zstd (.tar.zst) or lz4 (.tar.lz4), archiving files even where they could be renamed into the trash (defaults to compression from the config, or gzip)")
+}
+
+/// Create the `--auto-compress` argument shared by the move commands
+fn auto_compress_arg() -> Arg {
+    Arg::new("auto_compress")
+        .long("auto-compress")
+        .action(ArgAction::SetTrue)
+        .help("Choose the compression of each file from its extension: none for media and archives, zstd for text")
+}
+
+/// Create the `--no-prealloc` argument shared by the move commands
+fn no_prealloc_arg() -> Arg {
+    Arg::new("no_prealloc")
+        .long("no-prealloc")
+        .action(ArgAction::SetTrue)
+        .help("Do not reserve disk space for archives before writing them, for filesystems without fallocate")
+}
+
+/// Create the `--strict` argument shared by the move commands
+fn strict_arg() -> Arg {
+    Arg::new("strict")
+        .long("strict")
+        .action(ArgAction::SetTrue)
+        .help("Fail when a file inside a directory cannot be read, instead of leaving it out of the archive and in place")
+}
+
+/// Create the `--zstd-threads` argument shared by the move commands
+fn zstd_threads_arg() -> Arg {
+    Arg::new("zstd_threads")
+        .long("zstd-threads")
+        .value_name("N")
+        .value_parser(value_parser!(u32))
+        .help("Number of worker threads used by zstd compression (defaults to zstd_threads from the config, or 0 to compress on the main thread)")
+}
+
 /// Create the CLI
 fn create_cli() -> Command {
     Command::new("Trash CLI")
-        .version("1.0")
+        .version(env!("CARGO_PKG_VERSION"))
         .author("William Faircloth")
         .about("A CLI program to manage a trash folder")
         .arg(
@@ -60,8 +2061,115 @@ fn create_cli() -> Command {
                 .action(ArgAction::Append) // Allow multiple values
                 .num_args(1..),            // Accept one or more arguments
         )
+        .arg(jobs_arg())
+        .arg(compress_threshold_arg())
+        .arg(timestamps_arg())
+        .arg(compression_arg())
+        .arg(zstd_threads_arg())
+        .arg(auto_compress_arg())
+        .arg(no_prealloc_arg())
+        .arg(strict_arg())
+        .args(move_shred_args())
+        .args(move_confirm_args())
+        .args(rm_compat_args())
+        .arg(encrypt_arg())
+        .arg(interactive_arg())
+        .arg(glob_arg())
+        .arg(device_trash_arg())
+        .arg(home_trash_arg())
+        .arg(
+            Arg::new("no_auto_prune")
+                .long("no-auto-prune")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Do not expire items older than retention_days before running"),
+        )
+        .arg(
+            Arg::new("xdg")
+                .long("xdg")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Use the FreeDesktop.org trash shared with file managers, keeping items uncompressed"),
+        )
+        .arg(
+            Arg::new("compat")
+                .long("compat")
+                .global(true)
+                .value_name("TOOL")
+                .value_parser(PossibleValuesParser::new(["trash-cli"]))
+                .help("Read and write entries the way TOOL does, so both can list and restore them (trash-cli uses the FreeDesktop.org trash, as --xdg)"),
+        )
+        .arg(
+            Arg::new("desktop_sync")
+                .long("desktop-sync")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Like --xdg, but also list and restore the items left in the trs trash directory"),
+        )
+        .arg(
+            Arg::new("native_trash")
+                .long("native-trash")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .conflicts_with("xdg")
+                .help("On macOS, use the system trash (~/.Trash) shown by the Finder, keeping items uncompressed"),
+        )
+        .arg(
+            Arg::new("no_warn")
+                .long("no-warn")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Do not warn when the trash holds more than warn_size after adding to it"),
+        )
+        .arg(
+            Arg::new("user_trash")
+                .long("user-trash")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["xdg", "compat", "desktop_sync", "native_trash"])
+                .help("When running as root under sudo, use the trash of the user who ran sudo instead of root's, giving the files written back to them"),
+        )
+        .arg(
+            Arg::new("porcelain")
+                .long("porcelain")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Print stable, tab-separated output for scripts: records from show, ok/err lines from move and restore"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .conflicts_with("porcelain")
+                .help("Print results as JSON, and errors as JSON objects on stderr"),
+        )
+        .arg(
+            Arg::new("bytes")
+                .long("bytes")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Print sizes as exact numbers of bytes instead of in KiB, MiB, ..."),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .value_name("WHEN")
+                .global(true)
+                .value_parser(PossibleValuesParser::new(ColorChoice::NAMES))
+                .default_value("auto")
+                .help("Color listings: auto (when stdout is a terminal and NO_COLOR is not set), always or never"),
+        )
+        .arg(
+            Arg::new("log_file")
+                .long("log-file")
+                .value_name("PATH")
+                .global(true)
+                .help("Append a JSON line for each operation to this file"),
+        )
         .subcommand(
             Command::new("move")
+                .visible_aliases(["rm", "mv"])
                 .about("Move files or directories to the trash")
                 .arg(
                     Arg::new("file")
@@ -69,18 +2177,590 @@ fn create_cli() -> Command {
                         .action(ArgAction::Append) // Allow multiple values
                         .num_args(1..)             // Accept one or more arguments
                         .help("Path(s) to the file(s) or directory(ies) to move to trash")
-                ),
+                )
+                .arg(
+                    Arg::new("name")
+                        .long("name")
+                        .value_name("NAME")
+                        .help("Store the item in the trash under this name instead of its file name"),
+                )
+                .arg(
+                    Arg::new("schedule")
+                        .long("schedule")
+                        .value_name("DATETIME")
+                        .conflicts_with("name")
+                        .help("Leave the files in place and move them to the trash at DATETIME (e.g. '2025-06-01 18:00' or '18:00' for today) with 'trs schedule run'"),
+                )
+                .arg(jobs_arg())
+                .arg(compress_threshold_arg())
+                .arg(timestamps_arg())
+                .arg(compression_arg())
+                .arg(zstd_threads_arg())
+                .arg(auto_compress_arg())
+                .arg(no_prealloc_arg())
+                .arg(strict_arg())
+                .args(move_shred_args())
+                .args(move_confirm_args())
+                .args(rm_compat_args())
+                .arg(encrypt_arg().conflicts_with("schedule"))
+                .arg(interactive_arg().conflicts_with("schedule"))
+                .arg(glob_arg())
+                .arg(device_trash_arg())
+                .arg(home_trash_arg()),
+        )
+        .subcommand(
+            Command::new("interactive")
+                .about("Browse, search, restore and delete trash items in a full-screen interface"),
         )
         .subcommand(
             Command::new("restore")
-                .about("Interactively select and restore items from the trash to their original locations"),
+                .about("Restore items from the trash to their original locations, selecting them interactively when no entry is given")
+                .arg(
+                    Arg::new("entry")
+                        .value_hint(ValueHint::Other)
+                        .help("Name of the item in the trash (the archive suffix, such as .tar.gz, is optional)"),
+                )
+                .arg(
+                    Arg::new("member")
+                        .long("member")
+                        .value_name("PATH")
+                        .requires("entry")
+                        .help("Only extract this file or directory from the item's archive, leaving the archive in the trash"),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .value_name("PATH")
+                        .requires("entry")
+                        .help("Restore to this path instead of the original location"),
+                )
+                .arg(
+                    Arg::new("batch")
+                        .long("batch")
+                        .value_name("FILE")
+                        .conflicts_with("entry")
+                        .help("Restore the items named one per line in FILE, or stdin for '-' (lines starting with # are ignored)"),
+                )
+                .arg(
+                    Arg::new("pattern")
+                        .long("pattern")
+                        .value_name("GLOB")
+                        .conflicts_with_all(["entry", "batch"])
+                        .help("Restore every item whose name matches GLOB, e.g. '*.rs', in order of name"),
+                )
+                .arg(
+                    Arg::new("preview")
+                        .long("preview")
+                        .action(ArgAction::SetTrue)
+                        .requires("entry")
+                        .conflicts_with("member")
+                        .help("List where the item would be restored and every file it would write, then ask before restoring"),
+                )
+                .arg(
+                    Arg::new("merge")
+                        .long("merge")
+                        .action(ArgAction::SetTrue)
+                        .requires("entry")
+                        .conflicts_with_all(["member", "preview"])
+                        .help("Merge a directory into the one already at its destination, file by file, listing each file both have"),
+                )
+                .arg(
+                    Arg::new("prefer")
+                        .long("prefer")
+                        .value_name("WHICH")
+                        .value_parser(PossibleValuesParser::new(MergePreference::NAMES))
+                        .default_value("existing")
+                        .requires("merge")
+                        .help("Which copy of a file both directories have to keep: the one already there, the trashed one, or the newer"),
+                )
+                .arg(
+                    Arg::new("restore_parent")
+                        .long("restore-parent")
+                        .visible_alias("create-parents")
+                        .action(ArgAction::SetTrue)
+                        .help("Recreate the directories an item was in if they no longer exist, listing each one created"),
+                )
+                .group(ArgGroup::new("confirmable").args(["pattern", "preview"]))
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .action(ArgAction::SetTrue)
+                        .requires("confirmable")
+                        .help("Restore matching or previewed items without asking for confirmation"),
+                ),
         )
         .subcommand(
             Command::new("empty")
-                .about("Permanently delete all items in the trash folder"),
+                .about("Permanently delete all items in the trash folder, or only those matching the given patterns")
+                .arg(
+                    Arg::new("pattern")
+                        .action(ArgAction::Append)
+                        .num_args(0..)
+                        .help("Names, original paths or glob patterns of the items to delete, e.g. 'node_modules*'"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .action(ArgAction::SetTrue)
+                        .help("Delete matching items without asking for confirmation"),
+                )
+                .arg(
+                    Arg::new("older_than")
+                        .long("older-than")
+                        .value_name("DURATION")
+                        .help("Only delete items trashed more than this long ago, e.g. 30d"),
+                )
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("List what would be deleted and how much space it frees without deleting anything"),
+                )
+                .arg(
+                    Arg::new("include_pinned")
+                        .long("include-pinned")
+                        .action(ArgAction::SetTrue)
+                        .help("Delete pinned items too"),
+                )
+                .arg(
+                    Arg::new("duplicates")
+                        .long("duplicates")
+                        .action(ArgAction::SetTrue)
+                        .help("Only delete items trashed from the same original path as another, keeping one of each"),
+                )
+                .arg(
+                    Arg::new("keep")
+                        .long("keep")
+                        .value_name("N|WHICH")
+                        .help("Keep the N most recently trashed items and delete the rest; with --duplicates, which item of each group to keep (newest or oldest)"),
+                )
+                .args(shred_args()),
+        )
+        .subcommand(
+            Command::new("purge")
+                .about("Permanently delete specific items from the trash")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .value_hint(ValueHint::Other)
+                        .help("Name(s) of the item(s) in the trash (the archive suffix, such as .tar.gz, is optional)"),
+                )
+                .args(shred_args()),
+        )
+        .subcommand(
+            Command::new("undo")
+                .about("Reverse the last move or restore, stepping further back each time it is run"),
+        )
+        .subcommand(
+            Command::new("pin")
+                .about("Keep items in the trash when it is emptied or pruned")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .value_hint(ValueHint::Other)
+                        .help("Name(s) of the item(s) in the trash (the archive suffix, such as .tar.gz, is optional)"),
+                ),
+        )
+        .subcommand(
+            Command::new("unpin")
+                .about("Let pinned items be emptied and pruned again")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .value_hint(ValueHint::Other)
+                        .help("Name(s) of the item(s) in the trash (the archive suffix, such as .tar.gz, is optional)"),
+                ),
         )
         .subcommand(
             Command::new("show")
-                .about("Display a list of all items currently in the trash with their original paths"),
+                .about("Display a list of all items currently in the trash with their original paths, or details of one item")
+                .arg(
+                    Arg::new("name")
+                        .help("Show everything known about this item instead, looked up by name or part of its name or original location"),
+                )
+                .arg(
+                    Arg::new("age")
+                        .long("age")
+                        .action(ArgAction::SetTrue)
+                        .help("Show how long ago each item was trashed"),
+                )
+                .arg(
+                    Arg::new("size")
+                        .long("size")
+                        .action(ArgAction::SetTrue)
+                        .help("Show the size of each item"),
+                )
+                .arg(
+                    Arg::new("newer_than")
+                        .long("newer-than")
+                        .value_name("DURATION")
+                        .help("Only list items trashed less than this long ago (e.g. 2h, 7d)"),
+                )
+                .arg(
+                    Arg::new("older_than")
+                        .long("older-than")
+                        .value_name("DURATION")
+                        .help("Only list items trashed more than this long ago (e.g. 30d)"),
+                )
+                .arg(
+                    Arg::new("pinned")
+                        .long("pinned")
+                        .action(ArgAction::SetTrue)
+                        .help("Only list pinned items"),
+                )
+                .arg(
+                    Arg::new("files_only")
+                        .long("files-only")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("dirs_only")
+                        .help("Only list files"),
+                )
+                .arg(
+                    Arg::new("dirs_only")
+                        .long("dirs-only")
+                        .action(ArgAction::SetTrue)
+                        .help("Only list directories"),
+                )
+                .arg(
+                    Arg::new("duplicates")
+                        .long("duplicates")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["name", "newer_than", "older_than", "pinned", "files_only", "dirs_only", "tree"])
+                        .help("Only list items trashed from the same original path as another, grouped by that path"),
+                )
+                .arg(
+                    Arg::new("missing_parent")
+                        .long("missing-parent")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["name", "duplicates"])
+                        .help("Only list items whose original parent directory no longer exists, with the deepest part of the path that does"),
+                )
+                .arg(
+                    Arg::new("json_lines")
+                        .long("json-lines")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["name", "duplicates", "json", "porcelain"])
+                        .help("Print each item as a JSON object on its own line as soon as it is read, for streaming very large trashes"),
+                )
+                .arg(
+                    Arg::new("all_users")
+                        .long("all-users")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["name", "duplicates"])
+                        .help("When running as root under sudo, list root's trash and the trash of the user who ran sudo together, with who owns each item"),
+                )
+                .arg(
+                    Arg::new("owner")
+                        .long("owner")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["name", "duplicates"])
+                        .help("Show who trashed each item and on which machine, as user@host, for trashes shared between users or machines"),
+                )
+                .arg(
+                    Arg::new("tree")
+                        .long("tree")
+                        .action(ArgAction::SetTrue)
+                        .overrides_with("no_tree")
+                        .help("Draw the contents of archived directories beneath them"),
+                )
+                .arg(
+                    Arg::new("no_tree")
+                        .long("no-tree")
+                        .action(ArgAction::SetTrue)
+                        .overrides_with("tree")
+                        .help("Do not draw the contents of archived directories, or of the item given by name"),
+                )
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .value_name("N")
+                        .value_parser(value_parser!(usize))
+                        .help("Number of levels of archived directories to draw with --tree [default: 2]"),
+                )
+                .arg(
+                    Arg::new("no_header")
+                        .long("no-header")
+                        .action(ArgAction::SetTrue)
+                        .help("Print only the item rows, for use in scripts"),
+                ),
+        )
+        .subcommand(
+            Command::new("info")
+                .about("Show full details of a single item in the trash, including archive contents")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .value_hint(ValueHint::Other)
+                        .help("Name of the item in the trash (the archive suffix, such as .tar.gz, is optional)"),
+                ),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Show a summary of the trash: item counts, sizes, deletion dates and the largest items"),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write every item in the trash and its metadata to a single bundle for backup or transfer")
+                .arg(
+                    Arg::new("output")
+                        .required(true)
+                        .help("Path of the bundle to create, e.g. trash-backup.tar.gz"),
+                )
+                .arg(
+                    Arg::new("overwrite")
+                        .long("overwrite")
+                        .action(ArgAction::SetTrue)
+                        .help("Replace the output file if it already exists"),
+                )
+                .arg(
+                    Arg::new("compress")
+                        .long("compress")
+                        .value_name("ALGO")
+                        .value_parser(PossibleValuesParser::new(BundleCompression::NAMES))
+                        .default_value("gzip")
+                        .help("Compression of the bundle"),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Merge the items of a bundle written by export into the trash, or take in a single item")
+                .arg(
+                    Arg::new("bundle")
+                        .required(true)
+                        .value_name("PATH")
+                        .help("Path of the bundle to import, of a .trashinfo file of an XDG trash, or of an item with --item"),
+                )
+                .arg(
+                    Arg::new("item")
+                        .long("item")
+                        .action(ArgAction::SetTrue)
+                        .help("Move PATH into the trash as an item, recorded as trashed from where it is unless --original-path is given"),
+                )
+                .arg(
+                    Arg::new("original_path")
+                        .long("original-path")
+                        .value_name("PATH")
+                        .help("Original location to record for the imported item, where restore puts it back (implies --item)"),
+                )
+                .arg(
+                    Arg::new("replace")
+                        .long("replace")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["item", "original_path"])
+                        .help("Replace items with the same name instead of importing them under a new name"),
+                )
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["item", "original_path"])
+                        .help("Show what would be imported without changing anything"),
+                ),
+        )
+        .subcommand(
+            Command::new("import-system-trash")
+                .about("Move the items of the FreeDesktop.org home trash into the trs trash, keeping their original locations and deletion times")
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("List the items that would be imported without changing anything"),
+                ),
+        )
+        .subcommand(
+            Command::new("schedule")
+                .about("List, run or cancel deletions scheduled with move --schedule")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .about("List the scheduled deletions, soonest first"),
+                )
+                .subcommand(
+                    Command::new("run")
+                        .about("Move the items whose scheduled time has passed to the trash, e.g. from cron"),
+                )
+                .subcommand(
+                    Command::new("cancel")
+                        .about("Cancel a scheduled deletion, leaving the item in place")
+                        .arg(
+                            Arg::new("id")
+                                .required(true)
+                                .value_parser(value_parser!(u64))
+                                .help("ID of the deletion, as shown by schedule list"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("prune")
+                .about("Permanently delete expired items and the oldest items beyond the size limit")
+                .arg(
+                    Arg::new("retention_days")
+                        .long("retention-days")
+                        .value_name("DAYS")
+                        .value_parser(value_parser!(i64))
+                        .help("Delete items trashed more than this many days ago (defaults to retention_days from the config)"),
+                )
+                .arg(
+                    Arg::new("max_size")
+                        .long("max-size")
+                        .value_name("SIZE")
+                        .help("Maximum total trash size, e.g. 5GB (defaults to max_size from the config)"),
+                )
+                .arg(
+                    Arg::new("min_age")
+                        .long("min-age")
+                        .value_name("DURATION")
+                        .help("Never delete items trashed more recently than this, e.g. 1d (defaults to min_age from the config)"),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Get, set, unset or list configuration values")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("get")
+                        .about("Print the effective value of a configuration key")
+                        .arg(Arg::new("key").required(true).help("Configuration key")),
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("Set a configuration key in the config file")
+                        .arg(Arg::new("key").required(true).help("Configuration key"))
+                        .arg(Arg::new("value").required(true).help("Value to store")),
+                )
+                .subcommand(
+                    Command::new("unset")
+                        .about("Remove a configuration key from the config file")
+                        .arg(Arg::new("key").required(true).help("Configuration key")),
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("List all configuration keys with their values and sources"),
+                ),
+        )
+        .subcommand(
+            Command::new("version")
+                .about("Print the version of trs")
+                .arg(
+                    Arg::new("build")
+                        .long("build")
+                        .action(ArgAction::SetTrue)
+                        .help("Also print the git commit, compiler, target and enabled features, for bug reports"),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Generate a shell completion script and print it to stdout")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .value_parser(value_parser!(Shell))
+                        .help("Shell to generate completions for"),
+                )
+                .arg(
+                    Arg::new("install")
+                        .long("install")
+                        .action(ArgAction::SetTrue)
+                        .help("Write the script to the shell's completion directory instead of stdout"),
+                ),
         )
+        .subcommand(
+            Command::new("complete-entries")
+                .hide(true)
+                .about("Print the names of the items in the trash, one per line, for completion scripts"),
+        )
+        .subcommand(
+            Command::new("man")
+                .about("Generate a manual page in roff format and print it to stdout")
+                .arg(
+                    Arg::new("command")
+                        .help("Subcommand to print the page of, e.g. restore (defaults to trs itself)"),
+                )
+                .arg(
+                    Arg::new("out_dir")
+                        .long("out-dir")
+                        .value_name("DIR")
+                        .conflicts_with("command")
+                        .help("Write the pages of trs and every subcommand to DIR instead of stdout"),
+                ),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// The answer `ask_to_trash` takes from `typed`
+    fn answer(typed: &str) -> Answer {
+        ask_to_trash("a.conf", &mut Cursor::new(typed)).unwrap()
+    }
+
+    #[test]
+    fn ask_to_trash_understands_each_answer() {
+        assert!(matches!(answer("y\n"), Answer::Yes));
+        assert!(matches!(answer("YES\n"), Answer::Yes));
+        assert!(matches!(answer("n\n"), Answer::No));
+        assert!(matches!(answer("\n"), Answer::No));
+        assert!(matches!(answer("a\n"), Answer::All));
+        assert!(matches!(answer("q\n"), Answer::Quit));
+        // The end of the input stops like q
+        assert!(matches!(answer(""), Answer::Quit));
+    }
+
+    #[test]
+    fn ask_to_trash_asks_again_after_other_answers() {
+        let mut input = Cursor::new("maybe\nyep\ny\nn\n");
+        assert!(matches!(ask_to_trash("a.conf", &mut input).unwrap(), Answer::Yes));
+        // Only the answers it needed were read
+        assert!(matches!(ask_to_trash("b.conf", &mut input).unwrap(), Answer::No));
+    }
+
+    /// Trash `names`, created in a fresh directory, answering the questions with `typed`
+    ///
+    /// Returns the names left in place.
+    fn move_interactively(dir_name: &str, names: &[&str], typed: &str) -> Vec<String> {
+        let dir = std::env::temp_dir().join(format!("trs-unit-{}-{}", dir_name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let files: Vec<String> = names
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                fs::write(&path, *name).unwrap();
+                path.to_string_lossy().into_owned()
+            })
+            .collect();
+
+        let mut input = Cursor::new(typed);
+        let code = move_files(&files, &[], &dir.join("trash"), None, &MoveOptions::default(), Some(&mut input), OutputFormat::Human)
+            .unwrap();
+        assert_eq!(code, 0);
+        let left = names.iter().filter(|name| dir.join(name).exists()).map(|name| name.to_string()).collect();
+        fs::remove_dir_all(&dir).unwrap();
+        left
+    }
+
+    #[test]
+    fn move_files_asks_before_each_file() {
+        assert_eq!(move_interactively("ask-each", &["a.conf", "b.conf", "c.conf"], "y\nn\nwhat\ny\n"), ["b.conf"]);
+    }
+
+    #[test]
+    fn move_files_stops_asking_after_all() {
+        assert!(move_interactively("ask-all", &["a.conf", "b.conf", "c.conf"], "a\n").is_empty());
+    }
+
+    #[test]
+    fn move_files_stops_after_quit() {
+        assert_eq!(move_interactively("ask-quit", &["a.conf", "b.conf", "c.conf"], "y\nq\n"), ["b.conf", "c.conf"]);
+    }
 }
@@ -0,0 +1,537 @@
+//! Compression backends for trash archives
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::thread;
+use flate2::{Compress, Compression, Crc, FlushCompress};
+use flate2::bufread::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::ProgressSink;
+
+/// Size of the independently compressed blocks in the parallel encoder
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Fixed gzip header: deflate, no flags, no mtime, max compression, unknown OS
+const GZIP_HEADER: [u8; 10] = [0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff];
+
+/// Magic number at the start of a zstd frame
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Default size of the buffers wrapping archive files
+pub const DEFAULT_BUFFER_SIZE: usize = 128 * 1024;
+
+/// Compression level of zstd archives, which favours speed
+pub const ZSTD_LEVEL: i32 = 3;
+
+/// Number of compression jobs to use when none is specified
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Gzip writer used for trash archives, either single-threaded or parallel
+///
+/// Each chunk of uncompressed bytes written is passed to the sink's
+/// `advance`. Archives are written to a file, or through an
+/// [`ArchiveWriter`](crate::crypto::ArchiveWriter) that may encrypt them.
+pub enum GzWriter<'a, W: Write = fs::File> {
+    Single(GzEncoder<BufWriter<W>>, &'a dyn ProgressSink),
+    Parallel(ParallelGzEncoder<'a, BufWriter<W>>),
+}
+
+impl<'a, W: Write> GzWriter<'a, W> {
+    /// Create a buffered gzip writer for `file` that compresses using `jobs` threads
+    pub fn new(file: W, jobs: usize, buffer_size: usize, progress: &'a dyn ProgressSink) -> io::Result<Self> {
+        let file = BufWriter::with_capacity(buffer_size, file);
+        if jobs <= 1 {
+            Ok(GzWriter::Single(GzEncoder::new(file, Compression::best()), progress))
+        } else {
+            Ok(GzWriter::Parallel(ParallelGzEncoder::new(file, jobs, Compression::best(), progress)?))
+        }
+    }
+
+    /// Write any remaining data and the gzip trailer, returning the underlying file
+    pub fn finish(self) -> io::Result<W> {
+        let buffered = match self {
+            GzWriter::Single(enc, ..) => enc.finish()?,
+            GzWriter::Parallel(enc) => enc.finish()?,
+        };
+        // Flush the buffer so the archive is complete before the source is removed
+        buffered.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+impl<W: Write> Write for GzWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            GzWriter::Single(enc, progress) => {
+                let written = enc.write(buf)?;
+                progress.advance(written as u64);
+                Ok(written)
+            }
+            GzWriter::Parallel(enc) => enc.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            GzWriter::Single(enc, ..) => enc.flush(),
+            GzWriter::Parallel(enc) => enc.flush(),
+        }
+    }
+}
+
+/// Compression of the archives items are stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArchiveCompression {
+    /// `.tar.gz`, readable everywhere
+    #[default]
+    Gzip,
+    /// `.tar.zst`, smaller and faster to restore
+    Zstd,
+    /// `.tar.lz4`, larger but the fastest to write, for big files where time matters more than space
+    Lz4,
+}
+
+/// Another name for [`ArchiveCompression`]
+pub type CompressionAlgo = ArchiveCompression;
+
+impl ArchiveCompression {
+    /// Names accepted by `from_name`
+    pub const NAMES: &'static [&'static str] = &["gzip", "zstd", "lz4"];
+
+    /// Every compression, for checking whether a name is taken by an archive
+    pub const ALL: [ArchiveCompression; 3] = [ArchiveCompression::Gzip, ArchiveCompression::Zstd, ArchiveCompression::Lz4];
+
+    /// Look up a compression by the name used on the command line and in the config
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gzip" | "gz" => Some(ArchiveCompression::Gzip),
+            "zstd" | "zst" => Some(ArchiveCompression::Zstd),
+            "lz4" => Some(ArchiveCompression::Lz4),
+            _ => None,
+        }
+    }
+
+    /// Compression of the archive named `name`, if it has the suffix of one
+    ///
+    /// ```
+    /// use trs::compression::ArchiveCompression;
+    ///
+    /// assert_eq!(ArchiveCompression::from_file_name("logs.tar.zst"), Some(ArchiveCompression::Zstd));
+    /// assert_eq!(ArchiveCompression::from_file_name("disk.img.tar.lz4"), Some(ArchiveCompression::Lz4));
+    /// assert_eq!(ArchiveCompression::from_file_name("notes.txt.tar.gz"), Some(ArchiveCompression::Gzip));
+    /// assert_eq!(ArchiveCompression::from_file_name("notes.txt.gz"), None);
+    /// ```
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|compression| name.ends_with(compression.suffix()))
+    }
+
+    /// Suffix of archives with this compression
+    pub fn suffix(self) -> &'static str {
+        match self {
+            ArchiveCompression::Gzip => ".tar.gz",
+            ArchiveCompression::Zstd => ".tar.zst",
+            ArchiveCompression::Lz4 => ".tar.lz4",
+        }
+    }
+
+    /// Name shown by `info` and `show` for archives with this compression
+    pub fn label(self) -> &'static str {
+        match self {
+            ArchiveCompression::Gzip => "tar.gz",
+            ArchiveCompression::Zstd => "tar.zst",
+            ArchiveCompression::Lz4 => "tar.lz4",
+        }
+    }
+
+    /// Checksum of the data that reading an archive to the end verifies
+    pub fn checksum(self) -> &'static str {
+        match self {
+            ArchiveCompression::Gzip => "gzip CRC-32",
+            ArchiveCompression::Zstd => "zstd XXH64",
+            ArchiveCompression::Lz4 => "LZ4 XXH32",
+        }
+    }
+
+    /// Compression shown by `info` as `label`, gzip for anything else
+    pub fn from_label(label: &str) -> Self {
+        Self::ALL.into_iter().find(|compression| compression.label() == label).unwrap_or_default()
+    }
+}
+
+/// Extensions of files holding data that is already compressed
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "webp", "heic", "mp3", "aac", "ogg", "opus", "flac", "mp4", "m4a", "m4v", "mkv", "mov",
+    "avi", "webm", "zip", "gz", "tgz", "bz2", "xz", "zst", "lz4", "7z", "rar", "jar", "apk", "docx", "xlsx", "pptx",
+];
+
+/// Extensions of text files, which zstd shrinks well
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "log", "md", "csv", "tsv", "json", "xml", "html", "css", "yaml", "yml", "toml", "ini", "sql", "rs", "c", "h",
+    "cpp", "hpp", "go", "java", "py", "rb", "js", "ts", "sh",
+];
+
+/// Compression `--auto-compress` picks for a file named `name` from its extension
+///
+/// Returns `None` for data that is already compressed, which is best
+/// stored as it is, zstd for text, and `default` for anything else.
+///
+/// ```
+/// use trs::compression::{auto_compression, ArchiveCompression};
+///
+/// assert_eq!(auto_compression("holiday.MP4", ArchiveCompression::Gzip), None);
+/// assert_eq!(auto_compression("server.log", ArchiveCompression::Gzip), Some(ArchiveCompression::Zstd));
+/// assert_eq!(auto_compression("disk.img", ArchiveCompression::Lz4), Some(ArchiveCompression::Lz4));
+/// ```
+pub fn auto_compression(name: &str, default: ArchiveCompression) -> Option<ArchiveCompression> {
+    let extension = std::path::Path::new(name)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    if COMPRESSED_EXTENSIONS.contains(&extension.as_str()) {
+        None
+    } else if TEXT_EXTENSIONS.contains(&extension.as_str()) {
+        Some(ArchiveCompression::Zstd)
+    } else {
+        Some(default)
+    }
+}
+
+/// Compressor of trash archives, with gzip, zstd or LZ4
+///
+/// Each chunk of uncompressed bytes written is passed to the sink's
+/// `advance`.
+pub enum ArchiveEncoder<'a, W: Write = fs::File> {
+    Gzip(GzWriter<'a, W>),
+    Zstd(zstd::Encoder<'static, BufWriter<W>>, &'a dyn ProgressSink),
+    Lz4(lz4_flex::frame::FrameEncoder<BufWriter<W>>, &'a dyn ProgressSink),
+}
+
+impl<'a, W: Write> ArchiveEncoder<'a, W> {
+    /// Create a buffered writer for `file` compressing with `compression`
+    ///
+    /// Gzip uses `jobs` threads. Zstd compresses on the calling thread
+    /// unless `zstd_threads` asks for workers.
+    pub fn new(
+        file: W,
+        compression: ArchiveCompression,
+        jobs: usize,
+        zstd_threads: u32,
+        buffer_size: usize,
+        progress: &'a dyn ProgressSink,
+    ) -> io::Result<Self> {
+        match compression {
+            ArchiveCompression::Gzip => Ok(ArchiveEncoder::Gzip(GzWriter::new(file, jobs, buffer_size, progress)?)),
+            ArchiveCompression::Zstd => {
+                let mut encoder = zstd::Encoder::new(BufWriter::with_capacity(buffer_size, file), ZSTD_LEVEL)?;
+                // Lets restoring and `show` verify the data, as the gzip CRC does
+                encoder.include_checksum(true)?;
+                if zstd_threads > 0 {
+                    encoder.multithread(zstd_threads)?;
+                }
+                Ok(ArchiveEncoder::Zstd(encoder, progress))
+            }
+            ArchiveCompression::Lz4 => {
+                let mut frame = lz4_flex::frame::FrameInfo::new();
+                frame.content_checksum = true;
+                let writer = BufWriter::with_capacity(buffer_size, file);
+                Ok(ArchiveEncoder::Lz4(lz4_flex::frame::FrameEncoder::with_frame_info(frame, writer), progress))
+            }
+        }
+    }
+
+    /// Write any remaining data and the trailer, returning the underlying file
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            ArchiveEncoder::Gzip(enc) => enc.finish(),
+            ArchiveEncoder::Zstd(enc, ..) => enc.finish()?.into_inner().map_err(|e| e.into_error()),
+            ArchiveEncoder::Lz4(enc, ..) => enc.finish().map_err(io::Error::from)?.into_inner().map_err(|e| e.into_error()),
+        }
+    }
+}
+
+impl<W: Write> Write for ArchiveEncoder<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ArchiveEncoder::Gzip(enc) => enc.write(buf),
+            ArchiveEncoder::Zstd(enc, progress) => {
+                let written = enc.write(buf)?;
+                progress.advance(written as u64);
+                Ok(written)
+            }
+            ArchiveEncoder::Lz4(enc, progress) => {
+                let written = enc.write(buf)?;
+                progress.advance(written as u64);
+                Ok(written)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ArchiveEncoder::Gzip(enc) => enc.flush(),
+            ArchiveEncoder::Zstd(enc, ..) => enc.flush(),
+            ArchiveEncoder::Lz4(enc, ..) => enc.flush(),
+        }
+    }
+}
+
+/// Decompress an archive read from `reader` with `compression`
+pub fn archive_decoder<'a>(compression: ArchiveCompression, reader: impl BufRead + 'a) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(match compression {
+        ArchiveCompression::Gzip => Box::new(flate2::bufread::GzDecoder::new(reader)),
+        ArchiveCompression::Zstd => Box::new(zstd::Decoder::with_buffer(reader)?),
+        ArchiveCompression::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+    })
+}
+
+/// Outer compression of an exported trash bundle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BundleCompression {
+    #[default]
+    Gzip,
+    Zstd,
+    None,
+}
+
+impl BundleCompression {
+    /// Names accepted by `from_name`
+    pub const NAMES: &'static [&'static str] = &["gzip", "zstd", "none"];
+
+    /// Look up a compression by the name used on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gzip" | "gz" => Some(BundleCompression::Gzip),
+            "zstd" | "zst" => Some(BundleCompression::Zstd),
+            "none" => Some(BundleCompression::None),
+            _ => None,
+        }
+    }
+
+    /// File extension conventionally used for bundles with this compression
+    pub fn extension(self) -> &'static str {
+        match self {
+            BundleCompression::Gzip => "tar.gz",
+            BundleCompression::Zstd => "tar.zst",
+            BundleCompression::None => "tar",
+        }
+    }
+
+    /// Identify the compression of a bundle from its first bytes
+    ///
+    /// Anything that is neither gzip nor zstd is assumed to be a plain tar.
+    pub fn detect(magic: &[u8]) -> Self {
+        if magic.starts_with(&GZIP_HEADER[..2]) {
+            BundleCompression::Gzip
+        } else if magic.starts_with(&ZSTD_MAGIC) {
+            BundleCompression::Zstd
+        } else {
+            BundleCompression::None
+        }
+    }
+}
+
+/// Writer for the outer layer of an exported bundle
+pub enum BundleWriter {
+    Gzip(GzEncoder<BufWriter<fs::File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<fs::File>>),
+    None(BufWriter<fs::File>),
+}
+
+impl BundleWriter {
+    /// Create a buffered writer for `file` using `compression`
+    pub fn new(file: fs::File, compression: BundleCompression) -> io::Result<Self> {
+        let file = BufWriter::with_capacity(DEFAULT_BUFFER_SIZE, file);
+        Ok(match compression {
+            BundleCompression::Gzip => BundleWriter::Gzip(GzEncoder::new(file, Compression::default())),
+            BundleCompression::Zstd => BundleWriter::Zstd(zstd::Encoder::new(file, 0)?),
+            BundleCompression::None => BundleWriter::None(file),
+        })
+    }
+
+    /// Write any trailer and flush the buffer, returning the underlying file
+    pub fn finish(self) -> io::Result<fs::File> {
+        let buffered = match self {
+            BundleWriter::Gzip(enc) => enc.finish()?,
+            BundleWriter::Zstd(enc) => enc.finish()?,
+            BundleWriter::None(file) => file,
+        };
+        buffered.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+impl Write for BundleWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            BundleWriter::Gzip(enc) => enc.write(buf),
+            BundleWriter::Zstd(enc) => enc.write(buf),
+            BundleWriter::None(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            BundleWriter::Gzip(enc) => enc.flush(),
+            BundleWriter::Zstd(enc) => enc.flush(),
+            BundleWriter::None(file) => file.flush(),
+        }
+    }
+}
+
+/// Reader for the outer layer of a bundle, whatever its compression
+pub enum BundleReader {
+    Gzip(MultiGzDecoder<BufReader<fs::File>>),
+    Zstd(zstd::Decoder<'static, BufReader<fs::File>>),
+    None(BufReader<fs::File>),
+}
+
+impl BundleReader {
+    /// Create a buffered reader for `file`, detecting its compression
+    pub fn new(file: fs::File) -> io::Result<Self> {
+        let mut file = BufReader::with_capacity(DEFAULT_BUFFER_SIZE, file);
+        Ok(match BundleCompression::detect(file.fill_buf()?) {
+            BundleCompression::Gzip => BundleReader::Gzip(MultiGzDecoder::new(file)),
+            BundleCompression::Zstd => BundleReader::Zstd(zstd::Decoder::with_buffer(file)?),
+            BundleCompression::None => BundleReader::None(file),
+        })
+    }
+}
+
+impl Read for BundleReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            BundleReader::Gzip(dec) => dec.read(buf),
+            BundleReader::Zstd(dec) => dec.read(buf),
+            BundleReader::None(file) => file.read(buf),
+        }
+    }
+}
+
+/// Gzip encoder that deflates fixed-size blocks on a thread pool
+///
+/// Every block is ended with a sync flush so the compressed blocks can be
+/// concatenated into one deflate stream, wrapped in a single gzip member.
+/// The output is therefore readable by a plain `GzDecoder`.
+pub struct ParallelGzEncoder<'a, W: Write> {
+    inner: W,
+    pool: ThreadPool,
+    level: Compression,
+    blocks: Vec<Vec<u8>>,
+    current: Vec<u8>,
+    crc: Crc,
+    progress: &'a dyn ProgressSink,
+}
+
+impl<'a, W: Write> ParallelGzEncoder<'a, W> {
+    /// Create an encoder writing to `inner` with `jobs` worker threads
+    pub fn new(mut inner: W, jobs: usize, level: Compression, progress: &'a dyn ProgressSink) -> io::Result<Self> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()
+            .map_err(io::Error::other)?;
+        inner.write_all(&GZIP_HEADER)?;
+
+        Ok(ParallelGzEncoder {
+            inner,
+            pool,
+            level,
+            blocks: Vec::with_capacity(jobs),
+            current: Vec::with_capacity(BLOCK_SIZE),
+            crc: Crc::new(),
+            progress,
+        })
+    }
+
+    /// Compress all pending blocks in parallel and write them in order
+    fn compress_pending(&mut self) -> io::Result<()> {
+        if self.blocks.is_empty() {
+            return Ok(());
+        }
+
+        let level = self.level;
+        let progress = self.progress;
+        let compressed = self.pool.install(|| {
+            self.blocks
+                .par_iter()
+                .map(|block| {
+                    let out = deflate_block(block, level);
+                    progress.advance(block.len() as u64);
+                    out
+                })
+                .collect::<io::Result<Vec<_>>>()
+        })?;
+
+        for block in compressed {
+            self.inner.write_all(&block)?;
+        }
+        self.blocks.clear();
+        Ok(())
+    }
+
+    /// Write any remaining data and the gzip trailer, returning the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.current.is_empty() {
+            let block = std::mem::take(&mut self.current);
+            self.blocks.push(block);
+        }
+        self.compress_pending()?;
+
+        // An empty final block terminates the deflate stream
+        let mut end = Compress::new(self.level, false);
+        let mut out = Vec::with_capacity(16);
+        end.compress_vec(&[], &mut out, FlushCompress::Finish)
+            .map_err(io::Error::other)?;
+        self.inner.write_all(&out)?;
+
+        self.inner.write_all(&self.crc.sum().to_le_bytes())?;
+        self.inner.write_all(&self.crc.amount().to_le_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ParallelGzEncoder<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(BLOCK_SIZE - self.current.len());
+        self.current.extend_from_slice(&buf[..len]);
+        self.crc.update(&buf[..len]);
+
+        if self.current.len() == BLOCK_SIZE {
+            let block = std::mem::replace(&mut self.current, Vec::with_capacity(BLOCK_SIZE));
+            self.blocks.push(block);
+            if self.blocks.len() == self.pool.current_num_threads() {
+                self.compress_pending()?;
+            }
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Deflate a single block, ending it with a sync flush
+fn deflate_block(block: &[u8], level: Compression) -> io::Result<Vec<u8>> {
+    let mut compress = Compress::new(level, false);
+    let mut out = Vec::with_capacity(block.len() / 2 + 64);
+
+    loop {
+        if out.len() == out.capacity() {
+            out.reserve(block.len() / 4 + 64);
+        }
+        let consumed = compress.total_in() as usize;
+        compress.compress_vec(&block[consumed..], &mut out, FlushCompress::Sync)
+            .map_err(io::Error::other)?;
+
+        // The flush is complete once all input is consumed and output space remains
+        if compress.total_in() as usize == block.len() && out.len() < out.capacity() {
+            break;
+        }
+    }
+
+    Ok(out)
+}
@@ -0,0 +1,367 @@
+//! Configuration file handling
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use toml::{Table, Value};
+
+use crate::error::{Context, Result, TrsError};
+use crate::format::parse_size;
+
+/// Type of value accepted by a configuration key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Boolean,
+    Integer,
+    String,
+    Duration,
+    Size,
+}
+
+impl ValueKind {
+    /// Human readable name of the value type
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueKind::Boolean => "boolean",
+            ValueKind::Integer => "integer",
+            ValueKind::String => "string",
+            ValueKind::Duration => "duration",
+            ValueKind::Size => "size",
+        }
+    }
+}
+
+/// Schema entry for a configuration key
+#[derive(Debug)]
+pub struct ConfigKey {
+    pub name: &'static str,
+    pub kind: ValueKind,
+    pub default: Option<&'static str>,
+    pub description: &'static str,
+}
+
+/// All keys recognised in the configuration file
+pub const KEYS: &[ConfigKey] = &[
+    ConfigKey {
+        name: "trash_dir",
+        kind: ValueKind::String,
+        default: None,
+        description: "Directory where trashed items are stored (defaults to the local data directory)",
+    },
+    ConfigKey {
+        name: "jobs",
+        kind: ValueKind::Integer,
+        default: None,
+        description: "Number of threads used to compress archives (defaults to the number of cores)",
+    },
+    ConfigKey {
+        name: "buffer_size",
+        kind: ValueKind::Integer,
+        default: Some("131072"),
+        description: "Size in bytes of the I/O buffers used when reading and writing archives",
+    },
+    ConfigKey {
+        name: "shred_passes",
+        kind: ValueKind::Integer,
+        default: Some("1"),
+        description: "Number of random-data passes used by --shred",
+    },
+    ConfigKey {
+        name: "retention_days",
+        kind: ValueKind::Integer,
+        default: None,
+        description: "Automatically delete items trashed more than this many days ago",
+    },
+    ConfigKey {
+        name: "max_size",
+        kind: ValueKind::Size,
+        default: None,
+        description: "Maximum total size of the trash (e.g. 5GB); the oldest items are evicted beyond it",
+    },
+    ConfigKey {
+        name: "compress_threshold",
+        kind: ValueKind::Size,
+        default: None,
+        description: "Files smaller than this (e.g. 4KiB) are stored as they are instead of archived",
+    },
+    ConfigKey {
+        name: "confirm_files",
+        kind: ValueKind::Integer,
+        default: Some("10000"),
+        description: "Ask before trashing a directory holding more files than this (0 never asks)",
+    },
+    ConfigKey {
+        name: "confirm_size",
+        kind: ValueKind::Size,
+        default: Some("5GB"),
+        description: "Ask before trashing a directory larger than this (0 never asks)",
+    },
+    ConfigKey {
+        name: "warn_size",
+        kind: ValueKind::Size,
+        default: Some("1GB"),
+        description: "Warn after adding to the trash once it holds more than this (0 never warns)",
+    },
+    ConfigKey {
+        name: "metadata_name",
+        kind: ValueKind::String,
+        default: Some(".metadata"),
+        description: "Name of the metadata file in the trash directory, starting with '.'; an existing file is not renamed",
+    },
+    ConfigKey {
+        name: "min_age",
+        kind: ValueKind::Duration,
+        default: None,
+        description: "Minimum time an item stays in the trash before it can be evicted (e.g. 1d)",
+    },
+    ConfigKey {
+        name: "timestamps",
+        kind: ValueKind::String,
+        default: Some("keep"),
+        description: "Whether restored files keep their modification times (keep) or get the time of the restore (reset)",
+    },
+    ConfigKey {
+        name: "compression",
+        kind: ValueKind::String,
+        default: Some("gzip"),
+        description: "Compression of new archives: gzip (.tar.gz), zstd (.tar.zst) or lz4 (.tar.lz4); setting it archives files even on the trash's filesystem",
+    },
+    ConfigKey {
+        name: "auto_compress",
+        kind: ValueKind::Boolean,
+        default: Some("false"),
+        description: "Choose the compression of each file from its extension, keeping media and archives as they are",
+    },
+    ConfigKey {
+        name: "dedupe",
+        kind: ValueKind::Boolean,
+        default: Some("false"),
+        description: "Store files identical to one already in the trash as a link to its archive instead of a copy (hashing each file adds cost)",
+    },
+    ConfigKey {
+        name: "zstd_threads",
+        kind: ValueKind::Integer,
+        default: Some("0"),
+        description: "Number of worker threads used by zstd compression (0 compresses on the main thread)",
+    },
+    ConfigKey {
+        name: "xdg_compat",
+        kind: ValueKind::Boolean,
+        default: Some("false"),
+        description: "Use the FreeDesktop.org trash ($XDG_DATA_HOME/Trash) shared with file managers instead of trash_dir",
+    },
+    ConfigKey {
+        name: "desktop_sync",
+        kind: ValueKind::Boolean,
+        default: Some("false"),
+        description: "Trash into the FreeDesktop.org trash like xdg_compat, while still listing and restoring items left in trash_dir",
+    },
+    ConfigKey {
+        name: "native_trash",
+        kind: ValueKind::Boolean,
+        default: Some("false"),
+        description: "On macOS, use the system trash (~/.Trash) shown by the Finder instead of trash_dir",
+    },
+    ConfigKey {
+        name: "tui_theme",
+        kind: ValueKind::String,
+        default: Some("default"),
+        description: "Color theme of `trs interactive`: default, light or mono",
+    },
+    ConfigKey {
+        name: "tui_accent",
+        kind: ValueKind::String,
+        default: None,
+        description: "Accent color of `trs interactive`, as a color name or #rrggbb (overrides the theme)",
+    },
+];
+
+/// Where an effective configuration value comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueSource {
+    File,
+    Default,
+}
+
+impl ValueSource {
+    /// Human readable name of the source
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueSource::File => "config file",
+            ValueSource::Default => "default",
+        }
+    }
+}
+
+/// Environment variable naming a config file to use instead of the default one
+pub const CONFIG_VAR: &str = "TRS_CONFIG";
+
+/// Configuration loaded from `config.toml`
+#[derive(Debug, Clone)]
+pub struct Config {
+    path: PathBuf,
+    table: Table,
+}
+
+impl Config {
+    /// Default location of the configuration file
+    ///
+    /// That is `$TRS_CONFIG` when set, so scripts and tests can use a trash
+    /// of their own, and `trs/config.toml` in the config directory otherwise.
+    pub fn default_path() -> PathBuf {
+        if let Some(path) = std::env::var_os(CONFIG_VAR).filter(|path| !path.is_empty()) {
+            return PathBuf::from(path);
+        }
+        dirs::config_dir()
+            .expect("Could not find config directory")
+            .join("trs")
+            .join("config.toml")
+    }
+
+    /// Load the configuration from the default location
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::default_path())
+    }
+
+    /// Load the configuration from a file, treating a missing file as empty
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let table = if path.exists() {
+            let content = fs::read_to_string(path).with_context(|| format!("reading config {}", path.display()))?;
+            content.parse::<Table>().map_err(|e| {
+                TrsError::invalid_input(format!("Invalid config file {}: {}", path.display(), e))
+            })?
+        } else {
+            Table::new()
+        };
+
+        Ok(Config {
+            path: path.to_path_buf(),
+            table,
+        })
+    }
+
+    /// Write the configuration back to its file
+    pub fn save(&self) -> Result<()> {
+        let writing = || format!("writing config {}", self.path.display());
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(writing)?;
+        }
+        let content = toml::to_string(&self.table).map_err(io::Error::other).with_context(writing)?;
+        fs::write(&self.path, content).with_context(writing)?;
+        Ok(())
+    }
+
+    /// Path of the configuration file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Look up the schema entry for a key
+    pub fn key(name: &str) -> Result<&'static ConfigKey> {
+        KEYS.iter().find(|key| key.name == name).ok_or_else(|| {
+            let valid = KEYS.iter().map(|key| key.name).collect::<Vec<_>>().join(", ");
+            TrsError::invalid_input(format!("Unknown config key '{}'. Valid keys: {}", name, valid))
+        })
+    }
+
+    /// Get the effective value of a key as displayed to the user
+    pub fn get(&self, name: &str) -> Result<(Option<String>, ValueSource)> {
+        let key = Self::key(name)?;
+        match self.table.get(name) {
+            Some(value) => Ok((Some(display_value(value)), ValueSource::File)),
+            None => Ok((key.default.map(str::to_string), ValueSource::Default)),
+        }
+    }
+
+    /// Parse `raw` according to the key's schema and store it
+    pub fn set(&mut self, name: &str, raw: &str) -> Result<()> {
+        let key = Self::key(name)?;
+        let value = parse_value(key, raw)?;
+        self.table.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Remove a key from the file, returning whether it was set
+    pub fn unset(&mut self, name: &str) -> Result<bool> {
+        Self::key(name)?;
+        Ok(self.table.remove(name).is_some())
+    }
+
+    /// Effective boolean value of a key
+    pub fn boolean(&self, name: &str) -> Option<bool> {
+        self.raw(name).and_then(|raw| raw.parse().ok())
+    }
+
+    /// Effective integer value of a key
+    pub fn integer(&self, name: &str) -> Option<i64> {
+        self.raw(name).and_then(|raw| raw.parse().ok())
+    }
+
+    /// Effective string value of a key
+    pub fn string(&self, name: &str) -> Option<String> {
+        self.raw(name)
+    }
+
+    /// Effective duration value of a key
+    pub fn duration(&self, name: &str) -> Option<Duration> {
+        self.raw(name).and_then(|raw| parse_duration(&raw).ok())
+    }
+
+    /// Effective size value of a key in bytes
+    pub fn size(&self, name: &str) -> Option<u64> {
+        self.raw(name).and_then(|raw| parse_size(&raw).ok())
+    }
+
+    /// Effective value of a key from the file or its default
+    fn raw(&self, name: &str) -> Option<String> {
+        self.get(name).ok().and_then(|(value, _)| value)
+    }
+}
+
+/// Format a stored TOML value for display
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Parse a raw string into the TOML value required by a key
+fn parse_value(key: &ConfigKey, raw: &str) -> Result<Value> {
+    let invalid = || {
+        TrsError::invalid_input(format!("Invalid value '{}' for {}: expected {}", raw, key.name, key.kind.name()))
+    };
+
+    match key.kind {
+        ValueKind::Boolean => raw.parse::<bool>().map(Value::Boolean).map_err(|_| invalid()),
+        ValueKind::Integer => raw.parse::<i64>().map(Value::Integer).map_err(|_| invalid()),
+        ValueKind::String => Ok(Value::String(raw.to_string())),
+        ValueKind::Duration => parse_duration(raw)
+            .map(|_| Value::String(raw.to_string()))
+            .map_err(|_| invalid()),
+        ValueKind::Size => parse_size(raw)
+            .map(|_| Value::String(raw.to_string()))
+            .map_err(|_| invalid()),
+    }
+}
+
+/// Parse a duration such as `30d`, `12h`, `15m` or `45s`
+pub fn parse_duration(raw: &str) -> Result<Duration> {
+    let raw = raw.trim();
+    let split = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+    let (number, unit) = raw.split_at(split);
+
+    let invalid = || TrsError::invalid_input(format!("Invalid duration '{}'", raw));
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match unit.trim() {
+        "s" | "" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+
+    Ok(Duration::from_secs(number * seconds))
+}
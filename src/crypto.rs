@@ -0,0 +1,277 @@
+//! Encryption of archives at rest, for `trs move --encrypt`
+//!
+//! An encrypted item is its archive sealed with AES-256-GCM and stored with
+//! `.enc` appended, such as `.tar.gz.enc`. The key is derived from a passphrase with
+//! Argon2id. The salt and Argon2 parameters needed to derive it again are
+//! in the header of the file along with the nonce, so the metadata holds
+//! nothing needed for decryption. The archive is sealed in chunks with the
+//! STREAM construction, so items of any size are encrypted and decrypted
+//! without holding them in memory, and a damaged, truncated or reordered
+//! file fails to decrypt rather than going unnoticed.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::stream::{DecryptorBE32, EncryptorBE32};
+use aes_gcm::aead::generic_array::GenericArray;
+use aes_gcm::aead::OsRng;
+use aes_gcm::{Aes256Gcm, Key};
+use argon2::{Algorithm, Argon2, Params, Version};
+
+use crate::compression::ArchiveCompression;
+
+/// Extension appended to the names of encrypted archives
+pub const ENCRYPTED_EXTENSION: &str = ".enc";
+
+/// First bytes of an encrypted archive, naming the format and its version
+const MAGIC: &[u8; 8] = b"TRSENC01";
+
+/// Length of the random salt given to Argon2id
+const SALT_LEN: usize = 16;
+
+/// Length of the nonce prefix of the STREAM construction, which adds a counter and a last-chunk flag
+const NONCE_LEN: usize = 7;
+
+/// Bytes of the archive sealed in each chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Bytes the authentication tag adds to each chunk
+const TAG_LEN: usize = 16;
+
+/// Whether the trash entry `name` is an encrypted archive
+pub fn is_encrypted(name: &str) -> bool {
+    name.strip_suffix(ENCRYPTED_EXTENSION).is_some_and(|archive| ArchiveCompression::from_file_name(archive).is_some())
+}
+
+/// A passphrase to derive archive keys from, kept out of debug output
+#[derive(Clone)]
+pub struct Passphrase(String);
+
+impl Passphrase {
+    pub fn new(passphrase: impl Into<String>) -> Self {
+        Passphrase(passphrase.into())
+    }
+}
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Passphrase(..)")
+    }
+}
+
+/// Derive the 256-bit key for `passphrase` and `salt` with Argon2id
+fn derive_key(passphrase: &Passphrase, salt: &[u8], params: Params) -> io::Result<Key<Aes256Gcm>> {
+    let mut key = Key::<Aes256Gcm>::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.0.as_bytes(), salt, &mut key)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("deriving the key: {}", e)))?;
+    Ok(key)
+}
+
+/// Unreadable or tampered data, or the wrong passphrase: AES-GCM cannot tell them apart
+fn undecryptable() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "wrong passphrase, or the encrypted archive is damaged")
+}
+
+/// Encrypts everything written to it into `inner`, after a header
+///
+/// [`finish`](EncryptWriter::finish) must be called to seal the last chunk.
+///
+/// ```
+/// use std::io::{Read, Write};
+/// use trs::crypto::{DecryptReader, EncryptWriter, Passphrase};
+///
+/// let passphrase = Passphrase::new("correct horse");
+/// let mut writer = EncryptWriter::new(Vec::new(), &passphrase).unwrap();
+/// writer.write_all(b"secret notes").unwrap();
+/// let sealed = writer.finish().unwrap();
+///
+/// let mut plain = String::new();
+/// DecryptReader::new(sealed.as_slice(), &passphrase).unwrap().read_to_string(&mut plain).unwrap();
+/// assert_eq!(plain, "secret notes");
+///
+/// // Any other passphrase fails as soon as the archive is opened
+/// assert!(DecryptReader::new(sealed.as_slice(), &Passphrase::new("wrong")).is_err());
+/// ```
+pub struct EncryptWriter<W: Write> {
+    inner: W,
+    encryptor: Option<EncryptorBE32<Aes256Gcm>>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    /// Derive a key for `passphrase` with a fresh salt and write the header to `inner`
+    pub fn new(mut inner: W, passphrase: &Passphrase) -> io::Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+        let params = Params::default();
+        let key = derive_key(passphrase, &salt, params.clone())?;
+
+        inner.write_all(MAGIC)?;
+        for cost in [params.m_cost(), params.t_cost(), params.p_cost()] {
+            inner.write_all(&cost.to_le_bytes())?;
+        }
+        inner.write_all(&salt)?;
+        inner.write_all(&nonce)?;
+        Ok(EncryptWriter {
+            inner,
+            encryptor: Some(EncryptorBE32::new(&key, GenericArray::from_slice(&nonce))),
+            buffer: Vec::with_capacity(CHUNK_SIZE * 2),
+        })
+    }
+
+    /// Seal the last chunk and return the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let encryptor = self.encryptor.take().expect("the encryptor is only taken by finish");
+        let sealed = encryptor.encrypt_last(self.buffer.as_slice()).map_err(|_| io::Error::other("encryption failed"))?;
+        self.inner.write_all(&sealed)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        // A full chunk is only sealed once more follows, as the last one is marked
+        while self.buffer.len() > CHUNK_SIZE {
+            let encryptor = self.encryptor.as_mut().expect("the encryptor is only taken by finish");
+            let sealed = encryptor
+                .encrypt_next(&self.buffer[..CHUNK_SIZE])
+                .map_err(|_| io::Error::other("encryption failed"))?;
+            self.inner.write_all(&sealed)?;
+            self.buffer.drain(..CHUNK_SIZE);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The file an archive is written to, sealing it if a passphrase was given
+pub enum ArchiveWriter {
+    Plain(fs::File),
+    Encrypted(Box<EncryptWriter<fs::File>>),
+}
+
+impl ArchiveWriter {
+    /// Write to `file`, encrypting with a key derived from `passphrase` if there is one
+    pub fn new(file: fs::File, passphrase: Option<&Passphrase>) -> io::Result<Self> {
+        Ok(match passphrase {
+            Some(passphrase) => ArchiveWriter::Encrypted(Box::new(EncryptWriter::new(file, passphrase)?)),
+            None => ArchiveWriter::Plain(file),
+        })
+    }
+
+    /// Seal the last chunk if encrypting, returning the file
+    pub fn finish(self) -> io::Result<fs::File> {
+        match self {
+            ArchiveWriter::Plain(file) => Ok(file),
+            ArchiveWriter::Encrypted(writer) => writer.finish(),
+        }
+    }
+}
+
+impl Write for ArchiveWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ArchiveWriter::Plain(file) => file.write(buf),
+            ArchiveWriter::Encrypted(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ArchiveWriter::Plain(file) => file.flush(),
+            ArchiveWriter::Encrypted(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Decrypts an archive written by [`EncryptWriter`]
+pub struct DecryptReader<R: Read> {
+    inner: R,
+    decryptor: Option<DecryptorBE32<Aes256Gcm>>,
+    plain: Vec<u8>,
+    position: usize,
+    lookahead: Option<u8>,
+}
+
+impl<R: Read> DecryptReader<R> {
+    /// Read the header from `inner`, derive the key for `passphrase` and decrypt the first chunk
+    ///
+    /// A wrong passphrase is noticed here, before anything is read.
+    pub fn new(mut inner: R, passphrase: &Passphrase) -> io::Result<Self> {
+        let mut magic = [0u8; MAGIC.len()];
+        inner.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an archive encrypted by trs"));
+        }
+        let mut costs = [0u32; 3];
+        for cost in &mut costs {
+            let mut bytes = [0u8; 4];
+            inner.read_exact(&mut bytes)?;
+            *cost = u32::from_le_bytes(bytes);
+        }
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        inner.read_exact(&mut salt)?;
+        inner.read_exact(&mut nonce)?;
+
+        let params = Params::new(costs[0], costs[1], costs[2], None)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("invalid key derivation parameters: {}", e)))?;
+        let key = derive_key(passphrase, &salt, params)?;
+        let mut reader = DecryptReader {
+            inner,
+            decryptor: Some(DecryptorBE32::new(&key, GenericArray::from_slice(&nonce))),
+            plain: Vec::new(),
+            position: 0,
+            lookahead: None,
+        };
+        reader.refill()?;
+        Ok(reader)
+    }
+
+    /// Decrypt the next chunk into `plain`, leaving it empty after the last one
+    fn refill(&mut self) -> io::Result<()> {
+        self.plain.clear();
+        self.position = 0;
+        let Some(decryptor) = self.decryptor.as_mut() else {
+            return Ok(());
+        };
+
+        // Reading a byte past the chunk tells whether it is the last one
+        let sealed_len = CHUNK_SIZE + TAG_LEN;
+        let mut sealed = Vec::with_capacity(sealed_len + 1);
+        sealed.extend(self.lookahead.take());
+        (&mut self.inner).take((sealed_len + 1 - sealed.len()) as u64).read_to_end(&mut sealed)?;
+        self.plain = if sealed.len() > sealed_len {
+            self.lookahead = sealed.pop();
+            decryptor.decrypt_next(sealed.as_slice()).map_err(|_| undecryptable())?
+        } else {
+            let decryptor = self.decryptor.take().expect("checked above");
+            decryptor.decrypt_last(sealed.as_slice()).map_err(|_| undecryptable())?
+        };
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plain.len() {
+            if self.decryptor.is_none() {
+                return Ok(0);
+            }
+            self.refill()?;
+        }
+        let read = buf.len().min(self.plain.len() - self.position);
+        buf[..read].copy_from_slice(&self.plain[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
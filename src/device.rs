@@ -0,0 +1,194 @@
+//! Trash directories on other filesystems
+//!
+//! Moving an item into a trash on another filesystem means copying all of
+//! its data. A device trash is a `.trs-trash` directory at the root of the
+//! filesystem the item is on, like the `.Trash-$uid` directories of the XDG
+//! spec, so trashing it is a single compression pass without a copy between
+//! devices. Device trashes in use are recorded so restore can search them,
+//! along with the `.Trash-$uid` directories used in XDG mode.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Context, Result, TrsError};
+
+/// Name of the trash directory created at the root of a filesystem
+pub const DEVICE_TRASH_NAME: &str = ".trs-trash";
+
+/// File listing the device trashes in use, one per line
+fn registry_path() -> PathBuf {
+    dirs::data_local_dir()
+        .expect("Could not find local share directory")
+        .join("trs")
+        .join("device-trashes")
+}
+
+/// Root of the filesystem containing `path`
+///
+/// Walks up the parents of `path` for as long as they are on the same
+/// device; the last one is the mount point. `path` must exist.
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use std::path::Path;
+/// use trs::device::mount_root;
+///
+/// // The root directory is always its own mount point
+/// assert_eq!(mount_root(Path::new("/")).unwrap(), Path::new("/"));
+///
+/// // Any other directory lies below its mount point
+/// let dir = std::env::temp_dir().canonicalize().unwrap();
+/// assert!(dir.starts_with(mount_root(&dir).unwrap()));
+/// # }
+/// ```
+#[cfg(unix)]
+pub fn mount_root(path: &Path) -> io::Result<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    let device = fs::metadata(path)?.dev();
+    let mut root = path;
+    while let Some(parent) = root.parent() {
+        if fs::metadata(parent)?.dev() != device {
+            break;
+        }
+        root = parent;
+    }
+    Ok(root.to_path_buf())
+}
+
+/// Whether `a` and `b` are on the same filesystem
+///
+/// Paths that do not exist yet are compared by their nearest existing parent.
+#[cfg(unix)]
+pub(crate) fn same_device(a: &Path, b: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let device = |path: &Path| -> io::Result<u64> {
+        let existing = path.ancestors().find(|ancestor| ancestor.exists()).unwrap_or(path);
+        Ok(fs::metadata(existing)?.dev())
+    };
+    Ok(device(a)? == device(b)?)
+}
+
+/// Trash directory to use for `path` when it is on another filesystem than `home_trash`
+///
+/// `path` must be absolute. Returns `None` when the item is on the same
+/// filesystem as the home trash, or when the device trash cannot be created
+/// or recorded, for example on a read-only filesystem, so the caller falls
+/// back to the home trash.
+#[cfg(unix)]
+pub fn device_trash_dir(path: &Path, home_trash: &Path) -> Option<PathBuf> {
+    // Start from the parent, so trashing a mount point itself uses the
+    // filesystem it is mounted on
+    let parent = path.parent()?;
+    if same_device(parent, home_trash).ok()? {
+        return None;
+    }
+    let trash_dir = mount_root(parent).ok()?.join(DEVICE_TRASH_NAME);
+    fs::create_dir_all(&trash_dir).ok()?;
+    register(&trash_dir).ok()?;
+    Some(trash_dir)
+}
+
+/// Device trashes are not supported on this platform
+#[cfg(not(unix))]
+pub fn device_trash_dir(_path: &Path, _home_trash: &Path) -> Option<PathBuf> {
+    None
+}
+
+/// Whether `path` is on a solid-state drive, as reported by the kernel
+///
+/// SSDs write new data to fresh blocks, so overwriting a file in place
+/// does not reliably erase what it held. Returns `None` when this cannot be
+/// told, for example for network and virtual filesystems.
+#[cfg(target_os = "linux")]
+pub fn is_solid_state(path: &Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let device = fs::metadata(path).ok()?.dev();
+    let block = PathBuf::from(format!("/sys/dev/block/{}:{}", libc::major(device), libc::minor(device)));
+    // Partitions share the queue of their disk, one level up
+    let rotational = fs::read_to_string(block.join("queue/rotational"))
+        .or_else(|_| fs::read_to_string(block.join("../queue/rotational")))
+        .ok()?;
+    Some(rotational.trim() == "0")
+}
+
+/// The kind of drive is only known on Linux
+#[cfg(not(target_os = "linux"))]
+pub fn is_solid_state(_path: &Path) -> Option<bool> {
+    None
+}
+
+/// Bytes available to the current user on the filesystem containing `path`
+///
+/// Returns `None` where this cannot be asked, so callers go ahead as before.
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> io::Result<Option<u64>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).map_err(io::Error::from)?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: the path is NUL-terminated and `stats` is only read once filled in
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stats = unsafe { stats.assume_init() };
+    // The fields are narrower than 64 bits on some systems
+    #[allow(clippy::useless_conversion)]
+    Ok(Some(u64::from(stats.f_bavail).saturating_mul(u64::from(stats.f_frsize))))
+}
+
+/// Free space is only asked for on Unix
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+/// Fail with `InsufficientSpace` when `needed` bytes do not fit in the `available` ones
+///
+/// ```
+/// use trs::device::check_space;
+///
+/// assert!(check_space(1024, 4096).is_ok());
+/// assert!(check_space(4096, 4096).is_ok());
+///
+/// let e = check_space(5 << 30, 1 << 20).unwrap_err();
+/// assert_eq!(e.to_string(), "Not enough space in trash (need 5.0 GiB, have 1.0 MiB); pass --force to try anyway");
+/// ```
+pub fn check_space(needed: u64, available: u64) -> Result<()> {
+    if needed > available {
+        return Err(TrsError::InsufficientSpace { needed, available });
+    }
+    Ok(())
+}
+
+/// Device trashes that have been used and still exist
+///
+/// Trashes on filesystems that are not mounted right now are left out.
+pub fn known_trash_dirs() -> Vec<PathBuf> {
+    fs::read_to_string(registry_path())
+        .unwrap_or_default()
+        .lines()
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Record `trash_dir` as a device trash, if it is not already known
+pub(crate) fn register(trash_dir: &Path) -> Result<()> {
+    let registry = registry_path();
+    let content = fs::read_to_string(&registry).unwrap_or_default();
+    if content.lines().any(|line| Path::new(line) == trash_dir) {
+        return Ok(());
+    }
+
+    let writing = || format!("writing {}", registry.display());
+    if let Some(parent) = registry.parent() {
+        fs::create_dir_all(parent).with_context(writing)?;
+    }
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(&registry).with_context(writing)?;
+    writeln!(file, "{}", trash_dir.display()).with_context(writing)
+}
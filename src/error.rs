@@ -0,0 +1,157 @@
+//! Error type for trash operations
+
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Result type used throughout the crate
+pub type Result<T> = std::result::Result<T, TrsError>;
+
+/// Errors returned by trash operations
+#[derive(Debug, Error)]
+pub enum TrsError {
+    /// The path to move to the trash does not exist
+    #[error("{} does not exist", .0.display())]
+    SourceNotFound(PathBuf),
+    /// The named item does not exist in the trash
+    #[error("{0} not found in Trash")]
+    NotInTrash(String),
+    /// A name given by the user matches several items in the trash
+    #[error("{}", ambiguous_message(.name, .candidates))]
+    Ambiguous { name: String, candidates: Vec<String> },
+    /// The target path already exists
+    #[error("{0} already exists")]
+    AlreadyExists(String),
+    /// An ancestor of the path to restore to is a file rather than a directory
+    #[error(
+        "Cannot restore to {}: {} is not a directory; restore elsewhere with --to",
+        .path.display(),
+        .blocker.display()
+    )]
+    ParentNotDirectory { path: PathBuf, blocker: PathBuf },
+    /// The directory the item was in no longer exists, and recreating it was not asked for
+    #[error(
+        "Cannot restore to {}: {} no longer exists; pass --restore-parent to recreate it, or restore elsewhere with --to",
+        .path.display(),
+        .missing.display()
+    )]
+    ParentMissing { path: PathBuf, missing: PathBuf },
+    /// An archive in the trash could not be read
+    #[error("Archive {name} is corrupted: {source}")]
+    ArchiveError { name: String, source: io::Error },
+    /// The metadata file is not valid JSON
+    #[error("Metadata file {} is corrupted: {source}", .path.display())]
+    MetadataCorrupt { path: PathBuf, source: serde_json::Error },
+    /// Data could not be serialized as JSON
+    #[error("Could not serialize to JSON: {0}")]
+    Serialize(#[from] serde_json::Error),
+    /// Access to a path was denied
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    /// A directory holds more files or bytes than can be moved without confirming
+    #[error(
+        "{} holds {} files ({}); pass --yes to trash it anyway",
+        .path.display(),
+        crate::format::format_count(*.files),
+        crate::format::format_size(*.bytes)
+    )]
+    LargeDirectory { path: PathBuf, files: u64, bytes: u64 },
+    /// The filesystem of the trash has less free space than the item needs
+    #[error(
+        "Not enough space in trash (need {}, have {}); pass --force to try anyway",
+        crate::format::format_size(*.needed),
+        crate::format::format_size(*.available)
+    )]
+    InsufficientSpace { needed: u64, available: u64 },
+    /// Part of an item cannot be removed once it is archived, and making it writable was not asked for
+    #[error(
+        "Cannot remove {}: {} is read-only; pass --force to make it writable and trash it anyway",
+        .path.display(),
+        .blocker.display()
+    )]
+    ReadOnly { path: PathBuf, blocker: PathBuf },
+    /// The trash is larger than its configured limit
+    #[error("Trash quota exceeded: {current} bytes used of {limit} allowed")]
+    QuotaExceeded { limit: u64, current: u64 },
+    /// The metadata file is locked by another process
+    #[error("Timed out waiting for the metadata lock")]
+    LockTimeout,
+    /// The operation was stopped by Ctrl-C at a point where nothing was left half done
+    #[error("Interrupted, state saved.")]
+    Interrupted,
+    /// A command-line argument or config value is invalid
+    #[error("{0}")]
+    InvalidInput(String),
+    /// Any other I/O failure, with a description of what was being done
+    #[error("{}", io_message(.context, .source))]
+    Io { context: String, source: io::Error },
+}
+
+impl TrsError {
+    /// Create an error for invalid user input
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        TrsError::InvalidInput(message.into())
+    }
+
+    /// Exit code for the CLI, following the BSD `sysexits.h` conventions
+    ///
+    /// These stay clear of 1 and 2, which report that some or all of several
+    /// items failed.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            TrsError::InvalidInput(_) | TrsError::Ambiguous { .. } | TrsError::LargeDirectory { .. } => 64,
+            TrsError::ArchiveError { .. } | TrsError::MetadataCorrupt { .. } => 65,
+            TrsError::SourceNotFound(_) | TrsError::NotInTrash(_) => 66,
+            TrsError::Serialize(_) => 70,
+            TrsError::AlreadyExists(_)
+            | TrsError::ParentNotDirectory { .. }
+            | TrsError::ParentMissing { .. }
+            | TrsError::QuotaExceeded { .. }
+            | TrsError::InsufficientSpace { .. } => 73,
+            TrsError::Io { .. } => 74,
+            TrsError::LockTimeout => 75,
+            TrsError::PermissionDenied(_) | TrsError::ReadOnly { .. } => 77,
+            TrsError::Interrupted => crate::interrupt::EXIT_CODE,
+        }
+    }
+}
+
+/// Attach a description of the failed operation to I/O errors
+///
+/// The context should name the operation and the path involved, such as
+/// "removing /home/me/notes.txt", so the message `main` prints says which
+/// of many files was the problem.
+pub(crate) trait Context<T> {
+    fn with_context(self, context: impl FnOnce() -> String) -> Result<T>;
+}
+
+impl<T> Context<T> for io::Result<T> {
+    fn with_context(self, context: impl FnOnce() -> String) -> Result<T> {
+        self.map_err(|source| TrsError::Io { context: context(), source })
+    }
+}
+
+/// List the items an ambiguous name could refer to, one per line
+fn ambiguous_message(name: &str, candidates: &[String]) -> String {
+    let mut message = format!("{} matches {} items in Trash; give one of their names:", name, candidates.len());
+    for candidate in candidates {
+        message.push_str("\n  ");
+        message.push_str(candidate);
+    }
+    message
+}
+
+/// Message for an I/O error, prefixed with its context when there is one
+fn io_message(context: &str, source: &io::Error) -> String {
+    if context.is_empty() {
+        source.to_string()
+    } else {
+        format!("{}: {}", context, source)
+    }
+}
+
+impl From<io::Error> for TrsError {
+    fn from(source: io::Error) -> Self {
+        TrsError::Io { context: String::new(), source }
+    }
+}
@@ -0,0 +1,247 @@
+//! Parsing and formatting helpers for user-facing values
+
+use std::borrow::Cow;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+
+use crate::error::{Result, TrsError};
+
+/// Units used by `format_size`, each 1024 times the previous one
+const SIZE_UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Parse a size such as `5GB`, `300MiB`, `10k` or `1024` into bytes
+///
+/// Both decimal (`KB`, `MB`, ...) and binary (`KiB`, `MiB`, ...) suffixes are
+/// accepted; a bare number or single-letter suffix is treated as binary.
+///
+/// ```
+/// use trs::format::parse_size;
+///
+/// assert_eq!(parse_size("1024").unwrap(), 1024);
+/// assert_eq!(parse_size("300MiB").unwrap(), 300 * 1024 * 1024);
+/// assert_eq!(parse_size("5GB").unwrap(), 5_000_000_000);
+/// assert_eq!(parse_size("1.5k").unwrap(), 1536);
+/// assert!(parse_size("5 parsecs").is_err());
+/// ```
+pub fn parse_size(raw: &str) -> Result<u64> {
+    let raw = raw.trim();
+    let split = raw.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(raw.len());
+    let (number, unit) = raw.split_at(split);
+
+    let invalid = || TrsError::invalid_input(format!("Invalid size '{}'", raw));
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "t" | "tib" => 1 << 40,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        _ => return Err(invalid()),
+    };
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Format a size in bytes with binary units and one decimal, e.g. `3.4 MiB`
+///
+/// Sizes below 1 KiB are printed exactly. A value that would round up to
+/// 1024 of a unit is printed in the next unit instead.
+///
+/// ```
+/// use trs::format::format_size;
+///
+/// assert_eq!(format_size(0), "0 B");
+/// assert_eq!(format_size(1023), "1023 B");
+/// assert_eq!(format_size(1024), "1.0 KiB");
+/// assert_eq!(format_size(3_565_158), "3.4 MiB");
+/// assert_eq!(format_size(1024 * 1024 - 1), "1.0 MiB");
+/// assert_eq!(format_size(1 << 40), "1.0 TiB");
+/// assert_eq!(format_size(5 << 50), "5.0 PiB");
+/// assert_eq!(format_size(u64::MAX), "16.0 EiB");
+/// ```
+pub fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1023.95 && unit < SIZE_UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, SIZE_UNITS[unit])
+}
+
+/// Format a count with commas between groups of three digits, e.g. `52,311`
+///
+/// ```
+/// use trs::format::format_count;
+///
+/// assert_eq!(format_count(0), "0");
+/// assert_eq!(format_count(999), "999");
+/// assert_eq!(format_count(52_311), "52,311");
+/// assert_eq!(format_count(1_000_000), "1,000,000");
+/// ```
+pub fn format_count(count: u64) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+/// Format who trashed an item and where as `user@host`, leaving out whichever is unknown
+///
+/// ```
+/// use trs::format::format_owner;
+///
+/// assert_eq!(format_owner(Some("ana"), Some("laptop")).as_deref(), Some("ana@laptop"));
+/// assert_eq!(format_owner(Some("ana"), None).as_deref(), Some("ana"));
+/// assert_eq!(format_owner(None, Some("laptop")).as_deref(), Some("@laptop"));
+/// assert_eq!(format_owner(None, None), None);
+/// ```
+pub fn format_owner(user: Option<&str>, hostname: Option<&str>) -> Option<String> {
+    match (user, hostname) {
+        (Some(user), Some(hostname)) => Some(format!("{}@{}", user, hostname)),
+        (Some(user), None) => Some(user.to_string()),
+        (None, Some(hostname)) => Some(format!("@{}", hostname)),
+        (None, None) => None,
+    }
+}
+
+/// Format a size with `format_size`, or as an exact number of bytes when `exact`
+///
+/// `exact` is set by `--bytes`.
+pub fn display_size(bytes: u64, exact: bool) -> String {
+    if exact {
+        format!("{} B", bytes)
+    } else {
+        format_size(bytes)
+    }
+}
+
+/// Describe how much archiving an item of `original` bytes into `stored` bytes saved
+///
+/// Archives of tiny or already compressed files can end up larger, which is
+/// said as such.
+///
+/// ```
+/// use trs::format::format_ratio;
+///
+/// assert_eq!(format_ratio(3_355_443, 1_153_434), "3.2 MiB -> 1.1 MiB, 66% saved");
+/// assert_eq!(format_ratio(2, 98), "2 B -> 98 B, 4800% larger");
+/// assert_eq!(format_ratio(0, 45), "0 B -> 45 B");
+/// ```
+pub fn format_ratio(original: u64, stored: u64) -> String {
+    let sizes = format!("{} -> {}", format_size(original), format_size(stored));
+    if original == 0 {
+        return sizes;
+    }
+    let change = (stored as f64 / original as f64 - 1.0) * 100.0;
+    if change <= 0.0 {
+        format!("{}, {:.0}% saved", sizes, -change)
+    } else {
+        format!("{}, {:.0}% larger", sizes, change)
+    }
+}
+
+/// Format seconds since the Unix epoch as a local date and time
+pub fn format_timestamp(secs: u64) -> String {
+    match Local.timestamp_opt(secs as i64, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => "Unknown".to_string(),
+    }
+}
+
+/// Parse a local date and time such as `2025-06-01 18:30`, or `18:30` for today, into seconds since the Unix epoch
+///
+/// Seconds are optional, a `T` may separate the date from the time, and a
+/// date alone means its midnight. A full RFC 3339 time with an offset is
+/// accepted too.
+///
+/// ```
+/// use trs::format::{format_timestamp, parse_datetime};
+///
+/// let secs = parse_datetime("2025-06-01 18:30").unwrap();
+/// assert_eq!(format_timestamp(secs), "2025-06-01 18:30:00");
+/// assert_eq!(parse_datetime("2025-06-01T18:30:15Z").unwrap(), 1_748_802_615);
+/// assert!(parse_datetime("tomorrow").is_err());
+/// ```
+pub fn parse_datetime(raw: &str) -> Result<u64> {
+    let raw = raw.trim();
+    let invalid = || TrsError::invalid_input(format!("Invalid date and time '{}'", raw));
+    let secs = match DateTime::parse_from_rfc3339(raw) {
+        Ok(time) => time.timestamp(),
+        Err(_) => {
+            let local = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(raw, format).ok())
+                .or_else(|| NaiveDate::parse_from_str(raw, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)))
+                .or_else(|| {
+                    ["%H:%M:%S", "%H:%M"]
+                        .iter()
+                        .find_map(|format| NaiveTime::parse_from_str(raw, format).ok())
+                        .map(|time| Local::now().date_naive().and_time(time))
+                })
+                .ok_or_else(invalid)?;
+            Local.from_local_datetime(&local).earliest().ok_or_else(invalid)?.timestamp()
+        }
+    };
+    u64::try_from(secs).map_err(|_| invalid())
+}
+
+/// Format an elapsed number of seconds using its largest unit, e.g. `3d`
+pub fn format_age(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3_600 => format!("{}m", secs / 60),
+        3_600..86_400 => format!("{}h", secs / 3_600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+/// A stored path as it is shown to people, without the `\\?\` prefix of Windows verbatim paths
+///
+/// Paths too long for Windows without the prefix are stored with it so
+/// they can be restored; only their display changes.
+///
+/// ```
+/// use trs::format::display_path;
+///
+/// assert_eq!(display_path(r"\\?\C:\Users\me\notes.txt"), r"C:\Users\me\notes.txt");
+/// assert_eq!(display_path(r"\\?\UNC\server\share\notes.txt"), r"\\server\share\notes.txt");
+/// assert_eq!(display_path("/home/me/notes.txt"), "/home/me/notes.txt");
+/// ```
+pub fn display_path(path: &str) -> Cow<'_, str> {
+    if let Some(rest) = path.strip_prefix(r"\\?\UNC\") {
+        Cow::Owned(format!(r"\\{}", rest))
+    } else {
+        Cow::Borrowed(path.strip_prefix(r"\\?\").unwrap_or(path))
+    }
+}
+
+/// Escape a field for tab-separated `--porcelain` output
+///
+/// Backslashes, tabs and line breaks are written C-style (`\\`, `\t`, `\n`,
+/// `\r`) so every record stays on one line.
+pub fn escape_field(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,81 @@
+//! Stopping long operations cleanly on Ctrl-C
+//!
+//! While a [`Guard`] is alive, Ctrl-C only sets a flag. Long loops, such as
+//! archiving a directory or emptying the trash, call [`check`] at points
+//! where stopping leaves nothing half done: an archive being written is
+//! dropped before the original is touched, and the metadata is saved after
+//! the last deleted item. They then fail with
+//! [`TrsError::Interrupted`](crate::TrsError::Interrupted). Outside a guard,
+//! or on a second Ctrl-C, the process exits at once as usual.
+
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::error::{Result, TrsError};
+
+/// Exit code after an interruption, as for a process killed by SIGINT
+pub const EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static GUARDS: AtomicUsize = AtomicUsize::new(0);
+static INSTALL: Once = Once::new();
+
+/// Defers Ctrl-C to the next `check` until dropped
+///
+/// ```
+/// use trs::interrupt;
+///
+/// let _guard = interrupt::Guard::new();
+/// assert!(interrupt::check().is_ok());
+/// interrupt::request();
+/// assert!(interrupt::check().is_err());
+/// ```
+#[derive(Debug)]
+pub struct Guard(());
+
+impl Guard {
+    /// Start deferring Ctrl-C, installing the handler the first time
+    pub fn new() -> Self {
+        INSTALL.call_once(|| {
+            let handler = || {
+                if GUARDS.load(Ordering::SeqCst) == 0 || INTERRUPTED.swap(true, Ordering::SeqCst) {
+                    std::process::exit(EXIT_CODE);
+                }
+            };
+            if let Err(e) = ctrlc::set_handler(handler) {
+                tracing::debug!(error = %e, "could not install the Ctrl-C handler");
+            }
+        });
+        GUARDS.fetch_add(1, Ordering::SeqCst);
+        Guard(())
+    }
+}
+
+impl Default for Guard {
+    fn default() -> Self {
+        Guard::new()
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if GUARDS.fetch_sub(1, Ordering::SeqCst) == 1 {
+            INTERRUPTED.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Ask running operations to stop at their next `check`, as Ctrl-C does
+pub fn request() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Whether stopping has been asked for
+pub fn requested() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Fail with `Interrupted` if stopping has been asked for
+pub fn check() -> Result<()> {
+    if requested() { Err(TrsError::Interrupted) } else { Ok(()) }
+}
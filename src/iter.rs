@@ -0,0 +1,207 @@
+//! Lazy iteration over the items in a trash directory
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Context, Result, TrsError};
+use crate::metadata::{is_reserved_name, load_items, metadata_path, TrashItem};
+use crate::trash::{entry_size, find_item, TrashEntry};
+use crate::{macos, xdg};
+
+/// Kind of item stored in the trash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemType {
+    File,
+    Directory,
+}
+
+/// An entry in the trash directory together with its metadata
+#[derive(Debug, Clone)]
+pub struct TrashItemEntry {
+    /// Name of the entry inside the trash directory
+    pub name: String,
+    /// Location of the entry on disk
+    pub path: PathBuf,
+    /// Recorded metadata, if the entry has any
+    pub item: Option<TrashItem>,
+    /// Whether the item is a directory, from its metadata or the entry on disk
+    pub is_dir: bool,
+}
+
+impl TrashItemEntry {
+    /// Original location of the item, if known
+    pub fn original_path(&self) -> Option<&str> {
+        self.item.as_ref().map(|item| item.path.as_str())
+    }
+
+    /// Deletion time in seconds since the Unix epoch, if recorded
+    ///
+    /// For items the Finder put in the native macOS trash it is inferred
+    /// from the file.
+    pub fn deleted_at(&self) -> Option<u64> {
+        match &self.item {
+            Some(item) => item.deleted_at,
+            None if self.path.parent().is_some_and(macos::is_native_trash) => macos::inferred_deleted_at(&self.path),
+            None => None,
+        }
+    }
+
+    /// Whether the item was pinned with `pin_trash_item`
+    pub fn is_pinned(&self) -> bool {
+        self.item.as_ref().is_some_and(|item| item.pinned)
+    }
+
+    /// Kind of the item
+    pub fn item_type(&self) -> ItemType {
+        if self.is_dir { ItemType::Directory } else { ItemType::File }
+    }
+
+    /// Size on disk in bytes, reading the disk only when it was not recorded
+    pub fn size(&self) -> Result<u64> {
+        match self.item.as_ref().and_then(|item| item.size) {
+            Some(size) => Ok(size),
+            None => entry_size(&self.path),
+        }
+    }
+}
+
+impl TryFrom<TrashItemEntry> for TrashEntry {
+    type Error = TrsError;
+
+    fn try_from(entry: TrashItemEntry) -> Result<Self> {
+        Ok(TrashEntry {
+            original_path: entry.original_path().map(str::to_string),
+            deleted_at: entry.deleted_at(),
+            pinned: entry.is_pinned(),
+            user: entry.item.as_ref().and_then(|item| item.user.clone()),
+            hostname: entry.item.as_ref().and_then(|item| item.hostname.clone()),
+            is_dir: entry.is_dir,
+            size: entry.size()?,
+            name: entry.name,
+        })
+    }
+}
+
+/// Iterator over the items in a trash directory
+///
+/// The metadata file is read once, when the iterator is created, and items
+/// are read from the directory as the iterator reaches them. Filters are
+/// applied as items are read, so no entries are collected in memory.
+pub struct TrashIterator {
+    dir: PathBuf,
+    entries: Option<fs::ReadDir>,
+    metadata: HashMap<String, TrashItem>,
+    /// Whether `dir` is the native macOS trash
+    native: bool,
+    name: Option<String>,
+    item_type: Option<ItemType>,
+    since: Option<u64>,
+}
+
+impl TrashIterator {
+    /// Iterate over the items in `trash_dir`, which may not exist yet
+    pub fn new(trash_dir: &Path) -> Result<Self> {
+        let (entries, metadata) = if trash_dir.exists() {
+            let entries = fs::read_dir(trash_dir)
+                .with_context(|| format!("reading trash directory {}", trash_dir.display()))?;
+            (Some(entries), load_items(&metadata_path(trash_dir))?)
+        } else {
+            (None, HashMap::new())
+        };
+
+        Ok(TrashIterator {
+            dir: trash_dir.to_path_buf(),
+            entries,
+            metadata,
+            native: macos::is_native_trash(trash_dir),
+            name: None,
+            item_type: None,
+            since: None,
+        })
+    }
+
+    /// Only yield items whose trash name contains `query` (case-insensitive)
+    pub fn filter_by_name(mut self, query: &str) -> Self {
+        self.name = Some(query.to_lowercase());
+        self
+    }
+
+    /// Only yield items of the given kind
+    pub fn filter_by_type(mut self, item_type: ItemType) -> Self {
+        self.item_type = Some(item_type);
+        self
+    }
+
+    /// Only yield items trashed at or after `timestamp` (seconds since the Unix epoch)
+    ///
+    /// Items without a recorded deletion time are skipped.
+    pub fn filter_since(mut self, timestamp: u64) -> Self {
+        self.since = Some(timestamp);
+        self
+    }
+}
+
+impl TrashIterator {
+    /// Metadata from the `.trashinfo` file of an item in an XDG trash
+    ///
+    /// Items trashed by other file managers have no entry in our metadata.
+    fn trashinfo_item(&self, name: &str, path: &Path) -> Option<TrashItem> {
+        let info = xdg::read_trashinfo(xdg::trash_root(&self.dir)?, name)?;
+        Some(TrashItem {
+            deleted_at: info.deleted_at,
+            hostname: None,
+            user: None,
+            ..TrashItem::new(info.path, path.is_dir())
+        })
+    }
+}
+
+impl Iterator for TrashIterator {
+    type Item = Result<TrashItemEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.entries.as_mut()?.next()? {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e).with_context(|| format!("reading trash directory {}", self.dir.display()))),
+            };
+            let name = entry.file_name().into_string().unwrap_or_default();
+            if is_reserved_name(&name) || (self.native && macos::is_bookkeeping(&name)) {
+                continue;
+            }
+            if let Some(query) = &self.name
+                && !name.to_lowercase().contains(query)
+            {
+                continue;
+            }
+
+            let item = find_item(&self.metadata, &name)
+                .cloned()
+                .or_else(|| self.trashinfo_item(&name, &entry.path()));
+            let is_dir = match &item {
+                Some(item) => item.is_dir,
+                None => match entry.file_type().with_context(|| format!("reading {}", entry.path().display())) {
+                    Ok(file_type) => file_type.is_dir(),
+                    Err(e) => return Some(Err(e)),
+                },
+            };
+
+            let entry = TrashItemEntry {
+                name,
+                path: entry.path(),
+                item,
+                is_dir,
+            };
+            if self.item_type.is_some_and(|item_type| entry.item_type() != item_type) {
+                continue;
+            }
+            if let Some(since) = self.since
+                && entry.deleted_at().is_none_or(|deleted_at| deleted_at < since)
+            {
+                continue;
+            }
+            return Some(Ok(entry));
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Journal of recent operations, for `trs undo`
+//!
+//! Moves, restores, purges and empties each append a record to a journal
+//! kept next to the metadata of the trash they were made in, one JSON object
+//! per line. Only the last `JOURNAL_LIMIT` records are kept. `undo_last` in
+//! [`trash`](crate::trash) reverses the newest record and removes it, so
+//! undoing repeatedly steps back through earlier operations.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, TrsError};
+use crate::macos;
+use crate::metadata::metadata_path;
+
+/// Name of the journal file
+pub const JOURNAL_NAME: &str = ".journal";
+
+/// Number of records kept in the journal
+const JOURNAL_LIMIT: usize = 100;
+
+/// One operation recorded in the journal
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum JournalRecord {
+    /// An item was moved to `trash_dir` as `trash_name`
+    Move {
+        trash_name: String,
+        trash_dir: PathBuf,
+        original_path: String,
+    },
+    /// The item `trash_name` was restored from `trash_dir` to `path`
+    ///
+    /// When merged into a directory already at `path`, `merged` holds the
+    /// paths the restore created or replaced, the only ones to undo.
+    Restore {
+        trash_name: String,
+        trash_dir: PathBuf,
+        path: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        merged: Vec<String>,
+    },
+    /// Items were permanently deleted, which cannot be undone
+    Delete { names: Vec<String> },
+}
+
+/// Path of the journal of `trash_dir`, next to its metadata file
+///
+/// The native macOS trash uses a name that says which program it belongs to.
+pub fn journal_path(trash_dir: &Path) -> PathBuf {
+    let name = if macos::is_native_trash(trash_dir) { macos::JOURNAL_NAME } else { JOURNAL_NAME };
+    metadata_path(trash_dir).with_file_name(name)
+}
+
+/// Records in the journal of `trash_dir`, oldest first
+///
+/// A missing journal is empty. Lines that cannot be read, such as records
+/// written by a newer version, are skipped.
+pub fn read_journal(trash_dir: &Path) -> Result<Vec<JournalRecord>> {
+    let path = journal_path(trash_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path).map_err(|source| TrsError::Io {
+        context: format!("reading journal {}", path.display()),
+        source,
+    })?;
+    Ok(content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+/// Add a record to the end of the journal of `trash_dir`, dropping the oldest beyond `JOURNAL_LIMIT`
+pub(crate) fn append(trash_dir: &Path, record: JournalRecord) -> Result<()> {
+    let mut records = read_journal(trash_dir)?;
+    records.push(record);
+    let excess = records.len().saturating_sub(JOURNAL_LIMIT);
+    write_journal(trash_dir, &records[excess..])
+}
+
+/// Remove the newest record from the journal of `trash_dir`
+pub(crate) fn pop(trash_dir: &Path) -> Result<()> {
+    let mut records = read_journal(trash_dir)?;
+    records.pop();
+    write_journal(trash_dir, &records)
+}
+
+/// Replace the journal of `trash_dir` with `records`, syncing it to disk before returning
+fn write_journal(trash_dir: &Path, records: &[JournalRecord]) -> Result<()> {
+    let path = journal_path(trash_dir);
+    let mut content = String::new();
+    for record in records {
+        content.push_str(&serde_json::to_string(record)?);
+        content.push('\n');
+    }
+    let write = || -> std::io::Result<()> {
+        let mut file = fs::File::create(&path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    };
+    write().map_err(|source| TrsError::Io {
+        context: format!("writing journal {}", path.display()),
+        source,
+    })
+}
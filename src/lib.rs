@@ -1,8 +1,290 @@
 //! Trash management functionality
+//!
+//! The [`Trash`] struct is the public entry point for using `trs` as a
+//! library:
+//!
+//! ```no_run
+//! use trs::Trash;
+//!
+//! let trash = Trash::default();
+//! trash.move_to_trash("old-notes.txt")?;
+//! for entry in trash.list()? {
+//!     println!("{} <- {:?}", entry.name, entry.original_path);
+//! }
+//! # Ok::<(), trs::TrsError>(())
+//! ```
+//!
+//! To query the trash without printing anything, [`list_trash_items`] is the
+//! stable entry point. It returns serializable [`TrashEntry`] values and
+//! never creates the trash directory.
 
+#[cfg(feature = "async")]
+pub mod async_trash;
 pub mod cli;
+pub mod compression;
+pub mod config;
+pub mod crypto;
+pub mod device;
+pub mod error;
+pub mod format;
+pub mod interrupt;
+pub mod iter;
+pub mod journal;
+pub mod logging;
+pub mod macos;
+pub mod output;
+pub mod owner;
+pub mod schedule;
+pub mod sparse;
+pub mod style;
 pub mod trash;
+pub mod trashinfo;
 pub mod metadata;
+pub mod xdg;
+pub mod tui;
+
+use std::path::{Path, PathBuf};
+use indicatif::{ProgressBar, ProgressStyle};
+
+pub use error::{Result, TrsError};
+use iter::TrashIterator;
+use trash::{EmptyOptions, EmptySummary, ExportOptions, ImportOptions, ImportedItem, ItemDetails, MoveOptions, MovedItem, RestoreOptions, RestorePreview, RestoredItem, TrashStatus, Undone, VerifyIssue};
 
 // Re-export commonly used items
 pub use cli::run;
+pub use trash::{list_trash_items, TrashEntry};
+
+/// Receives progress and user-facing messages from trash operations
+///
+/// The core operations never print; everything they would tell the user goes
+/// through a sink. Implement this to drive your own display instead of the
+/// terminal bar used by the CLI. Updates may arrive from several threads
+/// while an archive is compressed in parallel.
+pub trait ProgressSink: Send + Sync {
+    /// The operation has started and will process `total` bytes
+    fn start(&self, total: u64);
+    /// `n` more bytes have been processed
+    fn advance(&self, n: u64);
+    /// A note to show while the operation runs, such as an item evicted along the way
+    fn message(&self, msg: &str);
+    /// The operation finished successfully, with a summary for the user
+    fn finish(&self, msg: &str);
+    /// The operation failed with `msg`
+    fn error(&self, msg: &str);
+}
+
+impl<P: ProgressSink + ?Sized> ProgressSink for Box<P> {
+    fn start(&self, total: u64) {
+        (**self).start(total);
+    }
+
+    fn advance(&self, n: u64) {
+        (**self).advance(n);
+    }
+
+    fn message(&self, msg: &str) {
+        (**self).message(msg);
+    }
+
+    fn finish(&self, msg: &str) {
+        (**self).finish(msg);
+    }
+
+    fn error(&self, msg: &str) {
+        (**self).error(msg);
+    }
+}
+
+/// Sink that ignores all progress updates and messages
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoOpProgress;
+
+impl ProgressSink for NoOpProgress {
+    fn start(&self, _total: u64) {}
+    fn advance(&self, _n: u64) {}
+    fn message(&self, _msg: &str) {}
+    fn finish(&self, _msg: &str) {}
+    fn error(&self, _msg: &str) {}
+}
+
+/// Sink that draws an `indicatif` progress bar on the terminal
+#[derive(Debug, Clone)]
+pub struct IndicatifProgress {
+    pb: ProgressBar,
+}
+
+impl IndicatifProgress {
+    /// Create a byte-based progress bar labelled with `message`
+    pub fn new(message: impl Into<String>) -> Self {
+        let pb = ProgressBar::new(0);
+        pb.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {msg}")
+            .unwrap()
+            .progress_chars("#>-"));
+        pb.set_message(message.into());
+        IndicatifProgress { pb }
+    }
+}
+
+impl ProgressSink for IndicatifProgress {
+    fn start(&self, total: u64) {
+        self.pb.set_length(total);
+        self.pb.set_position(0);
+    }
+
+    fn advance(&self, n: u64) {
+        self.pb.inc(n);
+    }
+
+    fn message(&self, msg: &str) {
+        self.pb.suspend(|| println!("{}", msg));
+    }
+
+    fn finish(&self, msg: &str) {
+        self.pb.finish_and_clear();
+        println!("{}", msg);
+    }
+
+    fn error(&self, _msg: &str) {
+        // The caller reports the error itself, so just remove the bar
+        self.pb.finish_and_clear();
+    }
+}
+
+/// A trash directory and the operations available on it
+#[derive(Debug, Clone)]
+pub struct Trash {
+    dir: PathBuf,
+}
+
+impl Trash {
+    /// Use the trash stored in `trash_dir`
+    pub fn new(trash_dir: PathBuf) -> Self {
+        Trash { dir: trash_dir }
+    }
+
+    /// Use the native macOS trash, `~/.Trash`, shared with the Finder
+    #[cfg(target_os = "macos")]
+    pub fn native() -> Result<Self> {
+        Ok(Trash::new(macos::native_trash_dir()?))
+    }
+
+    /// Directory holding the trashed items
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Move a file or directory to the trash
+    pub fn move_to_trash(&self, path: &str) -> Result<MovedItem> {
+        self.move_to_trash_with(path, &MoveOptions::default(), &NoOpProgress)
+    }
+
+    /// Move a file or directory to the trash with custom options and progress reporting
+    pub fn move_to_trash_with(&self, path: &str, options: &MoveOptions, progress: &impl ProgressSink) -> Result<MovedItem> {
+        trash::move_to_trash(path, &self.dir, options, progress)
+    }
+
+    /// Restore an item to its original location
+    pub fn restore(&self, name: &str) -> Result<RestoredItem> {
+        self.restore_with(name, &RestoreOptions::default(), &NoOpProgress)
+    }
+
+    /// Restore an item with custom options and progress reporting
+    pub fn restore_with(&self, name: &str, options: &RestoreOptions, progress: &impl ProgressSink) -> Result<RestoredItem> {
+        trash::restore_from_trash(name, &self.dir, options, progress)
+    }
+
+    /// Restore an item to `destination` instead of its original location
+    pub fn restore_to(&self, name: &str, destination: PathBuf) -> Result<RestoredItem> {
+        let options = RestoreOptions {
+            destination: Some(destination),
+            ..RestoreOptions::default()
+        };
+        self.restore_with(name, &options, &NoOpProgress)
+    }
+
+    /// Where an item would be restored to and every path restoring it would write
+    pub fn preview_restore(&self, name: &str) -> Result<RestorePreview> {
+        trash::restore_preview(name, &self.dir, &RestoreOptions::default())
+    }
+
+    /// Extract one file or directory from an archived item, keeping the rest in the trash
+    pub fn restore_member(&self, name: &str, member: &str) -> Result<PathBuf> {
+        trash::restore_member(name, member, &self.dir, &RestoreOptions::default(), &NoOpProgress)
+    }
+
+    /// Write every item and the metadata to a single bundle at `output`
+    pub fn export(&self, output: &Path, options: &ExportOptions) -> Result<usize> {
+        trash::export_trash(&self.dir, output, options, &NoOpProgress)
+    }
+
+    /// Merge the items of a bundle written by `export` into this trash
+    pub fn import(&self, bundle: &Path, options: &ImportOptions) -> Result<Vec<ImportedItem>> {
+        trash::import_trash(&self.dir, bundle, options, &NoOpProgress)
+    }
+
+    /// Reverse the last move or restore recorded in the journal
+    pub fn undo(&self) -> Result<Undone> {
+        trash::undo_last(&self.dir, &NoOpProgress)
+    }
+
+    /// Keep an item when the trash is emptied or pruned
+    pub fn pin(&self, name: &str) -> Result<String> {
+        trash::pin_trash_item(&self.dir, name, true)
+    }
+
+    /// Undo `pin`
+    pub fn unpin(&self, name: &str) -> Result<String> {
+        trash::pin_trash_item(&self.dir, name, false)
+    }
+
+    /// Permanently delete everything in the trash except pinned items
+    pub fn empty(&self) -> Result<EmptySummary> {
+        self.empty_with(&EmptyOptions::default(), &NoOpProgress)
+    }
+
+    /// Permanently delete items selected by `options` with progress reporting
+    pub fn empty_with(&self, options: &EmptyOptions, progress: &impl ProgressSink) -> Result<EmptySummary> {
+        trash::empty_trash(&self.dir, options, progress)
+    }
+
+    /// List all items in the trash
+    pub fn list(&self) -> Result<Vec<TrashEntry>> {
+        trash::list_trash_items(&self.dir)
+    }
+
+    /// Lazily iterate over the items in the trash
+    pub fn iter(&self) -> Result<TrashIterator> {
+        TrashIterator::new(&self.dir)
+    }
+
+    /// Find items whose name or original path contains `query`
+    pub fn search(&self, query: &str) -> Result<Vec<TrashEntry>> {
+        trash::search_trash(&self.dir, query)
+    }
+
+    /// Everything known about the item `name` refers to, checking its checksum
+    ///
+    /// `name` may be part of an item's name or original location, as long as
+    /// it matches only one item.
+    pub fn details(&self, name: &str) -> Result<ItemDetails> {
+        trash::item_details(&self.dir, name)
+    }
+
+    /// Summarize the number of items and space used
+    pub fn status(&self) -> Result<TrashStatus> {
+        trash::trash_status(&self.dir)
+    }
+
+    /// Check the trash for missing items, orphaned files and unreadable archives
+    pub fn verify(&self) -> Result<Vec<VerifyIssue>> {
+        trash::verify_trash(&self.dir)
+    }
+}
+
+impl Default for Trash {
+    /// Use the default trash in the local data directory
+    fn default() -> Self {
+        Trash::new(trash::default_trash_dir())
+    }
+}
@@ -0,0 +1,62 @@
+//! Diagnostic logging for the CLI
+//!
+//! Trash operations emit `tracing` events describing what they did, such as
+//! the chosen trash names, sizes and durations. Nothing is shown by default:
+//! `RUST_LOG` sends matching events to stderr, and a log file receives them
+//! as JSON lines for an audit trail of what happened to the trash.
+
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::prelude::*;
+
+use crate::error::{Context, Result};
+
+/// Events written to the log file when `RUST_LOG` is not set
+const DEFAULT_FILE_FILTER: &str = "trs=debug";
+
+/// Install the subscriber for the CLI, appending events to `log_file` if given
+///
+/// Does nothing when neither `RUST_LOG` nor a log file is set, so the
+/// default output is unchanged. A subscriber installed earlier, for example
+/// by a program embedding the CLI, is kept.
+pub fn init(log_file: Option<&Path>) -> Result<()> {
+    let rust_log = std::env::var_os("RUST_LOG").is_some();
+    let stderr = rust_log.then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_writer(io::stderr)
+            .with_filter(EnvFilter::from_default_env())
+    });
+
+    let file = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("opening log file {}", path.display()))?;
+            let filter = if rust_log {
+                EnvFilter::from_default_env()
+            } else {
+                EnvFilter::new(DEFAULT_FILE_FILTER)
+            };
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(false)
+                    .with_span_list(false)
+                    .with_writer(Mutex::new(file))
+                    .with_filter(filter),
+            )
+        }
+        None => None,
+    };
+
+    if stderr.is_some() || file.is_some() {
+        let _ = tracing_subscriber::registry().with(stderr).with(file).try_init();
+    }
+    Ok(())
+}
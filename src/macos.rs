@@ -0,0 +1,75 @@
+//! The native macOS trash
+//!
+//! With `--native-trash`, or `native_trash = true` in the config, `trs` uses
+//! `~/.Trash`, the trash the Finder shows, instead of its own directory.
+//! Items are moved in as they are, as in an XDG trash, and the fields macOS
+//! has no place for are kept in a `.trs-metadata` file next to them, the
+//! journal for `trs undo` in `.trs-journal` and scheduled deletions in
+//! `.trs-scheduled`. Items
+//! the Finder put in the trash have no entry there, so what can be is
+//! inferred from the files themselves.
+//!
+//! The layout is detected from the trash directory, so every operation on a
+//! [`Trash`](crate::Trash) or in [`trash`](crate::trash) works on it. On other
+//! platforms no directory is the native trash.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, TrsError};
+use crate::metadata;
+
+/// Name of the metadata file kept in the native trash
+pub const METADATA_NAME: &str = ".trs-metadata";
+
+/// Name of the journal kept in the native trash
+pub const JOURNAL_NAME: &str = ".trs-journal";
+
+/// Name of the schedule kept in the native trash
+pub const SCHEDULE_NAME: &str = ".trs-scheduled";
+
+/// Files the Finder keeps in the trash for itself
+const FINDER_FILES: [&str; 2] = [".DS_Store", ".localized"];
+
+/// The trash of the current user, `~/.Trash`
+fn home_trash() -> PathBuf {
+    dirs::home_dir()
+        .expect("Could not find home directory")
+        .join(".Trash")
+}
+
+/// The native trash, which only exists on macOS
+pub fn native_trash_dir() -> Result<PathBuf> {
+    if cfg!(target_os = "macos") {
+        Ok(home_trash())
+    } else {
+        Err(TrsError::invalid_input("The native trash is only available on macOS"))
+    }
+}
+
+/// Whether `trash_dir` is the native macOS trash
+pub fn is_native_trash(trash_dir: &Path) -> bool {
+    cfg!(target_os = "macos") && trash_dir == home_trash()
+}
+
+/// Whether `name` in the native trash is bookkeeping rather than a trashed item
+pub fn is_bookkeeping(name: &str) -> bool {
+    metadata::is_metadata_name(name, METADATA_NAME) || name == JOURNAL_NAME || name == SCHEDULE_NAME || FINDER_FILES.contains(&name)
+}
+
+/// Deletion time of an item the Finder put in the trash, in seconds since the Unix epoch
+///
+/// The Finder records the original location in a private format, so only
+/// the time can be inferred: moving an item into the trash updates its
+/// status change time.
+#[cfg(unix)]
+pub fn inferred_deleted_at(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    u64::try_from(std::fs::symlink_metadata(path).ok()?.ctime()).ok()
+}
+
+/// The deletion time cannot be inferred on this platform
+#[cfg(not(unix))]
+pub fn inferred_deleted_at(_path: &Path) -> Option<u64> {
+    None
+}
@@ -1,9 +1,16 @@
 use std::process;
-use trs::run;
+use trs::{run, TrsError};
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+    match run() {
+        Ok(code) => process::exit(code),
+        Err(e @ TrsError::Interrupted) => {
+            eprintln!("{}", e);
+            process::exit(e.exit_code());
+        }
+        Err(e) => {
+            eprintln!("Application error: {}", e);
+            process::exit(e.exit_code());
+        }
     }
 }
@@ -1,31 +1,786 @@
 //! Metadata for trash operations
+//!
+//! Each trash directory has a metadata file mapping entry names to a
+//! [`TrashItem`]. Two formats exist, both JSON objects keyed by name:
+//! version 1 stores each item as a JSON-encoded string, or a bare original
+//! path in the oldest files, and version 2 stores the items directly. Files
+//! in either format are read, and every write uses version 2, so a version 1
+//! file is migrated the first time the trash changes.
+//!
+//! Rewriting the whole file for each change costs more the more items the
+//! trash holds. Instead, saving appends the items that changed to a
+//! [`MetadataJournal`] next to the file, one JSON line each, and loading
+//! replays it over the file. Once the journal holds more than
+//! [`COMPACT_AFTER`] changes it is merged back into the file.
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
-use std::io;
-use std::path::Path;
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use serde_json;
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, Visitor};
 use serde::{Serialize, Deserialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+use crate::error::{Result, TrsError};
+use crate::trash::is_partial;
+use crate::{journal, macos, owner, schedule, xdg};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct TrashItem {
     pub path: String,
     pub is_dir: bool,
+    /// Deletion time in seconds since the Unix epoch, if recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<u64>,
+    /// Size in bytes of the entry stored in the trash, if recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Size in bytes of the item before it was archived, if recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_size: Option<u64>,
+    /// Whether the item is kept when the trash is emptied or pruned
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Whether the file is stored as it is rather than archived, being below the compress threshold
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub raw: bool,
+    /// Modification time of the item when it was trashed, in seconds since the Unix epoch, if recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<u64>,
+    /// Whether restored files get the time of the restore instead of their own modification time
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reset_timestamps: bool,
+    /// Name of the machine the item was trashed on, if recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Name of the user who trashed the item, if recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Hash of the file's data and permissions, recorded when the trash deduplicates files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// Paths inside a directory that could not be read, left out of its archive and in place
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
 }
 
-/// Load metadata from file
-pub fn load_metadata(metadata_file: &Path) -> io::Result<HashMap<String, String>> {
-    if metadata_file.exists() {
-        let content = fs::read_to_string(metadata_file)?;
-        Ok(serde_json::from_str(&content).unwrap_or_default())
-    } else {
-        Ok(HashMap::new())
+impl TrashItem {
+    /// Create metadata for an item deleted now, by the current user on this machine
+    pub fn new(path: String, is_dir: bool) -> Self {
+        TrashItem {
+            path,
+            is_dir,
+            deleted_at: Some(now_secs()),
+            size: None,
+            original_size: None,
+            pinned: false,
+            raw: false,
+            modified_at: None,
+            reset_timestamps: false,
+            hostname: owner::hostname().map(str::to_string),
+            user: owner::username().map(str::to_string),
+            content_hash: None,
+            skipped: Vec::new(),
+        }
+    }
+}
+
+/// Current time in seconds since the Unix epoch
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Name of the metadata file, unless the `metadata_name` config key changes it
+pub const METADATA_NAME: &str = ".metadata";
+
+thread_local! {
+    /// The name given to the innermost `use_metadata_name` still in effect on this thread
+    static CONFIGURED_NAME: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Metadata files named as given to `use_metadata_name`, until this is dropped
+#[must_use = "the name only applies until it is dropped"]
+pub struct MetadataName {
+    previous: Option<String>,
+}
+
+impl Drop for MetadataName {
+    fn drop(&mut self) {
+        CONFIGURED_NAME.with(|name| *name.borrow_mut() = self.previous.take());
     }
 }
 
-/// Save metadata to file
-pub fn save_metadata(metadata_file: &Path, metadata: &HashMap<String, String>) -> io::Result<()> {
-    let content = serde_json::to_string(metadata)?;
-    fs::write(metadata_file, content)?;
+/// Give metadata files `name` instead of `METADATA_NAME` on this thread while the result lives, for the `metadata_name` config key
+///
+/// The name must be a hidden file name, starting with `.` so it cannot
+/// be taken for a trashed item, and not one of the other files `trs`
+/// keeps in the trash. A metadata file still under `METADATA_NAME` is
+/// renamed the first time its path is looked up.
+pub fn use_metadata_name(name: &str) -> Result<MetadataName> {
+    check_metadata_name(name)?;
+    let previous = CONFIGURED_NAME.with(|configured| configured.replace(Some(name.to_string())));
+    Ok(MetadataName { previous })
+}
+
+/// Fail with `InvalidInput` unless `name` can be used as the name of metadata files
+fn check_metadata_name(name: &str) -> Result<()> {
+    let invalid = |why: &str| Err(TrsError::invalid_input(format!("Invalid metadata_name {:?}: {}", name, why)));
+    if !name.starts_with('.') || name == "." || name == ".." {
+        return invalid("it must be a file name starting with '.'");
+    }
+    if name.contains(['/', '\\']) {
+        return invalid("it must not contain a path separator");
+    }
+    if name == journal::JOURNAL_NAME
+        || name == schedule::SCHEDULE_NAME
+        || is_partial(name)
+        || name != METADATA_NAME && is_metadata_name(name, METADATA_NAME)
+    {
+        return invalid("the name is used by another file in the trash");
+    }
     Ok(())
 }
+
+/// Name of the metadata file in trash directories other than the native macOS trash
+pub fn metadata_name() -> String {
+    CONFIGURED_NAME.with(|name| name.borrow().clone()).unwrap_or_else(|| METADATA_NAME.to_string())
+}
+
+/// Path of the metadata file of `trash_dir`
+///
+/// In an XDG trash it is kept in the trash root rather than among the items
+/// in `files/`, so other file managers do not list it. The native macOS
+/// trash uses a name that says which program it belongs to.
+pub fn metadata_path(trash_dir: &Path) -> PathBuf {
+    if macos::is_native_trash(trash_dir) {
+        return trash_dir.join(macos::METADATA_NAME);
+    }
+    let root = xdg::trash_root(trash_dir).unwrap_or(trash_dir);
+    let name = metadata_name();
+    let path = root.join(&name);
+    if name != METADATA_NAME {
+        adopt_metadata(&root.join(METADATA_NAME), &path);
+    }
+    path
+}
+
+/// Rename the metadata file `default` and its journal to `configured`, unless it is already there
+///
+/// The journal goes first, so renaming the file finishes the move if it is
+/// interrupted in between. Files left under the default name by a failed
+/// rename are still never taken for items.
+fn adopt_metadata(default: &Path, configured: &Path) {
+    if configured.exists() {
+        return;
+    }
+    for extension in [JOURNAL_EXTENSION, ""] {
+        let (from, to) = (sibling(default, extension), sibling(configured, extension));
+        if from.exists() && !to.exists() && let Err(e) = fs::rename(&from, &to) {
+            tracing::debug!(error = %e, from = %from.display(), "could not rename the metadata");
+        }
+    }
+}
+
+/// Whether `name` in a trash directory is one of our files rather than a trashed item
+///
+/// Archives still being written are ours until they are complete, and
+/// metadata under the default name stays ours after `metadata_name` changes.
+pub(crate) fn is_reserved_name(name: &str) -> bool {
+    is_metadata_name(name, &metadata_name())
+        || is_metadata_name(name, METADATA_NAME)
+        || name == journal::JOURNAL_NAME
+        || name == schedule::SCHEDULE_NAME
+        || is_partial(name)
+}
+
+/// Whether `name` is the metadata file called `metadata_name`, its journal or its copy being compacted
+pub(crate) fn is_metadata_name(name: &str, metadata_name: &str) -> bool {
+    name.strip_prefix(metadata_name)
+        .is_some_and(|rest| rest.is_empty() || rest == JOURNAL_EXTENSION || rest == COMPACTING_EXTENSION)
+}
+
+/// Extension of the journal of a metadata file
+const JOURNAL_EXTENSION: &str = ".journal";
+
+/// Extension of the new metadata file written while compacting, until it replaces the old one
+const COMPACTING_EXTENSION: &str = ".compacting";
+
+/// Number of changes the journal of a metadata file holds before it is merged into the file
+pub const COMPACT_AFTER: usize = 1000;
+
+/// `metadata_file` with `extension` appended to its name
+fn sibling(metadata_file: &Path, extension: &str) -> PathBuf {
+    let mut path = metadata_file.as_os_str().to_os_string();
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// A format of the metadata file
+///
+/// `load` and `save` handle the file itself; implementations only convert
+/// between its contents and items.
+pub trait MetadataBackend {
+    /// Parse the contents of a metadata file that is not empty
+    fn decode(&self, content: &str) -> serde_json::Result<HashMap<String, TrashItem>>;
+
+    /// Serialize items as the contents of a metadata file
+    fn encode(&self, items: &HashMap<String, TrashItem>) -> serde_json::Result<String>;
+
+    /// Read the items in `metadata_file`
+    ///
+    /// A missing or empty file holds no items. Returns `MetadataCorrupt` if
+    /// the file is not valid, rather than discarding it.
+    fn load(&self, metadata_file: &Path) -> Result<HashMap<String, TrashItem>> {
+        match read_metadata_file(metadata_file)? {
+            Some(content) => self.decode(&content).map_err(|source| TrsError::MetadataCorrupt {
+                path: metadata_file.to_path_buf(),
+                source,
+            }),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// Replace `metadata_file` with `items`, syncing it to disk before returning
+    fn save(&self, metadata_file: &Path, items: &HashMap<String, TrashItem>) -> Result<()> {
+        let content = self.encode(items)?;
+        let write = || -> std::io::Result<()> {
+            let mut file = fs::File::create(metadata_file)?;
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        };
+        write().map_err(|source| TrsError::Io {
+            context: format!("writing metadata {}", metadata_file.display()),
+            source,
+        })
+    }
+}
+
+/// Version 1 of the metadata file, whose values are JSON-encoded items or bare paths
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonV1;
+
+impl MetadataBackend for JsonV1 {
+    fn decode(&self, content: &str) -> serde_json::Result<HashMap<String, TrashItem>> {
+        let raw: HashMap<String, String> = serde_json::from_str(content)?;
+        Ok(raw.into_iter().map(|(name, value)| (name, parse_item(&value))).collect())
+    }
+
+    fn encode(&self, items: &HashMap<String, TrashItem>) -> serde_json::Result<String> {
+        let raw = items.iter()
+            .map(|(name, item)| Ok((name, serde_json::to_string(item)?)))
+            .collect::<serde_json::Result<HashMap<_, _>>>()?;
+        serde_json::to_string(&raw)
+    }
+}
+
+/// Version 2 of the metadata file, whose values are the items themselves
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonV2;
+
+impl MetadataBackend for JsonV2 {
+    fn decode(&self, content: &str) -> serde_json::Result<HashMap<String, TrashItem>> {
+        serde_json::from_str(content)
+    }
+
+    fn encode(&self, items: &HashMap<String, TrashItem>) -> serde_json::Result<String> {
+        serde_json::to_string(items)
+    }
+}
+
+/// The format `content` of a metadata file is written in
+///
+/// Files whose values include a string are version 1, and any other file,
+/// including one that is not valid JSON, is read as version 2.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use trs::metadata::{detect_backend, JsonV1, JsonV2, MetadataBackend, TrashItem};
+///
+/// let v1 = r#"{"notes.txt.tar.gz":"{\"path\":\"/home/me/notes.txt\",\"is_dir\":false}"}"#;
+/// let items = detect_backend(v1).decode(v1).unwrap();
+/// assert_eq!(items["notes.txt.tar.gz"].path, "/home/me/notes.txt");
+///
+/// // Saving always writes version 2, which reads back the same
+/// let v2 = JsonV2.encode(&items).unwrap();
+/// assert_eq!(v2, r#"{"notes.txt.tar.gz":{"path":"/home/me/notes.txt","is_dir":false}}"#);
+/// assert_eq!(detect_backend(&v2).decode(&v2).unwrap()["notes.txt.tar.gz"].path, "/home/me/notes.txt");
+/// assert_eq!(JsonV1.encode(&items).unwrap(), v1);
+/// ```
+pub fn detect_backend(content: &str) -> &'static dyn MetadataBackend {
+    if is_version_1(content) { &JsonV1 } else { &JsonV2 }
+}
+
+/// Whether `content` of a metadata file is written in version 1
+fn is_version_1(content: &str) -> bool {
+    matches!(
+        serde_json::from_str::<serde_json::Value>(content),
+        Ok(serde_json::Value::Object(map)) if map.values().any(serde_json::Value::is_string)
+    )
+}
+
+/// Parse a single version 1 value, which may be a bare path in the oldest files
+pub(crate) fn parse_item(value: &str) -> TrashItem {
+    // Check if it's already in the new format
+    if value.starts_with("{\"path\":\"") {
+        // Try to parse as JSON
+        if let Ok(item) = serde_json::from_str::<TrashItem>(value) {
+            return item;
+        }
+    }
+    
+    let is_dir = Path::new(value).exists() && Path::new(value).is_dir();
+    TrashItem {
+        deleted_at: None,
+        hostname: None,
+        user: None,
+        ..TrashItem::new(value.to_string(), is_dir)
+    }
+}
+
+/// Contents of `metadata_file`, or `None` if it is missing or empty
+fn read_metadata_file(metadata_file: &Path) -> Result<Option<String>> {
+    if !metadata_file.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(metadata_file).map_err(|source| TrsError::Io {
+        context: format!("reading metadata {}", metadata_file.display()),
+        source,
+    })?;
+    Ok((!content.trim().is_empty()).then_some(content))
+}
+
+/// Load the items in a metadata file in either format, with the changes in its journal
+///
+/// A missing or empty file holds no items. Returns `MetadataCorrupt` if the
+/// file or its journal is not valid, rather than discarding it.
+pub fn load_items(metadata_file: &Path) -> Result<HashMap<String, TrashItem>> {
+    Ok(read_state(metadata_file)?.items)
+}
+
+/// Save items, syncing them to disk before returning
+///
+/// Only the items that differ from those stored are written, appended to
+/// the journal. The file is rewritten in the current format instead when
+/// the journal would grow past `COMPACT_AFTER` changes, when the file is
+/// missing, empty or in version 1, and after an interrupted compaction.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use trs::metadata::{compact_metadata_journal, load_items, save_items, MetadataJournal, TrashItem};
+///
+/// let file = std::env::temp_dir().join(format!("trs-journal-doc-{}", std::process::id()));
+/// let mut items: HashMap<_, _> = (0..100)
+///     .map(|i| (format!("file{}.txt.tar.gz", i), TrashItem::new(format!("/home/me/file{}.txt", i), false)))
+///     .collect();
+/// save_items(&file, &items).unwrap();
+///
+/// // Removing one item appends one line to the journal, leaving the file as it was
+/// let saved = std::fs::read(&file).unwrap();
+/// items.remove("file7.txt.tar.gz");
+/// save_items(&file, &items).unwrap();
+/// let journal = MetadataJournal::new(&file);
+/// assert_eq!(journal.read().unwrap().len(), 1);
+/// assert_eq!(std::fs::read(&file).unwrap(), saved);
+/// assert_eq!(load_items(&file).unwrap(), items);
+///
+/// // Compacting merges it into the file
+/// compact_metadata_journal(&file).unwrap();
+/// assert!(!journal.path().exists());
+/// assert_eq!(load_items(&file).unwrap(), items);
+/// std::fs::remove_file(&file).unwrap();
+/// ```
+pub fn save_items(metadata_file: &Path, items: &HashMap<String, TrashItem>) -> Result<()> {
+    let state = read_state(metadata_file)?;
+    let changes = changes_between(&state.items, items);
+    if state.rewrite || state.journaled + changes.len() > COMPACT_AFTER {
+        return rewrite(metadata_file, items);
+    }
+    MetadataJournal::new(metadata_file).append(&changes)
+}
+
+/// Merge the journal of `metadata_file` into it, leaving no journal
+///
+/// `save_items` does this once the journal grows past `COMPACT_AFTER`
+/// changes; call it before reading the file directly, as `export` does.
+pub fn compact_metadata_journal(metadata_file: &Path) -> Result<()> {
+    let state = read_state(metadata_file)?;
+    if state.journaled == 0 && !state.rewrite {
+        return Ok(());
+    }
+    rewrite(metadata_file, &state.items)
+}
+
+/// Items of a metadata file and what saving has to know about it
+struct MetadataState {
+    items: HashMap<String, TrashItem>,
+    /// Number of changes in the journal
+    journaled: usize,
+    /// Whether the file must be rewritten, being missing, empty, in version 1 or left by an interrupted compaction
+    rewrite: bool,
+}
+
+/// The file holding the items a journal applies to
+///
+/// Compacting writes the new file beside the old one and removes the
+/// journal before renaming it over the old file, so the new file is the
+/// one to read if it was not renamed yet.
+fn base_file(metadata_file: &Path) -> (PathBuf, bool) {
+    let compacting = sibling(metadata_file, COMPACTING_EXTENSION);
+    if !sibling(metadata_file, JOURNAL_EXTENSION).exists() && compacting.exists() {
+        (compacting, true)
+    } else {
+        (metadata_file.to_path_buf(), false)
+    }
+}
+
+/// Read `metadata_file` and replay its journal
+fn read_state(metadata_file: &Path) -> Result<MetadataState> {
+    let (base, interrupted) = base_file(metadata_file);
+    let (mut items, outdated) = match read_metadata_file(&base)? {
+        Some(content) => {
+            let version_1 = is_version_1(&content);
+            let backend: &dyn MetadataBackend = if version_1 { &JsonV1 } else { &JsonV2 };
+            let items = backend.decode(&content).map_err(|source| TrsError::MetadataCorrupt { path: base.clone(), source })?;
+            (items, version_1)
+        }
+        None => (HashMap::new(), true),
+    };
+    let changes = MetadataJournal::new(metadata_file).read()?;
+    let journaled = changes.len();
+    for change in changes {
+        change.apply(&mut items);
+    }
+    Ok(MetadataState { items, journaled, rewrite: outdated || interrupted })
+}
+
+/// The changes that turn `old` into `new`, removals first, each in name order
+fn changes_between(old: &HashMap<String, TrashItem>, new: &HashMap<String, TrashItem>) -> Vec<MetadataChange> {
+    let mut removed: Vec<_> = old.keys().filter(|name| !new.contains_key(*name)).collect();
+    removed.sort();
+    let mut inserted: Vec<_> = new.iter().filter(|(name, item)| old.get(*name) != Some(item)).collect();
+    inserted.sort_by_key(|(name, _)| *name);
+    removed
+        .into_iter()
+        .map(|name| MetadataChange::Remove(name.clone()))
+        .chain(inserted.into_iter().map(|(name, item)| MetadataChange::Insert(name.clone(), item.clone())))
+        .collect()
+}
+
+/// Replace `metadata_file` with `items` and drop its journal
+fn rewrite(metadata_file: &Path, items: &HashMap<String, TrashItem>) -> Result<()> {
+    let compacting = sibling(metadata_file, COMPACTING_EXTENSION);
+    JsonV2.save(&compacting, items)?;
+    MetadataJournal::new(metadata_file).remove()?;
+    fs::rename(&compacting, metadata_file).map_err(|source| TrsError::Io {
+        context: format!("moving {} to {}", compacting.display(), metadata_file.display()),
+        source,
+    })
+}
+
+/// A change to the items of a metadata file, as recorded in its journal
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataChange {
+    /// The item stored under the name, added or replacing one
+    Insert(String, TrashItem),
+    /// The name no longer has an item
+    Remove(String),
+}
+
+impl MetadataChange {
+    /// Name of the item changed
+    pub fn name(&self) -> &str {
+        match self {
+            MetadataChange::Insert(name, _) | MetadataChange::Remove(name) => name,
+        }
+    }
+
+    /// Make the change to `items`
+    pub fn apply(self, items: &mut HashMap<String, TrashItem>) {
+        match self {
+            MetadataChange::Insert(name, item) => {
+                items.insert(name, item);
+            }
+            MetadataChange::Remove(name) => {
+                items.remove(&name);
+            }
+        }
+    }
+}
+
+/// Changes to a metadata file not yet merged into it, one JSON object per line
+///
+/// The journal of `.metadata` is `.metadata.journal`. Changes are only
+/// appended, so a crash part way through a save can at worst leave its last
+/// line cut short, which is ignored when reading.
+#[derive(Debug, Clone)]
+pub struct MetadataJournal {
+    path: PathBuf,
+}
+
+impl MetadataJournal {
+    /// The journal of `metadata_file`, which need not exist
+    pub fn new(metadata_file: &Path) -> Self {
+        MetadataJournal { path: sibling(metadata_file, JOURNAL_EXTENSION) }
+    }
+
+    /// Path of the journal file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The changes in the journal, oldest first
+    ///
+    /// A missing journal is empty. Returns `MetadataCorrupt` if a complete
+    /// line is not a valid change.
+    pub fn read(&self) -> Result<Vec<MetadataChange>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(source) => {
+                return Err(TrsError::Io { context: format!("reading metadata journal {}", self.path.display()), source });
+            }
+        };
+        let complete = content.rfind('\n').map_or(0, |end| end + 1);
+        content[..complete]
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line).map_err(|source| TrsError::MetadataCorrupt { path: self.path.clone(), source })
+            })
+            .collect()
+    }
+
+    /// Append `changes` to the journal, syncing it to disk before returning
+    ///
+    /// A last line cut short by an earlier crash is dropped first, so the
+    /// changes start on a line of their own.
+    pub fn append(&self, changes: &[MetadataChange]) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let mut content = String::new();
+        for change in changes {
+            content.push_str(&serde_json::to_string(change)?);
+            content.push('\n');
+        }
+        let write = || -> std::io::Result<()> {
+            let mut file = fs::OpenOptions::new().create(true).read(true).append(true).open(&self.path)?;
+            drop_torn_line(&mut file)?;
+            file.write_all(content.as_bytes())?;
+            file.sync_data()
+        };
+        write().map_err(|source| TrsError::Io {
+            context: format!("writing metadata journal {}", self.path.display()),
+            source,
+        })
+    }
+
+    /// Delete the journal once its changes are merged into the metadata file
+    fn remove(&self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(TrsError::Io {
+                context: format!("removing metadata journal {}", self.path.display()),
+                source: e,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Truncate `journal` after its last complete line, if it does not end with one
+fn drop_torn_line(journal: &mut fs::File) -> std::io::Result<()> {
+    let len = journal.seek(SeekFrom::End(0))?;
+    if len == 0 {
+        return Ok(());
+    }
+    let mut last = [0u8];
+    journal.seek(SeekFrom::End(-1))?;
+    journal.read_exact(&mut last)?;
+    if last[0] == b'\n' {
+        return Ok(());
+    }
+    // Only after a crash, so reading the whole journal is fine
+    let mut content = Vec::new();
+    journal.seek(SeekFrom::Start(0))?;
+    journal.read_to_end(&mut content)?;
+    let complete = content.iter().rposition(|&byte| byte == b'\n').map_or(0, |end| end + 1);
+    journal.set_len(complete as u64)
+}
+
+/// Reads single items of a metadata file without deserializing the others
+///
+/// `load_items` builds every item, which dominates commands that look at
+/// one item in a trash holding thousands. The store streams the file
+/// instead and only deserializes the values of the names asked for; the
+/// others are scanned past without being built. Files in either format are
+/// read, and the changes in the journal replayed over them. Changing an
+/// item still needs `load_items` and `save_items`, which compare every item
+/// to find the ones to journal.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use trs::metadata::{save_items, LazyMetadataStore, TrashItem};
+///
+/// let file = std::env::temp_dir().join(format!("trs-lazy-doc-{}", std::process::id()));
+/// let items: HashMap<_, _> = (0..1000)
+///     .map(|i| (format!("file{}.txt.tar.gz", i), TrashItem::new(format!("/home/me/file{}.txt", i), false)))
+///     .collect();
+/// save_items(&file, &items).unwrap();
+///
+/// let store = LazyMetadataStore::new(&file);
+/// assert_eq!(store.get("file42.txt.tar.gz").unwrap().unwrap().path, "/home/me/file42.txt");
+/// assert!(store.get("missing.tar.gz").unwrap().is_none());
+/// assert_eq!(store.select(|name| name.starts_with("file99")).unwrap().len(), 11);
+/// std::fs::remove_file(&file).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct LazyMetadataStore {
+    metadata_file: PathBuf,
+}
+
+impl LazyMetadataStore {
+    /// A store reading `metadata_file`, which is not opened until an item is asked for
+    pub fn new(metadata_file: &Path) -> Self {
+        LazyMetadataStore { metadata_file: metadata_file.to_path_buf() }
+    }
+
+    /// The item stored under `name`, if there is one
+    pub fn get(&self, name: &str) -> Result<Option<TrashItem>> {
+        Ok(self.select(|key| key == name)?.remove(name))
+    }
+
+    /// The items whose names `wanted` accepts, by name
+    ///
+    /// A missing or empty file holds no items. Returns `MetadataCorrupt` if
+    /// the file is not valid, as `load_items` does.
+    pub fn select(&self, mut wanted: impl FnMut(&str) -> bool) -> Result<HashMap<String, TrashItem>> {
+        let mut items = self.select_stored(&mut wanted)?;
+        for change in MetadataJournal::new(&self.metadata_file).read()? {
+            if wanted(change.name()) {
+                change.apply(&mut items);
+            }
+        }
+        Ok(items)
+    }
+
+    /// The items in the file itself whose names `wanted` accepts
+    fn select_stored(&self, wanted: impl FnMut(&str) -> bool) -> Result<HashMap<String, TrashItem>> {
+        let (base, _) = base_file(&self.metadata_file);
+        let file = match fs::File::open(&base) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(source) => {
+                return Err(TrsError::Io { context: format!("reading metadata {}", base.display()), source });
+            }
+        };
+        let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(file));
+        let selected = SelectedItems(wanted).deserialize(&mut deserializer).and_then(|items| {
+            deserializer.end()?;
+            Ok(items)
+        });
+        match selected {
+            Ok(items) => Ok(items),
+            // A file of nothing but whitespace ends before its first value
+            Err(e) if e.is_eof() && read_metadata_file(&base)?.is_none() => Ok(HashMap::new()),
+            Err(source) => Err(TrsError::MetadataCorrupt { path: base, source }),
+        }
+    }
+}
+
+/// A value of the metadata object in either format
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "expected a trash item or a version 1 value")]
+enum StoredItem {
+    Item(TrashItem),
+    Encoded(String),
+}
+
+/// Deserializes the items of a metadata object whose names are accepted, skipping the others
+struct SelectedItems<F>(F);
+
+impl<'de, F: FnMut(&str) -> bool> DeserializeSeed<'de> for SelectedItems<F> {
+    type Value = HashMap<String, TrashItem>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(&str) -> bool> Visitor<'de> for SelectedItems<F> {
+    type Value = HashMap<String, TrashItem>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an object of trash items")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> std::result::Result<Self::Value, A::Error> {
+        let mut items = HashMap::new();
+        while let Some(name) = map.next_key::<String>()? {
+            if !(self.0)(&name) {
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            }
+            let item = match map.next_value()? {
+                StoredItem::Item(item) => item,
+                StoredItem::Encoded(value) => parse_item(&value),
+            };
+            items.insert(name, item);
+        }
+        Ok(items)
+    }
+}
+
+/// Load metadata from file as items, whichever format it is written in
+///
+/// Version 1 values are decoded here, so callers never see them: a bare
+/// path from the oldest files becomes an item with nothing else recorded.
+/// A missing or empty file is treated as empty metadata. Returns
+/// `MetadataCorrupt` if the file is not valid, rather than discarding it.
+///
+/// ```
+/// use trs::metadata::{load_metadata, save_metadata_typed};
+///
+/// let file = std::env::temp_dir().join(format!("trs-load-metadata-doc-{}", std::process::id()));
+/// std::fs::write(&file, r#"{"old.txt.tar.gz":"/home/me/old.txt"}"#).unwrap();
+/// let mut items = load_metadata(&file).unwrap();
+/// let old = &items["old.txt.tar.gz"];
+/// assert_eq!(old.path, "/home/me/old.txt");
+/// assert!(!old.is_dir);
+/// assert_eq!(old.deleted_at, None);
+///
+/// // Saving writes the current format, which loads back the same
+/// items.get_mut("old.txt.tar.gz").unwrap().pinned = true;
+/// save_metadata_typed(&file, &items).unwrap();
+/// assert_eq!(load_metadata(&file).unwrap(), items);
+/// std::fs::remove_file(&file).unwrap();
+/// ```
+pub fn load_metadata(metadata_file: &Path) -> Result<HashMap<String, TrashItem>> {
+    load_items(metadata_file)
+}
+
+/// Save metadata given as items, syncing it to disk before returning
+///
+/// The file is written in the current format, as `save_items` does.
+pub fn save_metadata_typed(metadata_file: &Path, metadata: &HashMap<String, TrashItem>) -> Result<()> {
+    save_items(metadata_file, metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_name_must_be_a_free_hidden_file_name() {
+        assert!(check_metadata_name(".trs-items").is_ok());
+        for name in ["metadata", ".", "..", ".meta/data", ".meta\\data", journal::JOURNAL_NAME, schedule::SCHEDULE_NAME, ".x.tar.gz.partial", ".metadata.journal"] {
+            assert!(matches!(check_metadata_name(name), Err(TrsError::InvalidInput(_))), "{} was accepted", name);
+        }
+    }
+}
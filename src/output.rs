@@ -0,0 +1,106 @@
+//! How the CLI presents command results
+//!
+//! Commands print tables, messages and progress bars for people by default.
+//! `--porcelain` and `--json` print records for other programs instead, and
+//! never draw progress bars, so stdout holds nothing but the records.
+
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::error::{Context, Result, TrsError};
+use crate::{IndicatifProgress, NoOpProgress, ProgressSink};
+
+/// How command results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Tables, messages and progress bars
+    #[default]
+    Human,
+    /// Stable tab-separated records
+    Porcelain,
+    /// JSON documents on stdout, and errors as JSON objects on stderr
+    Json,
+}
+
+impl OutputFormat {
+    /// Whether the output is meant for people rather than programs
+    pub fn is_human(self) -> bool {
+        self == OutputFormat::Human
+    }
+
+    /// Progress sink for an operation: a bar labelled with `message` for people, nothing otherwise
+    pub fn progress(self, message: impl Into<String>) -> Box<dyn ProgressSink> {
+        match self {
+            OutputFormat::Human => Box::new(IndicatifProgress::new(message)),
+            OutputFormat::Porcelain | OutputFormat::Json => Box::new(NoOpProgress),
+        }
+    }
+}
+
+/// Outcome for one of the items a command was given, as printed by `--json`
+///
+/// On success the fields of `result` are included next to `source` and
+/// `status`; on failure `error` holds the reason.
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemResult<T> {
+    /// Path or name of the item as given on the command line
+    pub source: String,
+    /// `ok` or `error`
+    pub status: &'static str,
+    #[serde(flatten)]
+    pub result: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl<T> ItemResult<T> {
+    /// Record the result of an operation on `source`
+    pub fn new(source: impl Into<String>, result: Result<T>) -> Self {
+        match result {
+            Ok(result) => ItemResult {
+                source: source.into(),
+                status: "ok",
+                result: Some(result),
+                error: None,
+            },
+            Err(e) => ItemResult::failed(source, e.to_string()),
+        }
+    }
+
+    /// Record that `source` could not be processed because of `reason`
+    pub fn failed(source: impl Into<String>, reason: impl Into<String>) -> Self {
+        ItemResult {
+            source: source.into(),
+            status: "error",
+            result: None,
+            error: Some(reason.into()),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Print `value` to stdout as JSON
+pub fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Print `value` to stdout as one line of JSON, flushed at once so readers get it as it comes
+pub fn print_json_line(value: &impl Serialize) -> Result<()> {
+    let mut out = io::stdout().lock();
+    serde_json::to_writer(&mut out, value)?;
+    writeln!(out).and_then(|()| out.flush()).with_context(|| "writing to stdout".to_string())
+}
+
+/// Print an error that stopped a command to stderr as a JSON object
+pub fn print_json_error(error: &TrsError) {
+    let value = serde_json::json!({
+        "error": error.to_string(),
+        "exit_code": error.exit_code(),
+    });
+    eprintln!("{}", value);
+}
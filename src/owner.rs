@@ -0,0 +1,191 @@
+//! Who trashed an item, and on which machine
+//!
+//! A trash directory on a shared or network filesystem may hold items from
+//! several users and machines, whose original paths only mean something
+//! where they were trashed. The user name and host name are recorded with
+//! each item, so `show --owner` can tell them apart and restore can warn
+//! about items trashed on another host. Both are looked up once per process.
+//!
+//! Under `sudo` the process runs as root but was started by another user,
+//! whose trash [`sudo_user`] finds. Items trashed there with `--user-trash`
+//! are recorded as theirs, and the files written for them are handed back
+//! with [`hand_over`] so they can manage the trash without root.
+
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Name of this machine, if it can be found
+///
+/// ```
+/// # #[cfg(unix)]
+/// assert!(trs::owner::hostname().is_some_and(|name| !name.is_empty()));
+/// ```
+pub fn hostname() -> Option<&'static str> {
+    static HOSTNAME: OnceLock<Option<String>> = OnceLock::new();
+    HOSTNAME.get_or_init(|| system_hostname().or_else(|| non_empty_var("COMPUTERNAME"))).as_deref()
+}
+
+/// Name of the user running `trs`, if it can be found
+///
+/// The account of the effective user is preferred over `$USER`, which may
+/// be left over from another user after `sudo` or `su`. After
+/// [`act_for`], the user acted for is named instead.
+pub fn username() -> Option<&'static str> {
+    static USERNAME: OnceLock<Option<String>> = OnceLock::new();
+    if let Some(user) = ACTING_FOR.get() {
+        return Some(&user.name);
+    }
+    USERNAME
+        .get_or_init(|| system_username().or_else(|| non_empty_var("USER")).or_else(|| non_empty_var("USERNAME")))
+        .as_deref()
+}
+
+/// The user items are trashed for, once set by `act_for`
+static ACTING_FOR: OnceLock<&'static SudoUser> = OnceLock::new();
+
+/// Record items trashed from now on as trashed by `user`, for `--user-trash`
+pub fn act_for(user: &'static SudoUser) {
+    let _ = ACTING_FOR.set(user);
+}
+
+/// The user who ran `sudo`, as found by `sudo_user`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SudoUser {
+    /// Account name
+    pub name: String,
+    pub uid: u32,
+    /// Primary group
+    pub gid: u32,
+    /// Home directory
+    pub home: PathBuf,
+}
+
+impl SudoUser {
+    /// The local data directory of the user, where their `trs` trash is kept
+    ///
+    /// `$XDG_DATA_HOME` cannot be trusted under `sudo`, which may keep it
+    /// from either user, so the default below the home directory is used.
+    pub fn data_local_dir(&self) -> PathBuf {
+        if cfg!(target_os = "macos") {
+            self.home.join("Library").join("Application Support")
+        } else {
+            self.home.join(".local").join("share")
+        }
+    }
+}
+
+/// The user who started `trs` with `sudo`, when it runs as root
+///
+/// Found from `$SUDO_UID` and the password database. There is none when
+/// not running as root, when `sudo` was run by root itself or when the
+/// account cannot be looked up.
+pub fn sudo_user() -> Option<&'static SudoUser> {
+    static SUDO_USER: OnceLock<Option<SudoUser>> = OnceLock::new();
+    SUDO_USER
+        .get_or_init(|| {
+            let uid = non_empty_var("SUDO_UID")?.parse().ok().filter(|&uid| uid != 0)?;
+            if !is_root() {
+                return None;
+            }
+            let (name, gid, home) = account(uid)?;
+            Some(SudoUser { name, uid, gid, home })
+        })
+        .as_ref()
+}
+
+/// Whether the effective user is root
+#[cfg(unix)]
+pub fn is_root() -> bool {
+    // SAFETY: geteuid cannot fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Only Unix systems have a root user
+#[cfg(not(unix))]
+pub fn is_root() -> bool {
+    false
+}
+
+/// Give `path` to `user`, without following it if it is a symbolic link
+///
+/// Does nothing unless running as root, as only root may give files away.
+#[cfg(unix)]
+pub fn hand_over(path: &Path, user: &SudoUser) -> io::Result<()> {
+    if !is_root() {
+        return Ok(());
+    }
+    std::os::unix::fs::lchown(path, Some(user.uid), Some(user.gid))
+}
+
+/// Ownership is not changed on other systems
+#[cfg(not(unix))]
+pub fn hand_over(_path: &Path, _user: &SudoUser) -> io::Result<()> {
+    Ok(())
+}
+
+/// The value of the environment variable `name`, unless it is unset or empty
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Host name as the system reports it
+#[cfg(unix)]
+fn system_hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    // SAFETY: the buffer is valid for its whole length
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return None;
+    }
+    let len = buffer.iter().position(|&byte| byte == 0).unwrap_or(buffer.len());
+    let name = String::from_utf8_lossy(&buffer[..len]).into_owned();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Only the environment is asked on other systems
+#[cfg(not(unix))]
+fn system_hostname() -> Option<String> {
+    None
+}
+
+/// Name of the account of the effective user, from the password database
+#[cfg(unix)]
+fn system_username() -> Option<String> {
+    // SAFETY: geteuid cannot fail
+    account(unsafe { libc::geteuid() }).map(|(name, _, _)| name).filter(|name| !name.is_empty())
+}
+
+/// Only the environment is asked on other systems
+#[cfg(not(unix))]
+fn system_username() -> Option<String> {
+    None
+}
+
+/// Name, primary group and home directory of the account `uid`, from the password database
+#[cfg(unix)]
+fn account(uid: u32) -> Option<(String, u32, PathBuf)> {
+    use std::ffi::CStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut passwd = std::mem::MaybeUninit::<libc::passwd>::uninit();
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let mut result = std::ptr::null_mut();
+    // SAFETY: every pointer is valid, and `result` is only read when the entry was found
+    let status = unsafe { libc::getpwuid_r(uid, passwd.as_mut_ptr(), buffer.as_mut_ptr(), buffer.len(), &mut result) };
+    if status != 0 || result.is_null() {
+        return None;
+    }
+    // SAFETY: the strings point into `buffer`, NUL-terminated, when the entry was found
+    let (name, home) = unsafe { (CStr::from_ptr((*result).pw_name), CStr::from_ptr((*result).pw_dir)) };
+    // SAFETY: the entry was found
+    let gid = unsafe { (*result).pw_gid };
+    let home = PathBuf::from(std::ffi::OsStr::from_bytes(home.to_bytes()));
+    Some((name.to_string_lossy().into_owned(), gid, home))
+}
+
+/// Accounts are not looked up on other systems
+#[cfg(not(unix))]
+fn account(_uid: u32) -> Option<(String, u32, PathBuf)> {
+    None
+}
@@ -0,0 +1,128 @@
+//! Deletions scheduled for later, for `trs move --schedule`
+//!
+//! Scheduling only records the path and when to trash it; the item is not
+//! opened, moved or locked until then, so it can stay in use. Entries are
+//! kept one JSON object per line in a file next to the metadata of the trash
+//! they will go to. `trs schedule run` moves the ones that are due with
+//! [`move_to_trash`](crate::trash::move_to_trash) and removes them.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, TrsError};
+use crate::macos;
+use crate::metadata::{metadata_path, now_secs};
+
+/// Name of the schedule file
+pub const SCHEDULE_NAME: &str = ".scheduled";
+
+/// A path to move to the trash at a later time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledItem {
+    /// Number given to `cancel`, unique within the schedule
+    pub id: u64,
+    /// Absolute path of the item
+    pub path: String,
+    /// When to trash the item, in seconds since the Unix epoch
+    pub due_at: u64,
+    /// When the item was scheduled, in seconds since the Unix epoch
+    pub scheduled_at: u64,
+}
+
+/// Path of the schedule of `trash_dir`, next to its metadata file
+///
+/// The native macOS trash uses a name that says which program it belongs to.
+pub fn schedule_path(trash_dir: &Path) -> PathBuf {
+    let name = if macos::is_native_trash(trash_dir) { macos::SCHEDULE_NAME } else { SCHEDULE_NAME };
+    metadata_path(trash_dir).with_file_name(name)
+}
+
+/// Scheduled items of `trash_dir`, soonest first
+///
+/// A missing schedule is empty. Lines that cannot be read are skipped.
+pub fn read_schedule(trash_dir: &Path) -> Result<Vec<ScheduledItem>> {
+    let path = schedule_path(trash_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path).map_err(|source| TrsError::Io {
+        context: format!("reading schedule {}", path.display()),
+        source,
+    })?;
+    let mut items: Vec<ScheduledItem> = content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect();
+    items.sort_by_key(|item| (item.due_at, item.id));
+    Ok(items)
+}
+
+/// Schedule `path` to be moved to `trash_dir` at `due_at`
+///
+/// The path must exist now and is made absolute, so the entry does not
+/// depend on the directory `schedule run` is started from. It is not
+/// opened.
+pub fn schedule(trash_dir: &Path, path: &Path, due_at: u64) -> Result<ScheduledItem> {
+    let absolute = std::path::absolute(path).map_err(|source| TrsError::Io {
+        context: format!("resolving {}", path.display()),
+        source,
+    })?;
+    if fs::symlink_metadata(&absolute).is_err() {
+        return Err(TrsError::SourceNotFound(path.to_path_buf()));
+    }
+    let mut items = read_schedule(trash_dir)?;
+    let item = ScheduledItem {
+        id: items.iter().map(|item| item.id).max().unwrap_or(0) + 1,
+        path: absolute.to_string_lossy().to_string(),
+        due_at,
+        scheduled_at: now_secs(),
+    };
+    items.push(item.clone());
+    write_schedule(trash_dir, &items)?;
+    Ok(item)
+}
+
+/// Remove the scheduled item `id` of `trash_dir`, returning it
+pub fn cancel(trash_dir: &Path, id: u64) -> Result<ScheduledItem> {
+    let mut items = read_schedule(trash_dir)?;
+    let Some(index) = items.iter().position(|item| item.id == id) else {
+        return Err(TrsError::invalid_input(format!("No scheduled deletion with id {}", id)));
+    };
+    let item = items.remove(index);
+    write_schedule(trash_dir, &items)?;
+    Ok(item)
+}
+
+/// Scheduled items of `trash_dir` due at or before `now`, soonest first
+pub fn due(trash_dir: &Path, now: u64) -> Result<Vec<ScheduledItem>> {
+    Ok(read_schedule(trash_dir)?.into_iter().filter(|item| item.due_at <= now).collect())
+}
+
+/// Remove the items with the given ids from the schedule of `trash_dir`, once they have been dealt with
+pub fn remove(trash_dir: &Path, ids: &[u64]) -> Result<()> {
+    let mut items = read_schedule(trash_dir)?;
+    items.retain(|item| !ids.contains(&item.id));
+    write_schedule(trash_dir, &items)
+}
+
+/// Replace the schedule of `trash_dir` with `items`, syncing it to disk before returning
+fn write_schedule(trash_dir: &Path, items: &[ScheduledItem]) -> Result<()> {
+    let path = schedule_path(trash_dir);
+    let mut content = String::new();
+    for item in items {
+        content.push_str(&serde_json::to_string(item)?);
+        content.push('\n');
+    }
+    let write = || -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::File::create(&path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    };
+    write().map_err(|source| TrsError::Io {
+        context: format!("writing schedule {}", path.display()),
+        source,
+    })
+}
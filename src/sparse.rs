@@ -0,0 +1,189 @@
+//! Archiving sparse files without their holes
+//!
+//! Disk images and database files often reserve far more space than they
+//! hold data. Archiving one as a regular file reads and compresses every
+//! hole as zeros, and extracting it writes them all back. Instead, files
+//! whose blocks on disk cover less than their length are stored as GNU
+//! sparse entries listing where the data is, found with `SEEK_DATA` and
+//! `SEEK_HOLE`. The `tar` crate extracts such entries by seeking over the
+//! holes, so restored files are sparse again. Other systems store all
+//! files whole.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use tar::{Builder, EntryType, GnuExtSparseHeader, Header};
+
+/// Size of a tar block; every data region but the last must be a multiple of it
+const BLOCK_SIZE: u64 = 512;
+
+/// Number of regions described in the main header of a sparse entry
+const HEADER_REGIONS: usize = 4;
+
+/// Number of regions described in each extension block
+const EXTENSION_REGIONS: usize = 21;
+
+/// Append the file at `path` to `tar` as `name`, leaving out its holes if it is sparse
+///
+/// ```
+/// # #[cfg(target_os = "linux")] {
+/// use std::fs;
+/// use std::os::unix::fs::MetadataExt;
+/// use std::path::Path;
+/// use tar::{Archive, Builder};
+///
+/// let dir = std::env::temp_dir().join(format!("trs-sparse-doc-{}", std::process::id()));
+/// fs::create_dir_all(&dir).unwrap();
+/// let image = dir.join("image");
+/// fs::File::create(&image).unwrap().set_len(64 << 20).unwrap();
+///
+/// // Only the headers are stored
+/// let mut tar = Builder::new(Vec::new());
+/// trs::sparse::append_file(&mut tar, &image, Path::new("restored")).unwrap();
+/// let archive = tar.into_inner().unwrap();
+/// assert!(archive.len() < 4096);
+///
+/// // And the extracted file is sparse again
+/// Archive::new(archive.as_slice()).unpack(&dir).unwrap();
+/// let restored = fs::metadata(dir.join("restored")).unwrap();
+/// assert_eq!(restored.len(), 64 << 20);
+/// assert!(restored.blocks() * 512 < restored.len() / 100);
+/// fs::remove_dir_all(&dir).unwrap();
+/// # }
+/// ```
+pub fn append_file<W: Write>(tar: &mut Builder<W>, path: &Path, name: &Path) -> io::Result<()> {
+    if !is_sparse(&fs::metadata(path)?) {
+        return tar.append_path_with_name(path, name);
+    }
+    let file = fs::File::open(path)?;
+    let metadata = file.metadata()?;
+    let Some(regions) = data_regions(&file, metadata.len())? else {
+        return tar.append_path_with_name(path, name);
+    };
+
+    let mut header = Header::new_gnu();
+    header.set_metadata(&metadata);
+    header.set_entry_type(EntryType::GNUSparse);
+    header.set_size(regions.iter().map(|(_, len)| len).sum());
+
+    // A hole at the end is marked by an empty region at the end of the file
+    let mut listed = regions.clone();
+    if listed.last().is_none_or(|(offset, len)| offset + len < metadata.len()) {
+        listed.push((metadata.len(), 0));
+    }
+    let gnu = header.as_gnu_mut().expect("new_gnu creates a GNU header");
+    gnu.set_real_size(metadata.len());
+    for (slot, (offset, len)) in gnu.sparse.iter_mut().zip(&listed) {
+        slot.set_offset(*offset);
+        slot.set_length(*len);
+    }
+
+    // The rest follow the header in extension blocks, ahead of the data
+    let mut extensions = Vec::new();
+    let rest = listed.get(HEADER_REGIONS..).unwrap_or_default();
+    gnu.set_is_extended(!rest.is_empty());
+    let chunks: Vec<_> = rest.chunks(EXTENSION_REGIONS).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let mut extension = GnuExtSparseHeader::new();
+        for (slot, (offset, len)) in extension.sparse.iter_mut().zip(chunk.iter()) {
+            slot.set_offset(*offset);
+            slot.set_length(*len);
+        }
+        extension.set_is_extended(i + 1 < chunks.len());
+        extensions.extend_from_slice(extension.as_bytes());
+    }
+
+    let data = DataReader { file, regions, current: 0, left: 0 };
+    tar.append_data(&mut header, name, extensions.as_slice().chain(data))
+}
+
+/// Whether the blocks allocated to a file cover less than its length
+#[cfg(unix)]
+pub fn is_sparse(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.is_file() && metadata.blocks() * BLOCK_SIZE < metadata.len()
+}
+
+/// Allocated blocks are not known on this platform
+#[cfg(not(unix))]
+pub fn is_sparse(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Offsets and lengths of the parts of `file` holding data, aligned to tar blocks
+///
+/// Returns `None` if the filesystem cannot tell where the holes are.
+#[cfg(target_os = "linux")]
+fn data_regions(file: &fs::File, len: u64) -> io::Result<Option<Vec<(u64, u64)>>> {
+    use std::os::fd::AsRawFd;
+
+    // Returns `None` when there is no data, or no hole, after `offset`
+    let seek = |offset: u64, whence| -> io::Result<Option<u64>> {
+        match unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) } {
+            -1 => match io::Error::last_os_error() {
+                e if e.raw_os_error() == Some(libc::ENXIO) => Ok(None),
+                e => Err(e),
+            },
+            position => Ok(Some(position as u64)),
+        }
+    };
+
+    let mut regions: Vec<(u64, u64)> = Vec::new();
+    let mut offset = 0;
+    while offset < len {
+        let start = match seek(offset, libc::SEEK_DATA) {
+            Ok(Some(start)) => start,
+            Ok(None) => break,
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let end = seek(start, libc::SEEK_HOLE)?.unwrap_or(len).min(len);
+        let start = start / BLOCK_SIZE * BLOCK_SIZE;
+        let aligned_end = end.div_ceil(BLOCK_SIZE).saturating_mul(BLOCK_SIZE).min(len);
+        match regions.last_mut() {
+            Some((previous, previous_len)) if *previous + *previous_len >= start => {
+                *previous_len = aligned_end - *previous;
+            }
+            _ => regions.push((start, aligned_end - start)),
+        }
+        offset = end.max(offset + 1);
+    }
+    Ok(Some(regions))
+}
+
+/// Holes can only be found on Linux
+#[cfg(not(target_os = "linux"))]
+fn data_regions(_file: &fs::File, _len: u64) -> io::Result<Option<Vec<(u64, u64)>>> {
+    Ok(None)
+}
+
+/// Reads the data regions of a file one after the other
+struct DataReader {
+    file: fs::File,
+    regions: Vec<(u64, u64)>,
+    current: usize,
+    left: u64,
+}
+
+impl Read for DataReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use std::io::{Seek, SeekFrom};
+
+        while self.left == 0 {
+            let Some(&(offset, len)) = self.regions.get(self.current) else {
+                return Ok(0);
+            };
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.current += 1;
+            self.left = len;
+        }
+        let limit = buf.len().min(usize::try_from(self.left).unwrap_or(usize::MAX));
+        let read = self.file.read(&mut buf[..limit])?;
+        if read == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file shrank while being archived"));
+        }
+        self.left -= read as u64;
+        Ok(read)
+    }
+}
@@ -0,0 +1,100 @@
+//! Colors for terminal listings
+//!
+//! Every highlight goes through [`Palette`], so new columns are colored the
+//! same way and disappear together when colors are off. Colors are only
+//! used for people: never with `--json` or `--porcelain`, and by default
+//! only when stdout is a terminal and `NO_COLOR` is not set.
+
+use std::io::{self, IsTerminal};
+use anstyle::{AnsiColor, Style};
+
+/// Size in bytes from which sizes are highlighted as large
+pub const LARGE_SIZE: u64 = 100 * 1024 * 1024;
+
+/// When to use colors, as chosen with `--color`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Names accepted by `--color`
+    pub const NAMES: [&'static str; 3] = ["auto", "always", "never"];
+
+    /// Parse a name from `NAMES`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+
+    /// Whether to color output written to stdout
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+/// Highlights for parts of a listing, which leave text unchanged when disabled
+///
+/// ```
+/// use trs::style::Palette;
+///
+/// assert_eq!(Palette::new(false).directory("docs/"), "docs/");
+/// assert!(!Palette::new(false).unknown("Unknown").contains('\x1b'));
+/// assert!(Palette::new(true).directory("docs/").starts_with('\x1b'));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    pub fn new(enabled: bool) -> Self {
+        Palette { enabled }
+    }
+
+    /// Name of a directory
+    pub fn directory(&self, text: &str) -> String {
+        self.paint(Style::new().bold().fg_color(Some(AnsiColor::Blue.into())), text)
+    }
+
+    /// A size, highlighted when it is at least `LARGE_SIZE`
+    pub fn size(&self, text: &str, size: u64) -> String {
+        if size >= LARGE_SIZE {
+            self.paint(Style::new().fg_color(Some(AnsiColor::Yellow.into())), text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// A note that should not be missed, such as which trash is used under sudo
+    pub fn notice(&self, text: &str) -> String {
+        self.paint(Style::new().bold().fg_color(Some(AnsiColor::Yellow.into())), text)
+    }
+
+    /// A value that is not known, such as a missing original location
+    pub fn unknown(&self, text: &str) -> String {
+        self.paint(Style::new().fg_color(Some(AnsiColor::Red.into())), text)
+    }
+
+    fn paint(&self, style: Style, text: &str) -> String {
+        if self.enabled {
+            format!("{}{}{:#}", style, text, style)
+        } else {
+            text.to_string()
+        }
+    }
+}
@@ -1,17 +1,767 @@
 //! Core trash operations implementation
 
 use std::fs;
-use std::io::{self, Write, BufRead};
-use std::path::Path;
+use std::io::{self, Write, BufRead, BufReader, Read, Seek};
+use std::path::{Component, Path, PathBuf};
 use std::env;
-use flate2::write::GzEncoder;
-use flate2::Compression;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use flate2::read::GzDecoder;
-use std::collections::HashMap;
+use glob::Pattern;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::hash_map::Entry;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use rayon::prelude::*;
 use tar::{Archive, Builder};
-use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+use tracing::{debug, info, warn};
 
-use crate::metadata::{load_metadata, save_metadata, TrashItem};
+use crate::compression::{self, default_jobs, ArchiveCompression, ArchiveEncoder, BundleCompression, BundleReader, BundleWriter, DEFAULT_BUFFER_SIZE};
+use crate::crypto::{self, ArchiveWriter, DecryptReader, Passphrase};
+use crate::error::{Context, Result, TrsError};
+use crate::format::{display_path, display_size, escape_field, format_age, format_count, format_owner, format_ratio, format_size, format_timestamp};
+use crate::iter::TrashIterator;
+use crate::style::Palette;
+use crate::journal::{self, JournalRecord};
+use crate::metadata::{compact_metadata_journal, detect_backend, is_reserved_name, load_items, metadata_path, now_secs, save_items, LazyMetadataStore, TrashItem};
+use crate::{device, macos, owner, sparse, xdg};
+use crate::{NoOpProgress, ProgressSink};
+
+/// Options controlling how items are moved to trash
+#[derive(Debug, Clone)]
+pub struct MoveOptions {
+    /// Number of threads used to compress archives (1 disables parallelism)
+    pub jobs: usize,
+    /// Size in bytes of the buffer used when writing archives
+    pub buffer_size: usize,
+    /// Compression of the archives items are stored in
+    pub compression: ArchiveCompression,
+    /// Number of worker threads compressing zstd archives (0 compresses on the calling thread)
+    pub zstd_threads: u32,
+    /// Pick the compression of each file from its extension, keeping already compressed files as they are
+    pub auto_compress: bool,
+    /// Maximum total size of the trash before the oldest items are evicted
+    pub max_size: Option<u64>,
+    /// Minimum age of an item before it can be evicted
+    pub min_age: Duration,
+    /// Name to store the item under instead of its file name
+    pub name: Option<String>,
+    /// Use a trash at the root of the item's filesystem when it is not the trash's
+    pub device_trash: bool,
+    /// Files smaller than this many bytes are stored as they are instead of archived; empty files always are
+    pub compress_threshold: u64,
+    /// Modification times the item gets back when it is restored
+    pub timestamps: Timestamps,
+    /// Overwrite a file once its data is archived, before removing it
+    pub shred: Option<ShredOptions>,
+    /// Encrypt archives with a key derived from this passphrase
+    pub encrypt: Option<Passphrase>,
+    /// Refuse directories holding more files or bytes than this with `LargeDirectory`
+    pub confirm_above: Option<LargeDirLimits>,
+    /// Refuse items to be archived that are larger than the free space left for the trash
+    pub check_space: bool,
+    /// Link files identical to one already archived in the trash to its archive instead of archiving them again
+    pub dedupe: bool,
+    /// Archive files even when they could be renamed into the trash as they are
+    pub compress: bool,
+    /// Reserve disk space for archives before writing them, to keep them in one piece
+    pub prealloc: bool,
+    /// Fail when a path inside a directory cannot be read, instead of leaving it out and in place
+    pub strict: bool,
+    /// Make read-only parts of an item writable so it can be removed once archived, as `rm -f` does
+    pub force: bool,
+}
+
+impl Default for MoveOptions {
+    fn default() -> Self {
+        MoveOptions {
+            jobs: default_jobs(),
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            compression: ArchiveCompression::Gzip,
+            zstd_threads: 0,
+            auto_compress: false,
+            max_size: None,
+            min_age: Duration::ZERO,
+            name: None,
+            device_trash: false,
+            compress_threshold: 0,
+            timestamps: Timestamps::Keep,
+            shred: None,
+            encrypt: None,
+            confirm_above: None,
+            check_space: true,
+            dedupe: false,
+            compress: false,
+            prealloc: true,
+            strict: false,
+            force: false,
+        }
+    }
+}
+
+/// Number of files and bytes in a directory, above which moving it needs confirming
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LargeDirLimits {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+impl LargeDirLimits {
+    /// Whether a directory of `usage` goes beyond either limit
+    pub fn exceeded_by(&self, usage: DirUsage) -> bool {
+        usage.files > self.files || usage.bytes > self.bytes
+    }
+}
+
+/// Files found by walking a directory and their total size in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DirUsage {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Options controlling how items are restored from trash
+#[derive(Debug, Clone)]
+pub struct RestoreOptions {
+    /// Size in bytes of the buffer used when reading archives
+    pub buffer_size: usize,
+    /// Restore to this path instead of the original location
+    pub destination: Option<PathBuf>,
+    /// Recreate the directories above the restored item that no longer exist, instead of failing
+    pub create_parents: bool,
+    /// Passphrase to decrypt encrypted archives with
+    pub passphrase: Option<Passphrase>,
+    /// Merge a directory into the one already at its destination, keeping this copy of each file both have
+    pub merge: Option<MergePreference>,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> Self {
+        RestoreOptions {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            destination: None,
+            create_parents: false,
+            passphrase: None,
+            merge: None,
+        }
+    }
+}
+
+/// Which copy of a file `restore --merge` keeps when the restored directory and the one already there both have it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergePreference {
+    /// The file already there, so nothing is overwritten
+    #[default]
+    Existing,
+    /// The file from the trash
+    Trashed,
+    /// Whichever was modified last, the file already there on a tie
+    Newer,
+}
+
+impl MergePreference {
+    /// Names accepted by `--prefer`
+    pub const NAMES: [&'static str; 3] = ["existing", "trashed", "newer"];
+
+    /// Parse a name from `NAMES`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "existing" => Some(MergePreference::Existing),
+            "trashed" => Some(MergePreference::Trashed),
+            "newer" => Some(MergePreference::Newer),
+            _ => None,
+        }
+    }
+
+    /// Whether the trashed copy, modified at `trashed`, replaces the one already there, modified at `existing`
+    ///
+    /// Times are in seconds since the Unix epoch.
+    ///
+    /// ```
+    /// use trs::trash::MergePreference;
+    ///
+    /// assert!(!MergePreference::Existing.replaces(200, 100));
+    /// assert!(MergePreference::Trashed.replaces(100, 200));
+    /// assert!(MergePreference::Newer.replaces(200, 100));
+    /// assert!(!MergePreference::Newer.replaces(100, 200));
+    /// assert!(!MergePreference::Newer.replaces(100, 100));
+    /// ```
+    pub fn replaces(self, trashed: u64, existing: u64) -> bool {
+        match self {
+            MergePreference::Existing => false,
+            MergePreference::Trashed => true,
+            MergePreference::Newer => trashed > existing,
+        }
+    }
+}
+
+/// A file both in a directory restored with `--merge` and already at its destination
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeConflict {
+    /// Location of the file
+    pub path: String,
+    /// Whether the trashed copy replaced the one that was there
+    pub replaced: bool,
+}
+
+/// Options controlling which items `empty_trash` deletes
+#[derive(Debug, Clone, Default)]
+pub struct EmptyOptions {
+    /// Only delete items trashed more than this long ago
+    pub older_than: Option<Duration>,
+    /// Only delete items whose name or original path matches one of these globs
+    pub patterns: Vec<String>,
+    /// Overwrite item data before deleting it
+    pub shred: Option<ShredOptions>,
+    /// Delete pinned items too
+    pub include_pinned: bool,
+    /// Only delete items trashed from the same original path as another, keeping one of each
+    pub duplicates: Option<KeepDuplicate>,
+    /// Keep this many of the most recently trashed items, deleting the older ones
+    pub keep_newest: Option<usize>,
+}
+
+/// Which item of a group of duplicates `empty --duplicates` keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeepDuplicate {
+    /// The one trashed last
+    #[default]
+    Newest,
+    /// The one trashed first
+    Oldest,
+}
+
+impl KeepDuplicate {
+    /// Names accepted by `--keep`
+    pub const NAMES: [&'static str; 2] = ["newest", "oldest"];
+
+    /// Parse a name from `NAMES`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "newest" => Some(KeepDuplicate::Newest),
+            "oldest" => Some(KeepDuplicate::Oldest),
+            _ => None,
+        }
+    }
+}
+
+/// Modification times of restored files, chosen when the item is trashed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timestamps {
+    /// The times the files had when they were trashed
+    #[default]
+    Keep,
+    /// The time of the restore, as for newly created files
+    Reset,
+}
+
+impl Timestamps {
+    /// Names accepted by `--timestamps`
+    pub const NAMES: [&'static str; 2] = ["keep", "reset"];
+
+    /// Parse a name from `NAMES`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "keep" => Some(Timestamps::Keep),
+            "reset" => Some(Timestamps::Reset),
+            _ => None,
+        }
+    }
+}
+
+/// Number of levels of archived directories drawn by `show --tree` unless `--depth` is given
+pub const DEFAULT_TREE_DEPTH: usize = 2;
+
+/// Options controlling which items and columns `show_trash_contents` lists
+#[derive(Debug, Clone)]
+pub struct ShowOptions {
+    /// Show how long ago each item was trashed
+    pub age: bool,
+    /// Show the size of each item, reading it from disk when it was not recorded
+    pub size: bool,
+    /// Print sizes as exact numbers of bytes rather than in binary units
+    pub bytes: bool,
+    /// Only list items trashed less than this long ago
+    pub newer_than: Option<Duration>,
+    /// Only list items trashed more than this long ago
+    pub older_than: Option<Duration>,
+    /// Only list pinned items
+    pub pinned: bool,
+    /// Only list files
+    pub files_only: bool,
+    /// Only list directories
+    pub dirs_only: bool,
+    /// Draw the contents of archived directories beneath them
+    pub tree: bool,
+    /// Number of levels of archived directories to draw with `tree`
+    pub depth: usize,
+    /// List only items trashed from the same original path as another, grouped by that path
+    pub duplicates: bool,
+    /// Only list items whose original parent directory no longer exists, with the deepest ancestor that does
+    pub missing_parent: bool,
+    /// Show who trashed each item and on which machine
+    pub owner: bool,
+    /// Print only the item rows, without the header or empty-trash notes
+    pub no_header: bool,
+    /// Print one tab-separated record per item for scripts, sorted by name
+    pub porcelain: bool,
+    /// Highlight directories, large sizes and unknown locations
+    pub color: bool,
+}
+
+impl Default for ShowOptions {
+    fn default() -> Self {
+        ShowOptions {
+            age: false,
+            size: false,
+            bytes: false,
+            newer_than: None,
+            older_than: None,
+            pinned: false,
+            files_only: false,
+            dirs_only: false,
+            tree: false,
+            depth: DEFAULT_TREE_DEPTH,
+            duplicates: false,
+            missing_parent: false,
+            owner: false,
+            no_header: false,
+            porcelain: false,
+            color: false,
+        }
+    }
+}
+
+/// Options controlling how `export_trash` writes a bundle
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Outer compression of the bundle
+    pub compression: BundleCompression,
+    /// Replace the output file if it already exists
+    pub overwrite: bool,
+}
+
+/// Options controlling how `import_trash` merges a bundle
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// Replace local items that have the same name instead of renaming the imported ones
+    pub replace: bool,
+}
+
+/// What importing a bundle does with one of its items
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    /// Added under its own name, or under a new one if that was taken
+    Add,
+    /// Replaces the local item with the same name
+    Replace,
+    /// Left out because the same item is already in the trash
+    Skip,
+}
+
+/// An item of a bundle and where `import_trash` puts it
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedItem {
+    /// Name of the item in the bundle
+    pub name: String,
+    /// Name the item is stored under in the local trash
+    pub local_name: String,
+    pub action: ImportAction,
+    /// Size in bytes of the item's data in the bundle
+    pub size: u64,
+}
+
+/// An item of the XDG trash, as listed by `system_trash_items`
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemTrashItem {
+    /// Name of the item in the `files/` directory of the XDG trash
+    pub name: String,
+    pub original_path: String,
+    pub is_dir: bool,
+    /// Deletion time in seconds since the Unix epoch, if the date was valid
+    pub deleted_at: Option<u64>,
+    /// Size on disk in bytes
+    pub size: u64,
+}
+
+/// How to overwrite data before it is deleted
+///
+/// This is best effort: on copy-on-write filesystems, SSDs with wear
+/// levelling and journaling filesystems the old data may survive elsewhere
+/// on the device.
+#[derive(Debug, Clone)]
+pub struct ShredOptions {
+    /// Number of passes of random data
+    pub passes: usize,
+    /// Finish with an additional pass of zeros
+    pub zero: bool,
+}
+
+impl Default for ShredOptions {
+    fn default() -> Self {
+        ShredOptions {
+            passes: 1,
+            zero: false,
+        }
+    }
+}
+
+/// An item currently in the trash
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashEntry {
+    /// Name of the entry inside the trash directory
+    pub name: String,
+    /// Original location of the item, if known
+    pub original_path: Option<String>,
+    pub is_dir: bool,
+    /// Size on disk in bytes
+    pub size: u64,
+    /// Deletion time in seconds since the Unix epoch, if recorded
+    pub deleted_at: Option<u64>,
+    /// Whether the item is kept when the trash is emptied or pruned
+    pub pinned: bool,
+    /// Name of the user who trashed the item, if recorded
+    pub user: Option<String>,
+    /// Name of the machine the item was trashed on, if recorded
+    pub hostname: Option<String>,
+}
+
+impl TrashEntry {
+    /// Who trashed the item and where, as `user@host`, if either is recorded
+    pub fn owner(&self) -> Option<String> {
+        format_owner(self.user.as_deref(), self.hostname.as_deref())
+    }
+}
+
+/// Where a trash keeps its items, as listed by `list_trash_entries`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashBackend {
+    /// The FreeDesktop.org trash shared with desktop file managers
+    Desktop,
+    /// The trash directory of `trs`, with items archived
+    Trs,
+}
+
+impl TrashBackend {
+    /// Name shown in listings
+    pub fn name(self) -> &'static str {
+        match self {
+            TrashBackend::Desktop => "desktop",
+            TrashBackend::Trs => "trs",
+        }
+    }
+}
+
+/// An item listed by `list_trash_entries`, with where it is kept
+#[derive(Debug, Clone, Serialize)]
+pub struct SourcedEntry {
+    #[serde(flatten)]
+    pub entry: TrashEntry,
+    pub backend: TrashBackend,
+    /// Trash directory holding the item
+    pub trash_dir: PathBuf,
+}
+
+/// An item listed with `missing_parent`, with the closest part of its original path that still exists
+#[derive(Debug, Clone, Serialize)]
+pub struct MissingParentEntry<T> {
+    #[serde(flatten)]
+    pub entry: T,
+    /// As found by `deepest_existing_ancestor`
+    pub deepest_existing_ancestor: Option<String>,
+}
+
+/// Items trashed from the same original path, as found by `duplicate_groups`
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub original_path: String,
+    /// The items, oldest first; the first is the original and the rest are duplicates
+    pub entries: Vec<TrashEntry>,
+}
+
+/// An item moved to the trash by `move_to_trash`
+#[derive(Debug, Clone, Serialize)]
+pub struct MovedItem {
+    /// Original location of the item
+    pub original_path: String,
+    /// Trash directory the item went to, a device trash with `device_trash`
+    pub trash_dir: PathBuf,
+    /// Name of the entry inside the trash directory
+    pub trash_name: String,
+    pub is_dir: bool,
+    /// Size on disk in bytes
+    pub size: u64,
+    /// Size in bytes before archiving
+    pub original_size: u64,
+    /// Paths inside the directory that could not be read, left out of the archive and in place
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+}
+
+/// An item restored by `restore_from_trash`
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoredItem {
+    /// Name of the entry the item was stored under in the trash
+    pub trash_name: String,
+    /// Location the item was restored to
+    pub path: String,
+    pub is_dir: bool,
+    /// Size in bytes of the entry in the trash
+    pub size: u64,
+    /// Files that were already there when the item was merged with `--merge`, and which copy was kept
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<MergeConflict>,
+    /// Paths that merging with `--merge` into a directory already there created or replaced
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub merged: Vec<String>,
+}
+
+/// An operation reversed by `undo_last`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "undone", rename_all = "lowercase")]
+pub enum Undone {
+    /// A move, undone by restoring the item
+    Move(RestoredItem),
+    /// A restore, undone by moving the item back to the trash
+    Restore(MovedItem),
+}
+
+/// What `empty_trash` deleted
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EmptySummary {
+    /// Names of the deleted entries
+    pub deleted: Vec<String>,
+    /// Total size on disk in bytes of the deleted entries
+    pub size: u64,
+}
+
+/// Summary of the trash directory
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashStatus {
+    pub trash_dir: PathBuf,
+    pub item_count: usize,
+    /// Total size on disk in bytes
+    pub total_size: u64,
+}
+
+/// Aggregate statistics about the trash
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TrashStats {
+    pub item_count: usize,
+    pub file_count: usize,
+    pub dir_count: usize,
+    /// Total size on disk in bytes
+    pub compressed_size: u64,
+    /// Total size in bytes before archiving of the items where it was recorded
+    pub original_size: u64,
+    /// Number of items trashed without a recorded original size
+    pub unknown_original_count: usize,
+    /// Earliest recorded deletion time in seconds since the Unix epoch
+    pub oldest: Option<u64>,
+    /// Latest recorded deletion time in seconds since the Unix epoch
+    pub newest: Option<u64>,
+    /// Largest entries by size on disk, largest first
+    pub largest: Vec<EntrySize>,
+}
+
+/// Name and size on disk of a trash entry
+#[derive(Debug, Clone, Serialize)]
+pub struct EntrySize {
+    pub name: String,
+    pub size: u64,
+}
+
+/// Full details of a single trash entry
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemInfo {
+    pub name: String,
+    pub original_path: Option<String>,
+    pub is_dir: bool,
+    /// Deletion time in seconds since the Unix epoch, if recorded
+    pub deleted_at: Option<u64>,
+    /// Size on disk in bytes
+    pub size: u64,
+    /// Storage format of the entry (`tar.gz`, `gzip` or `none`)
+    pub compression: String,
+    /// Paths stored inside the archive
+    pub contents: Vec<String>,
+    /// Paths that could not be read when the directory was trashed, and were left in place
+    pub skipped: Vec<String>,
+}
+
+/// Everything known about a single trash entry, as printed by `show_item_details`
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemDetails {
+    pub name: String,
+    /// Trash directory holding the entry
+    pub trash_dir: PathBuf,
+    pub original_path: Option<String>,
+    pub is_dir: bool,
+    /// Deletion time in seconds since the Unix epoch, if recorded
+    pub deleted_at: Option<u64>,
+    pub pinned: bool,
+    /// Name of the user who trashed the item, if recorded
+    pub user: Option<String>,
+    /// Name of the machine the item was trashed on, if recorded
+    pub hostname: Option<String>,
+    /// Storage format of the entry (`tar.gz`, `gzip` or `none`)
+    pub compression: String,
+    /// Size on disk in bytes
+    pub size: u64,
+    /// Size in bytes before the item was archived, if recorded
+    pub original_size: Option<u64>,
+    /// Number of files in the item, counting the item itself when it is a file
+    pub files: usize,
+    /// Number of directories below the top of the item
+    pub directories: usize,
+    /// Paths in the item starting with its own name, directories ending in `/`
+    pub contents: Vec<String>,
+    pub checksum: ChecksumStatus,
+    pub restore: RestoreStatus,
+}
+
+/// Result of checking the checksum stored with an entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum ChecksumStatus {
+    /// The gzip CRC-32, or zstd or LZ4 checksum, of the whole entry matches its contents
+    Verified,
+    /// The entry could not be read to the end or its CRC-32 does not match
+    Failed { reason: String },
+    /// The entry is stored uncompressed, without a checksum
+    Unavailable,
+}
+
+/// Whether an entry can be restored to its original location
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum RestoreStatus {
+    /// Nothing is at the original location
+    Ready { path: String },
+    /// Something already exists at the original location
+    Occupied { path: String },
+    /// The original location was not recorded, so it is restored to the current directory
+    Unknown,
+}
+
+/// What restoring an entry would write, as returned by `restore_preview`
+#[derive(Debug, Clone, Serialize)]
+pub struct RestorePreview {
+    pub name: String,
+    /// Trash directory holding the entry
+    pub trash_dir: PathBuf,
+    /// Path the entry would be restored to
+    pub destination: String,
+    pub is_dir: bool,
+    /// Whether something already exists at `destination`
+    pub occupied: bool,
+    /// Directories above `destination` that no longer exist, outermost first
+    pub missing_parents: Vec<String>,
+    /// Every path that would be written
+    pub contents: Vec<String>,
+    /// Paths in `contents` where something other than a directory already exists, which would be replaced
+    pub overwritten: Vec<String>,
+    /// Directories in `contents` that already exist, whose contents would be merged with the restored ones
+    pub merged: Vec<String>,
+    /// Total size in bytes of the files that would be written
+    pub total_size: u64,
+}
+
+/// A problem found while verifying the trash
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyIssue {
+    /// Name of the affected entry
+    pub name: String,
+    pub problem: String,
+}
+
+/// Trash directory for `path` in XDG mode
+///
+/// Follows the XDG trash spec: the `.Trash-$uid` directory at the root of
+/// the filesystem holding `path` when it exists and is writable, so the item
+/// is renamed rather than copied, otherwise the home trash in
+/// `$XDG_DATA_HOME/Trash`, and `global_trash` if the home trash cannot be
+/// created. Mount points are found by comparing device numbers, as for
+/// device trashes, rather than by reading `/proc/mounts`.
+pub fn find_trash_dir_for_path(path: &Path, global_trash: &Path) -> PathBuf {
+    xdg::mount_trash_files(path)
+        .or_else(|| xdg::home_trash_files().ok())
+        .unwrap_or_else(|| global_trash.to_path_buf())
+}
+
+/// Default trash directory inside the local data directory
+///
+/// On macOS that is `~/Library/Application Support`, which every application
+/// shares, so the trash goes in a `trs` directory there. A trash left at
+/// `Application Support/trash` by earlier versions is used until the new
+/// directory exists.
+pub fn default_trash_dir() -> PathBuf {
+    trash_dir_in(&dirs::data_local_dir().expect("Could not find local share directory"))
+}
+
+/// The default trash directory of the user whose local data directory is `data_dir`
+pub fn trash_dir_in(data_dir: &Path) -> PathBuf {
+    let legacy = data_dir.join("trash");
+    if !cfg!(target_os = "macos") {
+        return legacy;
+    }
+    let trash_dir = data_dir.join("trs").join("trash");
+    if !trash_dir.exists() && legacy.is_dir() {
+        legacy
+    } else {
+        trash_dir
+    }
+}
+
+/// Give the files written in `trash_dir` back to `user`, after running as root for them with `--user-trash`
+///
+/// The directories created for the trash below the home directory of
+/// `user`, the metadata and journal files and the archives are handed over
+/// when root owns them. Items renamed into the trash as they are keep the
+/// owner they had. Does nothing unless running as root.
+pub fn hand_over_trash(trash_dir: &Path, user: &owner::SudoUser) -> Result<()> {
+    if !owner::is_root() {
+        return Ok(());
+    }
+    let hand_over = |path: &Path| {
+        owner::hand_over(path, user).with_context(|| format!("giving {} to {}", path.display(), user.name))
+    };
+    for dir in trash_dir.ancestors().take_while(|dir| dir.starts_with(&user.home) && *dir != user.home) {
+        if owned_by_root(dir) {
+            hand_over(dir)?;
+        }
+    }
+    if !trash_dir.is_dir() {
+        return Ok(());
+    }
+    let metadata = load_items(&metadata_path(trash_dir))?;
+    for entry in fs::read_dir(trash_dir).with_context(|| format!("reading trash directory {}", trash_dir.display()))? {
+        let entry = entry.with_context(|| format!("reading trash directory {}", trash_dir.display()))?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let written = is_reserved_name(&name) || metadata.get(&name).is_some_and(|item| !item.raw);
+        if written && owned_by_root(&entry.path()) {
+            hand_over(&entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `path` exists and belongs to root, without following symbolic links
+#[cfg(unix)]
+fn owned_by_root(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.uid() == 0)
+}
+
+/// There is no root user on other systems
+#[cfg(not(unix))]
+fn owned_by_root(_path: &Path) -> bool {
+    false
+}
 
 /// Generate a unique filename for the trash by appending a number if necessary
 fn generate_unique_name(
@@ -19,552 +769,4404 @@ fn generate_unique_name(
     file_name: &str, 
     original_path: &str, 
     is_directory: bool,
-    metadata: &HashMap<String, (String, bool)>
+    metadata: &HashMap<String, TrashItem>
 ) -> String {
-    let file_stem = if file_name.ends_with(".tar.gz") {
-        file_name.trim_end_matches(".tar.gz")
-    } else if file_name.ends_with(".gz") {
-        file_name.trim_end_matches(".gz")
-    } else {
-        file_name
-    };
-    
     let original_path = Path::new(original_path);
     let mut unique_name = file_name.to_string();
     let mut counter = 1;
     
     // Check if file with this name already exists in trash and has the same type or comes from a different path
-    while trash_dir.join(&unique_name).exists() || 
-          metadata.iter().any(|(k, (v, item_is_dir))| {
-              k == &unique_name && (*item_is_dir == is_directory || Path::new(v) != original_path)
+    while trash_dir.join(&unique_name).exists() ||
+          archive_exists(trash_dir, &unique_name) ||
+          metadata.iter().any(|(k, item)| {
+              k == &unique_name && (item.is_dir == is_directory || Path::new(&item.path) != original_path)
           }) {
         // If it exists but has the same original path and type, it's not a duplicate
-        if metadata.iter().any(|(k, (v, item_is_dir))| {
-            k == &unique_name && *item_is_dir == is_directory && Path::new(v) == original_path
+        if metadata.iter().any(|(k, item)| {
+            k == &unique_name && item.is_dir == is_directory && Path::new(&item.path) == original_path
         }) {
             break;
         }
         
-        // Generate a new numbered name
-        if let Some(ext) = Path::new(file_stem).extension() {
-            let stem = Path::new(file_stem).file_stem().unwrap().to_string_lossy();
-            let ext_str = ext.to_string_lossy();
-            unique_name = format!("{}({}){}", stem, counter, if ext_str.is_empty() { "".to_string() } else { format!(".{}", ext_str) });
-        } else {
-            unique_name = format!("{}({})", file_stem, counter);
-        }
-        
-        // Add back extension if the original had it
-        if file_name.ends_with(".tar.gz") {
-            unique_name = format!("{}.tar.gz", unique_name);
-        } else if file_name.ends_with(".gz") {
-            unique_name = format!("{}.gz", unique_name);
-        }
-        
+        unique_name = numbered_name(file_name, counter);
         counter += 1;
     }
     
     unique_name
 }
 
-/// Move a file or directory to trash
-pub fn move_to_trash(file: &str, trash_dir: &Path) -> io::Result<()> {
-    fs::create_dir_all(trash_dir)?;
-    let file_path = Path::new(file);
-    
-    // Convert to absolute path
-    let absolute_path = fs::canonicalize(file_path)?;
-    let original_path = absolute_path.to_string_lossy().to_string();
-    
-    let file_name = file_path.file_name().unwrap().to_string_lossy();
-    let metadata_file = trash_dir.join(".metadata");
+/// Whether items in `trash_dir` are kept as they are rather than archived
+///
+/// XDG and native macOS trashes are shared with file managers, which expect
+/// the original files.
+fn keeps_items_as_is(trash_dir: &Path) -> bool {
+    xdg::trash_root(trash_dir).is_some() || macos::is_native_trash(trash_dir)
+}
 
-    // Load existing metadata and convert to new format if needed
-    let old_metadata = load_metadata(&metadata_file)?;
-    let mut metadata = convert_metadata_if_needed(&old_metadata);
-    
-    // Check if it's a directory
-    let is_directory = file_path.is_dir();
-    
-    // Generate a unique name for the trash file
-    let unique_name = generate_unique_name(trash_dir, &file_name, &original_path, is_directory, &metadata);
-    let trash_file = trash_dir.join(&unique_name);
-
-    // Create a progress bar
-    let pb = ProgressBar::new(100);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
-        .unwrap()
-        .progress_chars("#>-"));
-    pb.set_message(format!("Moving {} to Trash", file_name));
-
-    if file_path.is_file() {
-        // Update progress
-        pb.set_position(10);
-        
-        // Create a tar.gz archive for individual files
-        let trash_file_tar_gz = if !unique_name.ends_with(".tar.gz") { 
-            trash_file.with_extension("tar.gz") 
-        } else { 
-            trash_file
-        };
+/// First name based on `name` that is free in a trash keeping items as they are
+///
+/// In an XDG trash with root `xdg_root` the `.trashinfo` file must be free
+/// too, since other programs may have left either behind.
+fn unused_name(trash_dir: &Path, xdg_root: Option<&Path>, name: &str) -> String {
+    let taken = |candidate: &str| {
+        fs::symlink_metadata(trash_dir.join(candidate)).is_ok()
+            || xdg_root.is_some_and(|root| xdg::info_path(root, candidate).exists())
+    };
+    let mut unique_name = name.to_string();
+    let mut counter = 1;
+    while taken(&unique_name) {
+        unique_name = numbered_name(name, counter);
+        counter += 1;
+    }
+    unique_name
+}
 
-        // Create a tar archive and compress it with gzip
-        let tar_gz = fs::File::create(&trash_file_tar_gz)?;
-        let enc = GzEncoder::new(tar_gz, Compression::best());
-        let mut tar = Builder::new(enc);
-        
-        pb.set_position(30);
-        
-        // Add the file to the tar archive, preserving its name
-        tar.append_path_with_name(file_path, Path::new(&*file_name))?;
-        pb.set_position(70);
-        
-        tar.finish()?;
-        pb.set_position(90);
-        
-        // Delete the original file after successful archiving
-        fs::remove_file(file_path)?;
-        
-        let display_name = if unique_name == file_name.to_string() { 
-            file_name.to_string()
+/// First name based on `name` that is free for a file stored raw among archives
+///
+/// Archives are listed without their suffix, so the archive names must be
+/// free too.
+fn unused_raw_name(trash_dir: &Path, name: &str) -> String {
+    let taken = |candidate: &str| {
+        fs::symlink_metadata(trash_dir.join(candidate)).is_ok() || archive_exists(trash_dir, candidate)
+    };
+    let mut unique_name = name.to_string();
+    let mut counter = 1;
+    while taken(&unique_name) {
+        unique_name = numbered_name(name, counter);
+        counter += 1;
+    }
+    unique_name
+}
+
+/// Move `from` to `to`, copying and then deleting it across filesystems
+fn move_path(from: &Path, to: &Path) -> Result<()> {
+    move_path_with(from, to, |from, to| fs::rename(from, to))
+}
+
+/// [`move_path`], with `rename` tried first
+fn move_path_with(from: &Path, to: &Path, rename: impl Fn(&Path, &Path) -> io::Result<()>) -> Result<()> {
+    let moving = || format!("moving {} to {}", from.display(), to.display());
+    match retry_locked(|| rename(from, to)) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            if let Err(e) = copy_path(from, to) {
+                let _ = if to.is_dir() { fs::remove_dir_all(to) } else { fs::remove_file(to) };
+                return Err(e).with_context(moving);
+            }
+            let removed = retry_locked(|| if from.is_dir() { fs::remove_dir_all(from) } else { fs::remove_file(from) });
+            removed.with_context(|| format!("removing {}", from.display()))
+        }
+        result => result.with_context(moving),
+    }
+}
+
+/// Run `op` again a few times while another process has the file open
+///
+/// Windows refuses to rename or delete a file that is open elsewhere, for
+/// example while an indexer or virus scanner reads it, which usually only
+/// lasts a moment.
+fn retry_locked<T>(mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    const ATTEMPTS: u32 = 5;
+    const DELAY: Duration = Duration::from_millis(100);
+
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(e) if attempt < ATTEMPTS && is_locked(&e) => {
+                debug!(error = %e, attempt, "file in use by another process, retrying");
+                std::thread::sleep(DELAY * attempt);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether `e` is a Windows sharing or lock violation, caused by another process using the file
+fn is_locked(e: &io::Error) -> bool {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+
+    cfg!(windows) && matches!(e.raw_os_error(), Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION))
+}
+
+/// Copy a file, or a directory and everything in it
+fn copy_path(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(from)?.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+/// `file_name` with `(counter)` inserted before its extension
+///
+/// The archive suffix, or the `.gz` of the legacy format, is kept at the end.
+fn numbered_name(file_name: &str, counter: u32) -> String {
+    let suffix = ArchiveCompression::from_file_name(file_name)
+        .map(ArchiveCompression::suffix)
+        .or_else(|| file_name.ends_with(".gz").then_some(".gz"))
+        .unwrap_or("");
+    let file_stem = &file_name[..file_name.len() - suffix.len()];
+
+    let mut numbered = if let Some(ext) = Path::new(file_stem).extension() {
+        let stem = Path::new(file_stem).file_stem().unwrap().to_string_lossy();
+        let ext_str = ext.to_string_lossy();
+        format!("{}({}){}", stem, counter, if ext_str.is_empty() { "".to_string() } else { format!(".{}", ext_str) })
+    } else {
+        format!("{}({})", file_stem, counter)
+    };
+
+    // Add back extension if the original had it
+    numbered.push_str(suffix);
+    numbered
+}
+
+/// Extension of archives still being written, which are renamed to their final name once complete
+const PARTIAL_EXTENSION: &str = ".partial";
+
+/// Whether `name` is an archive still being written, or left by an interrupted move
+pub(crate) fn is_partial(name: &str) -> bool {
+    name.strip_suffix(PARTIAL_EXTENSION)
+        .is_some_and(|archive| ArchiveCompression::from_file_name(archive).is_some() || crypto::is_encrypted(archive))
+}
+
+/// The name an entry is listed under, without the suffix used to store it
+///
+/// That is the archive suffix, followed by `.enc` when encrypted, or the
+/// `.gz` of the legacy format.
+pub(crate) fn strip_storage_suffix(name: &str) -> &str {
+    let name = match crypto::is_encrypted(name) {
+        true => &name[..name.len() - crypto::ENCRYPTED_EXTENSION.len()],
+        false => name,
+    };
+    match ArchiveCompression::from_file_name(name) {
+        Some(compression) => &name[..name.len() - compression.suffix().len()],
+        None => name.strip_suffix(".gz").unwrap_or(name),
+    }
+}
+
+/// Path an archive is written to before it is complete
+fn partial_path(archive_path: &Path) -> PathBuf {
+    let mut partial = archive_path.as_os_str().to_os_string();
+    partial.push(PARTIAL_EXTENSION);
+    PathBuf::from(partial)
+}
+
+/// Flush a finished archive to disk and give it its final name in `trash_dir`
+///
+/// Writes only reach the page cache, so without this a crash shortly after
+/// the source is deleted could lose both copies. Until the rename, an
+/// interrupted move leaves only a `.partial` file, which is never taken for
+/// an item. Callers must finish before removing the source.
+///
+/// Space reserved by `create_archive` beyond what was written is given back first.
+fn finish_archive(mut archive: fs::File, partial_path: &Path, archive_path: &Path, trash_dir: &Path) -> Result<()> {
+    let written = archive.stream_position().with_context(|| format!("writing archive {}", partial_path.display()))?;
+    archive.set_len(written).with_context(|| format!("truncating archive {}", partial_path.display()))?;
+    archive.sync_all().with_context(|| format!("syncing archive {}", partial_path.display()))?;
+    fs::rename(partial_path, archive_path)
+        .with_context(|| format!("moving {} to {}", partial_path.display(), archive_path.display()))?;
+    sync_dir(trash_dir)
+}
+
+/// Remove the unfinished archive at `partial_path` if writing it failed, so none is left in the trash
+fn discard_partial<T>(partial_path: &Path, written: Result<T>) -> Result<T> {
+    if written.is_err() {
+        let _ = fs::remove_file(partial_path);
+    }
+    written
+}
+
+/// Remove `dir` and everything below it except the paths in `kept`, and the directories holding them
+fn remove_dir_except(dir: &Path, kept: &[&Path]) -> io::Result<()> {
+    if kept.is_empty() {
+        return fs::remove_dir_all(dir);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if kept.contains(&path.as_path()) {
+            continue;
+        }
+        if kept.iter().any(|kept| kept.starts_with(&path)) {
+            remove_dir_except(&path, kept)?;
+        } else if fs::symlink_metadata(&path)?.is_dir() {
+            fs::remove_dir_all(&path)?;
         } else {
-            format!("{} (as {})", file_name, unique_name.trim_end_matches(".tar.gz"))
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Paths in `path` that must be made writable before it can be removed, as `rm -f` would
+///
+/// On Unix those are the directories whose entries cannot be removed, and
+/// elsewhere read-only files and directories. Directories that cannot be
+/// listed are not looked into, since archiving leaves them in place or fails.
+fn read_only_paths(path: &Path, found: &mut Vec<PathBuf>) {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return;
+    };
+    if !metadata.is_dir() {
+        if cfg!(not(unix)) && blocks_removal(path) {
+            found.push(path.to_path_buf());
+        }
+        return;
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return;
+    };
+    if blocks_removal(path) {
+        found.push(path.to_path_buf());
+    }
+    for entry in entries.flatten() {
+        read_only_paths(&entry.path(), found);
+    }
+}
+
+/// Whether the entries of the directory `path` cannot be removed by the current user
+#[cfg(unix)]
+fn blocks_removal(path: &Path) -> bool {
+    !xdg::is_writable(path)
+}
+
+/// Whether the read-only attribute of `path` stops it from being removed
+#[cfg(not(unix))]
+fn blocks_removal(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.permissions().readonly())
+}
+
+/// Remove `file_path` with `remove` once it is archived to `archive_path`
+///
+/// If removing fails and `force` is set, the read-only paths left in
+/// `file_path` are made writable, as `rm -f` would, and removing is tried
+/// again. If it still fails, what was removed is put back from the archive
+/// and the archive is deleted. Fails with `ReadOnly` when a read-only path
+/// was in the way and `force` is not set.
+fn remove_archived(file_path: &Path, archive_path: &Path, options: &MoveOptions, remove: impl Fn() -> io::Result<()>) -> Result<()> {
+    let Err(error) = retry_locked(&remove) else {
+        return Ok(());
+    };
+    let mut read_only = Vec::new();
+    read_only_paths(file_path, &mut read_only);
+    let (error, made_writable) = if options.force && !read_only.is_empty() {
+        let made_writable = make_writable(&read_only);
+        match retry_locked(&remove) {
+            Ok(()) => return Ok(()),
+            Err(error) => (error, made_writable),
+        }
+    } else {
+        (error, Vec::new())
+    };
+    roll_back_removal(archive_path, file_path, options.encrypt.as_ref(), &made_writable)?;
+    match read_only.into_iter().next() {
+        Some(blocker) if !options.force => Err(TrsError::ReadOnly { path: file_path.to_path_buf(), blocker }),
+        _ => Err(error).with_context(|| format!("removing {}, left in place", file_path.display())),
+    }
+}
+
+/// Make each of `paths` writable for `--force`, returning the permissions they had
+///
+/// Only the owner's write bit is added on Unix. Paths that cannot be
+/// changed are left for removing them to fail.
+fn make_writable(paths: &[PathBuf]) -> Vec<(PathBuf, fs::Permissions)> {
+    let mut changed = Vec::new();
+    for path in paths {
+        let Ok(permissions) = fs::symlink_metadata(path).map(|metadata| metadata.permissions()) else {
+            continue;
         };
-        
-        pb.finish_with_message(format!("Moved file {} to Trash", display_name));
-        
-        // Update metadata with the actual trash name
-        let trash_name = trash_file_tar_gz.file_name().unwrap().to_string_lossy().to_string();
-        metadata.insert(trash_name, (original_path, false)); // false = file
-    } else if is_directory {
-        if file_path.read_dir()?.next().is_none() {
-            // Empty directory - just move it as is
-            pb.set_position(50);
-            
-            let trash_dir_path = trash_dir.join(&unique_name);
-            fs::rename(file_path, &trash_dir_path)?;
-            
-            pb.finish_with_message(format!("Moved empty directory {} to Trash", file_name));
-            
-            // Update metadata
-            metadata.insert(unique_name, (original_path, true)); // true = directory
-        } else {
-            // Non-empty directory - create a tar.gz archive
-            let trash_file_tar_gz = trash_file.with_extension("tar.gz");
-            
-            // Create a tar archive and compress it with gzip
-            let tar_gz = fs::File::create(&trash_file_tar_gz)?;
-            let enc = GzEncoder::new(tar_gz, Compression::best());
-            let mut tar = Builder::new(enc);
-            
-            // Define a base directory path for appending
-            let base_path = file_path;
-            
-            pb.set_position(20);
-            
-            // Add the directory itself first
-            tar.append_dir(file_path.file_name().unwrap(), file_path)?;
-            pb.set_position(30);
-            
-            // Recursive function to add directory contents to tar
-            fn add_dir_to_tar(
-                tar: &mut Builder<GzEncoder<fs::File>>,
-                dir: &Path,
-                base_path: &Path,
-                pb: &ProgressBar,
-            ) -> io::Result<()> {
-                for entry in fs::read_dir(dir)? {
-                    let entry = entry?;
-                    let path = entry.path();
-                    
-                    // Calculate the relative path from the base directory
-                    let rel_path = path.strip_prefix(base_path.parent().unwrap_or(Path::new("")))
-                        .unwrap_or(&path);
-                    
-                    if path.is_file() {
-                        tar.append_path_with_name(&path, rel_path)?;
-                        pb.inc(1); // Increment progress slightly for each file
-                    } else if path.is_dir() {
-                        // Create directory entry in the tar
-                        tar.append_dir(rel_path, &path)?;
-                        
-                        // Recursively add subdirectory contents
-                        add_dir_to_tar(tar, &path, base_path, pb)?;
+        let mut writable = permissions.clone();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            writable.set_mode(permissions.mode() | 0o200);
+        }
+        #[cfg(not(unix))]
+        writable.set_readonly(false);
+        match fs::set_permissions(path, writable) {
+            Ok(()) => changed.push((path.clone(), permissions)),
+            Err(e) => debug!(path = %path.display(), error = %e, "could not make writable"),
+        }
+    }
+    changed
+}
+
+/// Undo archiving `file_path` to `archive_path` after removing the original failed part way
+///
+/// What was already removed is extracted from the archive again, the
+/// permissions changed by `make_writable` are put back, and only then is
+/// the archive deleted, so the trash never holds an item that is also
+/// still in place. If the original cannot be completed, the archive is
+/// kept and the error says where.
+fn roll_back_removal(
+    archive_path: &Path,
+    file_path: &Path,
+    passphrase: Option<&Passphrase>,
+    made_writable: &[(PathBuf, fs::Permissions)],
+) -> Result<()> {
+    let parent = file_path.parent().unwrap_or(Path::new(""));
+    let putting_back = || format!("putting back {} from {}, which is kept", file_path.display(), archive_path.display());
+    let put_back = || -> io::Result<()> {
+        let file = fs::File::open(archive_path)?;
+        let reader: Box<dyn Read> = match passphrase {
+            Some(passphrase) if crypto::is_encrypted(&archive_path.to_string_lossy()) => Box::new(DecryptReader::new(file, passphrase)?),
+            _ => Box::new(file),
+        };
+        let mut archive = read_archive(archive_path, reader, DEFAULT_BUFFER_SIZE)?;
+        // Directories go last, deepest first, so their permissions cannot stop their contents being written
+        let mut directories = Vec::new();
+        for entry in archive.entries()? {
+            let entry = entry?;
+            if fs::symlink_metadata(parent.join(entry.path()?)).is_ok() {
+                continue;
+            }
+            if entry.header().entry_type().is_dir() {
+                directories.push(entry);
+            } else {
+                let mut entry = entry;
+                entry.unpack_in(parent)?;
+            }
+        }
+        directories.sort_by(|a, b| b.path_bytes().cmp(&a.path_bytes()));
+        for mut directory in directories {
+            directory.unpack_in(parent)?;
+        }
+        Ok(())
+    };
+    put_back().with_context(putting_back)?;
+    for (path, permissions) in made_writable {
+        if let Err(e) = fs::set_permissions(path, permissions.clone()) {
+            warn!(path = %path.display(), error = %e, "could not restore permissions");
+        }
+    }
+    fs::remove_file(archive_path).with_context(|| format!("removing {}", archive_path.display()))
+}
+
+/// Create the file an archive of `size` bytes of data is written to
+///
+/// Archives written a block at a time end up fragmented on filesystems
+/// such as ext4 and btrfs, so unless `prealloc` is off, space for
+/// `PREALLOC_PERCENT` of the data is reserved first. `finish_archive`
+/// truncates the file to what was written.
+fn create_archive(partial_path: &Path, size: u64, options: &MoveOptions) -> io::Result<fs::File> {
+    let file = fs::File::create(partial_path)?;
+    if options.prealloc {
+        preallocate(&file, size / 100 * PREALLOC_PERCENT);
+    }
+    Ok(file)
+}
+
+/// Share of the data of an item reserved for its archive, as a conservative guess at its compressed size
+const PREALLOC_PERCENT: u64 = 60;
+
+/// Reserve the first `len` bytes of `file` on disk
+///
+/// Failing to, as on filesystems without `fallocate`, is not an error:
+/// the archive is then written as it would be without.
+#[cfg(target_os = "linux")]
+fn preallocate(file: &fs::File, len: u64) {
+    use std::os::fd::AsRawFd;
+
+    let Ok(len) = libc::off_t::try_from(len) else {
+        return;
+    };
+    if len == 0 {
+        return;
+    }
+    // SAFETY: the descriptor belongs to `file`, which is open for writing
+    let status = unsafe { libc::posix_fallocate(file.as_raw_fd(), 0, len) };
+    if status != 0 {
+        debug!(error = %io::Error::from_raw_os_error(status), "could not preallocate archive");
+    }
+}
+
+/// Space is only reserved on Linux
+#[cfg(not(target_os = "linux"))]
+fn preallocate(_file: &fs::File, _len: u64) {}
+
+/// Names of the archives left in `trash_dir` by moves that were interrupted
+fn partial_archives(trash_dir: &Path) -> Result<Vec<String>> {
+    let reading = || format!("reading trash directory {}", trash_dir.display());
+    let mut names = Vec::new();
+    for entry in fs::read_dir(trash_dir).with_context(reading)? {
+        let name = entry.with_context(reading)?.file_name().to_string_lossy().to_string();
+        if is_partial(&name) {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+/// Make the entries of `dir` durable, so newly created files survive a crash
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<()> {
+    fs::File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .with_context(|| format!("syncing directory {}", dir.display()))
+}
+
+/// Directories cannot be opened for syncing on Windows, where NTFS journals
+/// the directory entries itself
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Identity of the file behind `metadata` if other hard links to it may exist
+#[cfg(unix)]
+fn hard_link_key(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+/// File identities are not exposed on stable Rust on Windows, so every
+/// link is archived as a copy there
+#[cfg(not(unix))]
+fn hard_link_key(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Hash of the data of the file at `path` and of its permissions, as hex
+///
+/// Archives of the same data differ in their headers, so deduplication
+/// compares files by this instead. The permissions are included because
+/// they are restored from the header of the archive that is shared.
+fn content_hash(path: &Path) -> io::Result<String> {
+    use blake2::{Blake2s256, Digest};
+
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Blake2s256::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        hasher.update(file.metadata()?.permissions().mode().to_le_bytes());
+    }
+    let mut buf = vec![0u8; DEFAULT_BUFFER_SIZE];
+    loop {
+        match file.read(&mut buf)? {
+            0 => break,
+            read => hasher.update(&buf[..read]),
+        }
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Store `unique_name` in `trash_dir` as a hard link to an archive of a file with the same hash and size
+///
+/// Encrypted archives are never shared, as each may have its own
+/// passphrase. Returns the path of the new link and the name of the entry
+/// it shares its archive with, or `None` if there is no such entry or the
+/// filesystem cannot link to it, in which case the file is archived as usual.
+fn link_identical(
+    trash_dir: &Path,
+    unique_name: &str,
+    hash: &str,
+    original_size: u64,
+    metadata: &HashMap<String, TrashItem>,
+) -> Option<(PathBuf, String)> {
+    let (existing, _) = metadata.iter().find(|(name, item)| {
+        item.content_hash.as_deref() == Some(hash)
+            && item.original_size == Some(original_size)
+            && !crypto::is_encrypted(name)
+            && trash_dir.join(name).is_file()
+    })?;
+    let suffix = &existing[strip_storage_suffix(existing).len()..];
+    let link = trash_dir.join(format!("{}{}", unique_name, suffix));
+    match fs::hard_link(trash_dir.join(existing), &link).and_then(|()| sync_dir(trash_dir).map_err(io::Error::other)) {
+        Ok(()) => Some((link, existing.clone())),
+        Err(e) => {
+            debug!(trash_name = %existing, error = %e, "could not link to an identical archive, archiving again");
+            let _ = fs::remove_file(&link);
+            None
+        }
+    }
+}
+
+/// Another entry in `trash_dir` whose stored file is a hard link to that of `name`, which must outlive it
+///
+/// Entries are only linked when their data is the same, but a link may
+/// have been broken since, leaving separate copies.
+fn shared_with<'a>(trash_dir: &Path, name: &str, metadata: &'a HashMap<String, TrashItem>) -> Option<&'a str> {
+    let hash = metadata.get(name)?.content_hash.as_deref()?;
+    metadata
+        .iter()
+        .find(|(other, item)| {
+            *other != name && item.content_hash.as_deref() == Some(hash) && same_file(&trash_dir.join(name), &trash_dir.join(other))
+        })
+        .map(|(other, _)| other.as_str())
+}
+
+/// Whether `a` and `b` are links to the same file
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => (a.dev(), a.ino()) == (b.dev(), b.ino()),
+        _ => false,
+    }
+}
+
+/// File identities are not exposed on stable Rust on Windows, so files
+/// that both exist are taken to be the same, keeping shared data readable
+#[cfg(not(unix))]
+fn same_file(a: &Path, b: &Path) -> bool {
+    a.is_file() && b.is_file()
+}
+
+/// Files up to this size are read ahead in parallel when archiving a directory; larger ones are streamed
+const READ_AHEAD_LIMIT: u64 = 256 * 1024;
+
+/// Number of paths read ahead at a time, which bounds the file contents held in memory
+const READ_AHEAD_PATHS: usize = 256;
+
+/// A path below a directory being archived
+struct ArchivedPath {
+    /// Location on disk
+    path: PathBuf,
+    /// Name in the archive, starting with the name of the archived directory
+    name: PathBuf,
+    is_dir: bool,
+}
+
+/// What is read of a file ahead of appending it to an archive
+struct ReadFile {
+    metadata: fs::Metadata,
+    /// Contents of the file, if it is small enough to hold and stored as a plain file
+    contents: Option<Vec<u8>>,
+}
+
+/// Append everything below the directory `dir` to `tar`, under names starting with its own
+///
+/// The tree is listed first, which only reads directories. Files are then
+/// read by the Rayon pool, a window of paths at a time, into a channel
+/// that this thread drains, appending each entry in listing order as soon
+/// as those before it are in. The archive is the same as one written entry
+/// by entry, and the compressor, which reports progress, is never left
+/// waiting on small files. Entries are appended between interruption
+/// checks, where the archive is merely incomplete.
+///
+/// Unless `strict` is set, files that cannot be read and directories that
+/// cannot be listed below `dir` are left out and returned, rather than
+/// failing the whole archive. Failing to write the archive is never skipped.
+fn append_dir_contents<W: Write>(tar: &mut Builder<W>, dir: &Path, strict: bool) -> Result<ArchivedDir> {
+    let mut paths = Vec::new();
+    let mut skipped = Vec::new();
+    archived_paths(dir, dir, &mut paths, (!strict).then_some(&mut skipped))?;
+    let mut links = HashMap::new();
+    // A pool thread waiting on the channel could hold up the reads queued behind it
+    if rayon::current_thread_index().is_some() {
+        for archived in &paths {
+            let read = (!archived.is_dir).then(|| read_ahead(&archived.path));
+            append_or_skip(tar, archived, read, &mut links, (!strict).then_some(&mut skipped))?;
+        }
+        return Ok(ArchivedDir::new(&paths, skipped));
+    }
+
+    for window in paths.chunks(READ_AHEAD_PATHS) {
+        let stop = AtomicBool::new(false);
+        rayon::in_place_scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            let stop = &stop;
+            scope.spawn(move |_| {
+                window.par_iter().enumerate().for_each_with(sender, |sender, (index, archived)| {
+                    if !stop.load(Ordering::Relaxed) {
+                        let _ = sender.send((index, (!archived.is_dir).then(|| read_ahead(&archived.path))));
+                    }
+                });
+            });
+
+            // Entries arrive in any order and wait for those before them
+            let mut pending = HashMap::new();
+            let mut next = 0;
+            let mut append = || -> Result<()> {
+                for (index, read) in &receiver {
+                    pending.insert(index, read);
+                    while let Some(read) = pending.remove(&next) {
+                        append_or_skip(tar, &window[next], read, &mut links, (!strict).then_some(&mut skipped))?;
+                        next += 1;
                     }
                 }
                 Ok(())
+            };
+            let appended = append();
+            // The rest of the window is not needed once appending fails
+            stop.store(appended.is_err(), Ordering::Relaxed);
+            appended
+        })?;
+    }
+    Ok(ArchivedDir::new(&paths, skipped))
+}
+
+/// What `append_dir_contents` archived of a directory
+struct ArchivedDir {
+    /// Number of files archived
+    files: u64,
+    /// Paths that could not be read, with why
+    skipped: Vec<(PathBuf, io::Error)>,
+}
+
+impl ArchivedDir {
+    fn new(paths: &[ArchivedPath], skipped: Vec<(PathBuf, io::Error)>) -> Self {
+        let listed = paths.iter().filter(|archived| !archived.is_dir).count();
+        let unread = skipped.iter().filter(|(path, _)| !path.is_dir()).count();
+        ArchivedDir { files: listed.saturating_sub(unread) as u64, skipped }
+    }
+
+    /// Why paths were skipped, such as "permission denied", each reason once
+    fn reasons(&self) -> String {
+        let mut reasons: Vec<String> = self.skipped.iter().map(|(_, e)| e.kind().to_string()).collect();
+        reasons.sort();
+        reasons.dedup();
+        reasons.join(", ")
+    }
+}
+
+/// Append `archived` as `append_archived` does, or add it to `skipped` if it could not be read
+///
+/// Without `skipped`, a file that cannot be read fails the archive.
+fn append_or_skip<W: Write>(
+    tar: &mut Builder<W>,
+    archived: &ArchivedPath,
+    read: Option<io::Result<ReadFile>>,
+    links: &mut HashMap<(u64, u64), PathBuf>,
+    skipped: Option<&mut Vec<(PathBuf, io::Error)>>,
+) -> Result<()> {
+    match (read, skipped) {
+        (Some(Err(e)), Some(skipped)) => {
+            warn!(path = %archived.path.display(), error = %e, "skipping unreadable file");
+            skipped.push((archived.path.clone(), e));
+            Ok(())
+        }
+        (read, _) => append_archived(tar, archived, read, links),
+    }
+}
+
+/// Paths below `dir` in the order they are archived, each directory before its contents
+///
+/// Names are relative to the parent of `base_path`. The tar crate joins
+/// their components with `/`, so archives created on Windows use the same
+/// separators as everywhere else. With `skipped`, directories below
+/// `base_path` that cannot be listed are added to it and archived empty.
+fn archived_paths(
+    dir: &Path,
+    base_path: &Path,
+    paths: &mut Vec<ArchivedPath>,
+    mut skipped: Option<&mut Vec<(PathBuf, io::Error)>>,
+) -> Result<()> {
+    let reading = || format!("reading directory {}", dir.display());
+    let entries = match (fs::read_dir(dir), skipped.as_deref_mut()) {
+        (Ok(entries), _) => entries,
+        (Err(e), Some(skipped)) if dir != base_path => {
+            warn!(path = %dir.display(), error = %e, "skipping unreadable directory");
+            skipped.push((dir.to_path_buf(), e));
+            return Ok(());
+        }
+        (Err(e), _) => return Err(e).with_context(reading),
+    };
+    for entry in entries {
+        crate::interrupt::check()?;
+        let path = entry.with_context(reading)?.path();
+        let name = path.strip_prefix(base_path.parent().unwrap_or(Path::new(""))).unwrap_or(&path).to_path_buf();
+        if path.is_file() {
+            paths.push(ArchivedPath { path, name, is_dir: false });
+        } else if path.is_dir() {
+            paths.push(ArchivedPath { path: path.clone(), name, is_dir: true });
+            archived_paths(&path, base_path, paths, skipped.as_deref_mut())?;
+        }
+    }
+    Ok(())
+}
+
+/// Read the metadata of the file at `path`, and its contents if it is small
+///
+/// Sparse files and files with other hard links are left to be streamed,
+/// as they may not be stored as plain data. Those are still opened, so a
+/// file that cannot be read fails here rather than while it is appended.
+fn read_ahead(path: &Path) -> io::Result<ReadFile> {
+    let metadata = fs::metadata(path)?;
+    let mut file = fs::File::open(path)?;
+    let contents = if metadata.len() <= READ_AHEAD_LIMIT && !sparse::is_sparse(&metadata) && hard_link_key(&metadata).is_none() {
+        let mut contents = Vec::with_capacity(metadata.len() as usize);
+        (&mut file).take(metadata.len()).read_to_end(&mut contents)?;
+        Some(contents)
+    } else {
+        None
+    };
+    Ok(ReadFile { metadata, contents })
+}
+
+/// Append the path `archived` to `tar`, with what was read of it if it is a file
+///
+/// Files hard-linked together are stored once, and later occurrences as
+/// link entries naming the first, found through `links`.
+fn append_archived<W: Write>(
+    tar: &mut Builder<W>,
+    archived: &ArchivedPath,
+    read: Option<io::Result<ReadFile>>,
+    links: &mut HashMap<(u64, u64), PathBuf>,
+) -> Result<()> {
+    // Between entries the archive is merely incomplete
+    crate::interrupt::check()?;
+    let (path, name) = (&archived.path, &archived.name);
+    let Some(read) = read else {
+        return tar.append_dir(name, path).with_context(|| format!("appending directory {} to archive", path.display()));
+    };
+    let appending = || format!("appending file {} to archive", path.display());
+    let ReadFile { metadata, contents } = read.with_context(appending)?;
+    let first = hard_link_key(&metadata).and_then(|key| match links.entry(key) {
+        Entry::Occupied(first) => Some(first.get().clone()),
+        Entry::Vacant(slot) => {
+            slot.insert(name.clone());
+            None
+        }
+    });
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata(&metadata);
+    if let Some(first) = first {
+        header.set_entry_type(tar::EntryType::Link);
+        header.set_size(0);
+        tar.append_link(&mut header, name, first).with_context(appending)
+    } else if let Some(contents) = contents {
+        // The file may have changed size since its metadata was read
+        header.set_size(contents.len() as u64);
+        tar.append_data(&mut header, name, contents.as_slice()).with_context(appending)
+    } else {
+        sparse::append_file(tar, path, name).with_context(appending)
+    }
+}
+
+/// Path of the archive stored for `unique_name` with `compression`, keeping its original extension
+fn archive_path(trash_dir: &Path, unique_name: &str, compression: ArchiveCompression) -> PathBuf {
+    if unique_name.ends_with(compression.suffix()) {
+        trash_dir.join(unique_name)
+    } else {
+        trash_dir.join(format!("{}{}", unique_name, compression.suffix()))
+    }
+}
+
+/// Whether an archive of any compression, encrypted or not, is stored for `unique_name`
+fn archive_exists(trash_dir: &Path, unique_name: &str) -> bool {
+    ArchiveCompression::ALL.into_iter().any(|compression| {
+        let path = archive_path(trash_dir, unique_name, compression);
+        path.exists() || encrypted_path(&path).exists()
+    })
+}
+
+/// Path of the archive a move with `options` stores for `unique_name`, encrypted or not
+fn stored_archive_path(trash_dir: &Path, unique_name: &str, options: &MoveOptions) -> PathBuf {
+    let path = archive_path(trash_dir, unique_name, options.compression);
+    if options.encrypt.is_some() { encrypted_path(&path) } else { path }
+}
+
+/// Compressor writing an archive to `file` as `options` ask
+fn archive_encoder<'a>(file: ArchiveWriter, options: &MoveOptions, progress: &'a dyn ProgressSink) -> io::Result<ArchiveEncoder<'a, ArchiveWriter>> {
+    ArchiveEncoder::new(file, options.compression, options.jobs, options.zstd_threads, options.buffer_size, progress)
+}
+
+/// Path of `archive_path` once encrypted, with `.enc` appended
+fn encrypted_path(archive_path: &Path) -> PathBuf {
+    let mut encrypted = archive_path.as_os_str().to_os_string();
+    encrypted.push(crypto::ENCRYPTED_EXTENSION);
+    PathBuf::from(encrypted)
+}
+
+/// Read the archive at `path` from `reader`, decompressing it as its name says
+///
+/// The `.enc` of an encrypted archive is left out, as `reader` decrypts it.
+/// An archive without a known suffix is read as gzip.
+fn read_archive<'a>(path: &Path, reader: impl Read + 'a, buffer_size: usize) -> io::Result<Archive<Box<dyn Read + 'a>>> {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let compression = encrypted_compression(&name);
+    let decoder = compression::archive_decoder(compression, BufReader::with_capacity(buffer_size, reader))?;
+    Ok(Archive::new(decoder))
+}
+
+/// Compression of the archive named `name`, whether or not it is encrypted, gzip if the name does not say
+fn encrypted_compression(name: &str) -> ArchiveCompression {
+    ArchiveCompression::from_file_name(name.trim_end_matches(crypto::ENCRYPTED_EXTENSION)).unwrap_or_default()
+}
+
+/// Open the archive at `path` with `read_archive`
+fn open_archive(path: &Path) -> io::Result<Archive<Box<dyn Read>>> {
+    read_archive(path, fs::File::open(path)?, DEFAULT_BUFFER_SIZE)
+}
+
+/// Remove the `(N)` counter that `generate_unique_name` adds on collisions
+fn strip_collision_suffix(name: &str) -> String {
+    fn strip(stem: &str) -> &str {
+        if let Some(inner) = stem.strip_suffix(')')
+            && let Some(open) = inner.rfind('(')
+            && open > 0
+            && inner[open + 1..].chars().all(|c| c.is_ascii_digit())
+            && open + 1 < inner.len()
+        {
+            return &stem[..open];
+        }
+        stem
+    }
+
+    let path = Path::new(name);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(ext)) => format!("{}.{}", strip(&stem.to_string_lossy()), ext.to_string_lossy()),
+        _ => strip(name).to_string(),
+    }
+}
+
+/// Reader that reports the bytes read through it
+struct ProgressReader<'a, R> {
+    inner: R,
+    progress: &'a dyn ProgressSink,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.progress.advance(read as u64);
+        Ok(read)
+    }
+}
+
+/// Add `record` to the journal of `trash_dir`
+///
+/// The operation has already happened, so a journal that cannot be written
+/// is only logged.
+fn record(trash_dir: &Path, record: JournalRecord) {
+    if let Err(e) = journal::append(trash_dir, record) {
+        warn!(error = %e, "could not update the journal");
+    }
+}
+
+/// Pass `result` through, telling `progress` about a failure
+fn reported<T>(progress: &dyn ProgressSink, result: Result<T>) -> Result<T> {
+    if let Err(e) = &result {
+        warn!(error = %e, "operation failed");
+        progress.error(&e.to_string());
+    }
+    result
+}
+
+/// Count the files inside a directory and add up their sizes
+///
+/// This is the walk that gives the progress bar its total before a
+/// directory is archived, and that decides whether it is large enough to
+/// ask about first. Directories below `dir` that cannot be read count as
+/// empty, leaving archiving to skip them or fail.
+pub fn dir_usage(dir: &Path) -> Result<DirUsage> {
+    let mut usage = DirUsage::default();
+    let reading = || format!("reading directory {}", dir.display());
+    for entry in fs::read_dir(dir).with_context(reading)? {
+        let path = entry.with_context(reading)?.path();
+        if path.is_file() {
+            usage.files += 1;
+            usage.bytes += fs::metadata(&path).with_context(|| format!("reading metadata of {}", path.display()))?.len();
+        } else if path.is_dir() {
+            match dir_usage(&path) {
+                Ok(inner) => {
+                    usage.files += inner.files;
+                    usage.bytes += inner.bytes;
+                }
+                Err(e) => debug!(path = %path.display(), error = %e, "could not measure directory"),
+            }
+        }
+    }
+    Ok(usage)
+}
+
+/// Expand a leading `~` or `~/` to the user's home directory
+pub fn expand_tilde(path: &str) -> PathBuf {
+    let rest = if path == "~" {
+        ""
+    } else if let Some(rest) = path.strip_prefix("~/") {
+        rest
+    } else {
+        return PathBuf::from(path);
+    };
+
+    match dirs::home_dir() {
+        Some(home) if rest.is_empty() => home,
+        Some(home) => home.join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Strip the `\\?\` prefix that `fs::canonicalize` adds on Windows
+///
+/// The verbatim form is kept for paths too long to be used without it.
+#[cfg(windows)]
+fn normalize_path(path: PathBuf) -> PathBuf {
+    const MAX_PATH: usize = 260;
+
+    let raw = path.to_string_lossy().into_owned();
+    if let Some(rest) = raw.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{}", rest))
+    } else if let Some(rest) = raw.strip_prefix(r"\\?\")
+        && rest.len() < MAX_PATH
+    {
+        PathBuf::from(rest)
+    } else {
+        path
+    }
+}
+
+/// Paths returned by `fs::canonicalize` need no normalization outside Windows
+#[cfg(not(windows))]
+fn normalize_path(path: PathBuf) -> PathBuf {
+    path
+}
+
+/// Check that a custom trash name can be used as a single trash entry
+fn validate_trash_name(name: &str) -> Result<&str> {
+    if name.is_empty() || name == "." || name == ".." || is_reserved_name(name) {
+        return Err(TrsError::invalid_input(format!("'{}' cannot be used as a trash name", name)));
+    }
+    if name.contains(['/', '\\']) {
+        return Err(TrsError::invalid_input(format!("Trash name '{}' must not contain path separators", name)));
+    }
+    Ok(name)
+}
+
+/// Refuse to move the trash, anything in it or anything containing it into the trash
+///
+/// Archiving a directory that contains the trash would also archive the
+/// archive being written. Both paths are canonical, so symbolic links such
+/// as `/var` to `/private/var` on macOS do not hide the overlap.
+fn check_outside_trash(file: &str, absolute_path: &Path, trash_dir: &Path) -> Result<()> {
+    let root = xdg::trash_root(trash_dir).unwrap_or(trash_dir);
+    let root = fs::canonicalize(root).map(normalize_path).with_context(|| format!("resolving {}", root.display()))?;
+    let overlap = if absolute_path == root {
+        "it is"
+    } else if absolute_path.starts_with(&root) {
+        "it is in"
+    } else if root.starts_with(absolute_path) {
+        "it contains"
+    } else {
+        return Ok(());
+    };
+    Err(TrsError::invalid_input(format!("Cannot move {} to Trash: {} the trash directory {}", file, overlap, root.display())))
+}
+
+/// Move a file or directory to trash, reporting the bytes archived to `progress`
+///
+/// If the original cannot be removed once it is archived, what was removed
+/// is put back from the archive and the archive is deleted. A directory
+/// holding read-only directories, whose entries cannot be removed, fails
+/// that way with `ReadOnly`, unless `force` is set to make them writable
+/// and try again as `rm -f` does.
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use std::fs;
+/// use std::os::unix::fs::PermissionsExt;
+/// use trs::metadata::{load_items, metadata_path};
+/// use trs::trash::{move_to_trash, MoveOptions};
+/// use trs::{NoOpProgress, TrsError};
+///
+/// let dir = std::env::temp_dir().join(format!("trs-read-only-doc-{}", std::process::id()));
+/// let trash_dir = dir.join("trash");
+/// let project = dir.join("project");
+/// fs::create_dir_all(project.join("locked")).unwrap();
+/// fs::write(project.join("locked").join("notes.txt"), "notes").unwrap();
+/// fs::set_permissions(project.join("locked"), fs::Permissions::from_mode(0o555)).unwrap();
+///
+/// // Root may remove anything, so only other users see the rest fail
+/// if !trs::owner::is_root() {
+///     let refused = move_to_trash(project.to_str().unwrap(), &trash_dir, &MoveOptions::default(), &NoOpProgress);
+///     assert!(matches!(refused, Err(TrsError::ReadOnly { .. })));
+///     assert!(project.join("locked").join("notes.txt").exists());
+///
+///     // A directory that cannot be taken out of its own is put back whole
+///     let shelf = dir.join("shelf");
+///     fs::create_dir_all(shelf.join("box")).unwrap();
+///     fs::write(shelf.join("box").join("letter.txt"), "letter").unwrap();
+///     fs::set_permissions(&shelf, fs::Permissions::from_mode(0o555)).unwrap();
+///     let stuck = move_to_trash(shelf.join("box").to_str().unwrap(), &trash_dir, &MoveOptions::default(), &NoOpProgress);
+///     assert!(stuck.is_err());
+///     assert_eq!(fs::read_to_string(shelf.join("box").join("letter.txt")).unwrap(), "letter");
+///     assert!(load_items(&metadata_path(&trash_dir)).unwrap().is_empty());
+///     assert!(fs::read_dir(&trash_dir).unwrap().all(|entry| !entry.unwrap().file_name().to_string_lossy().starts_with("box")));
+///     fs::set_permissions(&shelf, fs::Permissions::from_mode(0o755)).unwrap();
+/// }
+///
+/// let options = MoveOptions { force: true, ..MoveOptions::default() };
+/// let moved = move_to_trash(project.to_str().unwrap(), &trash_dir, &options, &NoOpProgress).unwrap();
+/// assert!(!project.exists());
+/// assert!(load_items(&metadata_path(&trash_dir)).unwrap().contains_key(&moved.trash_name));
+/// fs::remove_dir_all(&dir).unwrap();
+/// # }
+/// ```
+pub fn move_to_trash(file: &str, trash_dir: &Path, options: &MoveOptions, progress: &impl ProgressSink) -> Result<MovedItem> {
+    let moved = reported(progress, move_item(file, trash_dir, options, progress))?;
+    record(trash_dir, JournalRecord::Move {
+        trash_name: moved.trash_name.clone(),
+        trash_dir: moved.trash_dir.clone(),
+        original_path: moved.original_path.clone(),
+    });
+    Ok(moved)
+}
+
+fn move_item(file: &str, trash_dir: &Path, options: &MoveOptions, progress: &dyn ProgressSink) -> Result<MovedItem> {
+    let started = Instant::now();
+    let expanded_path = expand_tilde(file);
+    let file_path = expanded_path.as_path();
+    
+    // Convert to absolute path
+    let absolute_path = fs::canonicalize(file_path).map(normalize_path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => TrsError::SourceNotFound(file_path.to_path_buf()),
+        io::ErrorKind::PermissionDenied => TrsError::PermissionDenied(file.to_string()),
+        _ => e.into(),
+    })?;
+    let original_path = absolute_path.to_string_lossy().to_string();
+
+    // Keep the item on its own filesystem when asked
+    let device_trash = options.device_trash.then(|| device::device_trash_dir(&absolute_path, trash_dir)).flatten();
+    let trash_dir = device_trash.as_deref().unwrap_or(trash_dir);
+    fs::create_dir_all(trash_dir).with_context(|| format!("creating trash directory {}", trash_dir.display()))?;
+    check_outside_trash(file, &absolute_path, trash_dir)?;
+    
+    let file_name = file_path.file_name().unwrap().to_string_lossy();
+    let trash_name = match &options.name {
+        Some(name) => validate_trash_name(name)?,
+        None => &file_name,
+    };
+    let metadata_file = metadata_path(trash_dir);
+
+    // Load existing metadata and convert to new format if needed
+    let mut metadata = load_items(&metadata_file)?;
+    
+    // Check if it's a directory, and that it is not larger than expected
+    let is_directory = file_path.is_dir();
+    let usage = if is_directory { dir_usage(file_path)? } else { DirUsage::default() };
+    if is_directory && let Some(limits) = options.confirm_above && limits.exceeded_by(usage) {
+        return Err(TrsError::LargeDirectory { path: absolute_path, files: usage.files, bytes: usage.bytes });
+    }
+    // Kept for restoring, as copying the item across filesystems loses it
+    let modified_at = fs::metadata(file_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs());
+    
+    // Generate a unique name for the trash file
+    let xdg_root = xdg::trash_root(trash_dir);
+    let keep_as_is = keeps_items_as_is(trash_dir);
+    if keep_as_is && options.encrypt.is_some() {
+        return Err(TrsError::invalid_input(format!(
+            "Cannot encrypt {}: {} keeps items as they are for other programs",
+            file,
+            trash_dir.display()
+        )));
+    }
+    // With auto compression, files whose data is already compressed are
+    // stored as they are like small ones, or else with the quickest
+    // compression, and text gets zstd
+    let auto = (options.auto_compress && file_path.is_file())
+        .then(|| compression::auto_compression(&file_name, options.compression));
+    // Small files, and empty ones whatever the threshold, are stored raw,
+    // unless their name would be taken for an archive's or they are to be
+    // encrypted
+    let raw_allowed = !keep_as_is
+        && options.encrypt.is_none()
+        && file_path.is_file()
+        && !trash_name.ends_with(".gz")
+        && ArchiveCompression::from_file_name(trash_name).is_none()
+        && !crypto::is_encrypted(trash_name)
+        && !is_reserved_name(trash_name);
+    let store_raw = raw_allowed
+        && match fs::metadata(file_path).with_context(|| format!("reading metadata of {}", file_path.display()))?.len() {
+            0 => true,
+            len => len < options.compress_threshold || auto == Some(None),
+        };
+    // Unless compression or deduplication is asked for, other files are
+    // renamed into the trash as they are, which is only archived when it is
+    // on another filesystem. Only archives are shared between entries.
+    let renamed = if raw_allowed && !store_raw && !options.compress && !options.dedupe {
+        let unique_name = unused_raw_name(trash_dir, trash_name);
+        let target = trash_dir.join(&unique_name);
+        match retry_locked(|| fs::rename(file_path, &target)) {
+            Ok(()) => Some(unique_name),
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                debug!(path = %original_path, "trash is on another filesystem, archiving");
+                None
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("moving {} to {}", file_path.display(), target.display()));
+            }
+        }
+    } else {
+        None
+    };
+    let store_raw = store_raw || renamed.is_some();
+    let compression = match auto {
+        Some(Some(compression)) => compression,
+        Some(None) => ArchiveCompression::Lz4,
+        None => options.compression,
+    };
+    let options = &MoveOptions { compression, ..options.clone() };
+    // Archives are written in full before the original is removed, so
+    // running out of space part way would only waste the work
+    if options.check_space && !keep_as_is && !store_raw {
+        let needed = if is_directory {
+            usage.bytes
+        } else {
+            fs::metadata(file_path).with_context(|| format!("reading metadata of {}", file_path.display()))?.len()
+        };
+        let available = device::available_space(trash_dir).with_context(|| format!("checking free space for {}", trash_dir.display()))?;
+        if let Some(available) = available {
+            device::check_space(needed, available)?;
+        }
+    }
+    let unique_name = if let Some(renamed) = &renamed {
+        renamed.clone()
+    } else if keep_as_is {
+        unused_name(trash_dir, xdg_root, trash_name)
+    } else if store_raw {
+        unused_raw_name(trash_dir, trash_name)
+    } else {
+        generate_unique_name(trash_dir, trash_name, &original_path, is_directory, &metadata)
+    };
+    if unique_name != trash_name {
+        debug!(name = %trash_name, unique_name = %unique_name, "name taken in trash, using a numbered name");
+    }
+
+    if options.shred.is_some() {
+        if is_directory {
+            progress.message(&format!("Not overwriting {}: only files are shredded", file));
+        } else if keep_as_is || store_raw {
+            progress.message(&format!("Not overwriting {}: it is moved into the trash as it is, leaving no copy behind", file));
+        }
+    }
+
+    let stored_name = if keep_as_is || store_raw {
+        // Keep the item as it is, for other file managers or because it is
+        // too small to be worth archiving, described by a .trashinfo file in
+        // an XDG trash
+        let stored_path = trash_dir.join(&unique_name);
+        let current_path = if renamed.is_some() { stored_path.as_path() } else { file_path };
+        let original_size = if is_directory {
+            usage.bytes
+        } else {
+            fs::metadata(current_path).with_context(|| format!("reading metadata of {}", current_path.display()))?.len()
+        };
+        progress.start(original_size);
+
+        let item = TrashItem {
+            size: Some(original_size),
+            original_size: Some(original_size),
+            raw: store_raw,
+            ..TrashItem::new(original_path, is_directory)
+        };
+        if let Some(root) = xdg_root {
+            xdg::write_trashinfo(root, &unique_name, &item.path, item.deleted_at.unwrap_or_else(now_secs))?;
+        }
+        if renamed.is_none() && let Err(e) = move_path(file_path, &stored_path) {
+            if let Some(root) = xdg_root {
+                let _ = xdg::remove_trashinfo(root, &unique_name);
+            }
+            return Err(e);
+        }
+        progress.advance(original_size);
+
+        let kind = if is_directory { "directory" } else { "file" };
+        let stored = format!("{}, stored", format_size(original_size));
+        if unique_name == trash_name {
+            progress.finish(&format!("Moved {} {} to Trash ({})", kind, file_name, stored));
+        } else {
+            progress.finish(&format!("Moved {} {} (as {}) to Trash ({})", kind, file_name, unique_name, stored));
+        }
+        metadata.insert(unique_name.clone(), item);
+        unique_name
+    } else if file_path.is_file() {
+        let file_metadata = fs::metadata(file_path).with_context(|| format!("reading metadata of {}", file_path.display()))?;
+        let original_size = file_metadata.len();
+        let shredded = match &options.shred {
+            Some(shred) => shred_bytes(file_path, shred)?,
+            None => 0,
+        };
+        progress.start(original_size + shredded);
+
+        // A file already archived in the trash is linked to instead of archived again
+        let content_hash = match options.dedupe && options.encrypt.is_none() {
+            true => Some(content_hash(file_path).with_context(|| format!("hashing {}", file_path.display()))?),
+            false => None,
+        };
+        let shared = content_hash
+            .as_deref()
+            .and_then(|hash| link_identical(trash_dir, &unique_name, hash, original_size, &metadata));
+
+        let trash_file_tar_gz = match &shared {
+            Some((link, _)) => link.clone(),
+            None => {
+                // Create a tar.gz archive for individual files
+                let trash_file_tar_gz = stored_archive_path(trash_dir, &unique_name, options);
+                let partial = partial_path(&trash_file_tar_gz);
+
+                // Create a tar archive and compress it with gzip, removing it if that fails
+                let write_archive = || -> Result<()> {
+                    let creating = || format!("creating archive {}", partial.display());
+                    // The holes of sparse files are left out, so their length says little
+                    let data_size = if sparse::is_sparse(&file_metadata) { 0 } else { original_size };
+                    let tar_gz = create_archive(&partial, data_size, options)
+                        .and_then(|file| ArchiveWriter::new(file, options.encrypt.as_ref()))
+                        .with_context(creating)?;
+                    let enc = archive_encoder(tar_gz, options, progress).with_context(creating)?;
+                    let mut tar = Builder::new(enc);
+
+                    // Add the file to the tar archive, preserving its name
+                    sparse::append_file(&mut tar, file_path, Path::new(&*file_name))
+                        .with_context(|| format!("appending file {} to archive", file_path.display()))?;
+
+                    // Finalize the archive and make it durable before the original goes
+                    let archive = tar.into_inner().and_then(ArchiveEncoder::finish).and_then(ArchiveWriter::finish).with_context(|| {
+                        format!("writing archive {}", partial.display())
+                    })?;
+                    finish_archive(archive, &partial, &trash_file_tar_gz, trash_dir)
+                };
+                discard_partial(&partial, write_archive())?;
+                trash_file_tar_gz
+            }
+        };
+
+        // Overwrite the original only now, as the archive must hold its data.
+        // Failing to is not fatal: the data is safe and the file goes anyway.
+        if let Some(shred) = &options.shred {
+            let linked = fs::metadata(file_path).ok().and_then(|metadata| hard_link_key(&metadata)).is_some();
+            if linked {
+                progress.message(&format!("Not overwriting {}: other hard links to its data remain", file_path.display()));
+            } else if device::is_solid_state(file_path) == Some(true) {
+                progress.message(&format!(
+                    "Warning: {} is on an SSD, where overwriting it may leave its data on the drive",
+                    file_path.display()
+                ));
+            }
+            // Removing it could fail and put it back, which the archive must still be able to do
+            let stuck = cfg!(unix) && absolute_path.parent().is_some_and(blocks_removal);
+            if stuck {
+                progress.message(&format!("Not overwriting {}: its directory is read-only", file_path.display()));
+            }
+            if !linked && !stuck && let Err(e) = shred_file(file_path, shred, progress) {
+                progress.message(&format!("Warning: not overwriting {}: {}", file_path.display(), e));
+            }
+        }
+        
+        // Delete the original file after successful archiving, or delete the archive again if that fails
+        remove_archived(file_path, &trash_file_tar_gz, options, || fs::remove_file(file_path))?;
+        
+        let display_name = if unique_name == file_name { 
+            file_name.to_string()
+        } else {
+            format!("{} (as {})", file_name, strip_storage_suffix(&unique_name))
+        };
+        
+        let size = entry_size(&trash_file_tar_gz)?;
+        match &shared {
+            Some((_, existing)) => {
+                progress.advance(original_size);
+                progress.finish(&format!(
+                    "Moved file {} to Trash (same as {}, stored once)",
+                    display_name,
+                    strip_storage_suffix(existing)
+                ));
             }
+            None => progress.finish(&format!(
+                "Moved file {} to Trash ({}{})",
+                display_name,
+                format_ratio(original_size, size),
+                if options.encrypt.is_some() { ", encrypted" } else { "" }
+            )),
+        }
+        
+        // Update metadata with the actual trash name
+        let trash_name = trash_file_tar_gz.file_name().unwrap().to_string_lossy().to_string();
+        metadata.insert(trash_name.clone(), TrashItem {
+            size: Some(size),
+            original_size: Some(original_size),
+            content_hash,
+            ..TrashItem::new(original_path, false)
+        });
+        trash_name
+    } else if is_directory {
+        let is_empty = file_path.read_dir()
+            .with_context(|| format!("reading directory {}", file_path.display()))?
+            .next()
+            .is_none();
+        if is_empty {
+            // Empty directory - just move it as is
+            let trash_dir_path = trash_dir.join(&unique_name);
+            progress.start(0);
+            retry_locked(|| fs::rename(file_path, &trash_dir_path)).with_context(|| {
+                format!("moving {} to {}", file_path.display(), trash_dir_path.display())
+            })?;
             
-            // Add all contents
-            add_dir_to_tar(&mut tar, base_path, base_path, &pb)?;
+            if unique_name == file_name {
+                progress.finish(&format!("Moved empty directory {} to Trash", file_name));
+            } else {
+                progress.finish(&format!("Moved empty directory {} (as {}) to Trash", file_name, unique_name));
+            }
             
-            pb.set_position(80);
+            // Update metadata
+            metadata.insert(unique_name.clone(), TrashItem {
+                size: Some(0),
+                original_size: Some(0),
+                ..TrashItem::new(original_path, true)
+            });
+            unique_name
+        } else {
+            // Non-empty directory - create a tar.gz archive
+            let trash_file_tar_gz = stored_archive_path(trash_dir, &unique_name, options);
+            let partial = partial_path(&trash_file_tar_gz);
+            let original_size = usage.bytes;
+            progress.start(original_size);
             
-            // Finalize the archive
-            tar.finish()?;
+            // Create a tar archive and compress it with gzip. If that stops
+            // part way, the unfinished archive is removed and the directory
+            // is left as it was.
+            let write_archive = || -> Result<ArchivedDir> {
+                let creating = || format!("creating archive {}", partial.display());
+                let tar_gz = create_archive(&partial, original_size, options)
+                    .and_then(|file| ArchiveWriter::new(file, options.encrypt.as_ref()))
+                    .with_context(creating)?;
+                let enc = archive_encoder(tar_gz, options, progress).with_context(creating)?;
+                let mut tar = Builder::new(enc);
+
+                // Add the directory itself first, then all its contents
+                tar.append_dir(file_path.file_name().unwrap(), file_path)
+                    .with_context(|| format!("appending directory {} to archive", file_path.display()))?;
+                let archived = append_dir_contents(&mut tar, file_path, options.strict)?;
+
+                // Finalize the archive and make it durable before the original goes
+                let archive = tar.into_inner().and_then(ArchiveEncoder::finish).and_then(ArchiveWriter::finish).with_context(|| {
+                    format!("writing archive {}", partial.display())
+                })?;
+                finish_archive(archive, &partial, &trash_file_tar_gz, trash_dir)?;
+                Ok(archived)
+            };
+            let archived = discard_partial(&partial, write_archive())?;
             
-            pb.set_position(90);
+            // Remove the original directory after successful archiving, but
+            // not what was left out of the archive. If that fails part way,
+            // what was removed is put back and the archive deleted.
+            let kept: Vec<&Path> = archived.skipped.iter().map(|(path, _)| path.as_path()).collect();
+            remove_archived(file_path, &trash_file_tar_gz, options, || remove_dir_except(file_path, &kept))?;
             
-            // Remove the original directory after successful archiving
-            fs::remove_dir_all(file_path)?;
+            let display_name = if unique_name == file_name { 
+                file_name.to_string()
+            } else {
+                format!("{} (as {})", file_name, strip_storage_suffix(&unique_name))
+            };
+            
+            let size = entry_size(&trash_file_tar_gz)?;
+            let mut message = format!(
+                "Moved directory {} to Trash ({}{})",
+                display_name,
+                format_ratio(original_size, size),
+                if options.encrypt.is_some() { ", encrypted" } else { "" }
+            );
+            // Recorded by their original location, which `file_path` may only be relative to
+            let skipped: Vec<String> = archived.skipped.iter()
+                .map(|(path, _)| {
+                    let relative = path.strip_prefix(file_path).unwrap_or(path);
+                    Path::new(&original_path).join(relative).to_string_lossy().to_string()
+                })
+                .collect();
+            if !skipped.is_empty() {
+                message.push_str(&format!(
+                    "; archived {} files, skipped {} ({}), left in place: {}",
+                    format_count(archived.files),
+                    format_count(skipped.len() as u64),
+                    archived.reasons(),
+                    skipped.iter().map(|path| display_path(path)).collect::<Vec<_>>().join(", ")
+                ));
+            }
+            progress.finish(&message);
+            
+            // Update metadata
+            let trash_name = trash_file_tar_gz.file_name().unwrap().to_string_lossy().to_string();
+            metadata.insert(trash_name.clone(), TrashItem {
+                size: Some(size),
+                original_size: Some(original_size),
+                skipped,
+                ..TrashItem::new(original_path, true)
+            });
+            trash_name
+        }
+    } else {
+        return Err(TrsError::invalid_input(format!("{} is not a file or directory", file)));
+    };
+
+    if let Some(item) = metadata.get_mut(&stored_name) {
+        item.modified_at = modified_at;
+        item.reset_timestamps = options.timestamps == Timestamps::Reset;
+    }
+
+    // Save the updated metadata
+    save_items(&metadata_file, &metadata)?;
+    let item = &metadata[&stored_name];
+    info!(
+        path = %item.path,
+        trash_dir = %trash_dir.display(),
+        trash_name = %stored_name,
+        is_dir = item.is_dir,
+        size = item.size,
+        original_size = item.original_size,
+        duration_ms = started.elapsed().as_millis() as u64,
+        "moved"
+    );
+
+    let moved = MovedItem {
+        original_path: item.path.clone(),
+        trash_dir: trash_dir.to_path_buf(),
+        trash_name: stored_name,
+        is_dir: item.is_dir,
+        size: item.size.unwrap_or_default(),
+        original_size: item.original_size.unwrap_or_default(),
+        skipped: item.skipped.clone(),
+    };
+
+    // Evict the oldest items if the trash has grown past its quota
+    if let Some(max_size) = options.max_size {
+        for name in prune_to_size(trash_dir, max_size, options.min_age, Some(&moved.trash_name))? {
+            info!(trash_name = %name, max_size, "evicted");
+            progress.message(&format!("Evicted {} from Trash (over quota)", name));
+        }
+    }
+    Ok(moved)
+}
+
+/// Size on disk of a trash entry, recursing into raw directories
+pub fn entry_size(path: &Path) -> Result<u64> {
+    let metadata = fs::symlink_metadata(path).with_context(|| format!("reading metadata of {}", path.display()))?;
+    if metadata.is_dir() {
+        let reading = || format!("reading directory {}", path.display());
+        let mut total = 0;
+        for entry in fs::read_dir(path).with_context(reading)? {
+            total += entry_size(&entry.with_context(reading)?.path())?;
+        }
+        Ok(total)
+    } else {
+        Ok(metadata.len())
+    }
+}
+
+/// A one-line warning when the items in `trash_dir` take more than `threshold` bytes on disk
+///
+/// Suggests how to free space. A threshold of 0 never warns.
+///
+/// ```
+/// use trs::trash::capacity_warning;
+///
+/// let trash_dir = std::env::temp_dir().join(format!("trs-capacity-doc-{}", std::process::id()));
+/// std::fs::create_dir_all(&trash_dir).unwrap();
+/// std::fs::write(trash_dir.join("dump.sql"), vec![0; 4096]).unwrap();
+///
+/// let warning = capacity_warning(&trash_dir, 1024).unwrap().unwrap();
+/// assert!(warning.contains("4.0 KiB") && warning.contains("trs empty"));
+/// assert!(capacity_warning(&trash_dir, 1 << 20).unwrap().is_none());
+/// assert!(capacity_warning(&trash_dir, 0).unwrap().is_none());
+/// std::fs::remove_dir_all(&trash_dir).unwrap();
+/// ```
+pub fn capacity_warning(trash_dir: &Path, threshold: u64) -> Result<Option<String>> {
+    if threshold == 0 {
+        return Ok(None);
+    }
+    let usage = trash_usage(trash_dir)?;
+    Ok((usage > threshold).then(|| {
+        format!(
+            "Trash holds {}, over {}: free space with `trs empty` or `trs prune`, or set retention_days to expire old items",
+            format_size(usage),
+            format_size(threshold)
+        )
+    }))
+}
+
+/// Total size on disk of all items in the trash
+///
+/// Archives shared by several entries as hard links are counted once.
+pub fn trash_usage(trash_dir: &Path) -> Result<u64> {
+    if !trash_dir.exists() {
+        return Ok(0);
+    }
+
+    let reading = || format!("reading trash directory {}", trash_dir.display());
+    let mut total = 0;
+    let mut linked = HashSet::new();
+    for entry in fs::read_dir(trash_dir).with_context(reading)? {
+        let entry = entry.with_context(reading)?;
+        if is_reserved_name(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        let metadata = entry.metadata().with_context(|| format!("reading metadata of {}", entry.path().display()))?;
+        if metadata.is_file() && hard_link_key(&metadata).is_some_and(|key| !linked.insert(key)) {
+            continue;
+        }
+        total += entry_size(&entry.path())?;
+    }
+    Ok(total)
+}
+
+/// Simple xorshift generator used to produce overwrite data for shredding
+struct XorShift(u64);
+
+impl XorShift {
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            chunk.copy_from_slice(&self.0.to_le_bytes()[..chunk.len()]);
+        }
+    }
+}
+
+/// Overwrite the contents of a file in place according to `shred`
+///
+/// Files that cannot be opened for writing are skipped with a warning.
+fn shred_file(path: &Path, shred: &ShredOptions, progress: &dyn ProgressSink) -> Result<()> {
+    let mut file = match fs::OpenOptions::new().write(true).open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            progress.message(&format!("Warning: not overwriting {}: {}", path.display(), e));
+            return Ok(());
+        }
+    };
+    let overwriting = || format!("overwriting {}", path.display());
+    let len = file.metadata().with_context(overwriting)?.len();
+
+    let mut rng = XorShift(now_secs() ^ len ^ 0x9e37_79b9_7f4a_7c15);
+    let mut buf = vec![0u8; DEFAULT_BUFFER_SIZE];
+    let passes = shred.passes + usize::from(shred.zero);
+    for pass in 0..passes {
+        let zero_pass = shred.zero && pass == passes - 1;
+        file.seek(io::SeekFrom::Start(0)).with_context(overwriting)?;
+
+        let mut remaining = len;
+        while remaining > 0 {
+            let chunk = remaining.min(buf.len() as u64) as usize;
+            if zero_pass {
+                buf[..chunk].fill(0);
+            } else {
+                rng.fill(&mut buf[..chunk]);
+            }
+            file.write_all(&buf[..chunk]).with_context(overwriting)?;
+            remaining -= chunk as u64;
+            progress.advance(chunk as u64);
+        }
+        file.sync_all().with_context(overwriting)?;
+    }
+    Ok(())
+}
+
+/// Overwrite every file under `path`, recursing into raw directories
+fn shred_path(path: &Path, shred: &ShredOptions, progress: &dyn ProgressSink) -> Result<()> {
+    let metadata = fs::symlink_metadata(path).with_context(|| format!("reading metadata of {}", path.display()))?;
+    if metadata.is_dir() {
+        let reading = || format!("reading directory {}", path.display());
+        for entry in fs::read_dir(path).with_context(reading)? {
+            shred_path(&entry.with_context(reading)?.path(), shred, progress)?;
+        }
+    } else if metadata.is_file() {
+        shred_file(path, shred, progress)?;
+    }
+    Ok(())
+}
+
+/// Number of bytes that shredding `path` with `shred` will write
+fn shred_bytes(path: &Path, shred: &ShredOptions) -> Result<u64> {
+    Ok(entry_size(path)? * (shred.passes + usize::from(shred.zero)) as u64)
+}
+
+/// Permanently delete a single entry and its metadata
+fn remove_entry(trash_dir: &Path, name: &str, metadata: &mut HashMap<String, TrashItem>) -> Result<()> {
+    remove_entry_with(trash_dir, name, metadata, None, &NoOpProgress)
+}
+
+/// Permanently delete a single entry, overwriting its data first if `shred` is set
+fn remove_entry_with(
+    trash_dir: &Path,
+    name: &str,
+    metadata: &mut HashMap<String, TrashItem>,
+    shred: Option<&ShredOptions>,
+    progress: &dyn ProgressSink,
+) -> Result<()> {
+    let path = trash_dir.join(name);
+    // The data of an archive shared with another entry must stay readable for it
+    match (shred, shared_with(trash_dir, name, metadata)) {
+        (Some(_), Some(other)) => {
+            progress.message(&format!("Not overwriting {}: {} in the trash shares its data", name, other));
+        }
+        (Some(shred), None) => shred_path(&path, shred, progress)?,
+        (None, _) => {}
+    }
+    let removed = if path.is_dir() {
+        fs::remove_dir_all(&path)
+    } else {
+        fs::remove_file(&path)
+    };
+    removed.with_context(|| format!("removing {}", path.display()))?;
+    if let Some(root) = xdg::trash_root(trash_dir) {
+        xdg::remove_trashinfo(root, name)?;
+    }
+    metadata.remove(name);
+    Ok(())
+}
+
+/// Delete items whose recorded deletion time is older than `max_age`
+///
+/// Only metadata timestamps are consulted, so this is cheap when nothing has
+/// expired. Items without a recorded deletion time and pinned items are
+/// kept. Returns the names of the deleted entries.
+pub fn prune_older_than(trash_dir: &Path, max_age: Duration) -> Result<Vec<String>> {
+    let metadata_file = metadata_path(trash_dir);
+    let mut metadata = load_items(&metadata_file)?;
+
+    let cutoff = now_secs().saturating_sub(max_age.as_secs());
+    let mut expired = metadata
+        .iter()
+        .filter(|(_, item)| !item.pinned && item.deleted_at.is_some_and(|deleted_at| deleted_at < cutoff))
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    if expired.is_empty() {
+        return Ok(expired);
+    }
+    expired.sort();
+
+    for name in &expired {
+        if trash_dir.join(name).exists() {
+            remove_entry(trash_dir, name, &mut metadata)?;
+        } else {
+            metadata.remove(name);
+        }
+    }
+
+    save_items(&metadata_file, &metadata)?;
+    Ok(expired)
+}
+
+/// Delete the oldest items until the trash fits within `max_size` bytes
+///
+/// Items deleted less than `min_age` ago, pinned items and the entry named
+/// `keep` are never evicted. Returns the names of the evicted entries.
+pub fn prune_to_size(trash_dir: &Path, max_size: u64, min_age: Duration, keep: Option<&str>) -> Result<Vec<String>> {
+    let mut usage = trash_usage(trash_dir)?;
+    if usage <= max_size {
+        return Ok(Vec::new());
+    }
+
+    let metadata_file = metadata_path(trash_dir);
+    let mut metadata = load_items(&metadata_file)?;
+
+    // Items without a recorded deletion time are treated as the oldest
+    let cutoff = now_secs().saturating_sub(min_age.as_secs());
+    let native = macos::is_native_trash(trash_dir);
+    let mut candidates = fs::read_dir(trash_dir)
+        .with_context(|| format!("reading trash directory {}", trash_dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().into_string().unwrap_or_default())
+        .filter(|name| !(is_reserved_name(name) || native && macos::is_bookkeeping(name)) && Some(name.as_str()) != keep)
+        .filter(|name| !metadata.get(name).is_some_and(|item| item.pinned))
+        .map(|name| {
+            let deleted_at = metadata.get(&name).and_then(|item| item.deleted_at).unwrap_or(0);
+            (deleted_at, name)
+        })
+        .filter(|(deleted_at, _)| *deleted_at <= cutoff)
+        .collect::<Vec<_>>();
+    candidates.sort();
+
+    let mut evicted = Vec::new();
+    for (_, name) in candidates {
+        if usage <= max_size {
+            break;
+        }
+        // Removing an entry that shares its archive frees nothing
+        let size = match shared_with(trash_dir, &name, &metadata) {
+            Some(_) => 0,
+            None => entry_size(&trash_dir.join(&name))?,
+        };
+        remove_entry(trash_dir, &name, &mut metadata)?;
+        usage = usage.saturating_sub(size);
+        evicted.push(name);
+    }
+
+    save_items(&metadata_file, &metadata)?;
+    Ok(evicted)
+}
+
+/// Display contents of trash folder
+///
+/// Entries come from `list_trash_items` and are only formatted here. Age
+/// filters only use recorded timestamps.
+pub fn show_trash_contents(trash_dir: &Path, options: &ShowOptions) -> Result<()> {
+    if options.duplicates {
+        return show_duplicates(trash_dir, options);
+    }
+    if options.porcelain {
+        return show_porcelain(trash_dir, options);
+    }
+    if trash_dir.exists() {
+        let now = now_secs();
+        let entries: Vec<TrashEntry> = list_trash_items(trash_dir)?
+            .into_iter()
+            .filter(|entry| shows_entry(entry, now, options))
+            .collect();
+        let rows: Vec<_> = entries.iter().map(|entry| (trash_dir, entry, None)).collect();
+        print_listing(&rows, now, options);
+    } else {
+        // Try to create the trs-trash directory
+        match fs::create_dir_all(trash_dir) {
+            Ok(_) if options.no_header => {}
+            Ok(_) => {
+                println!("Trash folder created at: {}", trash_dir.display());
+                println!("Trash is empty.");
+            },
+            Err(e) => {
+                println!("Could not create trash folder at {}: {}", trash_dir.display(), e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print the groups of items trashed from the same original path
+///
+/// The oldest item of each group is marked as the original and the others
+/// as duplicates. For scripts, each item is one tab-separated record of its
+/// original location, name and mark.
+fn show_duplicates(trash_dir: &Path, options: &ShowOptions) -> Result<()> {
+    let groups = duplicate_groups(trash_dir)?;
+    let palette = Palette::new(options.color);
+    let mut out = io::stdout().lock();
+    let writing = || "writing to stdout".to_string();
+    for (index, group) in groups.iter().enumerate() {
+        if !options.porcelain {
+            if index > 0 {
+                writeln!(out).with_context(writing)?;
+            }
+            writeln!(out, "{}", display_path(&group.original_path)).with_context(writing)?;
+        }
+        for (position, entry) in group.entries.iter().enumerate() {
+            let mark = if position == 0 { "original" } else { "duplicate" };
+            if options.porcelain {
+                writeln!(out, "{}\t{}\t{}", escape_field(&group.original_path), escape_field(&entry.name), mark)
+                    .with_context(writing)?;
+                continue;
+            }
+            let (display_name, _, _) = get_entry_display_info(&entry.name, entry.is_dir, None);
+            let name = format!("{:<30}", display_name);
+            let name = if entry.is_dir { palette.directory(&name) } else { name };
+            let deleted = entry.deleted_at.map(format_timestamp);
+            let mut line = format!("  {} {:<9} {:<19}", name, mark, deleted.as_deref().unwrap_or("-"));
+            if options.size {
+                let size = format!("{:>12}", display_size(entry.size, options.bytes));
+                line.push_str(&format!(" {}", palette.size(&size, entry.size)));
+            }
+            writeln!(out, "{}", line.trim_end()).with_context(writing)?;
+        }
+    }
+    if groups.is_empty() && !options.no_header && !options.porcelain {
+        writeln!(out, "No duplicates.").with_context(writing)?;
+    }
+    Ok(())
+}
+
+/// Groups of items in `trash_dir` trashed from the same original path, sorted by that path
+///
+/// Items are ordered by deletion time, oldest first, with those whose time
+/// is unknown before the rest and ties broken by name. Items without a
+/// known original path are never duplicates.
+pub fn duplicate_groups(trash_dir: &Path) -> Result<Vec<DuplicateGroup>> {
+    let mut by_path: BTreeMap<String, Vec<TrashEntry>> = BTreeMap::new();
+    for entry in list_trash_items(trash_dir)? {
+        if let Some(path) = entry.original_path.clone() {
+            by_path.entry(path).or_default().push(entry);
+        }
+    }
+    Ok(by_path
+        .into_iter()
+        .filter(|(_, entries)| entries.len() > 1)
+        .map(|(original_path, mut entries)| {
+            entries.sort_by(|a, b| a.deleted_at.cmp(&b.deleted_at).then_with(|| a.name.cmp(&b.name)));
+            DuplicateGroup { original_path, entries }
+        })
+        .collect())
+}
+
+/// Print everything known about one entry, found with `lookup_entry`
+///
+/// The contents of the item are drawn as a tree at every depth when
+/// `options.tree` is set. Sizes follow `options.bytes`.
+pub fn show_item_details(trash_dir: &Path, name: &str, options: &ShowOptions) -> Result<()> {
+    let details = item_details(trash_dir, name)?;
+    let palette = Palette::new(options.color);
+    let unknown = || palette.unknown("Unknown");
+
+    println!("{:<18} {}", "Name:", details.name);
+    match &details.original_path {
+        Some(path) => println!("{:<18} {}", "Original Location:", display_path(path)),
+        None => println!("{:<18} {}", "Original Location:", unknown()),
+    }
+    println!("{:<18} {}", "Type:", if details.is_dir { "Directory" } else { "File" });
+    match details.deleted_at {
+        Some(deleted_at) => println!("{:<18} {}", "Deleted:", format_timestamp(deleted_at)),
+        None => println!("{:<18} {}", "Deleted:", unknown()),
+    }
+    if let Some(owner) = format_owner(details.user.as_deref(), details.hostname.as_deref()) {
+        println!("{:<18} {}", "Trashed By:", owner);
+    }
+    println!("{:<18} {}", "Pinned:", if details.pinned { "yes" } else { "no" });
+    println!("{:<18} {}", "Compression:", details.compression);
+    println!("{:<18} {}", "Compressed Size:", palette.size(&display_size(details.size, options.bytes), details.size));
+    match details.original_size {
+        Some(size) => println!("{:<18} {}", "Original Size:", palette.size(&display_size(size, options.bytes), size)),
+        None => println!("{:<18} {}", "Original Size:", unknown()),
+    }
+    println!("{:<18} {} file(s), {} directory(ies)", "Contents:", details.files, details.directories);
+    match &details.checksum {
+        ChecksumStatus::Verified => {
+            println!("{:<18} verified ({})", "Checksum:", ArchiveCompression::from_label(&details.compression).checksum())
+        }
+        ChecksumStatus::Failed { reason } => println!("{:<18} {}", "Checksum:", palette.unknown(&format!("failed: {}", reason))),
+        ChecksumStatus::Unavailable => println!("{:<18} not available, stored uncompressed", "Checksum:"),
+    }
+    match &details.restore {
+        RestoreStatus::Ready { path } => println!("{:<18} can be restored to {}", "Restore:", display_path(path)),
+        RestoreStatus::Occupied { path } => println!(
+            "{:<18} {}",
+            "Restore:",
+            palette.unknown(&format!("{} already exists; restore elsewhere with --to", display_path(path)))
+        ),
+        RestoreStatus::Unknown => println!("{:<18} to the current directory, as the original location is unknown", "Restore:"),
+    }
+
+    if options.tree && details.is_dir && !details.contents.is_empty() {
+        let paths: Vec<(PathBuf, bool)> = details.contents.iter()
+            .map(|path| (PathBuf::from(path), path.ends_with('/')))
+            .collect();
+        println!();
+        println!("{}", palette.directory(&format!("{}/", details.contents[0].trim_end_matches('/'))));
+        for line in draw_tree(&paths, None) {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Print the contents of an archived directory beneath its row in the listing
+///
+/// An unreadable archive is noted in place rather than ending the listing.
+fn print_archive_tree(archive_path: &Path, depth: usize, palette: &Palette) {
+    match archive_tree(archive_path, Some(depth)) {
+        Ok(lines) => {
+            for line in lines {
+                println!("{:<5} {}", "", line);
+            }
+        }
+        Err(e) => {
+            debug!(archive = %archive_path.display(), error = %e, "could not read archive");
+            println!("{:<5} {}", "", palette.unknown("(archive could not be read)"));
+        }
+    }
+}
+
+/// Whether `entry` passes the age, pinned and missing parent filters of `options`
+///
+/// Items without a known original location never have a missing parent, as
+/// they are restored to the current directory.
+fn shows_entry(entry: &TrashEntry, now: u64, options: &ShowOptions) -> bool {
+    within_age(entry.deleted_at, now, options.newer_than, options.older_than)
+        && (entry.pinned || !options.pinned)
+        && (!entry.is_dir || !options.files_only)
+        && (entry.is_dir || !options.dirs_only)
+        && (!options.missing_parent || entry.original_path.as_deref().is_some_and(parent_missing))
+}
+
+/// Whether the directory that held `original_path` no longer exists
+pub fn parent_missing(original_path: &str) -> bool {
+    !Path::new(original_path).parent().is_some_and(Path::exists)
+}
+
+/// The closest directory above `original_path` that still exists, if any
+///
+/// There is none when the whole path is gone, such as on a drive that is no
+/// longer attached on Windows.
+pub fn deepest_existing_ancestor(original_path: &str) -> Option<String> {
+    Path::new(original_path)
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+        .map(|ancestor| ancestor.to_string_lossy().to_string())
+}
+
+/// Print the rows of a listing, each with the trash directory holding it and the backend when merging several
+fn print_listing(rows: &[(&Path, &TrashEntry, Option<TrashBackend>)], now: u64, options: &ShowOptions) {
+    let palette = Palette::new(options.color);
+    let backends = rows.iter().any(|(_, _, backend)| backend.is_some());
+    for (index, (trash_dir, entry, backend)) in rows.iter().enumerate() {
+        if index == 0 && !options.no_header {
+            let mut header = format!("{:<5} {:<30}", "No.", "Name");
+            if backends {
+                header.push_str(&format!(" {:<7}", "Backend"));
+            }
+            if options.age {
+                header.push_str(&format!(" {:>6}", "Age"));
+            }
+            if options.size {
+                header.push_str(&format!(" {:>12}", "Size"));
+            }
+            if options.owner {
+                header.push_str(&format!(" {:<24}", "Owner"));
+            }
+            if options.missing_parent {
+                header.push_str(&format!(" {:<30}", "Existing Ancestor"));
+            }
+            println!("{} Original Location", header);
+        }
+
+        let (display_name, _, original_location) = get_entry_display_info(&entry.name, entry.is_dir, entry.original_path.as_deref());
+        let display_name = if entry.pinned { format!("[P] {}", display_name) } else { display_name };
+        // Pad before coloring, as escape sequences would count towards the width
+        let name = format!("{:<30}", display_name);
+        let name = if entry.is_dir { palette.directory(&name) } else { name };
+        let mut line = format!("{:<5} {}", index + 1, name);
+        if let Some(backend) = backend {
+            line.push_str(&format!(" {:<7}", backend.name()));
+        }
+        if options.age {
+            let age = entry.deleted_at.map(|deleted_at| format_age(now.saturating_sub(deleted_at)));
+            line.push_str(&format!(" {:>6}", age.as_deref().unwrap_or("-")));
+        }
+        if options.size {
+            let size = format!("{:>12}", display_size(entry.size, options.bytes));
+            line.push_str(&format!(" {}", palette.size(&size, entry.size)));
+        }
+        if options.owner {
+            match entry.owner() {
+                Some(owner) => line.push_str(&format!(" {:<24}", owner)),
+                None => line.push_str(&format!(" {}", palette.unknown(&format!("{:<24}", "-")))),
+            }
+        }
+        if options.missing_parent {
+            let ancestor = entry.original_path.as_deref().and_then(deepest_existing_ancestor);
+            match ancestor {
+                Some(ancestor) => line.push_str(&format!(" {:<30}", display_path(&ancestor))),
+                None => line.push_str(&format!(" {}", palette.unknown(&format!("{:<30}", "-")))),
+            }
+        }
+        if entry.original_path.is_none() {
+            println!("{} {}", line, palette.unknown(&original_location));
+        } else {
+            println!("{} {}", line, original_location);
+        }
+        if options.tree && entry.is_dir && ArchiveCompression::from_file_name(&entry.name).is_some() {
+            print_archive_tree(&trash_dir.join(&entry.name), options.depth, &palette);
+        }
+    }
+
+    if rows.is_empty() && !options.no_header {
+        if options.newer_than.is_some() || options.older_than.is_some() || options.pinned || options.missing_parent
+            || options.files_only || options.dirs_only
+        {
+            println!("No items match.");
+        } else {
+            println!("Trash is empty.");
+        }
+    }
+}
+
+/// Display the items of several trashes as one listing, with the backend holding each
+///
+/// Entries come from `list_trash_entries`, so an item found in more than
+/// one source is listed once. Trash directories that do not exist are not
+/// created. For scripts, the backend is added as a sixth field, before the
+/// deepest existing ancestor with `missing_parent`.
+pub fn show_merged_contents(sources: &[(PathBuf, TrashBackend)], options: &ShowOptions) -> Result<()> {
+    show_sourced_entries(&shown_entries(sources, options)?, true, options)
+}
+
+/// Display entries gathered from several trashes as one listing
+///
+/// With `backends`, the backend holding each entry is shown as a column
+/// and as the sixth field for scripts. Otherwise the sixth field is who
+/// trashed it, as `user@host`, for listings of several users' trashes.
+pub fn show_sourced_entries(entries: &[SourcedEntry], backends: bool, options: &ShowOptions) -> Result<()> {
+    if options.porcelain {
+        let mut out = io::stdout().lock();
+        for sourced in entries {
+            let entry = &sourced.entry;
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}{}",
+                escape_field(&entry.name),
+                if entry.is_dir { "dir" } else { "file" },
+                entry.size,
+                entry.deleted_at.map(|deleted_at| deleted_at.to_string()).unwrap_or_default(),
+                escape_field(entry.original_path.as_deref().unwrap_or_default()),
+                if backends { sourced.backend.name().to_string() } else { escape_field(&entry.owner().unwrap_or_default()) },
+                ancestor_field(entry, options),
+            )
+            .with_context(|| "writing to stdout".to_string())?;
+        }
+        return Ok(());
+    }
+    let rows: Vec<_> = entries.iter()
+        .map(|sourced| (sourced.trash_dir.as_path(), &sourced.entry, backends.then_some(sourced.backend)))
+        .collect();
+    print_listing(&rows, now_secs(), options);
+    Ok(())
+}
+
+/// Entries of several trashes, merged by `list_trash_entries`, that pass the age and pinned filters of `options`
+pub fn shown_entries(sources: &[(PathBuf, TrashBackend)], options: &ShowOptions) -> Result<Vec<SourcedEntry>> {
+    let now = now_secs();
+    Ok(list_trash_entries(sources)?
+        .into_iter()
+        .filter(|sourced| shows_entry(&sourced.entry, now, options))
+        .collect())
+}
+
+/// Print the trash for scripts: name, type, size, deletion time and original location
+///
+/// Records are sorted by name so the output is the same on every run, and
+/// unknown values are left empty. With `missing_parent`, the deepest
+/// existing ancestor is added as a last field. A missing trash directory is
+/// not created.
+fn show_porcelain(trash_dir: &Path, options: &ShowOptions) -> Result<()> {
+    let mut out = io::stdout().lock();
+    for entry in &shown_items(trash_dir, options)? {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}{}",
+            escape_field(&entry.name),
+            if entry.is_dir { "dir" } else { "file" },
+            entry.size,
+            entry.deleted_at.map(|deleted_at| deleted_at.to_string()).unwrap_or_default(),
+            escape_field(entry.original_path.as_deref().unwrap_or_default()),
+            ancestor_field(entry, options),
+        )
+        .with_context(|| "writing to stdout".to_string())?;
+    }
+    Ok(())
+}
+
+/// The deepest existing ancestor of `entry` as a trailing porcelain field, only with `missing_parent`
+fn ancestor_field(entry: &TrashEntry, options: &ShowOptions) -> String {
+    if !options.missing_parent {
+        return String::new();
+    }
+    let ancestor = entry.original_path.as_deref().and_then(deepest_existing_ancestor);
+    format!("\t{}", escape_field(ancestor.as_deref().unwrap_or_default()))
+}
+
+/// Entries that pass the age and pinned filters of `options`, sorted by name
+///
+/// A missing trash directory is not created.
+pub fn shown_items(trash_dir: &Path, options: &ShowOptions) -> Result<Vec<TrashEntry>> {
+    let now = now_secs();
+    let mut entries: Vec<TrashEntry> = list_trash_items(trash_dir)?
+        .into_iter()
+        .filter(|entry| shows_entry(entry, now, options))
+        .collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+/// Pass each entry of `trash_dir` that passes the filters of `options` to `each`, as it is read
+///
+/// Unlike `shown_items`, entries are not collected, so they come in the
+/// order the directory lists them rather than by name. Stops at the first
+/// error `each` returns. A missing trash directory is not created.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use trs::metadata::{metadata_path, save_items, TrashItem};
+/// use trs::trash::{stream_items, ShowOptions};
+///
+/// let trash_dir = std::env::temp_dir().join(format!("trs-stream-doc-{}", std::process::id()));
+/// std::fs::create_dir_all(&trash_dir).unwrap();
+/// let mut items = HashMap::new();
+/// for name in ["notes.txt", "todo.txt", "report.pdf"] {
+///     std::fs::write(trash_dir.join(name), name).unwrap();
+///     items.insert(name.to_string(), TrashItem::new(format!("/home/me/{}", name), false));
+/// }
+/// save_items(&metadata_path(&trash_dir), &items).unwrap();
+///
+/// // As written by `show --json-lines`, each line stands alone
+/// let mut out = Vec::new();
+/// stream_items(&trash_dir, &ShowOptions::default(), |entry| {
+///     out.extend(serde_json::to_vec(&entry)?);
+///     out.push(b'\n');
+///     Ok(())
+/// })
+/// .unwrap();
+/// let lines: Vec<serde_json::Value> = String::from_utf8(out).unwrap()
+///     .lines()
+///     .map(|line| serde_json::from_str(line).unwrap())
+///     .collect();
+/// assert_eq!(lines.len(), 3);
+/// for line in &lines {
+///     let name = line["name"].as_str().unwrap();
+///     assert_eq!(line["original_path"], format!("/home/me/{}", name));
+///     assert_eq!(line["is_dir"], false);
+/// }
+/// std::fs::remove_dir_all(&trash_dir).unwrap();
+/// ```
+pub fn stream_items(trash_dir: &Path, options: &ShowOptions, mut each: impl FnMut(TrashEntry) -> Result<()>) -> Result<()> {
+    let now = now_secs();
+    for entry in TrashIterator::new(trash_dir)? {
+        let entry = TrashEntry::try_from(entry?)?;
+        if shows_entry(&entry, now, options) {
+            each(entry)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether an item deleted at `deleted_at` passes the age filters
+///
+/// Items without a recorded deletion time never match a filter.
+fn within_age(deleted_at: Option<u64>, now: u64, newer_than: Option<Duration>, older_than: Option<Duration>) -> bool {
+    if newer_than.is_none() && older_than.is_none() {
+        return true;
+    }
+    let Some(deleted_at) = deleted_at else {
+        return false;
+    };
+    let age = now.saturating_sub(deleted_at);
+    newer_than.is_none_or(|limit| age < limit.as_secs()) && older_than.is_none_or(|limit| age > limit.as_secs())
+}
+
+/// Look up the metadata for an entry, allowing for legacy naming schemes
+pub(crate) fn find_item<'a, V>(metadata: &'a HashMap<String, V>, entry: &str) -> Option<&'a V> {
+    item_names(entry).iter().find_map(|name| metadata.get(name))
+}
+
+/// Names the metadata of `entry` may be stored under, in the order `find_item` tries them
+fn item_names(entry: &str) -> [String; 5] {
+    [
+        entry.to_string(),
+        entry.trim_end_matches(".tar.gz").to_string(),
+        entry.trim_end_matches(".gz").to_string(),
+        format!("{}.tar.gz", entry.trim_end_matches(".tar.gz")),
+        format!("{}.gz", entry.trim_end_matches(".gz")),
+    ]
+}
+
+/// Get display information for an entry
+fn get_entry_display_info(entry: &str, is_dir: bool, original_path: Option<&str>) -> (String, &'static str, String) {
+    // Get the type and display name
+    let display_name = if is_dir {
+        format!("{}/", strip_storage_suffix(entry))
+    } else {
+        strip_storage_suffix(entry).to_string()
+    };
+    
+    let item_type = if is_dir { "Directory" } else { "File" };
+    
+    // Get the original location
+    let original_location = original_path.map(display_path).unwrap_or(Cow::Borrowed("Unknown"));
+    
+    (display_name, item_type, original_location.into_owned())
+}
+
+/// Canonical path of the current directory
+fn current_dir() -> Result<PathBuf> {
+    env::current_dir()
+        .and_then(|dir| dir.canonicalize())
+        .with_context(|| "reading the current directory".to_string())
+}
+
+/// Fail with `ParentNotDirectory` if the nearest existing ancestor of `path` is not a directory
+///
+/// This happens when the original directory was replaced by a file.
+fn check_parent_dir(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent()
+        && let Some(blocker) = dir.ancestors().find(|ancestor| ancestor.exists())
+        && !blocker.is_dir()
+    {
+        return Err(TrsError::ParentNotDirectory { path: path.to_path_buf(), blocker: blocker.to_path_buf() });
+    }
+    Ok(())
+}
+
+/// Create the missing parent directories of `path`, where an item is being restored
+///
+/// Fails as `check_parent_dir` does when a file is in the way.
+fn create_parent_dir(path: &Path) -> Result<()> {
+    check_parent_dir(path)?;
+    match path.parent() {
+        Some(dir) => fs::create_dir_all(dir).with_context(|| format!("creating parent directory {}", dir.display())),
+        None => Ok(()),
+    }
+}
+
+/// Directories above `path` that do not exist, outermost first
+fn missing_parents(path: &Path) -> Vec<PathBuf> {
+    let mut missing: Vec<PathBuf> = path.ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .take_while(|dir| fs::symlink_metadata(dir).is_err())
+        .map(Path::to_path_buf)
+        .collect();
+    missing.reverse();
+    missing
+}
+
+/// Give the newly created directories `dirs` the permissions of `ancestor`, the deepest one that existed
+fn inherit_permissions(dirs: &[PathBuf], ancestor: &Path) -> Result<()> {
+    let permissions = fs::metadata(ancestor)
+        .with_context(|| format!("reading metadata of {}", ancestor.display()))?
+        .permissions();
+    for dir in dirs {
+        fs::set_permissions(dir, permissions.clone()).with_context(|| format!("setting permissions of {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Restore a file from trash, reporting the bytes read from the trash to `progress`
+///
+/// Members of an archive are never written outside the directory the item
+/// is restored to, even if the archive was tampered with:
+///
+/// ```
+/// use std::collections::HashMap;
+/// use std::fs;
+/// use flate2::{write::GzEncoder, Compression};
+/// use trs::metadata::{metadata_path, save_items, TrashItem};
+/// use trs::trash::{restore_from_trash, RestoreOptions};
+/// use trs::{NoOpProgress, TrsError};
+///
+/// let root = std::env::temp_dir().join(format!("trs-restore-doc-{}", std::process::id()));
+/// let trash_dir = root.join("trash");
+/// fs::create_dir_all(&trash_dir).unwrap();
+/// fs::create_dir_all(root.join("home")).unwrap();
+///
+/// // An archive of the directory `docs` with a member climbing out of it
+/// let mut header = tar::Header::new_old();
+/// let name = b"docs/../../escaped";
+/// header.as_old_mut().name[..name.len()].copy_from_slice(name);
+/// header.set_size(4);
+/// header.set_mode(0o644);
+/// header.set_cksum();
+/// let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+/// tar.append(&header, &b"evil"[..]).unwrap();
+/// fs::write(trash_dir.join("docs.tar.gz"), tar.into_inner().unwrap().finish().unwrap()).unwrap();
+/// let docs = root.join("home/docs").to_string_lossy().to_string();
+/// let items = HashMap::from([("docs.tar.gz".to_string(), TrashItem::new(docs, true))]);
+/// save_items(&metadata_path(&trash_dir), &items).unwrap();
+///
+/// let restored = restore_from_trash("docs.tar.gz", &trash_dir, &RestoreOptions::default(), &NoOpProgress);
+/// assert!(matches!(restored, Err(TrsError::ArchiveError { .. })));
+/// assert!(!root.join("escaped").exists());
+/// fs::remove_dir_all(&root).unwrap();
+/// ```
+pub fn restore_from_trash(file: &str, trash_dir: &Path, options: &RestoreOptions, progress: &impl ProgressSink) -> Result<RestoredItem> {
+    let restored = reported(progress, restore_item(file, trash_dir, options, progress))?;
+    record(trash_dir, JournalRecord::Restore {
+        trash_name: restored.trash_name.clone(),
+        trash_dir: trash_dir.to_path_buf(),
+        path: restored.path.clone(),
+        merged: restored.merged.clone(),
+    });
+    Ok(restored)
+}
+
+/// Reverse the newest operation in the journal of `trash_dir`
+///
+/// A move is undone by restoring the item to where it was, and a restore by
+/// moving the item back to the trash it came from; the record is then
+/// removed, so the next call undoes the operation before it. Permanent
+/// deletions cannot be undone and are reported as an error.
+pub fn undo_last(trash_dir: &Path, progress: &impl ProgressSink) -> Result<Undone> {
+    reported(progress, undo_item(trash_dir, progress))
+}
+
+fn undo_item(trash_dir: &Path, progress: &dyn ProgressSink) -> Result<Undone> {
+    let Some(last) = journal::read_journal(trash_dir)?.pop() else {
+        return Err(TrsError::invalid_input("Nothing to undo"));
+    };
+    let undone = match last {
+        JournalRecord::Move { trash_name, trash_dir: dir, .. } => {
+            // Putting the item back where it was includes the directories it was in
+            let options = RestoreOptions { create_parents: true, ..RestoreOptions::default() };
+            Undone::Move(restore_item(&trash_name, &dir, &options, progress)?)
+        }
+        JournalRecord::Restore { path, trash_dir: dir, merged, .. } if merged.is_empty() => {
+            Undone::Restore(move_item(&path, &dir, &MoveOptions::default(), progress)?)
+        }
+        JournalRecord::Restore { path, trash_dir: dir, merged, .. } => Undone::Restore(trash_merged(&path, &merged, &dir, progress)?),
+        JournalRecord::Delete { names } => {
+            return Err(TrsError::invalid_input(format!(
+                "The last operation permanently deleted {} and cannot be undone",
+                names.join(", ")
+            )));
+        }
+    };
+    journal::pop(trash_dir)?;
+    Ok(undone)
+}
+
+/// Move back to `trash_dir` the paths `merged` that a merged restore wrote into the directory `path`
+///
+/// Files that were in the directory before the restore stay. The merged
+/// paths are gathered under one raw directory entry, and the directories the
+/// restore created are removed once empty.
+fn trash_merged(path: &str, merged: &[String], trash_dir: &Path, progress: &dyn ProgressSink) -> Result<MovedItem> {
+    let target = Path::new(path);
+    fs::create_dir_all(trash_dir).with_context(|| format!("creating trash directory {}", trash_dir.display()))?;
+    let metadata_file = metadata_path(trash_dir);
+    let mut metadata = load_items(&metadata_file)?;
+    let xdg_root = xdg::trash_root(trash_dir);
+    let keep_as_is = keeps_items_as_is(trash_dir);
+    let name = target.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_else(|| path.to_string());
+    let unique_name = if keep_as_is { unused_name(trash_dir, xdg_root, &name) } else { unused_raw_name(trash_dir, &name) };
+    let stored = trash_dir.join(&unique_name);
+    fs::create_dir(&stored).with_context(|| format!("creating {}", stored.display()))?;
+
+    // Parents sort before what is in them, so each is in place before its contents
+    let mut merged: Vec<&Path> = merged.iter().map(Path::new).collect();
+    merged.sort();
+    let mut created = Vec::new();
+    let mut moved: Vec<(&Path, PathBuf)> = Vec::new();
+    for &from in &merged {
+        let Ok(existing) = fs::symlink_metadata(from) else { continue };
+        let to = stored.join(from.strip_prefix(target).unwrap_or(from));
+        let result = if existing.is_dir() {
+            created.push(from);
+            fs::create_dir_all(&to).with_context(|| format!("creating {}", to.display()))
+        } else {
+            create_parent_dir(&to).and_then(|()| move_path(from, &to))
+        };
+        if let Err(e) = result {
+            for (from, to) in moved.iter().rev() {
+                let _ = move_path(to, from);
+            }
+            let _ = fs::remove_dir_all(&stored);
+            return Err(e);
+        }
+        moved.push((from, to));
+    }
+    // Anything added to them since the restore keeps them in place
+    for dir in created.iter().rev() {
+        let _ = fs::remove_dir(dir);
+    }
+
+    let size = entry_size(&stored)?;
+    progress.start(size);
+    let item = TrashItem {
+        size: Some(size),
+        original_size: Some(size),
+        raw: !keep_as_is,
+        ..TrashItem::new(path.to_string(), true)
+    };
+    if let Some(root) = xdg_root {
+        xdg::write_trashinfo(root, &unique_name, &item.path, item.deleted_at.unwrap_or_else(now_secs))?;
+    }
+    metadata.insert(unique_name.clone(), item);
+    save_items(&metadata_file, &metadata)?;
+    progress.advance(size);
+    progress.finish(&format!("Moved what was merged into {} back to Trash ({}, stored)", path, format_size(size)));
+    Ok(MovedItem {
+        original_path: path.to_string(),
+        trash_dir: trash_dir.to_path_buf(),
+        trash_name: unique_name,
+        is_dir: true,
+        size,
+        original_size: size,
+        skipped: Vec::new(),
+    })
+}
+
+fn restore_item(file: &str, trash_dir: &Path, options: &RestoreOptions, progress: &dyn ProgressSink) -> Result<RestoredItem> {
+    let started = Instant::now();
+    let trash_file = trash_dir.join(file);
+    let metadata_file = metadata_path(trash_dir);
+    let mut metadata = load_items(&metadata_file)?;
+
+    // Items in an XDG trash are stored as they are, whatever their name
+    let xdg_root = xdg::trash_root(trash_dir);
+
+    let (original_location, is_dir) = restore_location(file, trash_dir, metadata.get(file), options)?;
+    let original_file = Path::new(&original_location);
+
+    // Bookkeeping files and unfinished archives are never items
+    if !trash_file.exists() || is_reserved_name(file) {
+        return Err(TrsError::NotInTrash(file.to_string()));
+    }
+    let size = entry_size(&trash_file)?;
+    let stored_raw = metadata.get(file).is_some_and(|item| item.raw);
+    let stored_as_is = keeps_items_as_is(trash_dir) || stored_raw;
+    let encrypted = trash_file.is_file() && !stored_as_is && crypto::is_encrypted(file);
+    if encrypted && options.passphrase.is_none() {
+        return Err(TrsError::invalid_input(format!("{} is encrypted; a passphrase is needed to restore it", file)));
+    }
+    if options.merge.is_some() && !is_dir {
+        return Err(TrsError::invalid_input(format!("{} is not a directory; only directories can be merged", file)));
+    }
+    // A path recorded on another machine of a shared trash may mean nothing here
+    if options.destination.is_none()
+        && let Some(hostname) = metadata.get(file).and_then(|item| item.hostname.as_deref())
+        && owner::hostname().is_some_and(|local| local != hostname)
+    {
+        warn!(trash_name = %file, hostname, "restoring an item trashed on another host");
+        progress.message(&format!(
+            "{} was trashed on {}; its original location {} may not be meant for this machine",
+            file, hostname, original_location
+        ));
+    }
+    let mut conflicts = Vec::new();
+    let mut merged = Vec::new();
+    let merging = options.merge.is_some() && original_file.is_dir();
+
+    // Only recreate the directories the item was in when asked to, and
+    // never where a file has taken the place of one
+    check_parent_dir(original_file)?;
+    let missing = missing_parents(original_file);
+    if let Some(top) = missing.first()
+        && !options.create_parents
+    {
+        return Err(TrsError::ParentMissing { path: original_file.to_path_buf(), missing: top.clone() });
+    }
+    progress.start(size);
+    create_parent_dir(original_file)?;
+    if let Some(ancestor) = missing.first().and_then(|top| top.parent()) {
+        inherit_permissions(&missing, ancestor)?;
+    }
+    for dir in &missing {
+        progress.message(&format!("Created missing directory {}", dir.display()));
+    }
+
+    let message = if trash_file.is_file() && stored_as_is {
+        move_path(&trash_file, original_file)?;
+        progress.advance(size);
+        format!("Restored file {} from Trash", file)
+    } else if trash_file.is_file() {
+        // Encrypted archives are decrypted as they are read, then extracted like any other
+        let file_stem = strip_storage_suffix(file);
+        let reading = || format!("reading {}", trash_file.display());
+        let reader = ProgressReader { inner: fs::File::open(&trash_file).with_context(reading)?, progress };
+        let reader: Box<dyn Read + '_> = match &options.passphrase {
+            Some(passphrase) if encrypted => Box::new(DecryptReader::new(reader, passphrase).with_context(reading)?),
+            _ => Box::new(reader),
+        };
+        
+        // Handle different file types
+        let message = if encrypted || ArchiveCompression::from_file_name(file).is_some() {
+            let relocated = options.destination.is_some();
+            let root = if relocated { original_file } else { original_file.parent().unwrap_or(Path::new(".")) };
+            // Every member is checked before any is written, so a refused archive leaves nothing behind
+            if is_dir {
+                let passphrase = options.passphrase.as_ref().filter(|_| encrypted);
+                check_members(&trash_file, file, passphrase, options.buffer_size, |member| {
+                    check_inside(file, &member_destination(member, original_file, true, relocated), root)
+                })?;
+            }
+
+            // Extract the archive
+            let mut archive = read_archive(&trash_file, reader, options.buffer_size).with_context(reading)?;
+            
+            // Directories restored elsewhere or merged are extracted entry by entry
+            if is_dir && (relocated || options.merge.is_some()) {
+                // Members not extracted because the file already there was kept
+                let mut kept = HashSet::new();
+                for entry in archive.entries().with_context(reading)? {
+                    let mut entry = entry.with_context(reading)?;
+                    let entry_path = entry.path().with_context(reading)?.into_owned();
+                    check_member(file, &entry_path)?;
+                    let target = member_destination(&entry_path, original_file, true, relocated);
+                    check_inside(file, &target, root)?;
+                    let extracting = || format!("extracting {} to {}", entry_path.display(), target.display());
+                    if let Some(preference) = options.merge
+                        && let Ok(existing) = fs::symlink_metadata(&target)
+                    {
+                        // Directories already there are kept as they are and filled in
+                        if existing.is_dir() {
+                            if !entry.header().entry_type().is_dir() {
+                                conflicts.push(MergeConflict { path: target.to_string_lossy().to_string(), replaced: false });
+                                kept.insert(entry_path);
+                            }
+                            continue;
+                        }
+                        // A file cannot be merged with a directory
+                        if entry.header().entry_type().is_dir() {
+                            return Err(TrsError::AlreadyExists(target.to_string_lossy().to_string()));
+                        }
+                        let replaced = preference.replaces(entry.header().mtime().unwrap_or(0), modified_secs(&existing));
+                        conflicts.push(MergeConflict { path: target.to_string_lossy().to_string(), replaced });
+                        if !replaced {
+                            kept.insert(entry_path);
+                            continue;
+                        }
+                        fs::remove_file(&target).with_context(extracting)?;
+                    }
+                    create_parent_dir(&target)?;
+                    let source = link_source(&entry).with_context(reading)?;
+                    if let Some(source) = &source {
+                        check_member(file, source)?;
+                    }
+                    match source {
+                        // The data of the first link is still in the archive
+                        Some(source) if kept.contains(&source) && !encrypted => {
+                            extract_archived(&trash_file, &source, &target, options.buffer_size).with_context(extracting)?;
+                        }
+                        // The first link was extracted under the destination too
+                        Some(source) => {
+                            let source = member_destination(&source, original_file, true, relocated);
+                            fs::hard_link(&source, &target).with_context(extracting)?;
+                        }
+                        None => {
+                            entry.unpack(&target).with_context(extracting)?;
+                        }
+                    }
+                    if merging {
+                        merged.push(target.to_string_lossy().to_string());
+                    }
+                }
+                format!("Restored directory {} from Trash", file_stem)
+            } else if is_dir {
+                // Extract to parent directory
+                let parent = original_file.parent().unwrap_or(Path::new("."));
+                unpack_contained(&mut archive, file, parent)?;
+                format!("Restored directory {} from Trash", file_stem)
+            } else {
+                // For single files, extract just that file to its correct location
+                if let Some(entry) = archive.entries().with_context(reading)?.next() {
+                    // If it's a single file, extract with the correct name
+                    entry.with_context(reading)?
+                        .unpack(original_file)
+                        .with_context(|| format!("extracting {} to {}", file, original_file.display()))?;
+                }
+                format!("Restored file {} from Trash", file_stem)
+            }
+        } else if file.ends_with(".gz") {
+            // Handle legacy .gz format for backward compatibility
+            let reader = BufReader::with_capacity(options.buffer_size, reader);
+            let mut decoder = GzDecoder::new(reader);
+            
+            // Stream the decompressed content instead of holding it in memory
+            let extracting = || format!("extracting {} to {}", file, original_file.display());
+            let mut writer = io::BufWriter::with_capacity(
+                options.buffer_size,
+                fs::File::create(original_file).with_context(extracting)?,
+            );
+            io::copy(&mut decoder, &mut writer).with_context(extracting)?;
+            writer.flush().with_context(extracting)?;
+            format!("Restored file {} from Trash", file_stem)
+        } else {
+            // Just copy the file as is (no compression)
+            progress.advance(fs::copy(&trash_file, original_file).with_context(|| {
+                format!("copying {} to {}", trash_file.display(), original_file.display())
+            })?);
+            format!("Restored file {} from Trash", file_stem)
+        };
+        
+        // Delete the trash file
+        retry_locked(|| fs::remove_file(&trash_file)).with_context(|| format!("removing {}", trash_file.display()))?;
+        message
+    } else if is_dir {
+        // For raw directory (not archived), just move it back
+        match options.merge {
+            Some(preference) if original_file.exists() => {
+                (conflicts, merged) = merge_stored_dir(&trash_file, original_file, preference)?;
+            }
+            _ => move_path(&trash_file, original_file)?,
+        }
+        format!("Restored directory {} from Trash", file)
+    } else {
+        return Err(TrsError::NotInTrash(file.to_string()));
+    };
+
+    if let Some(item) = metadata.get(file) {
+        apply_timestamps(original_file, item);
+    }
+
+    // Update metadata
+    if let Some(root) = xdg_root {
+        xdg::remove_trashinfo(root, file)?;
+    }
+    metadata.remove(file);
+    save_items(&metadata_file, &metadata)?;
+    info!(
+        trash_name = %file,
+        path = %original_location,
+        is_dir,
+        size,
+        duration_ms = started.elapsed().as_millis() as u64,
+        "restored"
+    );
+    progress.finish(&message);
+    Ok(RestoredItem {
+        trash_name: file.to_string(),
+        path: original_location,
+        is_dir,
+        size,
+        conflicts,
+        merged,
+    })
+}
+
+/// Move the directory `stored` in the trash into the existing directory `target`, file by file
+///
+/// Files only in `stored` are moved in, and those in both are decided by
+/// `preference`. What is left of `stored` is then removed. Returns the
+/// conflicts and the paths created or replaced.
+fn merge_stored_dir(stored: &Path, target: &Path, preference: MergePreference) -> Result<(Vec<MergeConflict>, Vec<String>)> {
+    let mut paths = Vec::new();
+    walk_paths(stored, target.to_path_buf(), &mut paths)?;
+    let mut conflicts = Vec::new();
+    let mut merged = Vec::new();
+    for (path, is_dir) in paths {
+        let source = stored.join(path.strip_prefix(target).unwrap_or(&path));
+        let Ok(existing) = fs::symlink_metadata(&path) else {
+            if is_dir {
+                fs::create_dir(&path).with_context(|| format!("creating {}", path.display()))?;
+            } else {
+                move_path(&source, &path)?;
+            }
+            merged.push(path.to_string_lossy().to_string());
+            continue;
+        };
+        if existing.is_dir() {
+            if !is_dir {
+                conflicts.push(MergeConflict { path: path.to_string_lossy().to_string(), replaced: false });
+            }
+            continue;
+        }
+        if is_dir {
+            return Err(TrsError::AlreadyExists(path.to_string_lossy().to_string()));
+        }
+        let trashed = fs::symlink_metadata(&source).with_context(|| format!("reading metadata of {}", source.display()))?;
+        let replaced = preference.replaces(modified_secs(&trashed), modified_secs(&existing));
+        conflicts.push(MergeConflict { path: path.to_string_lossy().to_string(), replaced });
+        if replaced {
+            fs::remove_file(&path).with_context(|| format!("removing {}", path.display()))?;
+            move_path(&source, &path)?;
+            merged.push(path.to_string_lossy().to_string());
+        }
+    }
+    retry_locked(|| fs::remove_dir_all(stored)).with_context(|| format!("removing {}", stored.display()))?;
+    Ok((conflicts, merged))
+}
+
+/// Modification time of a file in seconds since the Unix epoch, 0 if unknown
+fn modified_secs(metadata: &fs::Metadata) -> u64 {
+    metadata.modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Whether the archive member `member` stays below the directory it is extracted to
+fn is_contained(member: &Path) -> bool {
+    member.components().all(|part| matches!(part, Component::Normal(_) | Component::CurDir))
+}
+
+/// Fail with `ArchiveError` if the member `member` of the archive `file` is absolute or climbs out with `..`
+///
+/// Archives written by trs never hold such paths, but imported or tampered
+/// ones may.
+fn check_member(file: &str, member: &Path) -> Result<()> {
+    if is_contained(member) {
+        return Ok(());
+    }
+    Err(TrsError::ArchiveError {
+        name: file.to_string(),
+        source: io::Error::new(io::ErrorKind::InvalidData, format!("{} points outside the item", member.display())),
+    })
+}
+
+/// Fail with `ArchiveError` unless `target` is `root` or lies below it once symlinks are resolved
+///
+/// Checked before anything is written for each member of `file` that is
+/// not extracted with `unpack_contained`, so a symlink extracted earlier
+/// cannot lead a later member out of `root`. Directories still to be
+/// created are left out, as members never name `..`.
+fn check_inside(file: &str, target: &Path, root: &Path) -> Result<()> {
+    if target == root {
+        return Ok(());
+    }
+    let resolving = || format!("resolving {}", target.display());
+    let parent = target.parent().unwrap_or(Path::new("."));
+    let inside = resolve_existing(parent).with_context(resolving)?.starts_with(resolve_existing(root).with_context(resolving)?);
+    if inside {
+        return Ok(());
+    }
+    Err(TrsError::ArchiveError {
+        name: file.to_string(),
+        source: io::Error::new(io::ErrorKind::InvalidData, format!("{} leads outside {}", target.display(), root.display())),
+    })
+}
+
+/// Fail with `ArchiveError` if a member of the archive `file` at `archive_path` points outside the item
+///
+/// Each member is checked with `check_member`, as is the first link of a
+/// hard link, and then with `inside`. A member below a symlink member is
+/// refused too, as it would be written wherever the symlink leads. The
+/// archive is read through once more for this, before anything is extracted.
+fn check_members(
+    archive_path: &Path,
+    file: &str,
+    passphrase: Option<&Passphrase>,
+    buffer_size: usize,
+    inside: impl Fn(&Path) -> Result<()>,
+) -> Result<()> {
+    let reading = || format!("reading {}", archive_path.display());
+    let reader = fs::File::open(archive_path).with_context(reading)?;
+    let reader: Box<dyn Read> = match passphrase {
+        Some(passphrase) => Box::new(DecryptReader::new(reader, passphrase).with_context(reading)?),
+        None => Box::new(reader),
+    };
+    let mut archive = read_archive(archive_path, reader, buffer_size).with_context(reading)?;
+    let mut symlinks: Vec<PathBuf> = Vec::new();
+    for entry in archive.entries().with_context(reading)? {
+        let entry = entry.with_context(reading)?;
+        let member = entry.path().with_context(reading)?.into_owned();
+        check_member(file, &member)?;
+        if let Some(source) = link_source(&entry).with_context(reading)? {
+            check_member(file, &source)?;
+        }
+        if let Some(symlink) = symlinks.iter().find(|symlink| member != **symlink && member.starts_with(symlink)) {
+            return Err(TrsError::ArchiveError {
+                name: file.to_string(),
+                source: io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is below the symbolic link {}", member.display(), symlink.display()),
+                ),
+            });
+        }
+        inside(&member)?;
+        if entry.header().entry_type().is_symlink() {
+            symlinks.push(member);
+        }
+    }
+    Ok(())
+}
+
+/// `path` with symlinks resolved in the part of it that exists
+fn resolve_existing(path: &Path) -> io::Result<PathBuf> {
+    match path.ancestors().find(|dir| !dir.as_os_str().is_empty() && fs::symlink_metadata(dir).is_ok()) {
+        Some(existing) => Ok(fs::canonicalize(existing)?.join(path.strip_prefix(existing).unwrap_or(path))),
+        None => Ok(fs::canonicalize(".")?.join(path)),
+    }
+}
+
+/// Extract every member of `archive`, the item `file`, into `dst`, refusing those that point outside it
+///
+/// Like `Archive::unpack`, directories are extracted last, deepest first, so
+/// their permissions cannot stop their contents from being written, and
+/// every member is checked against symlinks extracted before it. Callers
+/// check every member with `check_members` first.
+fn unpack_contained<R: Read>(archive: &mut Archive<R>, file: &str, dst: &Path) -> Result<()> {
+    let extracting = || format!("extracting {} to {}", file, dst.display());
+    let mut directories = Vec::new();
+    for entry in archive.entries().with_context(extracting)? {
+        let mut entry = entry.with_context(extracting)?;
+        let member = entry.path().with_context(extracting)?.into_owned();
+        check_member(file, &member)?;
+        if let Some(source) = link_source(&entry).with_context(extracting)? {
+            check_member(file, &source)?;
+        }
+        if entry.header().entry_type().is_dir() {
+            directories.push(entry);
+        } else {
+            entry.unpack_in(dst).with_context(extracting)?;
+        }
+    }
+    directories.sort_by(|a, b| b.path_bytes().cmp(&a.path_bytes()));
+    for mut directory in directories {
+        directory.unpack_in(dst).with_context(extracting)?;
+    }
+    Ok(())
+}
+
+/// Set the modification times of a restored item as chosen when it was trashed
+///
+/// With `reset` every file gets the time of the restore. Otherwise a file
+/// gets back the time recorded when it was trashed, which archives keep
+/// anyway but copies across filesystems lose. Failures are only logged, as
+/// the item has been restored by then.
+fn apply_timestamps(path: &Path, item: &TrashItem) {
+    let applied = if item.reset_timestamps {
+        let now = SystemTime::now();
+        let mut paths = Vec::new();
+        walk_paths(path, path.to_path_buf(), &mut paths).and_then(|()| {
+            paths.iter()
+                .filter(|(path, _)| fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_file()))
+                .try_for_each(|(path, _)| set_modified(path, now))
+        })
+    } else if let Some(modified_at) = item.modified_at
+        && !item.is_dir
+    {
+        set_modified(path, UNIX_EPOCH + Duration::from_secs(modified_at))
+    } else {
+        Ok(())
+    };
+    if let Err(e) = applied {
+        warn!(path = %path.display(), error = %e, "could not set modification time");
+    }
+}
+
+/// Set the modification time of the file at `path`
+fn set_modified(path: &Path, time: SystemTime) -> Result<()> {
+    // On Unix the owner may set the times through a read-only handle, which
+    // is all a read-only file can be opened with
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .or_else(|_| fs::File::open(path))
+        .and_then(|file| file.set_modified(time))
+        .with_context(|| format!("setting the modification time of {}", path.display()))
+}
+
+/// Where `file` in `trash_dir` would be restored to, and whether it is a directory
+///
+/// `item` is the metadata of `file`, if it has any.
+fn restore_location(file: &str, trash_dir: &Path, item: Option<&TrashItem>, options: &RestoreOptions) -> Result<(String, bool)> {
+    let trash_file = trash_dir.join(file);
+    // Find the original location and type
+    let (original_location, is_dir) = match item {
+        Some(item) => (item.path.clone(), item.is_dir),
+        // Items trashed by other file managers only have a .trashinfo file
+        None => match xdg::trash_root(trash_dir).and_then(|root| xdg::read_trashinfo(root, file)) {
+            Some(info) => (info.path, trash_file.is_dir()),
+            None => {
+                // If not found in metadata, create a full path in current directory
+                let current_dir = normalize_path(current_dir()?);
+                let stored_name = strip_storage_suffix(file);
+                let path = current_dir.join(strip_collision_suffix(stored_name)).to_string_lossy().to_string();
+                
+                // Check if the trash item is a directory
+                let is_dir = trash_file.is_dir();
+                (path, is_dir)
+            },
+        },
+    };
+    match &options.destination {
+        Some(destination) => Ok((destination.to_string_lossy().to_string(), is_dir)),
+        None => Ok((original_location, is_dir)),
+    }
+}
+
+/// Where the archive member `member` of an item restored to `target` is written
+///
+/// Directories restored where they were are extracted next to `target`
+/// under their archived name, and those restored elsewhere with `target` in
+/// place of their top-level name. A file's only member is `target` itself.
+fn member_destination(member: &Path, target: &Path, is_dir: bool, relocated: bool) -> PathBuf {
+    match (is_dir, relocated) {
+        (true, false) => target.parent().unwrap_or(Path::new(".")).join(member),
+        (true, true) => member.components().skip(1).fold(target.to_path_buf(), |path, part| path.join(part)),
+        (false, _) => target.to_path_buf(),
+    }
+}
+
+/// What restoring `file` from `trash_dir` would write, without restoring it
+///
+/// Archives are read through without extracting anything, and items stored
+/// as they are are walked on disk. Each path is given where it would be
+/// written, as `restore_from_trash` would compute it, and checked for
+/// something already there.
+pub fn restore_preview(file: &str, trash_dir: &Path, options: &RestoreOptions) -> Result<RestorePreview> {
+    let trash_file = trash_dir.join(file);
+    if fs::symlink_metadata(&trash_file).is_err() {
+        return Err(TrsError::NotInTrash(file.to_string()));
+    }
+    let item = LazyMetadataStore::new(&metadata_path(trash_dir)).get(file)?;
+    let (destination, is_dir) = restore_location(file, trash_dir, item.as_ref(), options)?;
+    let target = Path::new(&destination);
+
+    // Each path with whether it is a directory and the bytes written to it
+    let stored_as_is = keeps_items_as_is(trash_dir) || item.as_ref().is_some_and(|item| item.raw);
+    let mut paths: Vec<(PathBuf, bool, u64)> = Vec::new();
+    if ArchiveCompression::from_file_name(file).is_some() && trash_file.is_file() && !stored_as_is {
+        let mut archive = open_archive(&trash_file).with_context(|| format!("reading {}", trash_file.display()))?;
+        let corrupted = |e| TrsError::ArchiveError { name: file.to_string(), source: e };
+        for entry in archive.entries().map_err(corrupted)? {
+            let entry = entry.map_err(corrupted)?;
+            let member = entry.path().map_err(corrupted)?;
+            let member_is_dir = entry.header().entry_type().is_dir();
+            let size = if member_is_dir { 0 } else { entry.size() };
+            paths.push((member_destination(&member, target, is_dir, options.destination.is_some()), member_is_dir, size));
+        }
+    } else if trash_file.is_dir() {
+        let mut walked = Vec::new();
+        walk_paths(&trash_file, target.to_path_buf(), &mut walked)?;
+        for (path, is_dir) in walked {
+            let source = trash_file.join(path.strip_prefix(target).unwrap_or(&path));
+            let size = if is_dir { 0 } else { fs::symlink_metadata(&source).map(|metadata| metadata.len()).unwrap_or(0) };
+            paths.push((path, is_dir, size));
+        }
+    } else {
+        let size = item.as_ref().and_then(|item| item.original_size).map_or_else(|| entry_size(&trash_file), Ok)?;
+        paths.push((target.to_path_buf(), false, size));
+    }
+
+    let mut overwritten = Vec::new();
+    let mut merged = Vec::new();
+    for (path, is_dir, _) in &paths {
+        match fs::symlink_metadata(path) {
+            Ok(existing) if existing.is_dir() && *is_dir => merged.push(path.to_string_lossy().to_string()),
+            Ok(_) => overwritten.push(path.to_string_lossy().to_string()),
+            Err(_) => {}
+        }
+    }
+
+    Ok(RestorePreview {
+        name: file.to_string(),
+        trash_dir: trash_dir.to_path_buf(),
+        occupied: fs::symlink_metadata(target).is_ok(),
+        missing_parents: missing_parents(target).iter().map(|dir| dir.to_string_lossy().to_string()).collect(),
+        contents: paths.iter().map(|(path, ..)| path.to_string_lossy().to_string()).collect(),
+        overwritten,
+        merged,
+        total_size: paths.iter().map(|(.., size)| size).sum(),
+        destination,
+        is_dir,
+    })
+}
+
+/// Path inside the archive of the entry `entry` is a hard link to, if it is one
+fn link_source<R: Read>(entry: &tar::Entry<R>) -> io::Result<Option<PathBuf>> {
+    if !entry.header().entry_type().is_hard_link() {
+        return Ok(None);
+    }
+    match entry.link_name()? {
+        Some(name) => Ok(Some(name.into_owned())),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "hard link entry without a target")),
+    }
+}
+
+/// Extract the entry at `entry_path` of the archive at `archive_path` to `target`
+///
+/// Used for hard links whose data is stored with an entry that was not
+/// extracted itself.
+fn extract_archived(archive_path: &Path, entry_path: &Path, target: &Path, buffer_size: usize) -> io::Result<()> {
+    let mut archive = read_archive(archive_path, fs::File::open(archive_path)?, buffer_size)?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()? == entry_path {
+            return entry.unpack(target).map(|_| ());
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not in the archive", entry_path.display())))
+}
+
+/// Extract a single member of an archived item without restoring the rest
+///
+/// `member` is a path inside the archive, either relative to the trashed
+/// directory or including its name. Directories are extracted with their
+/// contents. The archive is kept unless the member was its only entry.
+/// Returns where the member was written.
+pub fn restore_member(
+    file: &str,
+    member: &str,
+    trash_dir: &Path,
+    options: &RestoreOptions,
+    progress: &impl ProgressSink,
+) -> Result<PathBuf> {
+    reported(progress, restore_member_item(file, member, trash_dir, options, progress))
+}
+
+fn restore_member_item(
+    file: &str,
+    member: &str,
+    trash_dir: &Path,
+    options: &RestoreOptions,
+    progress: &dyn ProgressSink,
+) -> Result<PathBuf> {
+    let trash_file = trash_dir.join(file);
+    if !trash_file.is_file() {
+        return Err(TrsError::NotInTrash(file.to_string()));
+    }
+    if crypto::is_encrypted(file) && !keeps_items_as_is(trash_dir) {
+        return Err(TrsError::invalid_input(format!("{} is encrypted; restore it whole", file)));
+    }
+    if ArchiveCompression::from_file_name(file).is_none() || keeps_items_as_is(trash_dir) {
+        return Err(TrsError::invalid_input(format!("{} is not an archive with members", file)));
+    }
+
+    let metadata_file = metadata_path(trash_dir);
+    let mut metadata = load_items(&metadata_file)?;
+    let (original_location, is_dir) = match metadata.get(file) {
+        Some(item) => (PathBuf::from(&item.path), item.is_dir),
+        None => {
+            let current_dir = normalize_path(current_dir()?);
+            let stored_name = strip_storage_suffix(file);
+            (current_dir.join(strip_collision_suffix(stored_name)), false)
+        }
+    };
+
+    let member = Path::new(member.trim_end_matches(['/', '\\']));
+    if member.as_os_str().is_empty() {
+        return Err(TrsError::invalid_input("The member path must not be empty"));
+    }
+    progress.start(entry_size(&trash_file)?);
+    let inner = fs::File::open(&trash_file).with_context(|| format!("reading {}", trash_file.display()))?;
+    let reader = ProgressReader { inner, progress };
+    let corrupted = |e| TrsError::ArchiveError { name: file.to_string(), source: e };
+    let mut archive = read_archive(&trash_file, reader, options.buffer_size).map_err(corrupted)?;
+
+    let mut target = None;
+    let mut entry_count = 0;
+    // Where each extracted entry went, for the hard links that follow it
+    let mut extracted = HashMap::new();
+    for entry in archive.entries().map_err(corrupted)? {
+        let mut entry = entry.map_err(corrupted)?;
+        entry_count += 1;
+        let entry_path = entry.path().map_err(corrupted)?.into_owned();
+
+        // Directory archives store everything below the directory's own name
+        let relative = if is_dir {
+            entry_path.components().skip(1).collect::<PathBuf>()
+        } else {
+            entry_path.clone()
+        };
+        let Some(rest) = relative
+            .strip_prefix(member)
+            .or_else(|_| entry_path.strip_prefix(member))
+            .ok()
+            .map(Path::to_path_buf)
+        else {
+            continue;
+        };
+        check_member(file, &entry_path)?;
+
+        let root = match &options.destination {
+            Some(destination) => destination.clone(),
+            None if is_dir => {
+                // The member's own path inside the original directory
+                let depth = relative.components().count() - rest.components().count();
+                relative.components().take(depth).fold(original_location.clone(), |path, part| path.join(part))
+            }
+            None => original_location.clone(),
+        };
+        let path = if rest.as_os_str().is_empty() { root.clone() } else { root.join(&rest) };
+        check_inside(file, &path, &root)?;
+        create_parent_dir(&path)?;
+        let unpacked = match link_source(&entry).map_err(corrupted)? {
+            Some(source) => match extracted.get(&source) {
+                Some(first) => fs::hard_link(first, &path),
+                // The data is stored with the first link, outside the member
+                None => extract_archived(&trash_file, &source, &path, options.buffer_size),
+            },
+            None => entry.unpack(&path).map(|_| ()),
+        };
+        unpacked.with_context(|| format!("extracting {} to {}", entry_path.display(), path.display()))?;
+        extracted.insert(entry_path, path);
+        target.get_or_insert(root);
+    }
+
+    let Some(target) = target else {
+        return Err(TrsError::NotInTrash(format!("{} in {}", member.display(), file)));
+    };
+    if let Some(item) = metadata.get(file) {
+        apply_timestamps(&target, item);
+    }
+
+    // An archive whose only entry was extracted has nothing left to keep
+    if entry_count == 1 {
+        retry_locked(|| fs::remove_file(&trash_file)).with_context(|| format!("removing {}", trash_file.display()))?;
+        metadata.remove(file);
+        save_items(&metadata_file, &metadata)?;
+    }
+
+    progress.finish(&format!(
+        "Restored {} from {} to {}",
+        member.display(),
+        strip_storage_suffix(file),
+        target.display()
+    ));
+    Ok(target)
+}
+
+/// List all items in the trash
+///
+/// Sizes recorded in the metadata are used where available. Returns an
+/// empty list, without creating anything, when the trash directory does not
+/// exist.
+pub fn list_trash_items(trash_dir: &Path) -> Result<Vec<TrashEntry>> {
+    TrashIterator::new(trash_dir)?
+        .map(|entry| entry.and_then(TrashEntry::try_from))
+        .collect()
+}
+
+/// List the items of several trashes, each with the backend holding it
+///
+/// Sources are read in order. An item with the same original path and
+/// deletion time as one already listed, such as a copy registered in both
+/// stores, is only listed from the first source that has it.
+pub fn list_trash_entries(sources: &[(PathBuf, TrashBackend)]) -> Result<Vec<SourcedEntry>> {
+    let mut seen = HashSet::new();
+    let mut merged = Vec::new();
+    for (trash_dir, backend) in sources {
+        for entry in list_trash_items(trash_dir)? {
+            if let (Some(path), Some(deleted_at)) = (&entry.original_path, entry.deleted_at)
+                && !seen.insert((path.clone(), deleted_at))
+            {
+                debug!(name = %entry.name, trash_dir = %trash_dir.display(), "already listed from another backend");
+                continue;
+            }
+            merged.push(SourcedEntry { entry, backend: *backend, trash_dir: trash_dir.clone() });
+        }
+    }
+    Ok(merged)
+}
+
+/// Resolve a user-supplied name to an entry in the trash
+///
+/// The name may omit the archive suffix used for storage, such as
+/// `.tar.gz`, with `.enc` after it for encrypted archives, or the `.gz` of
+/// the legacy format. Paths, which could point outside the trash, are never
+/// entries.
+pub fn resolve_entry_name(trash_dir: &Path, name: &str) -> Result<String> {
+    if !matches!(Path::new(name).components().collect::<Vec<_>>()[..], [Component::Normal(_)]) {
+        return Err(TrsError::NotInTrash(name.to_string()));
+    }
+    let archives = ArchiveCompression::ALL.into_iter().map(|compression| format!("{}{}", name, compression.suffix()));
+    let encrypted = archives.clone().map(|archive| format!("{}{}", archive, crypto::ENCRYPTED_EXTENSION));
+    std::iter::once(name.to_string())
+        .chain(archives)
+        .chain(encrypted)
+        .chain(std::iter::once(format!("{}.gz", name)))
+        .find(|candidate| !is_reserved_name(candidate) && trash_dir.join(candidate).exists())
+        .ok_or_else(|| TrsError::NotInTrash(name.to_string()))
+}
+
+/// Find an item by name in `trash_dir` or, failing that, in the known device trashes
+///
+/// Returns the trash directory holding the item and its name there.
+pub fn locate_entry(trash_dir: &Path, name: &str) -> Result<(PathBuf, String)> {
+    match resolve_entry_name(trash_dir, name) {
+        Err(TrsError::NotInTrash(_)) => device::known_trash_dirs()
+            .into_iter()
+            .find_map(|dir| resolve_entry_name(&dir, name).ok().map(|entry| (dir, entry)))
+            .ok_or_else(|| TrsError::NotInTrash(name.to_string())),
+        result => result.map(|entry| (trash_dir.to_path_buf(), entry)),
+    }
+}
+
+/// List the paths stored in an archive without extracting it
+pub fn list_archive(archive_path: &Path) -> Result<Vec<String>> {
+    let mut archive = open_archive(archive_path).with_context(|| format!("reading {}", archive_path.display()))?;
+    let corrupted = |e| TrsError::ArchiveError { name: archive_path.display().to_string(), source: e };
+
+    let mut paths = Vec::new();
+    for entry in archive.entries().map_err(corrupted)? {
+        let entry = entry.map_err(corrupted)?;
+        paths.push(entry.path().map_err(corrupted)?.to_string_lossy().to_string());
+    }
+    Ok(paths)
+}
+
+/// Contents of an archive drawn as a tree, one line per entry below its top-level directory
+///
+/// Entries more than `depth` levels below the top are left out, and `None`
+/// draws every level. Directories end in `/`. Nothing is extracted; the
+/// archive is read once from start to end.
+pub fn archive_tree(archive_path: &Path, depth: Option<usize>) -> Result<Vec<String>> {
+    let mut archive = open_archive(archive_path).with_context(|| format!("reading {}", archive_path.display()))?;
+    let corrupted = |e| TrsError::ArchiveError { name: archive_path.display().to_string(), source: e };
+
+    let mut paths = Vec::new();
+    for entry in archive.entries().map_err(corrupted)? {
+        let entry = entry.map_err(corrupted)?;
+        let is_dir = entry.header().entry_type().is_dir();
+        paths.push((entry.path().map_err(corrupted)?.into_owned(), is_dir));
+    }
+    Ok(draw_tree(&paths, depth))
+}
+
+/// Draw `paths`, each with whether it is a directory, as a tree below their shared first component
+fn draw_tree(paths: &[(PathBuf, bool)], depth: Option<usize>) -> Vec<String> {
+    #[derive(Default)]
+    struct Node {
+        is_dir: bool,
+        children: BTreeMap<String, Node>,
+    }
+
+    fn draw(node: &Node, prefix: &str, lines: &mut Vec<String>) {
+        let count = node.children.len();
+        for (index, (name, child)) in node.children.iter().enumerate() {
+            let last = index + 1 == count;
+            let slash = if child.is_dir { "/" } else { "" };
+            lines.push(format!("{}{}{}{}", prefix, if last { "└── " } else { "├── " }, name, slash));
+            let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            draw(child, &prefix, lines);
+        }
+    }
+
+    let mut root = Node::default();
+    for (path, is_dir) in paths {
+        // The first component is the item itself, which the listing already shows
+        let components: Vec<String> = path.iter().skip(1).map(|part| part.to_string_lossy().to_string()).collect();
+        let shown = depth.map_or(components.len(), |depth| components.len().min(depth));
+        let mut node = &mut root;
+        for (index, part) in components.iter().take(shown).enumerate() {
+            node = node.children.entry(part.clone()).or_default();
+            // Anything with entries below it is a directory, even when they are too deep to draw
+            node.is_dir |= index + 1 < components.len() || *is_dir;
+        }
+    }
+
+    let mut lines = Vec::new();
+    draw(&root, "", &mut lines);
+    lines
+}
+
+/// Read up to `limit` bytes of a trashed file's original contents
+///
+/// Returns `None` for directories. Only the first entry of an archive is
+/// decompressed.
+pub fn read_entry_head(trash_dir: &Path, name: &str, limit: u64) -> Result<Option<Vec<u8>>> {
+    let path = trash_dir.join(name);
+    if path.is_dir() {
+        return Ok(None);
+    }
+
+    let file = BufReader::new(fs::File::open(&path).with_context(|| format!("reading {}", path.display()))?);
+    let mut head = Vec::new();
+    if ArchiveCompression::from_file_name(name).is_some() {
+        let corrupted = |e| TrsError::ArchiveError { name: name.to_string(), source: e };
+        let mut archive = read_archive(&path, file, DEFAULT_BUFFER_SIZE).map_err(corrupted)?;
+        let Some(entry) = archive.entries().map_err(corrupted)?.next() else {
+            return Ok(Some(head));
+        };
+        let entry = entry.map_err(corrupted)?;
+        if entry.header().entry_type().is_dir() {
+            return Ok(None);
+        }
+        entry.take(limit).read_to_end(&mut head).map_err(corrupted)?;
+    } else if name.ends_with(".gz") {
+        GzDecoder::new(file).take(limit).read_to_end(&mut head)
+            .with_context(|| format!("reading {}", path.display()))?;
+    } else {
+        file.take(limit).read_to_end(&mut head).with_context(|| format!("reading {}", path.display()))?;
+    }
+    Ok(Some(head))
+}
+
+/// Collect full details about a single entry
+pub fn item_info(trash_dir: &Path, name: &str) -> Result<ItemInfo> {
+    let name = resolve_entry_name(trash_dir, name)?;
+    let path = trash_dir.join(&name);
+
+    let item = LazyMetadataStore::new(&metadata_path(trash_dir)).get(&name)?;
+    let item = item.as_ref();
+
+    // The contents of encrypted archives are not known without the passphrase
+    let (compression, contents) = if let Some(compression) = ArchiveCompression::from_file_name(&name) {
+        (compression.label().to_string(), list_archive(&path)?)
+    } else if crypto::is_encrypted(&name) {
+        (format!("{}{}", encrypted_compression(&name).label(), crypto::ENCRYPTED_EXTENSION), Vec::new())
+    } else if name.ends_with(".gz") {
+        ("gzip".to_string(), Vec::new())
+    } else {
+        ("none".to_string(), Vec::new())
+    };
+
+    Ok(ItemInfo {
+        original_path: item.map(|item| item.path.clone()),
+        is_dir: item.map(|item| item.is_dir).unwrap_or(path.is_dir()),
+        deleted_at: item.and_then(|item| item.deleted_at),
+        size: entry_size(&path)?,
+        compression: compression.to_string(),
+        contents,
+        skipped: item.map(|item| item.skipped.clone()).unwrap_or_default(),
+        name,
+    })
+}
+
+/// Find the entry `name` refers to, for commands that act on a single item
+///
+/// Exact names are looked up as by `locate_entry`. Otherwise the name is
+/// searched for in the names and original locations of the items in
+/// `trash_dir`, as by `search_trash`, and must match exactly one of them.
+/// Returns the trash directory holding the item and its name there.
+pub fn lookup_entry(trash_dir: &Path, name: &str) -> Result<(PathBuf, String)> {
+    match locate_entry(trash_dir, name) {
+        Err(TrsError::NotInTrash(_)) => {}
+        result => return result,
+    }
+    let mut matches = search_trash(trash_dir, name)?;
+    match matches.len() {
+        0 => Err(TrsError::NotInTrash(name.to_string())),
+        1 => Ok((trash_dir.to_path_buf(), matches.remove(0).name)),
+        _ => {
+            matches.sort_by(|a, b| a.name.cmp(&b.name));
+            Err(TrsError::Ambiguous {
+                name: name.to_string(),
+                candidates: matches.iter()
+                    .map(|entry| match &entry.original_path {
+                        Some(path) => format!("{} ({})", entry.name, display_path(path)),
+                        None => entry.name.clone(),
+                    })
+                    .collect(),
+            })
+        }
+    }
+}
+
+/// Collect everything known about the entry `name` refers to, found with `lookup_entry`
+///
+/// The entry is read to the end to check its checksum, without extracting it.
+pub fn item_details(trash_dir: &Path, name: &str) -> Result<ItemDetails> {
+    let (trash_dir, name) = lookup_entry(trash_dir, name)?;
+    let path = trash_dir.join(&name);
+    let names = item_names(&name);
+    let metadata = LazyMetadataStore::new(&metadata_path(&trash_dir)).select(|key| names.iter().any(|name| name == key))?;
+    let item = find_item(&metadata, &name);
+    let size = entry_size(&path)?;
+
+    let mut paths = Vec::new();
+    let (compression, checksum) = if let Some(compression) = ArchiveCompression::from_file_name(&name) {
+        (compression.label().to_string(), read_archive_paths(&path, &mut paths))
+    } else if crypto::is_encrypted(&name) && path.is_file() {
+        (format!("{}{}", encrypted_compression(&name).label(), crypto::ENCRYPTED_EXTENSION), Ok(()))
+    } else if name.ends_with(".gz") && !path.is_dir() {
+        paths.push((PathBuf::from(name.trim_end_matches(".gz")), false));
+        let file = fs::File::open(&path).with_context(|| format!("reading {}", path.display()))?;
+        ("gzip".to_string(), io::copy(&mut GzDecoder::new(BufReader::new(file)), &mut io::sink()).map(drop))
+    } else {
+        walk_paths(&path, PathBuf::from(&name), &mut paths)?;
+        ("none".to_string(), Ok(()))
+    };
+    let checksum = match checksum {
+        Ok(()) if compression == "none" || crypto::is_encrypted(&name) => ChecksumStatus::Unavailable,
+        Ok(()) => ChecksumStatus::Verified,
+        Err(e) => ChecksumStatus::Failed { reason: e.to_string() },
+    };
+
+    let original_path = item.map(|item| item.path.clone());
+    let restore = match &original_path {
+        Some(original) if fs::symlink_metadata(original).is_ok() => RestoreStatus::Occupied { path: original.clone() },
+        Some(original) => RestoreStatus::Ready { path: original.clone() },
+        None => RestoreStatus::Unknown,
+    };
+
+    Ok(ItemDetails {
+        is_dir: item.map(|item| item.is_dir).unwrap_or(path.is_dir()),
+        deleted_at: item.and_then(|item| item.deleted_at),
+        pinned: item.is_some_and(|item| item.pinned),
+        user: item.and_then(|item| item.user.clone()),
+        hostname: item.and_then(|item| item.hostname.clone()),
+        compression: compression.to_string(),
+        original_size: item.and_then(|item| item.original_size).or((compression == "none").then_some(size)),
+        files: paths.iter().filter(|(_, is_dir)| !is_dir).count(),
+        directories: paths.iter().filter(|(path, is_dir)| *is_dir && path.components().count() > 1).count(),
+        contents: paths.iter()
+            .map(|(path, is_dir)| format!("{}{}", path.to_string_lossy(), if *is_dir { "/" } else { "" }))
+            .collect(),
+        checksum,
+        restore,
+        size,
+        original_path,
+        trash_dir,
+        name,
+    })
+}
+
+/// Read every entry of an archive, adding its path to `paths`, then the rest of the compressed stream
+///
+/// Reading to the end makes the decoder compare the checksum in the gzip
+/// trailer or at the end of the zstd frame, which the tar reader alone
+/// stops short of.
+fn read_archive_paths(archive_path: &Path, paths: &mut Vec<(PathBuf, bool)>) -> io::Result<()> {
+    let mut archive = open_archive(archive_path)?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let is_dir = entry.header().entry_type().is_dir();
+        paths.push((entry.path()?.into_owned(), is_dir));
+        io::copy(&mut entry, &mut io::sink())?;
+    }
+    io::copy(&mut archive.into_inner(), &mut io::sink()).map(drop)
+}
+
+/// Add `path`, shown as `shown`, and everything below it to `paths`
+fn walk_paths(path: &Path, shown: PathBuf, paths: &mut Vec<(PathBuf, bool)>) -> Result<()> {
+    // Symbolic links are listed rather than followed
+    let is_dir = fs::symlink_metadata(path).with_context(|| format!("reading metadata of {}", path.display()))?.is_dir();
+    if !is_dir {
+        paths.push((shown, false));
+        return Ok(());
+    }
+    paths.push((shown.clone(), true));
+    let reading = || format!("reading directory {}", path.display());
+    let mut children: Vec<_> = fs::read_dir(path).with_context(reading)?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<io::Result<_>>()
+        .with_context(reading)?;
+    children.sort();
+    for child in children {
+        walk_paths(&path.join(&child), shown.join(&child), paths)?;
+    }
+    Ok(())
+}
+
+/// Find items whose name or original path contains `query` (case-insensitive)
+pub fn search_trash(trash_dir: &Path, query: &str) -> Result<Vec<TrashEntry>> {
+    let query = query.to_lowercase();
+    TrashIterator::new(trash_dir)?
+        .filter(|entry| match entry {
+            Ok(entry) => {
+                entry.name.to_lowercase().contains(&query)
+                    || entry.original_path().is_some_and(|path| path.to_lowercase().contains(&query))
+            }
+            Err(_) => true,
+        })
+        .map(|entry| entry.and_then(TrashEntry::try_from))
+        .collect()
+}
+
+/// Summarize the number of items and space used by the trash
+pub fn trash_status(trash_dir: &Path) -> Result<TrashStatus> {
+    Ok(TrashStatus {
+        trash_dir: trash_dir.to_path_buf(),
+        item_count: list_trash_items(trash_dir)?.len(),
+        total_size: trash_usage(trash_dir)?,
+    })
+}
+
+/// Number of entries listed in `TrashStats::largest`
+const LARGEST_COUNT: usize = 5;
+
+/// Gather statistics about the trash
+///
+/// Sizes recorded in the metadata are used where available, so archives are
+/// never decompressed. An archive shared by deduplicated entries adds to
+/// the compressed size once. A missing trash yields zeroed statistics.
+pub fn trash_stats(trash_dir: &Path) -> Result<TrashStats> {
+    let mut stats = TrashStats::default();
+    let mut sizes = Vec::new();
+    let mut linked = HashSet::new();
+
+    for entry in TrashIterator::new(trash_dir)? {
+        let entry = entry?;
+        stats.item_count += 1;
+        if entry.is_dir {
+            stats.dir_count += 1;
+        } else {
+            stats.file_count += 1;
+        }
+
+        let size = match entry.item.as_ref().and_then(|item| item.size) {
+            Some(size) => size,
+            None => entry_size(&entry.path)?,
+        };
+        let shared = entry.item.as_ref().is_some_and(|item| item.content_hash.is_some())
+            && fs::symlink_metadata(&entry.path)
+                .ok()
+                .and_then(|metadata| hard_link_key(&metadata))
+                .is_some_and(|key| !linked.insert(key));
+        if !shared {
+            stats.compressed_size += size;
+        }
+        match entry.item.as_ref().and_then(|item| item.original_size) {
+            Some(original_size) => stats.original_size += original_size,
+            None => stats.unknown_original_count += 1,
+        }
+
+        if let Some(deleted_at) = entry.deleted_at() {
+            stats.oldest = Some(stats.oldest.map_or(deleted_at, |oldest| oldest.min(deleted_at)));
+            stats.newest = Some(stats.newest.map_or(deleted_at, |newest| newest.max(deleted_at)));
+        }
+        sizes.push(EntrySize { name: entry.name, size });
+    }
+
+    sizes.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    sizes.truncate(LARGEST_COUNT);
+    stats.largest = sizes;
+    Ok(stats)
+}
+
+/// Check that metadata and trash contents agree and that archives are readable
+pub fn verify_trash(trash_dir: &Path) -> Result<Vec<VerifyIssue>> {
+    let mut issues = Vec::new();
+    if !trash_dir.exists() {
+        return Ok(issues);
+    }
+
+    let metadata_file = metadata_path(trash_dir);
+    let metadata = load_items(&metadata_file)?;
+
+    for name in metadata.keys() {
+        if !trash_dir.join(name).exists() {
+            issues.push(VerifyIssue {
+                name: name.clone(),
+                problem: "metadata refers to a missing item".to_string(),
+            });
+        }
+    }
+
+    for entry in list_trash_items(trash_dir)? {
+        if entry.original_path.is_none() {
+            issues.push(VerifyIssue {
+                name: entry.name.clone(),
+                problem: "no metadata for item".to_string(),
+            });
+        }
+
+        if ArchiveCompression::from_file_name(&entry.name).is_some() {
+            let path = trash_dir.join(&entry.name);
+            let mut archive = open_archive(&path).with_context(|| format!("reading {}", path.display()))?;
+            let readable = archive.entries().and_then(|entries| {
+                for archive_entry in entries {
+                    io::copy(&mut archive_entry?, &mut io::sink())?;
+                }
+                Ok(())
+            });
+            if let Err(e) = readable {
+                issues.push(VerifyIssue {
+                    name: entry.name.clone(),
+                    problem: format!("archive is unreadable: {}", e),
+                });
+            }
+        }
+    }
+
+    for name in partial_archives(trash_dir)? {
+        issues.push(VerifyIssue {
+            name,
+            problem: "incomplete archive left by an interrupted move; emptying the trash deletes it".to_string(),
+        });
+    }
+
+    issues.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(issues)
+}
+
+/// Permanently delete one item from the trash, optionally shredding it first
+///
+/// When shredding, the bytes overwritten are reported to `progress`.
+/// Returns the name and size of the deleted entry.
+pub fn purge_entry(trash_dir: &Path, name: &str, shred: Option<&ShredOptions>, progress: &impl ProgressSink) -> Result<EntrySize> {
+    let purged = reported(progress, purge_item(trash_dir, name, shred, progress))?;
+    record(trash_dir, JournalRecord::Delete { names: vec![purged.name.clone()] });
+    Ok(purged)
+}
+
+fn purge_item(trash_dir: &Path, name: &str, shred: Option<&ShredOptions>, progress: &dyn ProgressSink) -> Result<EntrySize> {
+    let name = resolve_entry_name(trash_dir, name)?;
+    let metadata_file = metadata_path(trash_dir);
+    let mut metadata = load_items(&metadata_file)?;
+
+    let size = entry_size(&trash_dir.join(&name))?;
+    let total = match shred {
+        Some(shred) => shred_bytes(&trash_dir.join(&name), shred)?,
+        None => 0,
+    };
+    progress.start(total);
+    remove_entry_with(trash_dir, &name, &mut metadata, shred, progress)?;
+    save_items(&metadata_file, &metadata)?;
+    progress.finish(&format!("Permanently deleted {} from Trash", name));
+    Ok(EntrySize { name, size })
+}
+
+/// Pin or unpin an item, returning its name in the trash
+///
+/// Pinned items are skipped by `empty_trash` unless `include_pinned` is set,
+/// and never pruned. Items another program put in an XDG trash get metadata
+/// from their `.trashinfo` file; other items without metadata cannot be pinned.
+pub fn pin_trash_item(trash_dir: &Path, name: &str, pinned: bool) -> Result<String> {
+    let name = resolve_entry_name(trash_dir, name)?;
+    let metadata_file = metadata_path(trash_dir);
+    let mut metadata = load_items(&metadata_file)?;
+
+    let item = find_item(&metadata, &name).cloned().or_else(|| {
+        let info = xdg::read_trashinfo(xdg::trash_root(trash_dir)?, &name)?;
+        Some(TrashItem {
+            deleted_at: info.deleted_at,
+            hostname: None,
+            user: None,
+            ..TrashItem::new(info.path, trash_dir.join(&name).is_dir())
+        })
+    });
+    let Some(item) = item else {
+        return Err(TrsError::invalid_input(format!("{} has no recorded metadata and cannot be pinned", name)));
+    };
+    metadata.insert(name.clone(), TrashItem { pinned, ..item });
+    save_items(&metadata_file, &metadata)?;
+    Ok(name)
+}
+
+/// Whether a glob matches an entry's stored name, display name or original path
+fn entry_matches(entry: &TrashEntry, pattern: &Pattern) -> bool {
+    let display_name = strip_storage_suffix(&entry.name);
+    pattern.matches(&entry.name)
+        || pattern.matches(display_name)
+        || entry.original_path.as_deref().is_some_and(|path| {
+            pattern.matches(path)
+                || Path::new(path).file_name().is_some_and(|name| pattern.matches(&name.to_string_lossy()))
+        })
+}
+
+/// Entries whose name as listed by `show`, without the archive suffix, matches the glob `pattern`
+///
+/// Entries are sorted by that name, so items are always handled in the same order.
+pub fn entries_matching(trash_dir: &Path, pattern: &str) -> Result<Vec<TrashEntry>> {
+    let pattern = Pattern::new(pattern)
+        .map_err(|e| TrsError::invalid_input(format!("Invalid pattern '{}': {}", pattern, e)))?;
+    let display_name = |entry: &TrashEntry| strip_storage_suffix(&entry.name).to_string();
+
+    let mut entries = list_trash_items(trash_dir)?;
+    entries.retain(|entry| pattern.matches(&display_name(entry)));
+    entries.sort_by_key(display_name);
+    Ok(entries)
+}
+
+/// Entries that `empty_trash` would delete with the given options
+///
+/// When patterns are given, an entry must match both a pattern and the age
+/// filter. With `duplicates`, only the items of each group from
+/// `duplicate_groups` other than the one kept are considered. Pinned items
+/// are left out unless `include_pinned` is set. With `keep_newest`, the most
+/// recently trashed of the remaining entries are left out too. Returns
+/// `NotInTrash` if patterns are given and nothing matches them.
+pub fn empty_candidates(trash_dir: &Path, options: &EmptyOptions) -> Result<Vec<TrashEntry>> {
+    let patterns = options.patterns.iter()
+        .map(|raw| Pattern::new(raw).map_err(|e| TrsError::invalid_input(format!("Invalid pattern '{}': {}", raw, e))))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut entries = match options.duplicates {
+        Some(keep) => duplicate_groups(trash_dir)?
+            .into_iter()
+            .flat_map(|group| {
+                let mut entries = group.entries;
+                match keep {
+                    KeepDuplicate::Newest => entries.pop(),
+                    KeepDuplicate::Oldest => Some(entries.remove(0)),
+                };
+                entries
+            })
+            .collect(),
+        None => list_trash_items(trash_dir)?,
+    };
+    let now = now_secs();
+    entries.retain(|entry| {
+        (options.include_pinned || !entry.pinned)
+            && within_age(entry.deleted_at, now, None, options.older_than)
+            && (patterns.is_empty() || patterns.iter().any(|pattern| entry_matches(entry, pattern)))
+    });
+    if !patterns.is_empty() && entries.is_empty() {
+        return Err(TrsError::NotInTrash(options.patterns.join(", ")));
+    }
+    // Items without a deletion time count as the oldest
+    if let Some(keep) = options.keep_newest {
+        entries.sort_by_cached_key(|entry| {
+            (Reverse((entry.deleted_at, stored_at(&trash_dir.join(&entry.name)))), entry.name.clone())
+        });
+        entries.drain(..keep.min(entries.len()));
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+/// When the entry at `path` was put in the trash, to order items trashed within the same second
+///
+/// Renaming an entry into the trash changes its status time, as does
+/// writing an archive, so this is the time it was stored.
+#[cfg(unix)]
+fn stored_at(path: &Path) -> Option<Duration> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::symlink_metadata(path).ok()?;
+    Some(Duration::new(u64::try_from(metadata.ctime()).ok()?, u32::try_from(metadata.ctime_nsec()).ok()?))
+}
+
+/// The creation time of the entry stands in on other systems
+#[cfg(not(unix))]
+fn stored_at(path: &Path) -> Option<Duration> {
+    fs::symlink_metadata(path).and_then(|metadata| metadata.created()).ok()?.duration_since(UNIX_EPOCH).ok()
+}
+
+/// Empty trash folder permanently
+///
+/// The bytes deleted, or overwritten when shredding, are reported to `progress`.
+pub fn empty_trash(trash_dir: &Path, options: &EmptyOptions, progress: &impl ProgressSink) -> Result<EmptySummary> {
+    let summary = reported(progress, empty_items(trash_dir, options, progress))?;
+    if !summary.deleted.is_empty() {
+        record(trash_dir, JournalRecord::Delete { names: summary.deleted.clone() });
+    }
+    Ok(summary)
+}
+
+fn empty_items(trash_dir: &Path, options: &EmptyOptions, progress: &dyn ProgressSink) -> Result<EmptySummary> {
+    let started = Instant::now();
+    let mut summary = EmptySummary::default();
+    if trash_dir.exists() {
+        let metadata_file = metadata_path(trash_dir);
+        let mut metadata = load_items(&metadata_file)?;
+        
+        // Select the entries to delete up front for better progress indication
+        let entries = empty_candidates(trash_dir, options)?;
+        
+        if !entries.is_empty() {
+            // An archive shared by several of the entries is only counted for the first
+            let mut linked = HashSet::new();
+            let sizes = entries.iter()
+                .map(|entry| {
+                    let path = trash_dir.join(&entry.name);
+                    let shared = fs::symlink_metadata(&path)
+                        .ok()
+                        .filter(|metadata| metadata.is_file())
+                        .and_then(|metadata| hard_link_key(&metadata))
+                        .is_some_and(|key| !linked.insert(key));
+                    if shared { Ok(0) } else { entry_size(&path) }
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            // Track overwritten bytes instead of deleted bytes when shredding
+            let total = match &options.shred {
+                Some(shred) => sizes.iter().sum::<u64>() * (shred.passes + usize::from(shred.zero)) as u64,
+                None => sizes.iter().sum(),
+            };
+            progress.start(total);
             
-            let display_name = if unique_name == file_name.to_string() { 
-                file_name.to_string()
+            // Instead of removing the whole directory at once, remove items one by one for progress updates
+            for (entry, size) in entries.iter().zip(&sizes) {
+                // Stop between items, keeping the metadata of those left
+                if crate::interrupt::requested() {
+                    save_items(&metadata_file, &metadata)?;
+                    return Err(TrsError::Interrupted);
+                }
+                remove_entry_with(trash_dir, &entry.name, &mut metadata, options.shred.as_ref(), progress)?;
+                debug!(trash_name = %entry.name, size, shredded = options.shred.is_some(), "deleted");
+                if options.shred.is_none() {
+                    progress.advance(*size);
+                }
+                summary.deleted.push(entry.name.clone());
+                summary.size += size;
+            }
+            
+            info!(
+                items = entries.len(),
+                size = sizes.iter().sum::<u64>(),
+                duration_ms = started.elapsed().as_millis() as u64,
+                "emptied"
+            );
+            progress.finish("Trash emptied successfully");
+        } else {
+            progress.finish("Trash was already empty");
+        }
+        
+        // Reset the metadata so no entries refer to deleted items, keeping that of pinned items,
+        // and clean up after interrupted moves
+        if options.older_than.is_none() && options.patterns.is_empty() && options.duplicates.is_none() && options.keep_newest.is_none() {
+            metadata.retain(|name, item| item.pinned && !options.include_pinned && trash_dir.join(name).exists());
+            for name in partial_archives(trash_dir)? {
+                let path = trash_dir.join(&name);
+                fs::remove_file(&path).with_context(|| format!("removing {}", path.display()))?;
+                debug!(name = %name, "removed incomplete archive");
+            }
+        }
+        save_items(&metadata_file, &metadata)?;
+    } else {
+        progress.finish("Trash is already empty");
+    }
+    Ok(summary)
+}
+
+/// Write every item in the trash and its metadata to a single bundle at `output`
+///
+/// Items keep their names from the trash directory, at the top level of the
+/// bundle and after the metadata file, so `trs import` can rebuild the trash
+/// exactly. The size of each item is reported to `progress` as it is added.
+/// Fails with `AlreadyExists` if `output` exists, unless overwriting.
+/// Returns the number of items exported.
+pub fn export_trash(trash_dir: &Path, output: &Path, options: &ExportOptions, progress: &impl ProgressSink) -> Result<usize> {
+    reported(progress, export_items(trash_dir, output, options, progress))
+}
+
+fn export_items(trash_dir: &Path, output: &Path, options: &ExportOptions, progress: &dyn ProgressSink) -> Result<usize> {
+    let mut names = Vec::new();
+    if trash_dir.exists() {
+        let reading = || format!("reading trash directory {}", trash_dir.display());
+        for entry in fs::read_dir(trash_dir).with_context(reading)? {
+            let name = entry.with_context(reading)?.file_name().to_string_lossy().to_string();
+            if !is_reserved_name(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    let sizes = names.iter()
+        .map(|name| entry_size(&trash_dir.join(name)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut open = fs::OpenOptions::new();
+    if options.overwrite {
+        open.write(true).create(true).truncate(true);
+    } else {
+        open.write(true).create_new(true);
+    }
+    let file = open.open(output).map_err(|e| match e.kind() {
+        io::ErrorKind::AlreadyExists => TrsError::AlreadyExists(output.display().to_string()),
+        _ => TrsError::Io { context: format!("creating bundle {}", output.display()), source: e },
+    })?;
+
+    let write_bundle = || -> Result<()> {
+        let writing = || format!("writing bundle {}", output.display());
+        let mut bundle = Builder::new(BundleWriter::new(file, options.compression).with_context(writing)?);
+
+        // The metadata comes first so a bundle can be checked before any item is read
+        let metadata_file = metadata_path(trash_dir);
+        compact_metadata_journal(&metadata_file)?;
+        if metadata_file.exists() {
+            bundle.append_path_with_name(&metadata_file, ".metadata").with_context(writing)?;
+        } else {
+            let empty = b"{}";
+            let mut header = tar::Header::new_gnu();
+            header.set_size(empty.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(now_secs());
+            header.set_cksum();
+            bundle.append_data(&mut header, ".metadata", &empty[..]).with_context(writing)?;
+        }
+
+        progress.start(sizes.iter().sum());
+        for (name, size) in names.iter().zip(&sizes) {
+            let path = trash_dir.join(name);
+            let added = if path.is_dir() {
+                bundle.append_dir_all(name, &path)
             } else {
-                format!("{} (as {})", file_name, unique_name.trim_end_matches(".tar.gz"))
+                bundle.append_path_with_name(&path, name)
             };
-            
-            pb.finish_with_message(format!("Moved directory {} to Trash", display_name));
-            
-            // Update metadata
-            let trash_name = trash_file_tar_gz.file_name().unwrap().to_string_lossy().to_string();
-            metadata.insert(trash_name, (original_path, true)); // true = directory
+            added.with_context(|| format!("adding {} to bundle", path.display()))?;
+            progress.advance(*size);
         }
-    } else {
-        pb.finish_and_clear();
-        println!("Failed to move: {} not found", file);
-        return Ok(());
+
+        let file = bundle.into_inner().and_then(BundleWriter::finish).with_context(writing)?;
+        file.sync_all().with_context(writing)
+    };
+
+    // Do not leave a truncated bundle behind
+    if let Err(e) = write_bundle() {
+        let _ = fs::remove_file(output);
+        return Err(e);
     }
 
-    // Save the updated metadata
-    save_metadata_with_type(&metadata_file, &metadata)?;
-    Ok(())
+    progress.finish(&format!("Exported {} item(s) to {}", names.len(), output.display()));
+    Ok(names.len())
 }
 
-/// Convert old metadata format to new format if needed
-fn convert_metadata_if_needed(old_metadata: &HashMap<String, String>) -> HashMap<String, (String, bool)> {
-    let mut new_metadata = HashMap::new();
-    
-    for (key, value) in old_metadata {
-        // Check if it's already in the new format
-        if value.starts_with("{\"path\":\"") {
-            // Try to parse as JSON
-            if let Ok(item) = serde_json::from_str::<TrashItem>(value) {
-                new_metadata.insert(key.clone(), (item.path, item.is_dir));
-                continue;
-            }
+/// Items and metadata read from a bundle written by `export_trash`
+struct BundleContents {
+    metadata: HashMap<String, TrashItem>,
+    /// Item names at the top of the bundle, with whether each is a directory
+    /// and the size of its data
+    items: BTreeMap<String, (bool, u64)>,
+}
+
+/// Error for a bundle that cannot be read or was not written by `export_trash`
+fn malformed_bundle(bundle: &Path, source: io::Error) -> TrsError {
+    TrsError::ArchiveError { name: bundle.display().to_string(), source }
+}
+
+/// Open `bundle` as a tar archive, whatever its outer compression
+fn open_bundle(bundle: &Path) -> Result<Archive<BundleReader>> {
+    let reading = || format!("reading bundle {}", bundle.display());
+    let file = fs::File::open(bundle).with_context(reading)?;
+    Ok(Archive::new(BundleReader::new(file).with_context(reading)?))
+}
+
+/// Split a bundle entry path into its item name and the path inside the item
+///
+/// Returns `None` for absolute paths and paths containing `..`, which
+/// `export_trash` never writes.
+fn bundle_entry_path(path: &Path) -> Option<(String, PathBuf)> {
+    let mut components = path.components();
+    let name = match components.next()? {
+        Component::Normal(name) => name.to_str()?.to_string(),
+        _ => return None,
+    };
+    let inner = components
+        .map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect::<Option<PathBuf>>()?;
+    Some((name, inner))
+}
+
+/// Read the whole of `bundle`, checking that it has the layout `export_trash` writes
+fn read_bundle(bundle: &Path) -> Result<BundleContents> {
+    let invalid = |message: String| malformed_bundle(bundle, io::Error::new(io::ErrorKind::InvalidData, message));
+    let mut archive = open_bundle(bundle)?;
+    let mut entries = archive.entries().map_err(|e| malformed_bundle(bundle, e))?;
+
+    let mut first = match entries.next() {
+        Some(entry) => entry.map_err(|e| malformed_bundle(bundle, e))?,
+        None => return Err(invalid("the bundle is empty".to_string())),
+    };
+    let is_metadata = first.path().map_err(|e| malformed_bundle(bundle, e))?.as_ref() == Path::new(".metadata");
+    if !is_metadata || !first.header().entry_type().is_file() {
+        return Err(invalid("the first entry is not a .metadata file".to_string()));
+    }
+    let mut content = String::new();
+    first.read_to_string(&mut content).map_err(|e| malformed_bundle(bundle, e))?;
+    // Bundles hold the metadata file as it was, in either format
+    let metadata = if content.trim().is_empty() {
+        HashMap::new()
+    } else {
+        detect_backend(&content).decode(&content).map_err(|e| invalid(format!("its .metadata is not valid: {}", e)))?
+    };
+
+    // Reading every entry also checks that the data decompresses
+    let mut items = BTreeMap::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| malformed_bundle(bundle, e))?;
+        let path = entry.path().map_err(|e| malformed_bundle(bundle, e))?;
+        let (name, inner) = bundle_entry_path(&path)
+            .ok_or_else(|| invalid(format!("entry {} points outside the trash", path.display())))?;
+        if name == ".metadata" {
+            return Err(invalid("it has more than one .metadata file".to_string()));
         }
-        
-        let is_dir = Path::new(value).exists() && Path::new(value).is_dir();
-        new_metadata.insert(key.clone(), (value.clone(), is_dir));
+        let item = items.entry(name).or_insert((false, 0));
+        item.0 |= !inner.as_os_str().is_empty() || entry.header().entry_type().is_dir();
+        item.1 += entry.size();
     }
-    
-    new_metadata
+
+    Ok(BundleContents { metadata, items })
 }
 
-/// Save metadata with type information
-fn save_metadata_with_type(metadata_file: &Path, metadata: &HashMap<String, (String, bool)>) -> io::Result<()> {
-    // Convert to the old format for saving
-    let old_format: HashMap<String, String> = metadata
-        .iter()
-        .map(|(k, (path, is_dir))| {
-            let item = TrashItem {
-                path: path.clone(),
-                is_dir: *is_dir,
-            };
-            (k.clone(), serde_json::to_string(&item).unwrap_or_else(|_| path.clone()))
-        })
-        .collect();
-    
-    save_metadata(metadata_file, &old_format)
+/// Whether two metadata entries describe the same trashed item
+fn same_item(local: Option<&TrashItem>, imported: Option<&TrashItem>) -> bool {
+    match (local, imported) {
+        (Some(local), Some(imported)) => {
+            local.deleted_at.is_some()
+                && local.deleted_at == imported.deleted_at
+                && local.path == imported.path
+                && local.is_dir == imported.is_dir
+        }
+        _ => false,
+    }
 }
 
-/// Display contents of trash folder
-pub fn show_trash_contents(trash_dir: &Path) -> io::Result<()> {
-    let metadata_file = trash_dir.join(".metadata");
-    let old_metadata = load_metadata(&metadata_file)?;
-    let metadata = convert_metadata_if_needed(&old_metadata);
+/// Work out what `import_trash` would do with each item of `bundle`, without changing anything
+///
+/// The whole bundle is read, so a truncated or malformed bundle is reported
+/// here as `ArchiveError`. Items are returned sorted by name.
+pub fn plan_import(trash_dir: &Path, bundle: &Path, options: &ImportOptions) -> Result<Vec<ImportedItem>> {
+    plan_items(trash_dir, &read_bundle(bundle)?, options)
+}
 
+fn plan_items(trash_dir: &Path, contents: &BundleContents, options: &ImportOptions) -> Result<Vec<ImportedItem>> {
+    let local = load_items(&metadata_path(trash_dir))?;
+    let mut existing: HashSet<String> = local.keys().cloned().collect();
     if trash_dir.exists() {
-        let entries = fs::read_dir(trash_dir)?
-            .filter_map(|entry| entry.ok())
-            .map(|entry| entry.file_name().into_string().unwrap_or_default())
-            .filter(|name| name != ".metadata") // Exclude metadata file
-            .collect::<Vec<_>>();
+        let reading = || format!("reading trash directory {}", trash_dir.display());
+        for entry in fs::read_dir(trash_dir).with_context(reading)? {
+            existing.insert(entry.with_context(reading)?.file_name().to_string_lossy().to_string());
+        }
+    }
 
-        if entries.is_empty() {
-            println!("Trash is empty.");
+    // Names taken locally or by items earlier in the bundle
+    let mut in_use = existing.clone();
+    let mut plan = Vec::new();
+    for (name, &(_, size)) in &contents.items {
+        let (action, local_name) = if !in_use.contains(name) {
+            (ImportAction::Add, name.clone())
+        } else if existing.contains(name) && same_item(find_item(&local, name), find_item(&contents.metadata, name)) {
+            (ImportAction::Skip, name.clone())
+        } else if existing.contains(name) && options.replace {
+            (ImportAction::Replace, name.clone())
         } else {
-            println!("{:<5} {:<30} {}", "No.", "Name", "Original Location");
-            
-            for (i, entry) in entries.iter().enumerate() {
-                // Get metadata for this entry
-                let (display_name, _, original_location) = get_entry_display_info(trash_dir, entry, &metadata)?;
-                
-                println!("{:<5} {:<30} {}", i + 1, display_name, original_location);
+            let mut counter = 1;
+            let mut unique_name = numbered_name(name, counter);
+            while in_use.contains(&unique_name) {
+                counter += 1;
+                unique_name = numbered_name(name, counter);
             }
+            (ImportAction::Add, unique_name)
+        };
+        in_use.insert(local_name.clone());
+        plan.push(ImportedItem { name: name.clone(), local_name, action, size });
+    }
+    Ok(plan)
+}
+
+/// Merge the items of a bundle written by `export_trash` into the trash
+///
+/// The bundle is read in full and checked before anything local changes.
+/// Items whose name is taken are stored under a numbered name, or replace
+/// the local item with `replace`. Items already in the trash, with the same
+/// original path and deletion time, are skipped. The extracted bytes are
+/// reported to `progress`. Returns what was done with each item.
+pub fn import_trash(trash_dir: &Path, bundle: &Path, options: &ImportOptions, progress: &impl ProgressSink) -> Result<Vec<ImportedItem>> {
+    reported(progress, import_items(trash_dir, bundle, options, progress))
+}
+
+fn import_items(trash_dir: &Path, bundle: &Path, options: &ImportOptions, progress: &dyn ProgressSink) -> Result<Vec<ImportedItem>> {
+    // Bundles hold archives, which XDG and native trashes would not recognise
+    if xdg::trash_root(trash_dir).is_some() {
+        return Err(TrsError::invalid_input("Bundles cannot be imported into an XDG trash"));
+    }
+    if macos::is_native_trash(trash_dir) {
+        return Err(TrsError::invalid_input("Bundles cannot be imported into the native trash"));
+    }
+    let contents = read_bundle(bundle)?;
+    let plan = plan_items(trash_dir, &contents, options)?;
+    let metadata_file = metadata_path(trash_dir);
+    let mut metadata = load_items(&metadata_file)?;
+
+    fs::create_dir_all(trash_dir).with_context(|| format!("creating trash directory {}", trash_dir.display()))?;
+
+    // Unpack next to the final names first, so a failure leaves the trash as it was
+    let imported: Vec<&ImportedItem> = plan.iter().filter(|item| item.action != ImportAction::Skip).collect();
+    let staging: HashMap<&str, PathBuf> = imported.iter()
+        .map(|item| (item.name.as_str(), trash_dir.join(format!(".import-{}", item.local_name))))
+        .collect();
+    progress.start(imported.iter().map(|item| item.size).sum());
+    if let Err(e) = unpack_bundle(bundle, &staging, progress) {
+        for path in staging.values() {
+            let _ = if path.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) };
         }
-    } else {
-        // Try to create the trs-trash directory
-        match fs::create_dir_all(trash_dir) {
-            Ok(_) => {
-                println!("Trash folder created at: {}", trash_dir.display());
-                println!("Trash is empty.");
-            },
-            Err(e) => {
-                println!("Could not create trash folder at {}: {}", trash_dir.display(), e);
+        return Err(e);
+    }
+
+    for item in &plan {
+        match item.action {
+            ImportAction::Skip => {
+                progress.message(&format!("Skipped {}, already in Trash", item.name));
+                continue;
             }
+            ImportAction::Replace if fs::symlink_metadata(trash_dir.join(&item.local_name)).is_ok() => {
+                remove_entry_with(trash_dir, &item.local_name, &mut metadata, None, progress)?;
+                progress.message(&format!("Replaced {}", item.name));
+            }
+            ImportAction::Replace | ImportAction::Add => {}
+        }
+
+        let staged = &staging[item.name.as_str()];
+        let target = trash_dir.join(&item.local_name);
+        fs::rename(staged, &target).with_context(|| {
+            format!("moving {} to {}", staged.display(), target.display())
+        })?;
+        match find_item(&contents.metadata, &item.name) {
+            Some(entry) => metadata.insert(item.local_name.clone(), entry.clone()),
+            None => metadata.remove(&item.local_name),
+        };
+        if item.local_name != item.name {
+            progress.message(&format!("Imported {} as {}", item.name, item.local_name));
         }
     }
+
+    sync_dir(trash_dir)?;
+    save_items(&metadata_file, &metadata)?;
+    progress.finish(&format!("Imported {} item(s) from {}", imported.len(), bundle.display()));
+    Ok(plan)
+}
+
+/// Extract the items of `bundle` named in `targets` to the paths given there
+fn unpack_bundle(bundle: &Path, targets: &HashMap<&str, PathBuf>, progress: &dyn ProgressSink) -> Result<()> {
+    let mut archive = open_bundle(bundle)?;
+    for entry in archive.entries().map_err(|e| malformed_bundle(bundle, e))? {
+        let mut entry = entry.map_err(|e| malformed_bundle(bundle, e))?;
+        let path = entry.path().map_err(|e| malformed_bundle(bundle, e))?.into_owned();
+        let Some((name, inner)) = bundle_entry_path(&path) else {
+            return Err(malformed_bundle(bundle, io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("entry {} points outside the trash", path.display()),
+            )));
+        };
+
+        // The metadata and skipped items have no target
+        let Some(target) = targets.get(name.as_str()) else {
+            continue;
+        };
+        let target = if inner.as_os_str().is_empty() {
+            target.clone()
+        } else {
+            let target = target.join(inner);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).with_context(|| format!("creating directory {}", parent.display()))?;
+            }
+            target
+        };
+        entry.unpack(&target).with_context(|| format!("extracting {} to {}", path.display(), target.display()))?;
+        progress.advance(entry.size());
+    }
     Ok(())
 }
 
-/// Get display information for an entry
-fn get_entry_display_info(trash_dir: &Path, entry: &str, metadata: &HashMap<String, (String, bool)>) -> io::Result<(String, &'static str, String)> {
-    // Check if it's a directory on disk
-    let path_is_dir = fs::metadata(trash_dir.join(entry))?.is_dir();
-    
-    // Get the type and display name
-    let is_dir = if let Some((_, is_dir)) = metadata.get(entry)
-        .or_else(|| metadata.get(entry.trim_end_matches(".tar.gz")))
-        .or_else(|| metadata.get(entry.trim_end_matches(".gz")))
-        .or_else(|| metadata.get(&format!("{}.tar.gz", entry.trim_end_matches(".tar.gz"))))
-        .or_else(|| metadata.get(&format!("{}.gz", entry.trim_end_matches(".gz")))) {
-        *is_dir
-    } else {
-        path_is_dir
-    };
-    
-    let display_name = if is_dir {
-        format!("{}/", entry.trim_end_matches(".tar.gz").trim_end_matches(".gz"))
-    } else {
-        entry.trim_end_matches(".tar.gz").trim_end_matches(".gz").to_string()
+/// Items of the XDG trash with root `root` that `import_system_item` can move into a trs trash
+///
+/// Only items with both a `.trashinfo` file and data are listed, sorted by
+/// name.
+pub fn system_trash_items(root: &Path) -> Result<Vec<SystemTrashItem>> {
+    let mut items = Vec::new();
+    for name in xdg::described_items(root)? {
+        items.extend(system_trash_item(root, name)?);
+    }
+    Ok(items)
+}
+
+/// The item `name` of the XDG trash with root `root`, if its `.trashinfo` file can be read
+fn system_trash_item(root: &Path, name: String) -> Result<Option<SystemTrashItem>> {
+    let Some(info) = xdg::read_trashinfo(root, &name) else {
+        return Ok(None);
     };
-    
-    let item_type = if is_dir { "Directory" } else { "File" };
-    
-    // Get the original location
-    let original_location = metadata.get(entry)
-        .or_else(|| metadata.get(entry.trim_end_matches(".tar.gz")))
-        .or_else(|| metadata.get(entry.trim_end_matches(".gz")))
-        .or_else(|| metadata.get(&format!("{}.tar.gz", entry.trim_end_matches(".tar.gz"))))
-        .or_else(|| metadata.get(&format!("{}.gz", entry.trim_end_matches(".gz"))))
-        .map(|(path, _)| path.as_str())
-        .unwrap_or("Unknown");
-    
-    Ok((display_name, item_type, original_location.to_string()))
+    let path = root.join("files").join(&name);
+    Ok(Some(SystemTrashItem {
+        is_dir: fs::symlink_metadata(&path).with_context(|| format!("reading metadata of {}", path.display()))?.is_dir(),
+        size: entry_size(&path)?,
+        deleted_at: info.deleted_at,
+        original_path: info.path,
+        name,
+    }))
 }
 
-/// Restore a file from trash
-pub fn restore_from_trash(file: &str, trash_dir: &Path) -> io::Result<()> {
-    let trash_file = trash_dir.join(file);
-    let metadata_file = trash_dir.join(".metadata");
-    let old_metadata = load_metadata(&metadata_file)?;
-    let mut metadata = convert_metadata_if_needed(&old_metadata);
+/// Move `item` from the XDG trash with root `root` into `trash_dir`, as if it had been trashed with `trs`
+///
+/// The item is stored as by `move_to_trash`, under a name based on its
+/// original file name, and keeps its original location, deletion time and
+/// pin. Its `.trashinfo` file is removed only once it is stored. Imports are
+/// not recorded in the journal.
+pub fn import_system_item(
+    trash_dir: &Path,
+    root: &Path,
+    item: &SystemTrashItem,
+    options: &MoveOptions,
+    progress: &impl ProgressSink,
+) -> Result<MovedItem> {
+    reported(progress, import_system(trash_dir, root, item, options, progress))
+}
 
-    // Find the original location and type
-    let (original_location, is_dir) = match metadata.get(file) {
-        Some((location, is_dir)) => (location.clone(), *is_dir),
-        None => {
-            // If not found in metadata, create a full path in current directory
-            let current_dir = env::current_dir()?.canonicalize()?;
-            let path = current_dir.join(file.trim_end_matches(".tar.gz").trim_end_matches(".gz")).to_string_lossy().to_string();
-            
-            // Check if the trash item is a directory
-            let is_dir = trash_file.is_dir();
-            (path, is_dir)
-        },
-    };
-    let original_file = Path::new(&original_location);
+fn import_system(
+    trash_dir: &Path,
+    root: &Path,
+    item: &SystemTrashItem,
+    options: &MoveOptions,
+    progress: &dyn ProgressSink,
+) -> Result<MovedItem> {
+    if keeps_items_as_is(trash_dir) {
+        return Err(TrsError::invalid_input("Items can only be imported from the system trash into a trs trash directory"));
+    }
+    let xdg_metadata_file = metadata_path(&root.join("files"));
+    let mut xdg_metadata = load_items(&xdg_metadata_file)?;
+    let xdg_item = xdg_metadata.remove(&item.name);
+    let pinned = xdg_item.as_ref().is_some_and(|xdg_item| xdg_item.pinned);
+    let source = root.join("files").join(&item.name);
+    let moved = import_as(trash_dir, &source, &item.original_path, item.deleted_at, pinned, options, progress)?;
 
-    // Create a progress bar
-    let pb = ProgressBar::new(100);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
-        .unwrap()
-        .progress_chars("#>-"));
-    pb.set_message(format!("Restoring {} from Trash", file));
-    pb.set_position(10);
+    xdg::remove_trashinfo(root, &item.name)?;
+    if xdg_item.is_some() {
+        save_items(&xdg_metadata_file, &xdg_metadata)?;
+    }
+    info!(name = %item.name, trash_name = %moved.trash_name, path = %item.original_path, "imported from system trash");
+    Ok(moved)
+}
+
+/// Move the item described by the `.trashinfo` file `info_file` into `trash_dir`
+///
+/// `info_file` must be in the `info/` directory of an XDG trash, whose
+/// `files/` directory holds the item under the same name. The item is
+/// imported as by `import_system_item`.
+pub fn import_trashinfo(trash_dir: &Path, info_file: &Path, options: &MoveOptions, progress: &impl ProgressSink) -> Result<MovedItem> {
+    reported(progress, import_info_file(trash_dir, info_file, options, progress))
+}
 
-    // Create parent directories if they don't exist
-    if let Some(parent) = original_file.parent() {
-        fs::create_dir_all(parent)?;
+fn import_info_file(trash_dir: &Path, info_file: &Path, options: &MoveOptions, progress: &dyn ProgressSink) -> Result<MovedItem> {
+    let not_trashinfo = || TrsError::invalid_input(format!("{} is not a .trashinfo file of an XDG trash", info_file.display()));
+    let info_dir = info_file.parent().filter(|dir| dir.file_name().is_some_and(|name| name == "info")).ok_or_else(not_trashinfo)?;
+    let root = info_dir.parent().unwrap_or(Path::new("."));
+    let name = info_file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(".trashinfo"))
+        .ok_or_else(not_trashinfo)?;
+    if fs::symlink_metadata(root.join("files").join(name)).is_err() {
+        return Err(TrsError::SourceNotFound(root.join("files").join(name)));
     }
-    pb.set_position(20);
+    let item = system_trash_item(root, name.to_string())?.ok_or_else(not_trashinfo)?;
+    import_system(trash_dir, root, &item, options, progress)
+}
 
-    if trash_file.is_file() {
-        let file_stem = file.trim_end_matches(".tar.gz").trim_end_matches(".gz");
-        
-        // Handle different file types
-        if file.ends_with(".tar.gz") {
-            // Extract tar.gz archive
-            pb.set_message("Reading archive...");
-            pb.set_position(30);
-            
-            let tar_gz = fs::File::open(&trash_file)?;
-            let tar = GzDecoder::new(tar_gz);
-            let mut archive = Archive::new(tar);
-            
-            pb.set_message("Extracting files...");
-            pb.set_position(50);
-            
-            // If it's a directory archive, extract to parent directory
-            if is_dir {
-                // Extract to parent directory
-                let parent = original_file.parent().unwrap_or(Path::new("."));
-                archive.unpack(parent)?;
-                pb.finish_with_message(format!("Restored directory {} from Trash", file_stem));
-            } else {
-                // For single files, extract just that file to its correct location
-                for entry in archive.entries()? {
-                    let mut entry = entry?;
-                    let _entry_path = entry.path()?;  // Prefix with underscore to indicate intentional non-use
-                    
-                    // If it's a single file, extract with the correct name
-                    entry.unpack(original_file)?;
-                    break; // Only extract the first file
-                }
-                pb.finish_with_message(format!("Restored file {} from Trash", file_stem));
+/// Move `path` into `trash_dir` as an item trashed from `original_path`, or from where it is
+///
+/// This takes in files that were never anywhere else, or whose original
+/// location is known better than their current one. The item is stored as
+/// by `move_to_trash`, under the file name of its original path, and is
+/// deleted now as far as age filters are concerned. Imports are not
+/// recorded in the journal.
+///
+/// ```
+/// use std::fs;
+/// use trs::metadata::{load_items, metadata_path};
+/// use trs::trash::{import_path, MoveOptions};
+/// use trs::NoOpProgress;
+///
+/// let dir = std::env::temp_dir().join(format!("trs-import-doc-{}", std::process::id()));
+/// let trash_dir = dir.join("trash");
+/// fs::create_dir_all(&dir).unwrap();
+/// fs::write(dir.join("download.tmp"), "quarterly numbers").unwrap();
+///
+/// let original_path = std::path::absolute("/home/me/report.txt").unwrap();
+/// let original_path = original_path.to_str().unwrap();
+/// let moved = import_path(&trash_dir, &dir.join("download.tmp"), Some(original_path), &MoveOptions::default(), &NoOpProgress).unwrap();
+/// assert!(moved.trash_name.starts_with("report.txt"));
+/// assert_eq!(moved.original_path, original_path);
+/// assert_eq!(load_items(&metadata_path(&trash_dir)).unwrap()[&moved.trash_name].path, original_path);
+/// assert!(!dir.join("download.tmp").exists());
+/// fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn import_path(
+    trash_dir: &Path,
+    path: &Path,
+    original_path: Option<&str>,
+    options: &MoveOptions,
+    progress: &impl ProgressSink,
+) -> Result<MovedItem> {
+    reported(progress, import_file(trash_dir, path, original_path, options, progress))
+}
+
+fn import_file(trash_dir: &Path, path: &Path, original_path: Option<&str>, options: &MoveOptions, progress: &dyn ProgressSink) -> Result<MovedItem> {
+    if keeps_items_as_is(trash_dir) {
+        return Err(TrsError::invalid_input("Items can only be imported into a trs trash directory"));
+    }
+    let original_path = match original_path {
+        Some(original_path) => {
+            let absolute = std::path::absolute(original_path).map(normalize_path);
+            let absolute = absolute.with_context(|| format!("resolving {}", original_path))?;
+            if absolute.file_name().is_none() {
+                return Err(TrsError::invalid_input(format!("{} does not name a file or directory", original_path)));
             }
-        } else if file.ends_with(".gz") {
-            // Handle legacy .gz format for backward compatibility
-            pb.set_message("Decompressing file...");
-            pb.set_position(40);
-            
-            let mut decoder = GzDecoder::new(fs::File::open(&trash_file)?);
-            let mut restored_content = Vec::new();
-            io::copy(&mut decoder, &mut restored_content)?;
-            
-            pb.set_message("Writing file...");
-            pb.set_position(80);
-            
-            fs::write(original_file, restored_content)?;
-            pb.finish_with_message(format!("Restored file {} from Trash", file_stem));
-        } else {
-            // Just copy the file as is (no compression)
-            pb.set_message("Copying file...");
-            pb.set_position(50);
-            
-            fs::copy(&trash_file, original_file)?;
-            pb.finish_with_message(format!("Restored file {} from Trash", file_stem));
+            absolute.to_string_lossy().to_string()
         }
-        
-        // Delete the trash file
-        pb.set_message("Cleaning up...");
-        pb.set_position(90);
-        fs::remove_file(trash_file)?;
-    } else if trash_file.is_dir() && is_dir {
-        // For raw directory (not archived), just move it back
-        pb.set_message("Moving directory...");
-        pb.set_position(50);
-        
-        fs::rename(&trash_file, original_file)?;
-        pb.finish_with_message(format!("Restored directory {} from Trash", file));
-    } else {
-        pb.finish_and_clear();
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Failed to restore: {} not found in Trash or type mismatch", file),
-        ));
-    }
+        None => {
+            let absolute = fs::canonicalize(path).map(normalize_path).map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => TrsError::SourceNotFound(path.to_path_buf()),
+                _ => TrsError::Io { context: format!("resolving {}", path.display()), source: e },
+            })?;
+            absolute.to_string_lossy().to_string()
+        }
+    };
+    let moved = import_as(trash_dir, path, &original_path, None, false, options, progress)?;
+    info!(source = %path.display(), trash_name = %moved.trash_name, path = %original_path, "imported");
+    Ok(moved)
+}
 
-    // Update metadata
-    pb.set_message("Updating metadata...");
-    pb.set_position(95);
-    metadata.remove(file);
-    save_metadata_with_type(&metadata_file, &metadata)?;
-    pb.finish_and_clear();
-    Ok(())
+/// Move `source` into `trash_dir` as if it had been trashed from `original_path`
+///
+/// The deletion time is replaced with `deleted_at` when known, and the pin
+/// with `pinned`. The trash's quota is not applied and the item is not
+/// moved to a trash on its own device.
+fn import_as(
+    trash_dir: &Path,
+    source: &Path,
+    original_path: &str,
+    deleted_at: Option<u64>,
+    pinned: bool,
+    options: &MoveOptions,
+    progress: &dyn ProgressSink,
+) -> Result<MovedItem> {
+    let file_name = Path::new(original_path)
+        .file_name()
+        .or_else(|| source.file_name())
+        .map(|name| name.to_string_lossy().to_string());
+    let options = MoveOptions {
+        name: file_name,
+        max_size: None,
+        device_trash: false,
+        ..options.clone()
+    };
+    let mut moved = move_item(&source.to_string_lossy(), trash_dir, &options, progress)?;
+
+    // Replace what moving recorded with what is known of the item
+    let metadata_file = metadata_path(trash_dir);
+    let mut metadata = load_items(&metadata_file)?;
+    if let Some(stored) = metadata.get_mut(&moved.trash_name) {
+        stored.path = original_path.to_string();
+        stored.deleted_at = deleted_at.or(stored.deleted_at);
+        stored.pinned = pinned;
+    }
+    save_items(&metadata_file, &metadata)?;
+    moved.original_path = original_path.to_string();
+    Ok(moved)
 }
 
-/// Empty trash folder permanently
-pub fn empty_trash(trash_dir: &Path) -> io::Result<()> {
-    if trash_dir.exists() {
-        // Create progress bar
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(ProgressStyle::default_spinner()
-            .template("{spinner:.green} {elapsed_precise} {msg}")
-            .unwrap());
-        pb.set_message("Counting items in Trash...");
-        
-        // Count the number of entries for better progress indication
-        let entry_count = fs::read_dir(trash_dir)?
-            .filter_map(|entry| entry.ok())
-            .count();
-        
-        if entry_count > 0 {
-            // Switch to a progress bar if there are items to delete
-            let pb = ProgressBar::new(entry_count as u64);
-            pb.set_style(ProgressStyle::default_bar()
-                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.red/yellow}] {pos}/{len} {msg}")
-                .unwrap()
-                .progress_chars("#>-"));
-            pb.set_message("Emptying Trash...");
-            
-            // Instead of removing the whole directory at once, remove items one by one for progress updates
-            for entry_result in fs::read_dir(trash_dir)? {
-                if let Ok(entry) = entry_result {
-                    let path = entry.path();
-                    if path.is_dir() {
-                        fs::remove_dir_all(path)?;
-                    } else {
-                        fs::remove_file(path)?;
-                    }
-                    pb.inc(1);
-                }
+/// Parse a selection such as `1,3-5`, `1 3 5` or `all` into zero-based indices
+///
+/// Items are returned in the order given, without duplicates. The error is
+/// the message to show the user.
+fn parse_selection(input: &str, count: usize) -> std::result::Result<Vec<usize>, &'static str> {
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("all") {
+        return Ok((0..count).collect());
+    }
+
+    let mut selected = Vec::new();
+    for part in input.split([',', ' ']).filter(|part| !part.is_empty()) {
+        let number = |raw: &str| raw.trim().parse::<usize>().map_err(|_| "Invalid input.");
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => (number(start)?, number(end)?),
+            None => (number(part)?, number(part)?),
+        };
+        if start == 0 || start > end || end > count {
+            return Err("Invalid choice.");
+        }
+        for i in start - 1..end {
+            if !selected.contains(&i) {
+                selected.push(i);
             }
-            
-            pb.finish_with_message("Trash emptied successfully");
-        } else {
-            pb.finish_with_message("Trash was already empty");
         }
+    }
+
+    if selected.is_empty() {
+        Err("Invalid input.")
     } else {
-        println!("Trash is already empty");
+        Ok(selected)
     }
-    Ok(())
 }
 
-/// Interactive restore from trash
-pub fn interactive_restore(trash_dir: &Path) -> io::Result<()> {
+/// Interactive restore from trash, reporting the restore of the chosen item to `progress`
+pub fn interactive_restore(trash_dir: &Path, options: &RestoreOptions, progress: &impl ProgressSink) -> Result<()> {
     if trash_dir.exists() {
-        // Create a spinner while loading trash contents
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(ProgressStyle::default_spinner()
-            .template("{spinner:.green} {elapsed_precise} {msg}")
-            .unwrap());
-        pb.set_message("Loading trash contents...");
-        
-        let metadata_file = trash_dir.join(".metadata");
-        let old_metadata = load_metadata(&metadata_file)?;
-        let metadata = convert_metadata_if_needed(&old_metadata);
-        
-        let entries = fs::read_dir(trash_dir)?
-            .filter_map(|entry| entry.ok())
-            .map(|entry| entry.file_name().into_string().unwrap_or_default())
-            .filter(|name| name != ".metadata") // Exclude metadata file
-            .collect::<Vec<_>>();
-
-        // Clear the spinner when done
-        pb.finish_and_clear();
+        let entries = list_trash_items(trash_dir)?;
 
         if entries.is_empty() {
             println!("Trash is empty.");
             return Ok(());
         }
 
-        println!("Select a file or directory to restore:");
-        println!("{:<5} {:<30} {}", "No.", "Name", "Original Location");
+        println!("Select the files or directories to restore:");
+        println!("{:<5} {:<30} Original Location", "No.", "Name");
         
         for (i, entry) in entries.iter().enumerate() {
-            let (display_name, _, original_location) = get_entry_display_info(trash_dir, entry, &metadata)?;
+            let (display_name, _, original_location) = get_entry_display_info(&entry.name, entry.is_dir, entry.original_path.as_deref());
             println!("{:<5} {:<30} {}", i + 1, display_name, original_location);
         }
 
-        print!("Enter the number of the item to restore: ");
+        print!("Enter item number(s) to restore (e.g., 1,3-5 or 'all'): ");
         io::stdout().flush()?;
 
         let stdin = io::stdin();
         let input = stdin.lock().lines().next().unwrap_or_else(|| Ok(String::new()))?;
-        if let Ok(choice) = input.trim().parse::<usize>() {
-            if choice > 0 && choice <= entries.len() {
-                let file_to_restore = &entries[choice - 1];
-                restore_from_trash(file_to_restore, trash_dir)?;
-            } else {
-                println!("Invalid choice.");
+        let selection = match parse_selection(&input, entries.len()) {
+            Ok(selection) => selection,
+            Err(message) => {
+                println!("{}", message);
+                return Ok(());
             }
-        } else {
-            println!("Invalid input.");
+        };
+
+        // Restore everything that can be, then report what could not
+        let mut failed = Vec::new();
+        for &i in &selection {
+            let file_to_restore = &entries[i].name;
+            if let Err(e) = restore_from_trash(file_to_restore, trash_dir, options, progress) {
+                failed.push((file_to_restore, e));
+            }
+        }
+        for (name, e) in &failed {
+            eprintln!("Failed to restore {}: {}", name, e);
+        }
+        if !failed.is_empty() {
+            return Err(io::Error::other(format!(
+                "{} of {} item(s) could not be restored",
+                failed.len(),
+                selection.len()
+            )).into());
         }
     } else {
         // Try to create the trs-trash directory
@@ -580,3 +5182,284 @@ pub fn interactive_restore(trash_dir: &Path) -> io::Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// A fresh directory below the temporary directory, removed when dropped
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("trs-unit-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A rename that fails as it does between two filesystems
+    fn crosses_devices(_: &Path, _: &Path) -> io::Result<()> {
+        Err(io::ErrorKind::CrossesDevices.into())
+    }
+
+    /// Move `name`, created in `dir` with `contents`, to the trash in `dir` with deduplication on
+    fn trash_deduped(dir: &Path, name: &str, contents: &str) -> String {
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        let options = MoveOptions { dedupe: true, ..MoveOptions::default() };
+        move_to_trash(path.to_str().unwrap(), &dir.join("trash"), &options, &NoOpProgress).unwrap().trash_name
+    }
+
+    /// Files stored in the trash in `dir`, leaving out the metadata and other bookkeeping
+    fn stored_files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(dir.join("trash"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn deduped_entries_restore_one_at_a_time() {
+        let scratch = Scratch::new("dedupe-restore");
+        let dir = &scratch.0;
+        let first = trash_deduped(dir, "a.txt", "same build output");
+        let second = trash_deduped(dir, "b.txt", "same build output");
+        assert_ne!(first, second);
+        assert_eq!(stored_files(dir), [first.as_str(), second.as_str()]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            // Both entries are links to the same archive
+            let a = fs::metadata(dir.join("trash").join(&first)).unwrap();
+            let b = fs::metadata(dir.join("trash").join(&second)).unwrap();
+            assert_eq!((a.ino(), a.nlink()), (b.ino(), 2));
+        }
+
+        let options = RestoreOptions::default();
+        restore_from_trash(&first, &dir.join("trash"), &options, &NoOpProgress).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "same build output");
+        assert_eq!(stored_files(dir), [second.as_str()]);
+
+        restore_from_trash(&second, &dir.join("trash"), &options, &NoOpProgress).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "same build output");
+        assert!(stored_files(dir).is_empty());
+    }
+
+    #[test]
+    fn emptying_one_deduped_entry_keeps_the_other() {
+        let scratch = Scratch::new("dedupe-empty");
+        let dir = &scratch.0;
+        let first = trash_deduped(dir, "a.txt", "same build output");
+        let second = trash_deduped(dir, "b.txt", "same build output");
+        let other = trash_deduped(dir, "c.txt", "other build output");
+
+        let options = EmptyOptions { patterns: vec!["a.txt".to_string()], ..EmptyOptions::default() };
+        let summary = empty_trash(&dir.join("trash"), &options, &NoOpProgress).unwrap();
+        assert_eq!(summary.deleted, [first]);
+        assert_eq!(stored_files(dir), [second.as_str(), other.as_str()]);
+
+        restore_from_trash(&second, &dir.join("trash"), &RestoreOptions::default(), &NoOpProgress).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "same build output");
+
+        empty_trash(&dir.join("trash"), &EmptyOptions::default(), &NoOpProgress).unwrap();
+        assert!(stored_files(dir).is_empty());
+        assert!(list_trash_items(&dir.join("trash")).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn entries_only_share_data_while_linked() {
+        let scratch = Scratch::new("dedupe-shared");
+        let dir = &scratch.0;
+        let trash_dir = dir.join("trash");
+        let first = trash_deduped(dir, "a.txt", "same build output");
+        let second = trash_deduped(dir, "b.txt", "same build output");
+        let metadata = load_items(&metadata_path(&trash_dir)).unwrap();
+        assert_eq!(shared_with(&trash_dir, &first, &metadata), Some(second.as_str()));
+
+        // A copy in place of the link holds the same data, but no longer shares it
+        let copy = dir.join("copy");
+        fs::copy(trash_dir.join(&second), &copy).unwrap();
+        fs::rename(&copy, trash_dir.join(&second)).unwrap();
+        assert_eq!(shared_with(&trash_dir, &first, &metadata), None);
+        assert_eq!(shared_with(&trash_dir, &second, &metadata), None);
+    }
+
+    #[test]
+    fn finish_archive_renames_what_was_written() {
+        let scratch = Scratch::new("finish-archive");
+        let archive = scratch.0.join("a.txt.tar.gz");
+        let partial = partial_path(&archive);
+        let mut file = fs::File::create(&partial).unwrap();
+        file.write_all(b"archive").unwrap();
+        // Space reserved ahead of the data is given back
+        file.set_len(4096).unwrap();
+
+        finish_archive(file, &partial, &archive, &scratch.0).unwrap();
+        assert!(!partial.exists());
+        assert_eq!(fs::read(&archive).unwrap(), b"archive");
+    }
+
+    #[test]
+    fn strip_collision_suffix_removes_only_the_counter() {
+        assert_eq!(strip_collision_suffix("report(1).txt"), "report.txt");
+        assert_eq!(strip_collision_suffix("report(12)"), "report");
+        for kept in ["report.txt", "(1).txt", "report().txt", "report(v2).txt", "report (draft).txt"] {
+            assert_eq!(strip_collision_suffix(kept), kept);
+        }
+    }
+
+    #[test]
+    fn expand_tilde_only_expands_a_leading_tilde() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_tilde("~"), home);
+        assert_eq!(expand_tilde("~/notes.txt"), home.join("notes.txt"));
+        assert_eq!(expand_tilde("~/a/b"), home.join("a/b"));
+        for literal in ["~user/notes.txt", "a/~/b", "notes~", "./~", ""] {
+            assert_eq!(expand_tilde(literal), PathBuf::from(literal));
+        }
+    }
+
+    #[test]
+    fn retry_locked_returns_other_errors_at_once() {
+        let calls = Cell::new(0);
+        let result: io::Result<()> = retry_locked(|| {
+            calls.set(calls.get() + 1);
+            Err(io::ErrorKind::PermissionDenied.into())
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn retry_locked_retries_sharing_violations_on_windows() {
+        const ERROR_SHARING_VIOLATION: i32 = 32;
+
+        let calls = Cell::new(0);
+        let result = retry_locked(|| {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 { Err(io::Error::from_raw_os_error(ERROR_SHARING_VIOLATION)) } else { Ok(calls.get()) }
+        });
+        if cfg!(windows) {
+            assert_eq!(result.unwrap(), 3);
+        } else {
+            // Error 32 is something else elsewhere, EPIPE on Unix
+            assert!(result.is_err());
+            assert_eq!(calls.get(), 1);
+        }
+    }
+
+    #[test]
+    fn retry_locked_gives_up_after_a_few_attempts() {
+        let calls = Cell::new(0);
+        let result: io::Result<()> = retry_locked(|| {
+            calls.set(calls.get() + 1);
+            Err(io::Error::from_raw_os_error(33))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls.get(), if cfg!(windows) { 5 } else { 1 });
+    }
+
+    #[test]
+    fn move_path_copies_a_file_across_devices() {
+        let scratch = Scratch::new("move-file");
+        let from = scratch.0.join("from.txt");
+        let to = scratch.0.join("to.txt");
+        fs::write(&from, "contents").unwrap();
+
+        move_path_with(&from, &to, crosses_devices).unwrap();
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(&to).unwrap(), "contents");
+    }
+
+    #[test]
+    fn move_path_copies_a_directory_across_devices() {
+        let scratch = Scratch::new("move-dir");
+        let from = scratch.0.join("from");
+        let to = scratch.0.join("to");
+        fs::create_dir_all(from.join("nested/empty")).unwrap();
+        fs::write(from.join("top.txt"), "top").unwrap();
+        fs::write(from.join("nested/inner.txt"), "inner").unwrap();
+
+        move_path_with(&from, &to, crosses_devices).unwrap();
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(to.join("top.txt")).unwrap(), "top");
+        assert_eq!(fs::read_to_string(to.join("nested/inner.txt")).unwrap(), "inner");
+        assert!(to.join("nested/empty").is_dir());
+    }
+
+    #[test]
+    fn move_path_keeps_the_source_when_the_copy_fails() {
+        let scratch = Scratch::new("move-fail");
+        let from = scratch.0.join("from.txt");
+        fs::write(&from, "contents").unwrap();
+        // The copy cannot create a file in a directory that does not exist
+        let to = scratch.0.join("missing/to.txt");
+
+        assert!(move_path_with(&from, &to, crosses_devices).is_err());
+        assert_eq!(fs::read_to_string(&from).unwrap(), "contents");
+        assert!(!to.exists());
+    }
+
+    #[test]
+    fn move_path_reports_other_rename_errors() {
+        let scratch = Scratch::new("move-error");
+        let from = scratch.0.join("from.txt");
+        let to = scratch.0.join("to.txt");
+        fs::write(&from, "contents").unwrap();
+
+        let error = move_path_with(&from, &to, |_, _| Err(io::ErrorKind::PermissionDenied.into())).unwrap_err();
+        assert!(error.to_string().contains("from.txt"));
+        assert!(from.exists());
+        assert!(!to.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn user_trash_is_handed_over_to_the_sudo_user() {
+        use std::os::unix::fs::MetadataExt;
+        // Only root may give files away
+        if !owner::is_root() {
+            return;
+        }
+        let scratch = Scratch::new("hand-over");
+        let home = scratch.0.join("home");
+        fs::create_dir(&home).unwrap();
+        let user = owner::SudoUser { name: "someone".to_string(), uid: 4242, gid: 4243, home: home.clone() };
+        let trash_dir = trash_dir_in(&user.data_local_dir());
+        let archived = scratch.0.join("archived.txt");
+        fs::write(&archived, "archived").unwrap();
+        let options = MoveOptions { compress: true, ..MoveOptions::default() };
+        let archived = move_to_trash(archived.to_str().unwrap(), &trash_dir, &options, &NoOpProgress).unwrap();
+        let raw = scratch.0.join("raw.txt");
+        fs::write(&raw, "raw").unwrap();
+        let raw = move_to_trash(raw.to_str().unwrap(), &trash_dir, &MoveOptions::default(), &NoOpProgress).unwrap();
+        assert!(!raw.trash_name.ends_with(".gz"));
+
+        hand_over_trash(&trash_dir, &user).unwrap();
+        let owner = |path: &Path| {
+            let metadata = fs::symlink_metadata(path).unwrap();
+            (metadata.uid(), metadata.gid())
+        };
+        for dir in trash_dir.ancestors().take_while(|dir| *dir != home) {
+            assert_eq!(owner(dir), (4242, 4243), "{}", dir.display());
+        }
+        assert_eq!(owner(&metadata_path(&trash_dir)), (4242, 4243));
+        assert_eq!(owner(&trash_dir.join(&archived.trash_name)), (4242, 4243));
+        // The home directory and items renamed into the trash keep their owner
+        assert_eq!(owner(&home).0, 0);
+        assert_eq!(owner(&trash_dir.join(&raw.trash_name)).0, 0);
+    }
+}
@@ -0,0 +1,151 @@
+//! The `.trashinfo` format of the FreeDesktop.org trash
+//!
+//! Every item in an XDG trash is described by a small key file written by
+//! whichever program trashed it: `trs`, a file manager or `trash-put` from
+//! `trash-cli`. The original location is percent-encoded, and the deletion
+//! date is an RFC 3339 time without its offset, in local time. Some
+//! programs add fractional seconds or an offset anyway, so both are
+//! accepted when reading; only the plain form is written, which is what
+//! `trash-list` and file managers expect.
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+
+/// Format of `DeletionDate`, in local time
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Contents of a `.trashinfo` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashInfo {
+    /// Original location of the item, decoded
+    pub path: String,
+    /// Deletion time in seconds since the Unix epoch, if the date was valid
+    pub deleted_at: Option<u64>,
+}
+
+/// Read the `[Trash Info]` section of a `.trashinfo` file
+///
+/// Returns `None` without a `Path` key, as other programs may leave
+/// incomplete files behind. A `DeletionDate` that cannot be read is left
+/// out rather than failing the whole file.
+///
+/// ```
+/// use trs::trashinfo::parse;
+///
+/// let info = parse("[Trash Info]\nPath=/home/ana/My%20Files/r%C3%A9sum%C3%A9.txt\nDeletionDate=2024-03-01T10:20:30\n").unwrap();
+/// assert_eq!(info.path, "/home/ana/My Files/résumé.txt");
+/// assert!(info.deleted_at.is_some());
+///
+/// // Keys outside the section are ignored
+/// assert!(parse("Path=/tmp/a\n[Other]\nPath=/tmp/b\n").is_none());
+/// ```
+pub fn parse(content: &str) -> Option<TrashInfo> {
+    let mut path = None;
+    let mut deleted_at = None;
+    let mut in_section = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_section = line == "[Trash Info]";
+        } else if in_section && let Some((key, value)) = line.split_once('=') {
+            match key.trim() {
+                "Path" => path = Some(decode_path(value.trim())),
+                "DeletionDate" => deleted_at = parse_date(value.trim()),
+                _ => {}
+            }
+        }
+    }
+    Some(TrashInfo { path: path?, deleted_at })
+}
+
+/// Write the contents of a `.trashinfo` file for an item from `path` deleted at `deleted_at`
+///
+/// ```
+/// use trs::trashinfo::{format, parse};
+///
+/// let content = format("/tmp/a b.txt", 1_700_000_000);
+/// assert!(content.starts_with("[Trash Info]\nPath=/tmp/a%20b.txt\nDeletionDate="));
+/// assert_eq!(parse(&content).unwrap().deleted_at, Some(1_700_000_000));
+/// ```
+pub fn format(path: &str, deleted_at: u64) -> String {
+    format!("[Trash Info]\nPath={}\nDeletionDate={}\n", encode_path(path), format_date(deleted_at))
+}
+
+/// Format a deletion time as a `DeletionDate` in local time, empty if it cannot be represented
+fn format_date(deleted_at: u64) -> String {
+    Local.timestamp_opt(deleted_at as i64, 0)
+        .single()
+        .map(|time| time.format(DATE_FORMAT).to_string())
+        .unwrap_or_default()
+}
+
+/// Parse a `DeletionDate` into seconds since the Unix epoch
+///
+/// Dates are in local time as the spec requires; fractional seconds are
+/// dropped, and a full RFC 3339 time with an offset is read as such.
+fn parse_date(value: &str) -> Option<u64> {
+    let secs = match DateTime::parse_from_rfc3339(value) {
+        Ok(time) => time.timestamp(),
+        Err(_) => {
+            let date = NaiveDateTime::parse_from_str(value, &format!("{}%.f", DATE_FORMAT)).ok()?;
+            Local.from_local_datetime(&date).earliest()?.timestamp()
+        }
+    };
+    u64::try_from(secs).ok()
+}
+
+/// Percent-encode a path for the `Path` key, keeping `/` and unreserved characters
+///
+/// This matches `trash-put`, which quotes everything else byte by byte.
+///
+/// ```
+/// use trs::trashinfo::encode_path;
+///
+/// assert_eq!(encode_path("/home/ana/My Files/résumé.txt"), "/home/ana/My%20Files/r%C3%A9sum%C3%A9.txt");
+/// assert_eq!(encode_path("/tmp/50%_done~"), "/tmp/50%25_done~");
+/// ```
+pub fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Decode a percent-encoded `Path` value, leaving invalid escapes as they are
+///
+/// Escapes are decoded to bytes first, so multi-byte UTF-8 characters come
+/// out whole; bytes that are not valid UTF-8 are replaced.
+///
+/// ```
+/// use trs::trashinfo::decode_path;
+///
+/// assert_eq!(decode_path("/tmp/a%20b/%E6%97%A5%E6%9C%AC.txt"), "/tmp/a b/日本.txt");
+/// assert_eq!(decode_path("/tmp/100%"), "/tmp/100%");
+/// assert_eq!(decode_path("/tmp/%zz"), "/tmp/%zz");
+/// ```
+pub fn decode_path(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
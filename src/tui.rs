@@ -0,0 +1,376 @@
+//! Full-screen terminal interface for browsing, restoring and deleting trash items
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::crypto;
+use crate::config::Config;
+use crate::error::{Result, TrsError};
+use crate::format::{display_path, format_size, format_timestamp};
+use crate::trash::{list_trash_items, purge_entry, read_entry_head, restore_from_trash, strip_storage_suffix, RestoreOptions, TrashEntry};
+use crate::ProgressSink;
+
+/// Number of bytes read from a file for the preview pane
+const PREVIEW_BYTES: u64 = 16 * 1024;
+
+/// Names of the built-in themes
+const THEMES: &[&str] = &["default", "light", "mono"];
+
+/// Colors used by the interface
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub border: Color,
+    pub accent: Color,
+    pub text: Color,
+    pub dim: Color,
+}
+
+impl Theme {
+    /// Look up a built-in theme by name
+    pub fn named(name: &str) -> Result<Self> {
+        match name {
+            "default" => Ok(Theme { border: Color::Blue, accent: Color::Cyan, text: Color::Reset, dim: Color::DarkGray }),
+            "light" => Ok(Theme { border: Color::Gray, accent: Color::Blue, text: Color::Black, dim: Color::Gray }),
+            "mono" => Ok(Theme { border: Color::Reset, accent: Color::Reset, text: Color::Reset, dim: Color::Reset }),
+            _ => Err(TrsError::invalid_input(format!(
+                "Unknown theme '{}'. Valid themes: {}",
+                name,
+                THEMES.join(", ")
+            ))),
+        }
+    }
+
+    /// Theme selected by the `tui_theme` and `tui_accent` config keys
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut theme = Theme::named(&config.string("tui_theme").unwrap_or_else(|| "default".to_string()))?;
+        if let Some(accent) = config.string("tui_accent") {
+            theme.accent = Color::from_str(&accent)
+                .map_err(|_| TrsError::invalid_input(format!("Invalid color '{}' for tui_accent", accent)))?;
+        }
+        Ok(theme)
+    }
+}
+
+/// What keystrokes currently do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Normal,
+    Search,
+    ConfirmDelete,
+}
+
+/// Sink that keeps the summary of a finished operation for the status line
+#[derive(Default)]
+struct StatusSink(Mutex<String>);
+
+impl StatusSink {
+    fn into_status(self) -> String {
+        self.0.into_inner().unwrap_or_default()
+    }
+}
+
+impl ProgressSink for StatusSink {
+    fn start(&self, _total: u64) {}
+    fn advance(&self, _n: u64) {}
+    fn message(&self, _msg: &str) {}
+
+    fn finish(&self, msg: &str) {
+        if let Ok(mut status) = self.0.lock() {
+            *status = msg.to_string();
+        }
+    }
+
+    fn error(&self, _msg: &str) {}
+}
+
+/// State of the interactive interface
+struct App {
+    trash_dir: PathBuf,
+    options: RestoreOptions,
+    theme: Theme,
+    entries: Vec<TrashEntry>,
+    /// Indices into `entries` matching the search query
+    visible: Vec<usize>,
+    list: ListState,
+    mode: Mode,
+    query: String,
+    status: String,
+    /// Name of the entry the preview was read for, and the preview text
+    preview: Option<(String, String)>,
+}
+
+/// Run the interface until the user quits
+pub fn run(trash_dir: &Path, options: &RestoreOptions, theme: Theme) -> Result<()> {
+    let mut app = App::new(trash_dir, options, theme)?;
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn new(trash_dir: &Path, options: &RestoreOptions, theme: Theme) -> Result<Self> {
+        let mut app = App {
+            trash_dir: trash_dir.to_path_buf(),
+            options: options.clone(),
+            theme,
+            entries: Vec::new(),
+            visible: Vec::new(),
+            list: ListState::default(),
+            mode: Mode::Normal,
+            query: String::new(),
+            status: String::new(),
+            preview: None,
+        };
+        app.reload()?;
+        Ok(app)
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                match self.mode {
+                    Mode::Normal => match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        _ => self.handle_normal(key)?,
+                    },
+                    Mode::Search => self.handle_search(key),
+                    Mode::ConfirmDelete => self.handle_confirm(key)?,
+                }
+            }
+        }
+    }
+
+    /// Handle a key in normal mode
+    fn handle_normal(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.list.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.list.select_next(),
+            KeyCode::PageUp => self.list.scroll_up_by(10),
+            KeyCode::PageDown => self.list.scroll_down_by(10),
+            KeyCode::Home => self.list.select_first(),
+            KeyCode::End => self.list.select_last(),
+            KeyCode::Char('/') => {
+                self.mode = Mode::Search;
+                self.status.clear();
+            }
+            KeyCode::Enter => {
+                if let Some(entry) = self.selected() {
+                    let name = entry.name.clone();
+                    let sink = StatusSink::default();
+                    self.status = match restore_from_trash(&name, &self.trash_dir, &self.options, &sink) {
+                        Ok(_) => sink.into_status(),
+                        Err(e) => format!("Failed to restore {}: {}", name, e),
+                    };
+                    self.reload()?;
+                }
+            }
+            KeyCode::Char('d') => {
+                if let Some(entry) = self.selected() {
+                    self.status = format!("Permanently delete {}? (y/n)", entry.name);
+                    self.mode = Mode::ConfirmDelete;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handle a key while typing a search query
+    fn handle_search(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => self.mode = Mode::Normal,
+            KeyCode::Esc => {
+                self.query.clear();
+                self.mode = Mode::Normal;
+                self.apply_filter();
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.apply_filter();
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.apply_filter();
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle the answer to the delete confirmation
+    fn handle_confirm(&mut self, key: KeyEvent) -> Result<()> {
+        self.mode = Mode::Normal;
+        if !matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+            self.status.clear();
+            return Ok(());
+        }
+
+        let Some(entry) = self.selected() else {
+            return Ok(());
+        };
+        let name = entry.name.clone();
+        let sink = StatusSink::default();
+        self.status = match purge_entry(&self.trash_dir, &name, None, &sink) {
+            Ok(_) => sink.into_status(),
+            Err(e) => format!("Failed to delete {}: {}", name, e),
+        };
+        self.reload()
+    }
+
+    /// Re-read the trash after it has changed
+    fn reload(&mut self) -> Result<()> {
+        self.entries = list_trash_items(&self.trash_dir)?;
+        self.entries.sort_by(|a, b| a.name.cmp(&b.name));
+        self.preview = None;
+        self.apply_filter();
+        Ok(())
+    }
+
+    /// Recompute the visible entries from the search query, keeping the selection in range
+    fn apply_filter(&mut self) {
+        let query = self.query.to_lowercase();
+        self.visible = self.entries.iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                entry.name.to_lowercase().contains(&query)
+                    || entry.original_path.as_ref().is_some_and(|path| path.to_lowercase().contains(&query))
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let selected = match self.list.selected() {
+            _ if self.visible.is_empty() => None,
+            Some(i) => Some(i.min(self.visible.len() - 1)),
+            None => Some(0),
+        };
+        self.list.select(selected);
+    }
+
+    fn selected(&self) -> Option<&TrashEntry> {
+        self.list.selected()
+            .and_then(|i| self.visible.get(i))
+            .map(|&i| &self.entries[i])
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let [list, side] = Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(main);
+        let [details, preview] = Layout::vertical([Constraint::Length(8), Constraint::Min(3)]).areas(side);
+
+        self.draw_list(frame, list);
+        self.draw_details(frame, details);
+        self.draw_preview(frame, preview);
+        self.draw_status(frame, status);
+    }
+
+    fn block(&self, title: &str) -> Block<'static> {
+        Block::bordered()
+            .title(title.to_string())
+            .border_style(Style::new().fg(self.theme.border))
+    }
+
+    fn draw_list(&mut self, frame: &mut Frame, area: Rect) {
+        let items = self.visible.iter()
+            .map(|&i| {
+                let entry = &self.entries[i];
+                let name = strip_storage_suffix(&entry.name);
+                let label = if entry.is_dir { format!("{}/", name) } else { name.to_string() };
+                ListItem::new(label).style(Style::new().fg(self.theme.text))
+            })
+            .collect::<Vec<_>>();
+
+        let title = if self.query.is_empty() {
+            format!("Trash ({})", self.entries.len())
+        } else {
+            format!("Trash ({}/{}) /{}", self.visible.len(), self.entries.len(), self.query)
+        };
+        let list = List::new(items)
+            .block(self.block(&title))
+            .highlight_style(Style::new().fg(self.theme.accent).add_modifier(Modifier::BOLD | Modifier::REVERSED))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, area, &mut self.list);
+    }
+
+    fn draw_details(&self, frame: &mut Frame, area: Rect) {
+        let label = |text: &'static str| Span::styled(format!("{:<18}", text), Style::new().fg(self.theme.accent));
+        let lines = match self.selected() {
+            Some(entry) => vec![
+                Line::from(vec![label("Name:"), Span::raw(entry.name.clone())]),
+                Line::from(vec![label("Original Location:"), Span::raw(entry.original_path.as_deref().map(display_path).unwrap_or("Unknown".into()).into_owned())]),
+                Line::from(vec![label("Type:"), Span::raw(if entry.is_dir { "Directory" } else { "File" })]),
+                Line::from(vec![label("Deleted:"), Span::raw(entry.deleted_at.map(format_timestamp).unwrap_or_else(|| "Unknown".to_string()))]),
+                Line::from(vec![label("Size:"), Span::raw(format!("{} ({} bytes)", format_size(entry.size), entry.size))]),
+            ],
+            None => vec![Line::styled("Trash is empty.", Style::new().fg(self.theme.dim))],
+        };
+        frame.render_widget(Paragraph::new(lines).block(self.block("Details")), area);
+    }
+
+    fn draw_preview(&mut self, frame: &mut Frame, area: Rect) {
+        let name = self.selected().map(|entry| entry.name.clone());
+        let text = match name {
+            Some(name) => {
+                if self.preview.as_ref().is_none_or(|(cached, _)| *cached != name) {
+                    let preview = preview_text(&self.trash_dir, &name);
+                    self.preview = Some((name, preview));
+                }
+                self.preview.as_ref().map(|(_, text)| text.clone()).unwrap_or_default()
+            }
+            None => String::new(),
+        };
+        let paragraph = Paragraph::new(text)
+            .style(Style::new().fg(self.theme.text))
+            .wrap(Wrap { trim: false })
+            .block(self.block("Preview"));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn draw_status(&self, frame: &mut Frame, area: Rect) {
+        let line = match self.mode {
+            Mode::Search => Line::from(vec![
+                Span::styled("/", Style::new().fg(self.theme.accent)),
+                Span::raw(self.query.clone()),
+            ]),
+            _ if !self.status.is_empty() => Line::raw(self.status.clone()),
+            _ => Line::styled(
+                "↑/↓ move  Enter restore  d delete  / search  q quit",
+                Style::new().fg(self.theme.dim),
+            ),
+        };
+        frame.render_widget(Paragraph::new(line), area);
+    }
+}
+
+/// Text shown in the preview pane for an entry
+fn preview_text(trash_dir: &Path, name: &str) -> String {
+    if crypto::is_encrypted(name) {
+        return "Encrypted, no preview available.".to_string();
+    }
+    let head = match read_entry_head(trash_dir, name, PREVIEW_BYTES) {
+        Ok(Some(head)) => head,
+        Ok(None) => return "No preview for directories.".to_string(),
+        Err(e) => return format!("Could not read preview: {}", e),
+    };
+
+    // Allow a multi-byte character to be cut off at the end of the preview
+    let text = match std::str::from_utf8(&head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default(),
+        Err(_) => return "Binary file, no preview available.".to_string(),
+    };
+    if text.contains('\0') {
+        "Binary file, no preview available.".to_string()
+    } else {
+        text.to_string()
+    }
+}
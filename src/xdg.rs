@@ -0,0 +1,159 @@
+//! FreeDesktop.org (XDG) trash layout
+//!
+//! An XDG trash keeps trashed items unchanged in `files/` and describes each
+//! one with a `<name>.trashinfo` file in `info/`, so file managers such as
+//! Nautilus and Dolphin can list and restore them. In this mode `trs` uses
+//! `files/` as its trash directory and keeps its own metadata, for the fields
+//! the spec has no place for, in the trash root next to it. The
+//! `.trashinfo` files themselves are read and written by
+//! [`trashinfo`](crate::trashinfo).
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Context, Result, TrsError};
+use crate::trashinfo;
+pub use crate::trashinfo::TrashInfo;
+
+/// The home trash of the current user, `$XDG_DATA_HOME/Trash`
+pub fn home_trash() -> PathBuf {
+    dirs::data_dir()
+        .expect("Could not find data directory")
+        .join("Trash")
+}
+
+/// The `files/` directory of the home trash, creating the trash if needed
+pub fn home_trash_files() -> Result<PathBuf> {
+    let root = home_trash();
+    for dir in [root.join("files"), root.join("info")] {
+        fs::create_dir_all(&dir).with_context(|| format!("creating trash directory {}", dir.display()))?;
+    }
+    Ok(root.join("files"))
+}
+
+/// The `files/` directory of the `.Trash-$uid` trash on the filesystem holding `path`
+///
+/// Only used when `path` is on another filesystem than the home trash and
+/// the `.Trash-$uid` directory at the root of that filesystem already exists,
+/// belongs to the current user and is writable. Its `files/` and `info/`
+/// directories are created if needed, and the trash is recorded so restore
+/// can search it.
+#[cfg(unix)]
+pub fn mount_trash_files(path: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let parent = fs::canonicalize(parent).ok()?;
+    if crate::device::same_device(&parent, &home_trash()).ok()? {
+        return None;
+    }
+
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+    let root = crate::device::mount_root(&parent).ok()?.join(format!(".Trash-{}", uid));
+    let metadata = fs::symlink_metadata(&root).ok()?;
+    if !metadata.is_dir() || metadata.uid() != uid || !is_writable(&root) {
+        return None;
+    }
+    for dir in [root.join("files"), root.join("info")] {
+        fs::create_dir_all(&dir).ok()?;
+    }
+    let files = root.join("files");
+    crate::device::register(&files).ok()?;
+    Some(files)
+}
+
+/// Per-mount trashes are not supported on this platform
+#[cfg(not(unix))]
+pub fn mount_trash_files(_path: &Path) -> Option<PathBuf> {
+    None
+}
+
+/// Whether the current user may write to `path`
+#[cfg(unix)]
+pub(crate) fn is_writable(path: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    CString::new(path.as_os_str().as_bytes())
+        // SAFETY: the pointer is a valid NUL-terminated string for the duration of the call
+        .is_ok_and(|path| unsafe { libc::access(path.as_ptr(), libc::W_OK) } == 0)
+}
+
+/// Root of the XDG trash whose `files/` directory is `trash_dir`, if it is one
+pub fn trash_root(trash_dir: &Path) -> Option<&Path> {
+    let root = trash_dir.parent()?;
+    (trash_dir.file_name()? == "files" && root.join("info").is_dir()).then_some(root)
+}
+
+/// Path of the `.trashinfo` file describing `name`
+pub fn info_path(root: &Path, name: &str) -> PathBuf {
+    root.join("info").join(format!("{}.trashinfo", name))
+}
+
+/// Write the `.trashinfo` file for `name`
+///
+/// The file is created exclusively, which reserves the name as the spec
+/// requires, so this fails with `AlreadyExists` if it is taken.
+pub fn write_trashinfo(root: &Path, name: &str, path: &str, deleted_at: u64) -> Result<()> {
+    let info_file = info_path(root, name);
+    let content = trashinfo::format(path, deleted_at);
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&info_file)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => TrsError::AlreadyExists(info_file.display().to_string()),
+            _ => TrsError::Io { context: format!("creating {}", info_file.display()), source: e },
+        })?;
+    file.write_all(content.as_bytes())
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("writing {}", info_file.display()))
+}
+
+/// Read the `.trashinfo` file for `name`
+///
+/// Returns `None` if it is missing or has no `Path`, as other programs may
+/// leave incomplete files behind. A relative `Path`, which `trash-put`
+/// writes in the trash at the top of a mount, is made absolute against the
+/// directory holding the trash.
+pub fn read_trashinfo(root: &Path, name: &str) -> Option<TrashInfo> {
+    let mut info = trashinfo::parse(&fs::read_to_string(info_path(root, name)).ok()?)?;
+    if Path::new(&info.path).is_relative() {
+        info.path = root.parent().unwrap_or(root).join(&info.path).to_string_lossy().to_string();
+    }
+    Some(info)
+}
+
+/// Names of the items in the trash with root `root` that have a `.trashinfo` file, sorted
+///
+/// Items without one, and `.trashinfo` files whose item is gone, are left
+/// out, as a file manager would.
+pub fn described_items(root: &Path) -> Result<Vec<String>> {
+    let info_dir = root.join("info");
+    let reading = || format!("reading trash directory {}", info_dir.display());
+    let mut names = Vec::new();
+    for entry in fs::read_dir(&info_dir).with_context(reading)? {
+        let file_name = entry.with_context(reading)?.file_name().to_string_lossy().to_string();
+        if let Some(name) = file_name.strip_suffix(".trashinfo")
+            && fs::symlink_metadata(root.join("files").join(name)).is_ok()
+        {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Remove the `.trashinfo` file for `name`, if there is one
+pub fn remove_trashinfo(root: &Path, name: &str) -> Result<()> {
+    let info_file = info_path(root, name);
+    match fs::remove_file(&info_file) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("removing {}", info_file.display()))
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,509 @@
+//! Behavior of the `trs` binary, run against a sandboxed trash
+
+mod common;
+
+use std::fs;
+
+use common::Sandbox;
+use trs::metadata::{load_items, metadata_path, save_items};
+
+#[test]
+fn porcelain_show_is_byte_stable() {
+    let sandbox = Sandbox::new("porcelain-stable");
+    sandbox.file("notes.txt", "notes");
+    sandbox.file("project/src/main.rs", "fn main() {}");
+    common::stdout(&sandbox.trs(&["move", "notes.txt", "project"]));
+    // Windows does not allow tabs in file names
+    let tabbed = cfg!(unix);
+    if tabbed {
+        sandbox.file("tab\tname.txt", "escaped");
+        common::stdout(&sandbox.trs(&["move", "tab\tname.txt"]));
+    }
+
+    let first = sandbox.trs(&["--porcelain", "show"]);
+    let second = sandbox.trs(&["--porcelain", "show"]);
+    assert_eq!(common::stdout(&first), common::stdout(&second));
+    assert_eq!(first.stdout, second.stdout);
+
+    // One tab-separated record per entry, with tabs in names escaped
+    let shown = common::stdout(&first);
+    let records: Vec<Vec<&str>> = shown.lines().map(|line| line.split('\t').collect()).collect();
+    assert_eq!(records.len(), if tabbed { 3 } else { 2 });
+    assert!(records.iter().all(|fields| fields.len() == 5));
+    assert!(!tabbed || records.iter().any(|fields| fields[0] == "tab\\tname.txt"));
+    assert!(records.iter().any(|fields| fields[0] == "project.tar.gz" && fields[1] == "dir"));
+}
+
+#[test]
+fn completion_scripts_complete_entry_names() {
+    let sandbox = Sandbox::new("complete-entries");
+    sandbox.file("notes.txt", "notes");
+    sandbox.file("project/src/main.rs", "fn main() {}");
+    common::stdout(&sandbox.trs(&["move", "notes.txt", "project"]));
+
+    // The hidden subcommand the scripts call lists every entry
+    let listed = common::stdout(&sandbox.trs(&["complete-entries"]));
+    let mut names: Vec<&str> = listed.lines().collect();
+    names.sort();
+    assert_eq!(names, ["notes.txt", "project.tar.gz"]);
+    assert!(!common::stdout(&sandbox.trs(&["--help"])).contains("complete-entries"));
+
+    let bash = common::stdout(&sandbox.trs(&["completions", "bash"]));
+    assert!(bash.contains(r#"" restore purge pin unpin info ""#));
+    assert!(bash.contains("complete -F _trs_with_entries"));
+    let zsh = common::stdout(&sandbox.trs(&["completions", "zsh"]));
+    assert!(zsh.contains("_trs_entries() {"));
+    assert!(zsh.lines().any(|line| line.starts_with("'::entry -- ") && line.ends_with(":_trs_entries' \\")));
+    let fish = common::stdout(&sandbox.trs(&["completions", "fish"]));
+    assert!(fish.contains(r#"complete -c trs -n "__fish_trs_using_subcommand restore" -f -a "(trs complete-entries 2>/dev/null)""#));
+}
+
+#[test]
+fn empty_leaves_no_entries_behind() {
+    let sandbox = Sandbox::new("empty-show");
+    sandbox.file("a.txt", "aaaa");
+    sandbox.file("project/src/main.rs", "fn main() {}");
+    common::stdout(&sandbox.trs(&["move", "a.txt", "project"]));
+    // An entry whose file has already gone from the trash
+    let metadata_file = metadata_path(&sandbox.trash_dir());
+    let mut items = load_items(&metadata_file).unwrap();
+    let orphan = items["a.txt"].clone();
+    items.insert("vanished.txt".to_string(), orphan);
+    save_items(&metadata_file, &items).unwrap();
+
+    common::stdout(&sandbox.trs(&["empty"]));
+    assert_eq!(common::stdout(&sandbox.trs(&["show"])).trim(), "Trash is empty.");
+    assert_eq!(common::stdout(&sandbox.trs(&["--porcelain", "show"])), "");
+    assert!(load_items(&metadata_file).unwrap().is_empty());
+    let stored: Vec<_> = fs::read_dir(sandbox.trash_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .filter(|name| !name.to_string_lossy().starts_with('.'))
+        .collect();
+    assert!(stored.is_empty(), "{:?} left in the trash", stored);
+}
+
+/// `dirs::home_dir` only reads `HOME` on Unix
+#[cfg(unix)]
+#[test]
+fn tilde_paths_are_expanded() {
+    let sandbox = Sandbox::new("tilde");
+    let notes = sandbox.home().join("notes.txt");
+    fs::write(&notes, "notes").unwrap();
+
+    // Arguments reach trs without a shell to expand them
+    let moved = common::stdout(&sandbox.trs(&["--porcelain", "move", "~/notes.txt"]));
+    assert_eq!(moved, "ok\t~/notes.txt\n");
+    assert!(!notes.exists());
+    let items = load_items(&metadata_path(&sandbox.trash_dir())).unwrap();
+    assert_eq!(items["notes.txt"].path, notes.to_str().unwrap());
+
+    common::stdout(&sandbox.trs(&["restore", "notes.txt"]));
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "notes");
+}
+
+#[test]
+fn failures_do_not_stop_the_other_files() {
+    let sandbox = Sandbox::new("partial");
+    let first = sandbox.file("a.txt", "a");
+    let last = sandbox.file("b.txt", "b");
+
+    let output = sandbox.trs(&["a.txt", "missing.txt", "b.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing.txt"));
+    assert!(!first.exists() && !last.exists());
+    let shown = common::stdout(&sandbox.trs(&["--porcelain", "show"]));
+    let mut names: Vec<&str> = shown.lines().filter_map(|line| line.split('\t').next()).collect();
+    names.sort();
+    assert_eq!(names, ["a.txt", "b.txt"]);
+
+    assert_eq!(sandbox.trs(&["missing.txt", "gone.txt"]).status.code(), Some(1));
+    sandbox.file("c.txt", "c");
+    assert_eq!(sandbox.trs(&["c.txt"]).status.code(), Some(0));
+}
+
+#[test]
+fn collided_entries_restore_without_the_counter() {
+    let sandbox = Sandbox::new("collided");
+    for contents in ["first", "second"] {
+        sandbox.file("report.txt", contents);
+        common::stdout(&sandbox.trs(&["move", "--compression", "gzip", "report.txt"]));
+    }
+    let shown = common::stdout(&sandbox.trs(&["--porcelain", "show"]));
+    assert!(shown.lines().any(|line| line.starts_with("report(1).txt.tar.gz\t")), "{}", shown);
+
+    // Without metadata, items are restored to the working directory under their stored name
+    let metadata_file = metadata_path(&sandbox.trash_dir());
+    fs::remove_file(&metadata_file).unwrap();
+    let _ = fs::remove_file(metadata_file.with_extension("journal"));
+    common::stdout(&sandbox.trs(&["restore", "report(1).txt"]));
+    assert_eq!(fs::read_to_string(sandbox.work().join("report.txt")).unwrap(), "second");
+    assert!(!sandbox.work().join("report(1).txt").exists());
+}
+
+#[test]
+fn move_name_sets_the_stored_name() {
+    let sandbox = Sandbox::new("move-name");
+    let report = sandbox.file("report.txt", "report");
+
+    let moved = common::stdout(&sandbox.trs(&["--porcelain", "move", "--name", "q3-report.txt", "report.txt"]));
+    assert_eq!(moved, "ok\treport.txt\n");
+    let items = load_items(&metadata_path(&sandbox.trash_dir())).unwrap();
+    assert_eq!(items["q3-report.txt"].path, report.to_str().unwrap());
+    assert!(sandbox.trash_dir().join("q3-report.txt").exists());
+
+    common::stdout(&sandbox.trs(&["restore", "q3-report.txt"]));
+    assert_eq!(fs::read_to_string(&report).unwrap(), "report");
+
+    for name in ["dir/name.txt", ".metadata"] {
+        let output = sandbox.trs(&["move", "--name", name, "report.txt"]);
+        assert!(!output.status.success(), "--name {} was accepted", name);
+        assert!(report.exists());
+    }
+    sandbox.file("other.txt", "other");
+    assert!(!sandbox.trs(&["move", "--name", "both.txt", "report.txt", "other.txt"]).status.success());
+}
+
+#[test]
+fn glob_expands_quoted_patterns() {
+    let sandbox = Sandbox::new("glob");
+    let logs = [sandbox.file("a.log", "a"), sandbox.file("b.log", "b"), sandbox.file("logs/c.log", "c")];
+    let kept = sandbox.file("notes.txt", "notes");
+
+    // Without --glob the pattern is a file name, and there is no such file
+    assert_eq!(sandbox.trs(&["move", "*.log"]).status.code(), Some(1));
+    assert!(logs.iter().all(|log| log.exists()));
+
+    common::stdout(&sandbox.trs(&["move", "--glob", "*.log", "logs/*.log"]));
+    assert!(logs.iter().all(|log| !log.exists()));
+    assert!(kept.exists());
+    let shown = common::stdout(&sandbox.trs(&["--porcelain", "show"]));
+    assert_eq!(shown.lines().count(), 3);
+
+    // A pattern that matches nothing counts as a failure
+    let output = sandbox.trs(&["move", "--glob", "*.none"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No files match '*.none'"));
+    let output = sandbox.trs(&["--porcelain", "move", "--glob", "*.none", "notes.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\tnotes.txt\nerr\t*.none\tNo files match\n");
+}
+
+#[test]
+fn error_context_reaches_the_message() {
+    let sandbox = Sandbox::new("error-context");
+    sandbox.file("notes.txt", "notes");
+    fs::write(sandbox.trash_dir(), "not a directory").unwrap();
+
+    let output = sandbox.trs(&["notes.txt"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(sandbox.trash_dir().to_str().unwrap()), "{}", stderr);
+}
+
+#[test]
+fn log_file_records_moves() {
+    let sandbox = Sandbox::new("log-file");
+    let notes = sandbox.file("notes.txt", "notes");
+    let log = sandbox.home().join("trs.jsonl");
+    fs::write(&log, "{\"message\":\"earlier\"}\n").unwrap();
+
+    let output = sandbox.trs(&["--log-file", log.to_str().unwrap(), "notes.txt"]);
+    common::stdout(&output);
+    // Nothing is logged to stderr without RUST_LOG
+    assert!(output.stderr.is_empty());
+
+    let records: Vec<serde_json::Value> =
+        fs::read_to_string(&log).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(records[0]["message"], "earlier");
+    let moved = records.iter().find(|record| record["message"] == "moved").unwrap();
+    assert_eq!(moved["level"], "INFO");
+    assert_eq!(moved["path"], notes.to_str().unwrap());
+    assert_eq!(moved["trash_dir"], sandbox.trash_dir().to_str().unwrap());
+    assert_eq!(moved["trash_name"], "notes.txt");
+    assert_eq!(moved["is_dir"], false);
+    assert_eq!(moved["size"], 5);
+    assert!(moved["duration_ms"].is_u64());
+}
+
+#[test]
+fn version_is_the_package_version() {
+    let sandbox = Sandbox::new("version");
+    let version = env!("CARGO_PKG_VERSION");
+    assert_eq!(common::stdout(&sandbox.trs(&["--version"])), format!("Trash CLI {}\n", version));
+    assert_eq!(common::stdout(&sandbox.trs(&["version"])), format!("trs {}\n", version));
+
+    let build = common::stdout(&sandbox.trs(&["version", "--build"]));
+    let mut lines = build.lines();
+    assert_eq!(lines.next(), Some(format!("trs {}", version).as_str()));
+    let keys: Vec<&str> = lines.filter_map(|line| line.split_once(": ")).map(|(key, _)| key).collect();
+    assert_eq!(keys, ["commit", "rustc", "target", "features"]);
+
+    let json: serde_json::Value = serde_json::from_str(&common::stdout(&sandbox.trs(&["--json", "version", "--build"]))).unwrap();
+    assert_eq!(json["version"], version);
+    assert!(json["rustc"].as_str().is_some_and(|rustc| rustc.starts_with("rustc ")));
+}
+
+#[test]
+fn man_pages_come_from_the_cli_definition() {
+    let sandbox = Sandbox::new("man");
+    let restore = common::stdout(&sandbox.trs(&["man", "restore"]));
+    assert!(restore.contains(".TH trs-restore 1"));
+    assert!(restore.contains("selecting them interactively when no entry is given"));
+    assert!(restore.contains(r"\fB\-\-member\fR"));
+
+    let out_dir = sandbox.home().join("man");
+    common::stdout(&sandbox.trs(&["man", "--out-dir", out_dir.to_str().unwrap()]));
+    for page in ["trs.1", "trs-restore.1", "trs-config-set.1"] {
+        assert!(out_dir.join(page).is_file(), "{} is missing", page);
+    }
+    assert_eq!(fs::read_to_string(out_dir.join("trs-restore.1")).unwrap(), restore);
+    // Hidden subcommands get no page
+    assert!(!out_dir.join("trs-complete-entries.1").exists());
+}
+
+#[test]
+fn restore_preview_lists_every_member() {
+    let sandbox = Sandbox::new("restore-preview");
+    sandbox.file("project/README.md", "readme");
+    sandbox.file("project/src/main.rs", "fn main() {}");
+    sandbox.file("project/src/lib.rs", "");
+    common::stdout(&sandbox.trs(&["move", "project"]));
+    let project = sandbox.work().join("project");
+    let members = [
+        project.clone(),
+        project.join("README.md"),
+        project.join("src"),
+        project.join("src").join("main.rs"),
+        project.join("src").join("lib.rs"),
+    ];
+
+    // Without a terminal to confirm on, nothing is restored
+    let preview = common::stdout(&sandbox.trs(&["restore", "--preview", "project"]));
+    assert!(preview.starts_with(&format!("Restore to:  {}\n", project.display())), "{}", preview);
+    let listed: Vec<&str> = preview.lines().filter_map(|line| line.strip_prefix("  ")).collect();
+    assert_eq!(listed.len(), members.len(), "{}", preview);
+    for member in &members {
+        assert!(listed.contains(&member.to_str().unwrap()), "{} is not listed", member.display());
+    }
+    assert!(!project.exists());
+
+    let json: serde_json::Value = serde_json::from_str(&common::stdout(&sandbox.trs(&["--json", "restore", "--preview", "project"]))).unwrap();
+    assert_eq!(json["contents"].as_array().unwrap().len(), members.len());
+    assert_eq!(json["total_size"], 18);
+
+    common::stdout(&sandbox.trs(&["restore", "--preview", "--yes", "project"]));
+    assert!(members.iter().all(|member| member.exists()));
+}
+
+/// Names of the items `show --json` lists with `args`, sorted
+fn shown_names(sandbox: &Sandbox, args: &[&str]) -> Vec<String> {
+    let shown: Vec<serde_json::Value> =
+        serde_json::from_str(&common::stdout(&sandbox.trs(&[&["--json", "show"], args].concat()))).unwrap();
+    let mut names: Vec<String> = shown.iter().map(|item| item["name"].as_str().unwrap().to_string()).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn show_filters_files_and_directories() {
+    let sandbox = Sandbox::new("show-type");
+    sandbox.file("a.txt", "a");
+    sandbox.file("b.txt", "b");
+    sandbox.file("docs/index.md", "index");
+    sandbox.file("src/main.rs", "fn main() {}");
+    common::stdout(&sandbox.trs(&["move", "a.txt", "b.txt", "docs", "src"]));
+
+    assert_eq!(shown_names(&sandbox, &[]), ["a.txt", "b.txt", "docs.tar.gz", "src.tar.gz"]);
+    assert_eq!(shown_names(&sandbox, &["--files-only"]), ["a.txt", "b.txt"]);
+    assert_eq!(shown_names(&sandbox, &["--dirs-only"]), ["docs.tar.gz", "src.tar.gz"]);
+    let porcelain = common::stdout(&sandbox.trs(&["--porcelain", "show", "--dirs-only"]));
+    assert!(porcelain.lines().all(|line| line.split('\t').nth(1) == Some("dir")), "{}", porcelain);
+
+    let output = sandbox.trs(&["show", "--files-only", "--dirs-only"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
+
+#[test]
+fn interactive_needs_a_terminal() {
+    let sandbox = Sandbox::new("interactive");
+    let conf = sandbox.file("a.conf", "conf");
+
+    let output = sandbox.trs(&["move", "--interactive", "a.conf"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--interactive needs a terminal"));
+    assert!(conf.exists());
+}
+
+#[test]
+fn rm_alias_takes_rm_flags() {
+    let sandbox = Sandbox::new("rm");
+    sandbox.file("build/out/app.o", "object");
+    sandbox.file("old.log", "log");
+
+    common::stdout(&sandbox.trs(&["rm", "-rf", "build"]));
+    assert!(!sandbox.work().join("build").exists());
+    common::stdout(&sandbox.trs(&["mv", "-R", "-f", "--verbose", "old.log"]));
+    assert!(!sandbox.work().join("old.log").exists());
+    assert_eq!(shown_names(&sandbox, &[]), ["build.tar.gz", "old.log"]);
+}
+
+#[test]
+fn empty_keep_deletes_all_but_the_newest() {
+    let sandbox = Sandbox::new("empty-keep");
+    let names = ["a.txt", "b.txt", "c.txt", "d.txt", "e.txt"];
+    for name in names {
+        sandbox.file(name, name);
+    }
+    common::stdout(&sandbox.trs(&[&["move"][..], &names].concat()));
+    // Trashed a minute apart, a.txt first
+    let metadata_file = metadata_path(&sandbox.trash_dir());
+    let mut items = load_items(&metadata_file).unwrap();
+    for (minute, name) in names.iter().enumerate() {
+        items.get_mut(*name).unwrap().deleted_at = Some(1_700_000_000 + 60 * minute as u64);
+    }
+    save_items(&metadata_file, &items).unwrap();
+
+    let dry_run: serde_json::Value =
+        serde_json::from_str(&common::stdout(&sandbox.trs(&["--json", "empty", "--keep", "2", "--dry-run"]))).unwrap();
+    let mut listed: Vec<&str> = dry_run["deleted"].as_array().unwrap().iter().map(|name| name.as_str().unwrap()).collect();
+    listed.sort();
+    assert_eq!(listed, ["a.txt", "b.txt", "c.txt"]);
+    assert_eq!(shown_names(&sandbox, &[]).len(), 5);
+
+    common::stdout(&sandbox.trs(&["empty", "--keep", "2", "--yes"]));
+    assert_eq!(shown_names(&sandbox, &[]), ["d.txt", "e.txt"]);
+    assert_eq!(load_items(&metadata_file).unwrap().len(), 2);
+    assert!(!sandbox.trash_dir().join("a.txt").exists());
+}
+
+#[test]
+fn restore_refuses_to_recreate_a_missing_parent_unasked() {
+    let sandbox = Sandbox::new("missing-parent");
+    let main = sandbox.file("old-project/src/main.rs", "fn main() {}");
+    common::stdout(&sandbox.trs(&["move", main.to_str().unwrap()]));
+    fs::remove_dir_all(sandbox.work().join("old-project")).unwrap();
+
+    // Without a terminal to ask on, the restore fails and says what to do
+    let output = sandbox.trs(&["restore", "main.rs"]);
+    assert_eq!(output.status.code(), Some(73));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--restore-parent") && stderr.contains("--to"), "{}", stderr);
+    assert!(!sandbox.work().join("old-project").exists());
+    assert_eq!(shown_names(&sandbox, &[]), ["main.rs"]);
+
+    let restored = common::stdout(&sandbox.trs(&["restore", "--create-parents", "main.rs"]));
+    assert!(restored.contains(&format!("Created missing directory {}", sandbox.work().join("old-project").display())), "{}", restored);
+    assert_eq!(fs::read_to_string(&main).unwrap(), "fn main() {}");
+
+    // The flag's own name works as well as its alias
+    common::stdout(&sandbox.trs(&["move", main.to_str().unwrap()]));
+    fs::remove_dir_all(sandbox.work().join("old-project")).unwrap();
+    common::stdout(&sandbox.trs(&["restore", "--restore-parent", "main.rs"]));
+    assert!(main.exists());
+}
+
+#[test]
+fn user_trash_needs_sudo() {
+    let sandbox = Sandbox::new("user-trash");
+    let output = sandbox.trs(&["--user-trash", "show"]);
+    assert_eq!(output.status.code(), Some(64));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("only applies when running as root under sudo"));
+    let output = sandbox.trs(&["show", "--all-users"]);
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[cfg(unix)]
+#[test]
+fn sudo_says_which_trash_is_used() {
+    // SUDO_UID is only believed when running as root
+    if !common::is_root() {
+        return;
+    }
+    let sandbox = Sandbox::new("sudo-note");
+    sandbox.file("notes.txt", "notes");
+    // The daemon account, whose trash is only ever read here
+    let sudo = |args: &[&str]| sandbox.command(args).env("SUDO_UID", "1").stdin(std::process::Stdio::null()).output().unwrap();
+
+    let output = sudo(&["move", "notes.txt"]);
+    common::stdout(&output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!("using root's trash in {}", sandbox.trash_dir().display())), "{}", stderr);
+    assert!(stderr.contains("pass --user-trash"), "{}", stderr);
+    assert_eq!(shown_names(&sandbox, &[]), ["notes.txt"]);
+
+    // Both trashes are listed together, with who owns each item
+    let shown = common::stdout(&sudo(&["show", "--all-users"]));
+    assert!(shown.lines().next().is_some_and(|header| header.contains("Owner")), "{}", shown);
+    assert!(shown.contains("notes.txt"), "{}", shown);
+
+    // Machine-readable output goes without the note
+    let output = sudo(&["--json", "show"]);
+    common::stdout(&output);
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn warns_after_adding_to_a_trash_over_warn_size() {
+    let sandbox = Sandbox::new("warn-size");
+    sandbox.config("warn_size = \"1KB\"");
+    sandbox.file("dump.sql", vec![b'x'; 4096]);
+    sandbox.file("more.sql", vec![b'x'; 4096]);
+    let warned = |output: &std::process::Output| String::from_utf8_lossy(&output.stderr).contains("Warning: Trash holds");
+
+    let output = sandbox.trs(&["move", "dump.sql"]);
+    common::stdout(&output);
+    assert!(warned(&output), "{}", String::from_utf8_lossy(&output.stderr));
+
+    // Commands that fail or add nothing do not warn
+    let output = sandbox.trs(&["move", "missing.sql"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!warned(&output));
+    let output = sandbox.trs(&["schedule", "list"]);
+    common::stdout(&output);
+    assert!(!warned(&output));
+    assert!(!warned(&sandbox.trs(&["show"])));
+
+    let output = sandbox.trs(&["move", "--no-warn", "more.sql"]);
+    common::stdout(&output);
+    assert!(!warned(&output), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn imported_files_restore_to_the_given_original_path() {
+    let sandbox = Sandbox::new("import-item");
+    let download = sandbox.file("download.tmp", "quarterly numbers");
+    let report = sandbox.work().join("reports").join("q3.txt");
+    fs::create_dir(sandbox.work().join("reports")).unwrap();
+
+    common::stdout(&sandbox.trs(&["import", "--original-path", report.to_str().unwrap(), "download.tmp"]));
+    assert!(!download.exists());
+    assert!(!report.exists());
+    let items = load_items(&metadata_path(&sandbox.trash_dir())).unwrap();
+    assert_eq!(items.len(), 1);
+    assert!(items.values().all(|item| item.path == report.to_str().unwrap()));
+
+    common::stdout(&sandbox.trs(&["restore", "q3.txt"]));
+    assert_eq!(fs::read_to_string(&report).unwrap(), "quarterly numbers");
+    assert!(!download.exists());
+    assert!(shown_names(&sandbox, &[]).is_empty());
+}
+
+#[test]
+fn color_follows_the_color_flag() {
+    let sandbox = Sandbox::new("color");
+    sandbox.file("project/README.md", "readme");
+    common::stdout(&sandbox.trs(&["move", "project"]));
+    let show = |color: &str| {
+        let output = sandbox.command(&["--color", color, "show"]).env_remove("NO_COLOR").output().unwrap();
+        common::stdout(&output)
+    };
+
+    let plain = show("never");
+    assert!(plain.contains("project/"), "{}", plain);
+    assert!(!plain.contains('\x1b'), "{:?}", plain);
+    let colored = show("always");
+    assert!(colored.contains("\x1b["), "{:?}", colored);
+    assert!(colored.contains("project/"), "{:?}", colored);
+}
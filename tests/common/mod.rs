@@ -0,0 +1,229 @@
+//! Helpers shared by the integration tests
+//!
+//! Each test works in a [`Sandbox`] of its own below the temporary
+//! directory, with a config file pointing `trs` at a trash inside it, so
+//! tests can run in parallel and never touch the user's real trash.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use trs::ProgressSink;
+
+/// A directory holding the files a test trashes, its trash and its config
+pub struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    /// Create an empty sandbox named after the test
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "trs-test-{}-{}-{}",
+            name,
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("work")).unwrap();
+        fs::create_dir_all(root.join("home")).unwrap();
+        // Paths are recorded resolved, as `/private/var` on macOS and without short names on Windows
+        let root = fs::canonicalize(&root).unwrap();
+        let root = match root.to_str().and_then(|root| root.strip_prefix(r"\\?\")) {
+            Some(plain) => PathBuf::from(plain),
+            None => root,
+        };
+        let sandbox = Sandbox { root };
+        // A literal string, so Windows paths need no escaping
+        sandbox.config(&format!("trash_dir = '{}'", sandbox.trash_dir().display()));
+        sandbox
+    }
+
+    /// Directory the files to trash are created in, and the CLI runs in
+    pub fn work(&self) -> PathBuf {
+        self.root.join("work")
+    }
+
+    /// Home directory given to the CLI
+    pub fn home(&self) -> PathBuf {
+        self.root.join("home")
+    }
+
+    /// Trash directory of the sandbox
+    pub fn trash_dir(&self) -> PathBuf {
+        self.root.join("trash")
+    }
+
+    /// Config file of the sandbox
+    pub fn config_path(&self) -> PathBuf {
+        self.root.join("config.toml")
+    }
+
+    /// Add `line` to the config file
+    pub fn config(&self, line: &str) {
+        let mut config = fs::OpenOptions::new().create(true).append(true).open(self.config_path()).unwrap();
+        writeln!(config, "{}", line).unwrap();
+    }
+
+    /// Write `contents` to `name` below the work directory, creating the directories above it
+    pub fn file(&self, name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.work().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Run the `trs` binary with `args` in the work directory
+    pub fn trs(&self, args: &[&str]) -> Output {
+        self.command(args).stdin(Stdio::null()).output().unwrap()
+    }
+
+    /// The command running the `trs` binary with `args` against the sandbox
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_trs"));
+        command
+            .args(args)
+            .current_dir(self.work())
+            .env("TRS_CONFIG", self.config_path())
+            .env("HOME", self.home())
+            .env("XDG_DATA_HOME", self.root.join("data"))
+            .env("NO_COLOR", "1")
+            .env_remove("RUST_LOG")
+            .env_remove("TRS_PASSPHRASE")
+            .env_remove("SUDO_UID");
+        command
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        make_writable(&self.root);
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Give back write permission to everything below `path`, which tests may have taken away
+fn make_writable(path: &Path) {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return;
+    };
+    if metadata.is_symlink() {
+        return;
+    }
+    let mut permissions = metadata.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o700);
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    let _ = fs::set_permissions(path, permissions);
+    if metadata.is_dir()
+        && let Ok(entries) = fs::read_dir(path)
+    {
+        for entry in entries.flatten() {
+            make_writable(&entry.path());
+        }
+    }
+}
+
+/// Standard output of a finished command, checking that it succeeded
+pub fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "trs failed with {}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+/// Whether the tests run as root, who may read and remove anything
+#[cfg(unix)]
+pub fn is_root() -> bool {
+    trs::owner::is_root()
+}
+
+/// Only Unix systems have a root user
+#[cfg(not(unix))]
+pub fn is_root() -> bool {
+    false
+}
+
+/// Bytes that gzip cannot make smaller, from a fixed seed
+pub fn noise(len: usize) -> Vec<u8> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 24) as u8
+        })
+        .collect()
+}
+
+/// What an operation reported to its progress sink
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Start(u64),
+    Advance(u64),
+    Message(String),
+    Finish(String),
+    Error(String),
+}
+
+/// A progress sink that records every event, for asserting on them
+#[derive(Debug, Default)]
+pub struct Recorder {
+    events: Mutex<Vec<Event>>,
+}
+
+impl Recorder {
+    /// Events reported so far, in order
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// The message of the last `finish`
+    pub fn finished(&self) -> Option<String> {
+        self.events().into_iter().rev().find_map(|event| match event {
+            Event::Finish(message) => Some(message),
+            _ => None,
+        })
+    }
+
+    fn push(&self, event: Event) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl ProgressSink for Recorder {
+    fn start(&self, total: u64) {
+        self.push(Event::Start(total));
+    }
+
+    fn advance(&self, n: u64) {
+        self.push(Event::Advance(n));
+    }
+
+    fn message(&self, msg: &str) {
+        self.push(Event::Message(msg.to_string()));
+    }
+
+    fn finish(&self, msg: &str) {
+        self.push(Event::Finish(msg.to_string()));
+    }
+
+    fn error(&self, msg: &str) {
+        self.push(Event::Error(msg.to_string()));
+    }
+}
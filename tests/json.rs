@@ -0,0 +1,158 @@
+//! The `--json` output of the CLI, parsed back with serde
+
+mod common;
+
+use common::Sandbox;
+use serde::Deserialize;
+use serde_json::Value;
+use trs::metadata::{load_items, metadata_path};
+
+/// An element of the array printed by `move --json` and `restore --json`
+#[derive(Debug, Deserialize)]
+struct ItemResult {
+    source: String,
+    status: String,
+    trash_name: Option<String>,
+    path: Option<String>,
+    original_path: Option<String>,
+    size: Option<u64>,
+    error: Option<String>,
+}
+
+/// The object `empty --json` prints
+#[derive(Debug, Deserialize)]
+struct EmptySummary {
+    deleted: Vec<String>,
+    size: u64,
+}
+
+/// A line printed by `show --json-lines`
+#[derive(Debug, Deserialize)]
+struct ShownEntry {
+    name: String,
+    original_path: Option<String>,
+    is_dir: bool,
+    size: u64,
+    deleted_at: Option<u64>,
+    pinned: bool,
+}
+
+/// The object printed on stderr when a command fails with `--json`
+#[derive(Debug, Deserialize)]
+struct ErrorObject {
+    error: String,
+    exit_code: i32,
+}
+
+#[test]
+fn move_prints_a_result_per_file() {
+    let sandbox = Sandbox::new("json-move");
+    sandbox.file("a.txt", "first");
+    sandbox.file("b.txt", "second");
+
+    let output = sandbox.trs(&["--json", "move", "a.txt", "b.txt", "missing.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    let results: Vec<ItemResult> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(results.len(), 3);
+
+    assert_eq!((results[0].source.as_str(), results[0].status.as_str()), ("a.txt", "ok"));
+    assert_eq!(results[0].trash_name.as_deref(), Some("a.txt"));
+    assert!(results[0].original_path.as_deref().is_some_and(|path| path.ends_with("a.txt")));
+    assert_eq!(results[0].size, Some(5));
+    assert_eq!((results[1].source.as_str(), results[1].status.as_str()), ("b.txt", "ok"));
+
+    assert_eq!((results[2].source.as_str(), results[2].status.as_str()), ("missing.txt", "error"));
+    assert!(results[2].error.as_deref().is_some_and(|error| error.contains("missing.txt")));
+    assert!(results[2].trash_name.is_none());
+}
+
+#[test]
+fn restore_prints_a_result_per_item() {
+    let sandbox = Sandbox::new("json-restore");
+    let notes = sandbox.file("notes.txt", "notes");
+    common::stdout(&sandbox.trs(&["move", "notes.txt"]));
+
+    let output = sandbox.trs(&["--json", "restore", "notes.txt"]);
+    let results: Vec<ItemResult> = serde_json::from_str(&common::stdout(&output)).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!((results[0].source.as_str(), results[0].status.as_str()), ("notes.txt", "ok"));
+    assert_eq!(results[0].path.as_deref(), Some(notes.to_str().unwrap()));
+    assert!(notes.exists());
+
+    let output = sandbox.trs(&["--json", "restore", "notes.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+    let results: Vec<ItemResult> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(results[0].status, "error");
+    assert!(results[0].error.as_deref().is_some_and(|error| error.contains("notes.txt")));
+}
+
+#[test]
+fn empty_prints_a_summary() {
+    let sandbox = Sandbox::new("json-empty");
+    sandbox.file("a.txt", "aaaa");
+    sandbox.file("b.txt", "bbbbbb");
+    common::stdout(&sandbox.trs(&["move", "a.txt", "b.txt"]));
+
+    let summary: EmptySummary = serde_json::from_str(&common::stdout(&sandbox.trs(&["--json", "empty"]))).unwrap();
+    let mut deleted = summary.deleted;
+    deleted.sort();
+    assert_eq!(deleted, ["a.txt", "b.txt"]);
+    assert_eq!(summary.size, 10);
+
+    let shown: Vec<Value> = serde_json::from_str(&common::stdout(&sandbox.trs(&["--json", "show"]))).unwrap();
+    assert!(shown.is_empty());
+}
+
+#[test]
+fn errors_are_objects_on_stderr() {
+    let sandbox = Sandbox::new("json-error");
+    let output = sandbox.trs(&["--json", "info", "missing.txt"]);
+    assert_eq!(output.status.code(), Some(66));
+    assert!(output.stdout.is_empty());
+    let error: ErrorObject = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error.exit_code, 66);
+    assert!(error.error.contains("missing.txt"));
+}
+
+#[test]
+fn show_includes_who_trashed_each_item() {
+    let sandbox = Sandbox::new("json-owner");
+    sandbox.file("notes.txt", "notes");
+    common::stdout(&sandbox.trs(&["move", "notes.txt"]));
+
+    let items = load_items(&metadata_path(&sandbox.trash_dir())).unwrap();
+    let item = &items["notes.txt"];
+    assert_eq!(item.user.as_deref(), trs::owner::username());
+    assert_eq!(item.hostname.as_deref(), trs::owner::hostname());
+    assert!(item.user.as_deref().is_some_and(|user| !user.is_empty()));
+    assert!(item.hostname.as_deref().is_some_and(|hostname| !hostname.is_empty()));
+
+    let shown: Vec<Value> = serde_json::from_str(&common::stdout(&sandbox.trs(&["--json", "show"]))).unwrap();
+    assert_eq!(shown.len(), 1);
+    assert_eq!(shown[0]["user"].as_str(), item.user.as_deref());
+    assert_eq!(shown[0]["hostname"].as_str(), item.hostname.as_deref());
+}
+
+#[test]
+fn show_json_lines_prints_an_object_per_line() {
+    let sandbox = Sandbox::new("json-lines");
+    sandbox.file("a.txt", "first");
+    sandbox.file("project/src/main.rs", "fn main() {}");
+    common::stdout(&sandbox.trs(&["move", "a.txt", "project"]));
+    common::stdout(&sandbox.trs(&["pin", "a.txt"]));
+
+    let stdout = common::stdout(&sandbox.trs(&["show", "--json-lines"]));
+    assert!(stdout.ends_with('\n'));
+    let mut entries: Vec<ShownEntry> = stdout.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0].name, "a.txt");
+    assert!(entries[0].original_path.as_deref().is_some_and(|path| path.ends_with("a.txt")));
+    assert!(!entries[0].is_dir && entries[0].pinned);
+    assert_eq!(entries[0].size, 5);
+    assert!(entries[1].name.starts_with("project"));
+    assert!(entries[1].original_path.as_deref().is_some_and(|path| path.ends_with("project")));
+    assert!(entries[1].is_dir && !entries[1].pinned);
+    assert!(entries.iter().all(|entry| entry.deleted_at.is_some()));
+}
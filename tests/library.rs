@@ -0,0 +1,841 @@
+//! Round trips through the library API, on every platform CI runs on
+
+mod common;
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use common::{Event, Recorder, Sandbox};
+use trs::metadata::{load_items, metadata_path};
+use trs::trash::{self, MoveOptions, RestoreOptions, Timestamps, Undone};
+use trs::format::format_size;
+use trs::{NoOpProgress, ProgressSink};
+
+/// Move `path` to the sandbox trash with `options`, returning its entry name
+fn trash(sandbox: &Sandbox, path: &Path, options: &MoveOptions) -> String {
+    trash::move_to_trash(path.to_str().unwrap(), &sandbox.trash_dir(), options, &NoOpProgress).unwrap().trash_name
+}
+
+/// Restore the entry `name` from the sandbox trash to where it came from
+fn restore(sandbox: &Sandbox, name: &str) -> trash::RestoredItem {
+    trash::restore_from_trash(name, &sandbox.trash_dir(), &RestoreOptions::default(), &NoOpProgress).unwrap()
+}
+
+#[test]
+fn small_file_round_trip() {
+    let sandbox = Sandbox::new("lib-small");
+    let path = sandbox.file("notes.txt", "notes");
+
+    let name = trash(&sandbox, &path, &MoveOptions::default());
+    assert!(!path.exists());
+    let restored = restore(&sandbox, &name);
+    assert_eq!(restored.path, path.to_str().unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "notes");
+    assert!(trash::list_trash_items(&sandbox.trash_dir()).unwrap().is_empty());
+}
+
+#[test]
+fn compressed_file_round_trip() {
+    let sandbox = Sandbox::new("lib-compressed");
+    let contents = "compressible ".repeat(10_000);
+    let path = sandbox.file("log.txt", &contents);
+
+    let options = MoveOptions { compress: true, ..MoveOptions::default() };
+    let name = trash(&sandbox, &path, &options);
+    assert!(!path.exists());
+    restore(&sandbox, &name);
+    assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+}
+
+/// Chunk `index` of the large file, which differs from every other chunk
+fn chunk(noise: &[u8], index: u32) -> Vec<u8> {
+    let mut chunk = noise.to_vec();
+    chunk[..4].copy_from_slice(&index.to_le_bytes());
+    chunk
+}
+
+#[test]
+fn large_file_round_trip() {
+    const CHUNK: usize = 1024 * 1024;
+    const CHUNKS: u32 = 200;
+
+    let sandbox = Sandbox::new("lib-large");
+    let path = sandbox.work().join("large.bin");
+    // Repeated noise compresses quickly even in debug builds
+    let noise = common::noise(CHUNK / 64).repeat(64);
+    let mut file = io::BufWriter::new(fs::File::create(&path).unwrap());
+    for index in 0..CHUNKS {
+        file.write_all(&chunk(&noise, index)).unwrap();
+    }
+    file.into_inner().unwrap().sync_all().unwrap();
+
+    // An odd buffer size, so writes do not line up with tar blocks
+    let options = MoveOptions { compress: true, buffer_size: 100_003, ..MoveOptions::default() };
+    let name = trash(&sandbox, &path, &options);
+    assert!(!path.exists());
+    restore(&sandbox, &name);
+
+    let mut restored = io::BufReader::new(fs::File::open(&path).unwrap());
+    let mut read = vec![0u8; CHUNK];
+    for index in 0..CHUNKS {
+        restored.read_exact(&mut read).unwrap();
+        assert!(read == chunk(&noise, index), "chunk {} differs", index);
+    }
+    assert_eq!(restored.read(&mut read).unwrap(), 0, "restored file is too long");
+}
+
+#[test]
+fn directory_round_trip() {
+    let sandbox = Sandbox::new("lib-dir");
+    sandbox.file("project/README.md", "readme");
+    sandbox.file("project/src/main.rs", "fn main() {}");
+    fs::create_dir_all(sandbox.work().join("project/empty")).unwrap();
+    let project = sandbox.work().join("project");
+
+    let name = trash(&sandbox, &project, &MoveOptions::default());
+    assert!(!project.exists());
+    let restored = restore(&sandbox, &name);
+    assert!(restored.is_dir);
+    assert_eq!(fs::read_to_string(project.join("README.md")).unwrap(), "readme");
+    assert_eq!(fs::read_to_string(project.join("src/main.rs")).unwrap(), "fn main() {}");
+    assert!(project.join("empty").is_dir());
+}
+
+#[test]
+fn info_lists_the_files_in_a_directory_archive() {
+    let sandbox = Sandbox::new("lib-info");
+    sandbox.file("project/README.md", "readme");
+    sandbox.file("project/src/main.rs", "fn main() {}");
+    sandbox.file("project/src/lib.rs", "");
+    trash(&sandbox, &sandbox.work().join("project"), &MoveOptions::default());
+
+    // The entry is found without its archive suffix
+    let info = trash::item_info(&sandbox.trash_dir(), "project").unwrap();
+    assert_eq!(info.name, "project.tar.gz");
+    assert!(info.is_dir);
+    assert_eq!(info.compression, "tar.gz");
+    let mut contents = info.contents;
+    contents.sort();
+    assert_eq!(contents, ["project", "project/README.md", "project/src", "project/src/lib.rs", "project/src/main.rs"]);
+    // Listing the archive leaves it in the trash
+    assert!(sandbox.trash_dir().join("project.tar.gz").is_file());
+    assert!(!sandbox.work().join("project").exists());
+}
+
+#[test]
+fn one_member_is_extracted_from_a_directory_archive() {
+    let sandbox = Sandbox::new("lib-member");
+    sandbox.file("project/README.md", "readme");
+    sandbox.file("project/src/main.rs", "fn main() {}");
+    sandbox.file("project/src/lib.rs", "pub fn lib() {}");
+    let project = sandbox.work().join("project");
+    let name = trash(&sandbox, &project, &MoveOptions::default());
+
+    let written =
+        trash::restore_member(&name, "src/main.rs", &sandbox.trash_dir(), &RestoreOptions::default(), &NoOpProgress).unwrap();
+    assert_eq!(written, project.join("src/main.rs"));
+    assert_eq!(fs::read_to_string(&written).unwrap(), "fn main() {}");
+    assert!(!project.join("README.md").exists());
+    assert!(!project.join("src/lib.rs").exists());
+
+    // The archive keeps every member, so the whole item still restores
+    let info = trash::item_info(&sandbox.trash_dir(), &name).unwrap();
+    assert!(info.contents.iter().any(|path| path == "project/src/main.rs"));
+    fs::remove_dir_all(&project).unwrap();
+    restore(&sandbox, &name);
+    assert_eq!(fs::read_to_string(project.join("README.md")).unwrap(), "readme");
+    assert_eq!(fs::read_to_string(project.join("src/lib.rs")).unwrap(), "pub fn lib() {}");
+    assert_eq!(fs::read_to_string(project.join("src/main.rs")).unwrap(), "fn main() {}");
+}
+
+/// Total of the `advance` events in `events`
+fn advanced(events: &[Event]) -> u64 {
+    events.iter().map(|event| if let Event::Advance(n) = event { *n } else { 0 }).sum()
+}
+
+#[test]
+fn progress_is_reported_to_the_sink() {
+    let sandbox = Sandbox::new("lib-progress");
+    let path = sandbox.file("log.txt", "line\n".repeat(50_000));
+
+    let recorder = Recorder::default();
+    let options = MoveOptions { compress: true, ..MoveOptions::default() };
+    let moved = trash::move_to_trash(path.to_str().unwrap(), &sandbox.trash_dir(), &options, &recorder).unwrap();
+    let events = recorder.events();
+    assert_eq!(events[0], Event::Start(250_000));
+    // The archive adds tar headers to the data
+    assert!(advanced(&events) >= 250_000);
+    assert!(recorder.finished().unwrap().starts_with("Moved file log.txt to Trash"));
+    assert!(!events.iter().any(|event| matches!(event, Event::Error(_))));
+
+    let recorder = Recorder::default();
+    trash::restore_from_trash(&moved.trash_name, &sandbox.trash_dir(), &RestoreOptions::default(), &recorder).unwrap();
+    let events = recorder.events();
+    assert_eq!(events[0], Event::Start(moved.size));
+    assert_eq!(advanced(&events), moved.size);
+    assert_eq!(recorder.finished().as_deref(), Some("Restored file log.txt from Trash"));
+
+    trash(&sandbox, &path, &MoveOptions::default());
+    let recorder = Recorder::default();
+    trash::empty_trash(&sandbox.trash_dir(), &trash::EmptyOptions::default(), &recorder).unwrap();
+    assert_eq!(recorder.events(), [Event::Start(250_000), Event::Advance(250_000), Event::Finish("Trash emptied successfully".to_string())]);
+}
+
+/// What was on disk each time a move reported progress
+struct Snapshots<'a> {
+    source: &'a Path,
+    archive: PathBuf,
+    /// Whether the source, the archive and its `.partial` file existed, at each event
+    seen: Mutex<Vec<(bool, bool, bool)>>,
+}
+
+impl Snapshots<'_> {
+    fn snapshot(&self) {
+        let partial = PathBuf::from(format!("{}.partial", self.archive.display()));
+        self.seen.lock().unwrap().push((self.source.exists(), self.archive.exists(), partial.exists()));
+    }
+}
+
+impl ProgressSink for Snapshots<'_> {
+    fn start(&self, _total: u64) {}
+
+    fn advance(&self, _n: u64) {
+        self.snapshot();
+    }
+
+    fn message(&self, _msg: &str) {}
+
+    fn finish(&self, _msg: &str) {
+        self.snapshot();
+    }
+
+    fn error(&self, _msg: &str) {}
+}
+
+#[test]
+fn archives_are_complete_before_the_source_is_removed() {
+    let sandbox = Sandbox::new("lib-sync-order");
+    let path = sandbox.file("log.txt", "line\n".repeat(200_000));
+    let snapshots = Snapshots {
+        source: &path,
+        archive: sandbox.trash_dir().join("log.txt.tar.gz"),
+        seen: Mutex::default(),
+    };
+
+    let options = MoveOptions { compress: true, buffer_size: 4096, ..MoveOptions::default() };
+    trash::move_to_trash(path.to_str().unwrap(), &sandbox.trash_dir(), &options, &snapshots).unwrap();
+    let seen = snapshots.seen.into_inner().unwrap();
+    let (last, writing) = seen.split_last().unwrap();
+    assert!(!writing.is_empty());
+    // While the archive is written, only the .partial file exists next to the source
+    assert!(writing.iter().all(|&seen| seen == (true, false, true)), "{:?}", writing);
+    // The source only goes once the archive has its final name
+    assert_eq!(*last, (false, true, false));
+}
+
+/// Message of the error moving `path` to the sandbox trash with `options`
+fn move_error(sandbox: &Sandbox, path: &Path, options: &MoveOptions) -> String {
+    trash::move_to_trash(path.to_str().unwrap(), &sandbox.trash_dir(), options, &NoOpProgress).unwrap_err().to_string()
+}
+
+#[cfg(unix)]
+#[test]
+fn errors_name_an_unreadable_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Root reads every file
+    if common::is_root() {
+        return;
+    }
+    let sandbox = Sandbox::new("lib-unreadable");
+    let path = sandbox.file("secret.txt", "secret");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o200)).unwrap();
+
+    let options = MoveOptions { compress: true, ..MoveOptions::default() };
+    let message = move_error(&sandbox, &path, &options);
+    assert!(message.contains(path.to_str().unwrap()), "{}", message);
+    assert!(path.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn unreadable_files_in_a_directory_are_skipped_unless_strict() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Root reads every file
+    if common::is_root() {
+        return;
+    }
+    let sandbox = Sandbox::new("lib-skipped");
+    let dir = sandbox.work().join("logs");
+    sandbox.file("logs/app.log", "started");
+    let secret = sandbox.file("logs/secret.log", "secret");
+    fs::set_permissions(&secret, fs::Permissions::from_mode(0o200)).unwrap();
+    let options = MoveOptions { compress: true, ..MoveOptions::default() };
+
+    // Strict moves fail on the first unreadable file, leaving the directory and no partial archive
+    let strict = MoveOptions { strict: true, ..options.clone() };
+    let message = move_error(&sandbox, &dir, &strict);
+    assert!(message.contains(secret.to_str().unwrap()), "{}", message);
+    assert!(dir.join("app.log").exists());
+    assert!(fs::read_dir(sandbox.trash_dir()).into_iter().flatten().flatten().all(|entry| !entry.file_name().to_string_lossy().ends_with(".partial")));
+    assert!(trash::list_trash_items(&sandbox.trash_dir()).unwrap().is_empty());
+
+    // Otherwise the rest is archived, and what was skipped is left in place and recorded
+    let recorder = Recorder::default();
+    let moved = trash::move_to_trash(dir.to_str().unwrap(), &sandbox.trash_dir(), &options, &recorder).unwrap();
+    assert_eq!(moved.skipped, [secret.to_str().unwrap()]);
+    let finished = recorder.finished().unwrap();
+    assert!(finished.contains("skipped 1 (permission denied)"), "{}", finished);
+    assert!(secret.exists());
+    assert!(!dir.join("app.log").exists());
+
+    let info = trash::item_info(&sandbox.trash_dir(), &moved.trash_name).unwrap();
+    assert_eq!(info.skipped, moved.skipped);
+    assert!(info.contents.iter().any(|path| path.ends_with("app.log")), "{:?}", info.contents);
+    assert!(!info.contents.iter().any(|path| path.ends_with("secret.log")), "{:?}", info.contents);
+}
+
+#[cfg(unix)]
+#[test]
+fn directories_that_cannot_be_removed_are_put_back() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let sandbox = Sandbox::new("lib-read-only");
+    let project = sandbox.work().join("project");
+    sandbox.file("project/README.md", "readme");
+    sandbox.file("project/src/main.rs", "fn main() {}");
+    sandbox.file("project/locked/notes.txt", "notes");
+    fs::set_permissions(project.join("locked"), fs::Permissions::from_mode(0o555)).unwrap();
+    let options = MoveOptions { compress: true, ..MoveOptions::default() };
+
+    // Root may remove anything, so only other users see removing fail part way
+    if !common::is_root() {
+        let error = trash::move_to_trash(project.to_str().unwrap(), &sandbox.trash_dir(), &options, &NoOpProgress).unwrap_err();
+        assert!(matches!(error, trs::TrsError::ReadOnly { ref blocker, .. } if *blocker == project.join("locked")), "{}", error);
+        assert_eq!(fs::read_to_string(project.join("README.md")).unwrap(), "readme");
+        assert_eq!(fs::read_to_string(project.join("src/main.rs")).unwrap(), "fn main() {}");
+        assert_eq!(fs::read_to_string(project.join("locked/notes.txt")).unwrap(), "notes");
+        assert_eq!(fs::metadata(project.join("locked")).unwrap().permissions().mode() & 0o777, 0o555);
+        assert!(trash::list_trash_items(&sandbox.trash_dir()).unwrap().is_empty());
+        assert!(load_items(&metadata_path(&sandbox.trash_dir())).unwrap().is_empty());
+        let left: Vec<_> = fs::read_dir(sandbox.trash_dir()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert!(left.iter().all(|name| !name.to_string_lossy().starts_with("project")), "{:?}", left);
+
+        // A directory that cannot be taken out of its own is put back even with force
+        let shelf = sandbox.work().join("shelf");
+        sandbox.file("shelf/box/letter.txt", "letter");
+        fs::set_permissions(&shelf, fs::Permissions::from_mode(0o555)).unwrap();
+        let forced = MoveOptions { force: true, ..options.clone() };
+        assert!(trash::move_to_trash(shelf.join("box").to_str().unwrap(), &sandbox.trash_dir(), &forced, &NoOpProgress).is_err());
+        assert_eq!(fs::read_to_string(shelf.join("box/letter.txt")).unwrap(), "letter");
+        assert!(trash::list_trash_items(&sandbox.trash_dir()).unwrap().is_empty());
+        fs::set_permissions(&shelf, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    // With force, the read-only directory is made writable and the whole project trashed
+    let forced = MoveOptions { force: true, ..options };
+    let name = trash(&sandbox, &project, &forced);
+    assert!(!project.exists());
+    restore(&sandbox, &name);
+    assert_eq!(fs::read_to_string(project.join("locked/notes.txt")).unwrap(), "notes");
+    assert_eq!(fs::read_to_string(project.join("src/main.rs")).unwrap(), "fn main() {}");
+}
+
+#[test]
+fn errors_name_the_path_they_are_about() {
+    let sandbox = Sandbox::new("lib-error-context");
+    let path = sandbox.file("notes.txt", "notes ".repeat(100));
+
+    // The trash cannot be created where a file is in the way
+    fs::write(sandbox.trash_dir(), "not a directory").unwrap();
+    let message = move_error(&sandbox, &path, &MoveOptions::default());
+    assert!(message.contains(sandbox.trash_dir().to_str().unwrap()), "{}", message);
+    fs::remove_file(sandbox.trash_dir()).unwrap();
+
+    // An archive that is not one fails to restore naming it
+    let options = MoveOptions { compress: true, ..MoveOptions::default() };
+    let name = trash(&sandbox, &path, &options);
+    fs::write(sandbox.trash_dir().join(&name), "not an archive").unwrap();
+    let message = trash::restore_from_trash(&name, &sandbox.trash_dir(), &RestoreOptions::default(), &NoOpProgress)
+        .unwrap_err()
+        .to_string();
+    assert!(message.contains(&name), "{}", message);
+    assert!(!path.exists());
+}
+
+#[test]
+fn list_returns_every_entry() {
+    let sandbox = Sandbox::new("lib-list");
+    assert!(trash::list_trash_items(&sandbox.trash_dir()).unwrap().is_empty());
+    let notes = sandbox.file("notes.txt", "notes");
+    sandbox.file("project/src/main.rs", "fn main() {}");
+    let project = sandbox.work().join("project");
+    let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    trash(&sandbox, &notes, &MoveOptions::default());
+    trash(&sandbox, &project, &MoveOptions::default());
+
+    let mut entries = trash::list_trash_items(&sandbox.trash_dir()).unwrap();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    let summary: Vec<_> = entries
+        .iter()
+        .map(|entry| (entry.name.as_str(), entry.original_path.as_deref().map(PathBuf::from), entry.is_dir))
+        .collect();
+    assert_eq!(summary, [("notes.txt", Some(notes), false), ("project.tar.gz", Some(project), true)]);
+    assert_eq!(entries[0].size, 5);
+    for entry in &entries {
+        assert!(entry.deleted_at.is_some_and(|deleted_at| deleted_at >= before));
+        assert!(entry.size > 0 && !entry.pinned);
+    }
+}
+
+#[test]
+fn restores_blocked_by_a_file_keep_the_entry() {
+    let sandbox = Sandbox::new("lib-parent-file");
+    let path = sandbox.file("project/src/main.rs", "fn main() {}");
+    let name = trash(&sandbox, &path, &MoveOptions::default());
+    // The directory tree was replaced by a file
+    let project = sandbox.work().join("project");
+    fs::remove_dir_all(&project).unwrap();
+    fs::write(&project, "now a file").unwrap();
+
+    let error = trash::restore_from_trash(&name, &sandbox.trash_dir(), &RestoreOptions::default(), &NoOpProgress).unwrap_err();
+    match &error {
+        trs::TrsError::ParentNotDirectory { blocker, .. } => assert_eq!(blocker, &project),
+        error => panic!("unexpected error: {}", error),
+    }
+    assert_eq!(error.exit_code(), 73);
+    assert!(error.to_string().contains("--to"), "{}", error);
+    assert_eq!(fs::read_to_string(&project).unwrap(), "now a file");
+    let entries = trash::list_trash_items(&sandbox.trash_dir()).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name, name);
+
+    let elsewhere = sandbox.work().join("main.rs");
+    let options = RestoreOptions { destination: Some(elsewhere.clone()), ..RestoreOptions::default() };
+    trash::restore_from_trash(&name, &sandbox.trash_dir(), &options, &NoOpProgress).unwrap();
+    assert_eq!(fs::read_to_string(&elsewhere).unwrap(), "fn main() {}");
+}
+
+#[test]
+fn undo_reverses_a_move() {
+    let sandbox = Sandbox::new("lib-undo-move");
+    let path = sandbox.file("notes.txt", "notes");
+    let name = trash(&sandbox, &path, &MoveOptions::default());
+
+    match trash::undo_last(&sandbox.trash_dir(), &NoOpProgress).unwrap() {
+        Undone::Move(restored) => assert_eq!(restored.trash_name, name),
+        Undone::Restore(_) => panic!("undid a restore"),
+    }
+    assert_eq!(fs::read_to_string(&path).unwrap(), "notes");
+    assert!(trash::list_trash_items(&sandbox.trash_dir()).unwrap().is_empty());
+    // The move was the only operation
+    assert!(trash::undo_last(&sandbox.trash_dir(), &NoOpProgress).is_err());
+}
+
+#[test]
+fn undo_reverses_a_restore() {
+    let sandbox = Sandbox::new("lib-undo-restore");
+    let path = sandbox.file("notes.txt", "notes");
+    let name = trash(&sandbox, &path, &MoveOptions::default());
+    restore(&sandbox, &name);
+
+    match trash::undo_last(&sandbox.trash_dir(), &NoOpProgress).unwrap() {
+        Undone::Restore(moved) => assert_eq!(PathBuf::from(moved.original_path), path),
+        Undone::Move(_) => panic!("undid a move"),
+    }
+    assert!(!path.exists());
+    let entries = trash::list_trash_items(&sandbox.trash_dir()).unwrap();
+    assert_eq!(entries.len(), 1);
+
+    // Stepping further back undoes the original move
+    assert!(matches!(trash::undo_last(&sandbox.trash_dir(), &NoOpProgress).unwrap(), Undone::Move(_)));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "notes");
+}
+
+#[test]
+fn undoing_a_merge_keeps_what_was_already_there() {
+    let sandbox = Sandbox::new("lib-undo-merge");
+    let project = sandbox.work().join("project");
+    sandbox.file("project/a.txt", "trashed");
+    sandbox.file("project/sub/b.txt", "trashed");
+    let name = trash(&sandbox, &project, &MoveOptions::default());
+    sandbox.file("project/a.txt", "existing");
+    sandbox.file("project/c.txt", "existing");
+
+    let options = RestoreOptions { merge: Some(trash::MergePreference::Trashed), ..RestoreOptions::default() };
+    let restored = trash::restore_from_trash(&name, &sandbox.trash_dir(), &options, &NoOpProgress).unwrap();
+    assert_eq!(restored.merged.len(), 3, "{:?}", restored.merged);
+    assert!(matches!(trash::undo_last(&sandbox.trash_dir(), &NoOpProgress).unwrap(), Undone::Restore(_)));
+
+    assert_eq!(fs::read_to_string(project.join("c.txt")).unwrap(), "existing");
+    assert!(!project.join("a.txt").exists());
+    assert!(!project.join("sub").exists());
+    let entries = trash::list_trash_items(&sandbox.trash_dir()).unwrap();
+    assert_eq!(entries.len(), 1);
+
+    // What was undone merges back in again
+    trash::restore_from_trash(&entries[0].name, &sandbox.trash_dir(), &options, &NoOpProgress).unwrap();
+    assert_eq!(fs::read_to_string(project.join("a.txt")).unwrap(), "trashed");
+    assert_eq!(fs::read_to_string(project.join("sub/b.txt")).unwrap(), "trashed");
+    assert_eq!(fs::read_to_string(project.join("c.txt")).unwrap(), "existing");
+}
+
+#[test]
+fn undo_refuses_a_purge() {
+    let sandbox = Sandbox::new("lib-undo-purge");
+    let path = sandbox.file("notes.txt", "notes");
+    let name = trash(&sandbox, &path, &MoveOptions::default());
+    trash::purge_entry(&sandbox.trash_dir(), &name, None, &NoOpProgress).unwrap();
+
+    let error = trash::undo_last(&sandbox.trash_dir(), &NoOpProgress).unwrap_err();
+    assert!(error.to_string().contains(&name), "{}", error);
+    assert!(!path.exists());
+}
+
+#[test]
+fn interrupted_archives_are_ignored_and_emptied() {
+    let sandbox = Sandbox::new("lib-partial");
+    let path = sandbox.file("notes.txt", "notes");
+    trash(&sandbox, &path, &MoveOptions::default());
+    // What a move interrupted while writing the archive leaves behind
+    let partial = sandbox.trash_dir().join("b.txt.tar.gz.partial");
+    fs::write(&partial, "half an archive").unwrap();
+
+    let entries = trash::list_trash_items(&sandbox.trash_dir()).unwrap();
+    assert_eq!(entries.iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>(), ["notes.txt"]);
+    for name in ["b.txt", "b.txt.tar.gz", "b.txt.tar.gz.partial", ".metadata"] {
+        assert!(trash::restore_from_trash(name, &sandbox.trash_dir(), &RestoreOptions::default(), &NoOpProgress).is_err(), "{} restored", name);
+        assert!(trash::item_info(&sandbox.trash_dir(), name).is_err());
+    }
+    let issues = trash::verify_trash(&sandbox.trash_dir()).unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].name, "b.txt.tar.gz.partial");
+
+    let summary = trash::empty_trash(&sandbox.trash_dir(), &trash::EmptyOptions::default(), &NoOpProgress).unwrap();
+    assert_eq!(summary.deleted, ["notes.txt"]);
+    assert!(!partial.exists());
+    assert!(trash::verify_trash(&sandbox.trash_dir()).unwrap().is_empty());
+}
+
+#[test]
+fn renamed_metadata_keeps_the_items_and_the_old_name_reserved() {
+    let sandbox = Sandbox::new("lib-metadata-name");
+    let trash_dir = sandbox.trash_dir();
+    let first = sandbox.file("first.txt", "first");
+    trash(&sandbox, &first, &MoveOptions::default());
+
+    // The metadata under the default name is taken over by the new one
+    let named = trs::metadata::use_metadata_name(".trs-items").unwrap();
+    let second = sandbox.file("second.txt", "second");
+    trash(&sandbox, &second, &MoveOptions::default());
+    let mut paths: Vec<_> = trash::list_trash_items(&trash_dir).unwrap().into_iter().map(|entry| entry.original_path).collect();
+    paths.sort();
+    assert_eq!(paths, [first, second].map(|path| Some(path.to_string_lossy().to_string())));
+    assert_eq!(metadata_path(&trash_dir), trash_dir.join(".trs-items"));
+    assert!(!trash_dir.join(".metadata").exists() && !trash_dir.join(".metadata.journal").exists());
+    drop(named);
+
+    // Metadata written under the default name again is never an item
+    trash(&sandbox, &sandbox.file("third.txt", "third"), &MoveOptions::default());
+    let _named = trs::metadata::use_metadata_name(".trs-items").unwrap();
+    let names: Vec<_> = trash::list_trash_items(&trash_dir).unwrap().into_iter().map(|entry| entry.name).collect();
+    assert_eq!(names.len(), 3, "{:?}", names);
+    assert!(names.iter().all(|name| !name.starts_with(".metadata")), "{:?}", names);
+    for name in [".metadata", ".metadata.journal"] {
+        let restored = trash::restore_from_trash(name, &trash_dir, &RestoreOptions::default(), &NoOpProgress);
+        assert!(matches!(restored, Err(trs::TrsError::NotInTrash(_))), "{:?}", restored);
+    }
+    trash::empty_trash(&trash_dir, &trash::EmptyOptions::default(), &NoOpProgress).unwrap();
+    assert!(trash_dir.join(".metadata").exists() || trash_dir.join(".metadata.journal").exists());
+}
+
+#[test]
+fn saves_after_a_torn_journal_line_keep_the_trash_readable() {
+    let sandbox = Sandbox::new("lib-torn-journal");
+    for name in ["a.txt", "b.txt"] {
+        trash(&sandbox, &sandbox.file(name, name), &MoveOptions::default());
+    }
+    // What a crash while appending to the journal leaves behind
+    let journal = trs::metadata::MetadataJournal::new(&metadata_path(&sandbox.trash_dir()));
+    assert!(journal.path().exists());
+    let mut file = fs::OpenOptions::new().append(true).open(journal.path()).unwrap();
+    file.write_all(br#"{"insert":["x",{"path":"/x""#).unwrap();
+    drop(file);
+
+    trash(&sandbox, &sandbox.file("c.txt", "c.txt"), &MoveOptions::default());
+    let mut names: Vec<String> = trash::list_trash_items(&sandbox.trash_dir()).unwrap().into_iter().map(|entry| entry.name).collect();
+    names.sort();
+    assert_eq!(names, ["a.txt", "b.txt", "c.txt"]);
+    let items = load_items(&metadata_path(&sandbox.trash_dir())).unwrap();
+    assert!(items["c.txt"].path.ends_with("c.txt"));
+    assert!(!items.contains_key("x"));
+}
+
+#[test]
+fn moves_report_the_compression_ratio() {
+    let sandbox = Sandbox::new("lib-ratio");
+    let path = sandbox.file("report.txt", "quarterly numbers\n".repeat(20_000));
+    let recorder = Recorder::default();
+    let options = MoveOptions { compress: true, ..MoveOptions::default() };
+    let moved = trash::move_to_trash(path.to_str().unwrap(), &sandbox.trash_dir(), &options, &recorder).unwrap();
+
+    let expected = format!(
+        "Moved file report.txt to Trash ({} -> {}, {:.0}% saved)",
+        format_size(360_000),
+        format_size(moved.size),
+        (1.0 - moved.size as f64 / 360_000.0) * 100.0
+    );
+    assert_eq!(recorder.finished().unwrap(), expected);
+    assert!(moved.size < 360_000 / 10);
+
+    // Files kept as they are say so instead
+    let path = sandbox.file("small.txt", "small");
+    let recorder = Recorder::default();
+    trash::move_to_trash(path.to_str().unwrap(), &sandbox.trash_dir(), &MoveOptions::default(), &recorder).unwrap();
+    assert_eq!(recorder.finished().unwrap(), "Moved file small.txt to Trash (5 B, stored)");
+}
+
+/// Links are only recognised by their inode on Unix
+#[cfg(unix)]
+#[test]
+fn hard_links_are_archived_once_and_restored() {
+    use std::os::unix::fs::MetadataExt;
+
+    let sandbox = Sandbox::new("lib-hard-links");
+    let data = sandbox.file("backup/data.bin", common::noise(64 * 1024));
+    fs::create_dir_all(sandbox.work().join("backup/older")).unwrap();
+    let link = sandbox.work().join("backup/older/data.bin");
+    fs::hard_link(&data, &link).unwrap();
+    let backup = sandbox.work().join("backup");
+
+    let options = MoveOptions { compress: true, ..MoveOptions::default() };
+    let moved = trash::move_to_trash(backup.to_str().unwrap(), &sandbox.trash_dir(), &options, &NoOpProgress).unwrap();
+    // Noise does not compress, so a second copy would double the archive
+    assert!(moved.size < 64 * 1024 * 3 / 2, "archive is {} bytes", moved.size);
+
+    restore(&sandbox, &moved.trash_name);
+    let (data, link) = (fs::metadata(&data).unwrap(), fs::metadata(&link).unwrap());
+    assert_eq!((data.dev(), data.ino()), (link.dev(), link.ino()));
+    assert_eq!(data.nlink(), 2);
+    assert_eq!(data.len(), 64 * 1024);
+}
+
+#[test]
+fn same_names_get_separate_entries() {
+    let sandbox = Sandbox::new("lib-same-name");
+    let path = sandbox.file("a.txt", "first");
+    let first = trash(&sandbox, &path, &MoveOptions::default());
+    sandbox.file("a.txt", "second");
+    let second = trash(&sandbox, &path, &MoveOptions::default());
+    assert_ne!(first, second);
+
+    restore(&sandbox, &second);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+    fs::remove_file(&path).unwrap();
+    restore(&sandbox, &first);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+}
+
+#[test]
+fn files_below_the_threshold_are_stored_raw() {
+    let sandbox = Sandbox::new("lib-threshold");
+    let tiny = sandbox.file("tiny.txt", "ten bytes!");
+    let large = sandbox.file("large.txt", "x".repeat(2048));
+    let options = MoveOptions { compress: true, compress_threshold: 1024, ..MoveOptions::default() };
+
+    let tiny_name = trash(&sandbox, &tiny, &options);
+    let large_name = trash(&sandbox, &large, &options);
+    assert_eq!(tiny_name, "tiny.txt");
+    assert_ne!(large_name, "large.txt");
+    // The stored file is the original bytes, with no archive around them
+    assert_eq!(fs::read(sandbox.trash_dir().join(&tiny_name)).unwrap(), b"ten bytes!");
+    let items = load_items(&metadata_path(&sandbox.trash_dir())).unwrap();
+    assert!(items[&tiny_name].raw);
+    assert!(!items[&large_name].raw);
+
+    restore(&sandbox, &tiny_name);
+    assert_eq!(fs::read(&tiny).unwrap(), b"ten bytes!");
+    assert!(!sandbox.trash_dir().join(&tiny_name).exists());
+}
+
+#[test]
+fn empty_files_are_stored_raw() {
+    let sandbox = Sandbox::new("lib-empty-file");
+    let path = sandbox.file("empty.log", "");
+    // Even when compression is asked for
+    let options = MoveOptions { compress: true, ..MoveOptions::default() };
+    let moved = trash::move_to_trash(path.to_str().unwrap(), &sandbox.trash_dir(), &options, &NoOpProgress).unwrap();
+    assert_eq!(moved.trash_name, "empty.log");
+    assert_eq!(moved.size, 0);
+    assert_eq!(fs::metadata(sandbox.trash_dir().join("empty.log")).unwrap().len(), 0);
+
+    restore(&sandbox, &moved.trash_name);
+    let restored = fs::metadata(&path).unwrap();
+    assert!(restored.is_file());
+    assert_eq!(restored.len(), 0);
+}
+
+/// Modification time of the file at `path`
+fn modified(path: &Path) -> SystemTime {
+    fs::metadata(path).unwrap().modified().unwrap()
+}
+
+/// Create `name` in the sandbox, last modified at the start of 2020
+fn old_file(sandbox: &Sandbox, name: &str) -> (PathBuf, SystemTime) {
+    let path = sandbox.file(name, "old contents ".repeat(100));
+    let old = UNIX_EPOCH + Duration::from_secs(1_577_836_800);
+    fs::File::options().write(true).open(&path).unwrap().set_modified(old).unwrap();
+    (path, old)
+}
+
+#[test]
+fn keep_timestamps_restores_the_original_mtime() {
+    let sandbox = Sandbox::new("lib-keep-mtime");
+    for compress in [false, true] {
+        let (path, old) = old_file(&sandbox, "old.txt");
+        let options = MoveOptions { timestamps: Timestamps::Keep, compress, ..MoveOptions::default() };
+        let name = trash(&sandbox, &path, &options);
+        restore(&sandbox, &name);
+        assert_eq!(modified(&path), old, "compress: {}", compress);
+        fs::remove_file(&path).unwrap();
+    }
+}
+
+#[test]
+fn reset_timestamps_restores_with_the_current_time() {
+    let sandbox = Sandbox::new("lib-reset-mtime");
+    for compress in [false, true] {
+        let (path, old) = old_file(&sandbox, "old.txt");
+        let options = MoveOptions { timestamps: Timestamps::Reset, compress, ..MoveOptions::default() };
+        let name = trash(&sandbox, &path, &options);
+        // Filesystems may round the time down to the second
+        let before = SystemTime::now() - Duration::from_secs(2);
+        restore(&sandbox, &name);
+        let restored = modified(&path);
+        assert!(restored >= before && restored != old, "compress: {}", compress);
+        fs::remove_file(&path).unwrap();
+    }
+}
+
+#[test]
+fn stored_paths_have_no_verbatim_prefix() {
+    let sandbox = Sandbox::new("lib-stored-path");
+    let path = sandbox.file("notes.txt", "notes");
+    let name = trash(&sandbox, &path, &MoveOptions::default());
+
+    let items = load_items(&metadata_path(&sandbox.trash_dir())).unwrap();
+    let stored = &items[&name].path;
+    assert!(!stored.starts_with(r"\\?\"), "{} was stored", stored);
+    assert_eq!(Path::new(stored), path);
+}
+
+/// Write `contents` to `name` in the sandbox, last modified `secs` seconds after the Unix epoch
+fn file_modified_at(sandbox: &Sandbox, name: &str, contents: &str, secs: u64) -> PathBuf {
+    let path = sandbox.file(name, contents);
+    fs::File::options().write(true).open(&path).unwrap().set_modified(UNIX_EPOCH + Duration::from_secs(secs)).unwrap();
+    path
+}
+
+/// Contents of each file in `project` after merging the trashed copy into it, preferring `prefer`
+fn merged(prefer: trash::MergePreference) -> [String; 3] {
+    let sandbox = Sandbox::new("lib-merge");
+    let project = sandbox.work().join("project");
+    // One file is newer in the trash, one newer in place, and one only in place
+    file_modified_at(&sandbox, "project/newer-trashed.txt", "trashed", 2_000_000_000);
+    file_modified_at(&sandbox, "project/newer-existing.txt", "trashed", 1_000_000_000);
+    let name = trash(&sandbox, &project, &MoveOptions { compress: true, ..MoveOptions::default() });
+    file_modified_at(&sandbox, "project/newer-trashed.txt", "existing", 1_000_000_000);
+    file_modified_at(&sandbox, "project/newer-existing.txt", "existing", 2_000_000_000);
+    sandbox.file("project/only-existing.txt", "existing");
+
+    let options = RestoreOptions { merge: Some(prefer), ..RestoreOptions::default() };
+    let restored = trash::restore_from_trash(&name, &sandbox.trash_dir(), &options, &NoOpProgress).unwrap();
+    assert_eq!(restored.conflicts.len(), 2);
+    for conflict in &restored.conflicts {
+        let contents = fs::read_to_string(&conflict.path).unwrap();
+        assert_eq!(conflict.replaced, contents == "trashed", "{}", conflict.path);
+    }
+    assert!(trash::list_trash_items(&sandbox.trash_dir()).unwrap().is_empty());
+    ["newer-trashed.txt", "newer-existing.txt", "only-existing.txt"].map(|file| fs::read_to_string(project.join(file)).unwrap())
+}
+
+#[test]
+fn merging_keeps_the_preferred_copy() {
+    use trash::MergePreference;
+
+    assert_eq!(merged(MergePreference::Existing), ["existing", "existing", "existing"]);
+    assert_eq!(merged(MergePreference::Trashed), ["trashed", "trashed", "existing"]);
+    assert_eq!(merged(MergePreference::Newer), ["trashed", "existing", "existing"]);
+}
+
+/// Store a gzip archive of the directory `docs` holding `members`, as the entry `docs.tar.gz` trashed from `original`
+fn tampered_archive(sandbox: &Sandbox, original: &Path, members: &[(&str, tar::EntryType, &str)]) {
+    use flate2::{Compression, write::GzEncoder};
+
+    let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for &(name, kind, contents) in members {
+        let mut header = tar::Header::new_old();
+        // Set directly, as `set_path` refuses paths climbing out with `..`
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_entry_type(kind);
+        header.set_mode(0o755);
+        if kind == tar::EntryType::Symlink {
+            header.set_link_name(contents).unwrap();
+            header.set_size(0);
+            header.set_cksum();
+            tar.append(&header, io::empty()).unwrap();
+        } else {
+            header.set_size(contents.len() as u64);
+            header.set_cksum();
+            tar.append(&header, contents.as_bytes()).unwrap();
+        }
+    }
+    fs::create_dir_all(sandbox.trash_dir()).unwrap();
+    fs::write(sandbox.trash_dir().join("docs.tar.gz"), tar.into_inner().unwrap().finish().unwrap()).unwrap();
+    let item = trs::metadata::TrashItem::new(original.to_string_lossy().to_string(), true);
+    trs::metadata::save_items(&metadata_path(&sandbox.trash_dir()), &[("docs.tar.gz".to_string(), item)].into()).unwrap();
+}
+
+#[test]
+fn archives_with_a_bad_last_member_restore_nothing() {
+    use tar::EntryType;
+
+    let sandbox = Sandbox::new("lib-bad-member");
+    let docs = sandbox.work().join("docs");
+    let outside = sandbox.work().join("outside");
+    fs::create_dir(&outside).unwrap();
+    let good = [("docs/", EntryType::Directory, ""), ("docs/a.txt", EntryType::Regular, "a"), ("docs/sub/", EntryType::Directory, "")];
+    let link = outside.to_string_lossy().to_string();
+    let bad = [
+        vec![("docs/../escaped", EntryType::Regular, "evil")],
+        vec![("docs/link", EntryType::Symlink, link.as_str()), ("docs/link/evil.txt", EntryType::Regular, "evil")],
+    ];
+
+    for bad in bad {
+        let members: Vec<_> = good.iter().copied().chain(bad).collect();
+        tampered_archive(&sandbox, &docs, &members);
+        let elsewhere = sandbox.work().join("elsewhere");
+        let attempts = [
+            RestoreOptions::default(),
+            RestoreOptions { destination: Some(elsewhere.clone()), ..RestoreOptions::default() },
+            RestoreOptions { merge: Some(trash::MergePreference::Trashed), ..RestoreOptions::default() },
+        ];
+        for options in attempts {
+            if options.merge.is_some() {
+                fs::create_dir(&docs).unwrap();
+            }
+            let restored = trash::restore_from_trash("docs.tar.gz", &sandbox.trash_dir(), &options, &NoOpProgress);
+            assert!(matches!(restored, Err(trs::TrsError::ArchiveError { .. })), "{:?}", restored);
+            assert!(!docs.join("a.txt").exists() && !docs.join("link").exists());
+            assert!(!elsewhere.exists());
+            assert!(!sandbox.work().join("escaped").exists());
+            assert_eq!(fs::read_dir(&outside).unwrap().count(), 0);
+            assert_eq!(trash::list_trash_items(&sandbox.trash_dir()).unwrap().len(), 1);
+            let _ = fs::remove_dir(&docs);
+        }
+    }
+}